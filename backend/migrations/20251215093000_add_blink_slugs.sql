@@ -0,0 +1,15 @@
+-- Add human-readable slugs to blinks
+ALTER TABLE blinks ADD COLUMN slug TEXT UNIQUE;
+
+-- Every slug a blink has ever used, so renamed blinks keep resolving
+CREATE TABLE blink_slugs (
+    slug TEXT PRIMARY KEY,
+    blink_id UUID NOT NULL REFERENCES blinks(id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX blink_slugs_blink_id_idx ON blink_slugs (blink_id);
+
+ALTER TABLE blink_slugs ENABLE ROW LEVEL SECURITY;
+
+CREATE POLICY "Allow all" ON blink_slugs FOR ALL USING (true);
@@ -0,0 +1,15 @@
+-- Operator-managed actions.json rules, served after the configured ones
+CREATE TABLE action_rules (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    position INTEGER NOT NULL DEFAULT 0,
+    path_pattern TEXT NOT NULL,
+    -- NULL maps the pattern onto itself (idempotent rule)
+    api_path TEXT,
+    exclude BOOLEAN NOT NULL DEFAULT false,
+    CHECK (NOT (exclude AND api_path IS NOT NULL))
+);
+
+ALTER TABLE action_rules ENABLE ROW LEVEL SECURITY;
+
+CREATE POLICY "Allow all" ON action_rules FOR ALL USING (true);
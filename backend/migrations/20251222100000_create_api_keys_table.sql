@@ -0,0 +1,27 @@
+-- Scoped API keys for programmatic access. Only a SHA-256 hash of each key is stored.
+CREATE TABLE api_keys (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    name TEXT NOT NULL,
+    owner TEXT NOT NULL,
+    prefix TEXT NOT NULL,
+    key_hash TEXT NOT NULL UNIQUE,
+    scopes TEXT[] NOT NULL,
+    expires_at TIMESTAMPTZ,
+    last_used_at TIMESTAMPTZ,
+    revoked_at TIMESTAMPTZ,
+    -- Set on keys replaced by a rotation
+    rotated_to UUID REFERENCES api_keys(id),
+    CHECK (scopes <@ ARRAY['create', 'read', 'update', 'analytics']::TEXT[])
+);
+
+CREATE INDEX api_keys_owner_idx ON api_keys (owner);
+
+ALTER TABLE api_keys ENABLE ROW LEVEL SECURITY;
+
+CREATE POLICY "Allow all" ON api_keys FOR ALL USING (true);
+
+-- Owner of the API key that created a blink; NULL for anonymous blinks
+ALTER TABLE blinks ADD COLUMN owner TEXT;
+
+CREATE INDEX blinks_owner_idx ON blinks (owner);
@@ -0,0 +1,57 @@
+-- Immutable history of blink metadata. `blinks` holds the current revision.
+CREATE TABLE blink_revisions (
+    blink_id UUID NOT NULL REFERENCES blinks(id) ON DELETE CASCADE,
+    revision INTEGER NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    -- Owner and API key that made the change; NULL for anonymous blinks
+    author TEXT,
+    author_key_id UUID REFERENCES api_keys(id),
+    -- Set when the revision restores an earlier one
+    rolled_back_from INTEGER,
+    title TEXT NOT NULL,
+    icon_url TEXT NOT NULL,
+    description TEXT NOT NULL,
+    label TEXT NOT NULL,
+    type blink_type NOT NULL,
+    config JSONB NOT NULL,
+    PRIMARY KEY (blink_id, revision)
+);
+
+CREATE FUNCTION forbid_blink_revision_update() RETURNS trigger AS $$
+BEGIN
+    RAISE EXCEPTION 'blink revisions are immutable';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER blink_revisions_immutable
+    BEFORE UPDATE ON blink_revisions
+    FOR EACH ROW EXECUTE FUNCTION forbid_blink_revision_update();
+
+ALTER TABLE blinks ADD COLUMN revision INTEGER NOT NULL DEFAULT 1;
+
+INSERT INTO blink_revisions (
+    blink_id, revision, created_at, author, title, icon_url, description, label, type, config
+)
+SELECT id, 1, COALESCE(created_at, now()), owner, title, icon_url, description, label, type, config
+FROM blinks;
+
+-- Every transaction served for a blink and the revision it was built from
+CREATE TABLE action_builds (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    blink_id UUID NOT NULL,
+    revision INTEGER NOT NULL,
+    account TEXT NOT NULL,
+    transaction TEXT NOT NULL,
+    message TEXT,
+    FOREIGN KEY (blink_id, revision) REFERENCES blink_revisions(blink_id, revision) ON DELETE CASCADE
+);
+
+CREATE INDEX action_builds_revision_idx ON action_builds (blink_id, revision);
+CREATE INDEX action_builds_account_idx ON action_builds (account);
+
+ALTER TABLE blink_revisions ENABLE ROW LEVEL SECURITY;
+ALTER TABLE action_builds ENABLE ROW LEVEL SECURITY;
+
+CREATE POLICY "Allow all" ON blink_revisions FOR ALL USING (true);
+CREATE POLICY "Allow all" ON action_builds FOR ALL USING (true);
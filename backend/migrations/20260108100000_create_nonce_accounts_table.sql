@@ -0,0 +1,21 @@
+-- Durable nonce accounts controlled by the backend's nonce authority.
+-- A lease hands one account to a single transaction; leased accounts are not
+-- handed out again until the sweep sees their nonce advance on chain.
+CREATE TABLE nonce_accounts (
+    pubkey TEXT PRIMARY KEY,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    leased_at TIMESTAMPTZ,
+    -- After this the backend advances the nonce itself, invalidating the
+    -- transaction it was handed out for
+    leased_until TIMESTAMPTZ,
+    -- Nonce value the leased transaction was built with
+    leased_nonce TEXT
+);
+
+CREATE INDEX nonce_accounts_available_idx ON nonce_accounts (created_at) WHERE leased_at IS NULL;
+
+ALTER TABLE action_builds ADD COLUMN nonce_account TEXT;
+
+ALTER TABLE nonce_accounts ENABLE ROW LEVEL SECURITY;
+
+CREATE POLICY "Allow all" ON nonce_accounts FOR ALL USING (true);
@@ -0,0 +1,41 @@
+-- Token balances that weight a poll, read once per weighting. Amounts are raw
+-- token units and may exceed BIGINT.
+CREATE TABLE vote_snapshots (
+    blink_id UUID PRIMARY KEY REFERENCES blinks(id) ON DELETE CASCADE,
+    mint TEXT NOT NULL,
+    -- Slot named by the poll's snapshot policy; NULL for "creation"
+    requested_slot BIGINT,
+    -- Slot the balances were read at
+    slot BIGINT NOT NULL,
+    taken_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE TABLE vote_snapshot_balances (
+    blink_id UUID NOT NULL REFERENCES vote_snapshots(blink_id) ON DELETE CASCADE,
+    wallet TEXT NOT NULL,
+    balance NUMERIC(20, 0) NOT NULL,
+    PRIMARY KEY (blink_id, wallet)
+);
+
+-- Votes whose memo transaction was confirmed on chain. One per wallet.
+CREATE TABLE votes (
+    blink_id UUID NOT NULL REFERENCES blinks(id) ON DELETE CASCADE,
+    voter TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    selection TEXT NOT NULL,
+    -- NULL for unweighted polls
+    weight NUMERIC(20, 0),
+    signature TEXT NOT NULL UNIQUE,
+    slot BIGINT NOT NULL,
+    PRIMARY KEY (blink_id, voter)
+);
+
+CREATE INDEX votes_selection_idx ON votes (blink_id, selection);
+
+ALTER TABLE vote_snapshots ENABLE ROW LEVEL SECURITY;
+ALTER TABLE vote_snapshot_balances ENABLE ROW LEVEL SECURITY;
+ALTER TABLE votes ENABLE ROW LEVEL SECURITY;
+
+CREATE POLICY "Allow all" ON vote_snapshots FOR ALL USING (true);
+CREATE POLICY "Allow all" ON vote_snapshot_balances FOR ALL USING (true);
+CREATE POLICY "Allow all" ON votes FOR ALL USING (true);
@@ -0,0 +1,20 @@
+ALTER TYPE blink_type ADD VALUE 'mint';
+
+-- Serial numbers of mint blinks. A row is reserved when its transaction is
+-- built and kept once the mint account exists on chain.
+CREATE TABLE nft_mints (
+    mint TEXT PRIMARY KEY,
+    blink_id UUID NOT NULL REFERENCES blinks(id) ON DELETE CASCADE,
+    number INTEGER NOT NULL,
+    buyer TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    -- NULL once the mint landed
+    reserved_until TIMESTAMPTZ,
+    UNIQUE (blink_id, number)
+);
+
+CREATE INDEX nft_mints_buyer_idx ON nft_mints (blink_id, buyer);
+
+ALTER TABLE nft_mints ENABLE ROW LEVEL SECURITY;
+
+CREATE POLICY "Allow all" ON nft_mints FOR ALL USING (true);
@@ -0,0 +1,19 @@
+-- Every signature a vault key added to an action transaction. Rows outlive
+-- their blink, so blink_id is not a foreign key.
+CREATE TABLE signer_audit_log (
+    id BIGSERIAL PRIMARY KEY,
+    signer TEXT NOT NULL,
+    pubkey TEXT NOT NULL,
+    blink_id UUID NOT NULL,
+    payer TEXT NOT NULL,
+    signature TEXT NOT NULL,
+    -- Program of each instruction, in order
+    programs TEXT[] NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX signer_audit_log_signer_idx ON signer_audit_log (signer, created_at);
+
+ALTER TABLE signer_audit_log ENABLE ROW LEVEL SECURITY;
+
+CREATE POLICY "Allow all" ON signer_audit_log FOR ALL USING (true);
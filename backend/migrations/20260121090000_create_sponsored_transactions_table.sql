@@ -0,0 +1,22 @@
+-- Transactions whose fees a blink's sponsor pays. A row is pending from the
+-- moment the transaction is built until the cluster shows whether it landed.
+CREATE TYPE sponsored_status AS ENUM ('pending', 'landed', 'expired');
+
+CREATE TABLE sponsored_transactions (
+    id BIGSERIAL PRIMARY KEY,
+    blink_id UUID NOT NULL REFERENCES blinks(id) ON DELETE CASCADE,
+    wallet TEXT NOT NULL,
+    fee_lamports BIGINT NOT NULL,
+    -- The sponsor's signature, which is the transaction id. NULL until the
+    -- sponsor signed
+    signature TEXT,
+    status sponsored_status NOT NULL DEFAULT 'pending',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX sponsored_transactions_blink_idx ON sponsored_transactions (blink_id, status);
+CREATE INDEX sponsored_transactions_wallet_idx ON sponsored_transactions (wallet, created_at);
+
+ALTER TABLE sponsored_transactions ENABLE ROW LEVEL SECURITY;
+
+CREATE POLICY "Allow all" ON sponsored_transactions FOR ALL USING (true);
@@ -0,0 +1,33 @@
+ALTER TYPE blink_type ADD VALUE 'claim';
+
+-- The Merkle root of a claim blink's allowlist.
+CREATE TABLE claim_allowlists (
+    blink_id UUID PRIMARY KEY REFERENCES blinks(id) ON DELETE CASCADE,
+    merkle_root TEXT NOT NULL,
+    total_lamports BIGINT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+-- One allowlisted wallet and its Merkle proof. A claim is in flight from the
+-- moment its transaction is built until `reserved_until`, after which the
+-- cluster shows whether it landed.
+CREATE TABLE claim_allocations (
+    blink_id UUID NOT NULL REFERENCES claim_allowlists(blink_id) ON DELETE CASCADE,
+    wallet TEXT NOT NULL,
+    lamports BIGINT NOT NULL,
+    proof TEXT[] NOT NULL,
+    -- The distributor's signature, which is the transaction id.
+    signature TEXT,
+    reserved_until TIMESTAMPTZ,
+    claimed_at TIMESTAMPTZ,
+    PRIMARY KEY (blink_id, wallet)
+);
+
+CREATE INDEX claim_allocations_reserved_idx ON claim_allocations (blink_id, reserved_until)
+    WHERE signature IS NOT NULL AND claimed_at IS NULL;
+
+ALTER TABLE claim_allowlists ENABLE ROW LEVEL SECURITY;
+ALTER TABLE claim_allocations ENABLE ROW LEVEL SECURITY;
+
+CREATE POLICY "Allow all" ON claim_allowlists FOR ALL USING (true);
+CREATE POLICY "Allow all" ON claim_allocations FOR ALL USING (true);
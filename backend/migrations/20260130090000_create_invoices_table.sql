@@ -0,0 +1,31 @@
+-- Overdue invoices are open invoices past their due date.
+CREATE TYPE invoice_status AS ENUM ('open', 'paid', 'cancelled');
+
+-- An invoice issued as a single-use payment blink.
+CREATE TABLE invoices (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    blink_id UUID NOT NULL UNIQUE REFERENCES blinks(id) ON DELETE CASCADE,
+    owner TEXT NOT NULL,
+    number TEXT NOT NULL,
+    -- `SOL` or the mint of an SPL token.
+    currency TEXT NOT NULL,
+    decimals SMALLINT NOT NULL,
+    -- In base units of the currency.
+    amount BIGINT NOT NULL,
+    -- Read-only account added to the transfer, by which the payment is found.
+    reference TEXT NOT NULL UNIQUE,
+    due_date DATE NOT NULL,
+    note TEXT,
+    status invoice_status NOT NULL DEFAULT 'open',
+    signature TEXT,
+    payer TEXT,
+    paid_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    UNIQUE (owner, number)
+);
+
+CREATE INDEX invoices_owner_idx ON invoices (owner, created_at);
+
+ALTER TABLE invoices ENABLE ROW LEVEL SECURITY;
+
+CREATE POLICY "Allow all" ON invoices FOR ALL USING (true);
@@ -0,0 +1,51 @@
+ALTER TYPE blink_type ADD VALUE 'subscription';
+
+-- Lapsed subscriptions are active ones whose paid period has ended.
+CREATE TYPE subscription_status AS ENUM ('active', 'cancelled');
+
+-- A wallet subscribed to a subscription blink.
+CREATE TABLE subscriptions (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    blink_id UUID NOT NULL REFERENCES blinks(id) ON DELETE CASCADE,
+    subscriber TEXT NOT NULL,
+    status subscription_status NOT NULL DEFAULT 'active',
+    -- NULL until the first payment landed.
+    paid_through TIMESTAMPTZ,
+    -- Token delegate approved by the subscriber, the mint and the base units
+    -- it may still charge.
+    delegate TEXT,
+    delegate_mint TEXT,
+    allowance BIGINT NOT NULL DEFAULT 0,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    UNIQUE (blink_id, subscriber)
+);
+
+-- A payment built for a subscription, found on the cluster by `reference`.
+-- Payments that do not land are dropped.
+CREATE TABLE subscription_payments (
+    reference TEXT PRIMARY KEY,
+    subscription_id UUID NOT NULL REFERENCES subscriptions(id) ON DELETE CASCADE,
+    -- `SOL` or the mint of an SPL token.
+    currency TEXT NOT NULL,
+    decimals SMALLINT NOT NULL,
+    -- In base units of the currency.
+    amount BIGINT NOT NULL,
+    period_days INTEGER NOT NULL,
+    -- Allowance the subscriber approves for `delegate` with this payment.
+    delegate TEXT,
+    approves BIGINT,
+    -- Charged by the delegate rather than paid by the subscriber.
+    charged BOOLEAN NOT NULL DEFAULT false,
+    signature TEXT,
+    confirmed_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX subscription_payments_pending_idx
+    ON subscription_payments (subscription_id) WHERE confirmed_at IS NULL;
+
+ALTER TABLE subscriptions ENABLE ROW LEVEL SECURITY;
+ALTER TABLE subscription_payments ENABLE ROW LEVEL SECURITY;
+
+CREATE POLICY "Allow all" ON subscriptions FOR ALL USING (true);
+CREATE POLICY "Allow all" ON subscription_payments FOR ALL USING (true);
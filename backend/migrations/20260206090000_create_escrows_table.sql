@@ -0,0 +1,37 @@
+ALTER TYPE blink_type ADD VALUE 'escrow';
+
+CREATE TYPE escrow_status AS ENUM ('open', 'funded', 'released', 'refunded');
+CREATE TYPE escrow_step AS ENUM ('deposit', 'release', 'refund');
+
+-- The deal of an escrow blink. Its terms are fixed by the deposit: later
+-- changes to the blink's config only apply while the deal is open.
+CREATE TABLE escrows (
+    blink_id UUID PRIMARY KEY REFERENCES blinks(id) ON DELETE CASCADE,
+    status escrow_status NOT NULL DEFAULT 'open',
+    -- Vault key holding the deposit, by name and address.
+    escrow TEXT NOT NULL,
+    escrow_key TEXT NOT NULL,
+    -- `SOL` or the mint of an SPL token.
+    currency TEXT NOT NULL,
+    decimals SMALLINT NOT NULL,
+    -- In base units of the currency.
+    amount BIGINT NOT NULL,
+    timeout_days INTEGER NOT NULL,
+    on_timeout escrow_step NOT NULL,
+    buyer TEXT,
+    seller TEXT NOT NULL,
+    -- The transfer in flight, found on the cluster by `pending_reference`:
+    -- a deposit while open, a release or refund while funded.
+    pending_step escrow_step,
+    pending_reference TEXT,
+    pending_at TIMESTAMPTZ,
+    deposit_signature TEXT,
+    funded_at TIMESTAMPTZ,
+    settlement_signature TEXT,
+    closed_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+ALTER TABLE escrows ENABLE ROW LEVEL SECURITY;
+
+CREATE POLICY "Allow all" ON escrows FOR ALL USING (true);
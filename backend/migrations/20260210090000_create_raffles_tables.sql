@@ -0,0 +1,56 @@
+ALTER TYPE blink_type ADD VALUE 'raffle';
+
+-- A raffle blink whose first ticket was sold. Its terms and draw slot are
+-- fixed then.
+CREATE TABLE raffles (
+    blink_id UUID PRIMARY KEY REFERENCES blinks(id) ON DELETE CASCADE,
+    -- Wallet the ticket sales are paid to.
+    wallet TEXT NOT NULL,
+    -- In lamports.
+    ticket_price BIGINT NOT NULL,
+    max_per_wallet INTEGER NOT NULL,
+    winners INTEGER NOT NULL,
+    closes_at TIMESTAMPTZ NOT NULL,
+    -- The winners are drawn from the blockhash of the first block at or
+    -- after this slot.
+    draw_slot BIGINT NOT NULL,
+    -- Set by the draw.
+    seed TEXT,
+    seed_slot BIGINT,
+    ticket_hash TEXT,
+    drawn_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+-- Tickets bought in one transfer, found on the cluster by `reference`.
+-- Purchases that do not land are dropped.
+CREATE TABLE raffle_purchases (
+    reference TEXT PRIMARY KEY,
+    blink_id UUID NOT NULL REFERENCES raffles(blink_id) ON DELETE CASCADE,
+    wallet TEXT NOT NULL,
+    tickets INTEGER NOT NULL,
+    -- In lamports.
+    amount BIGINT NOT NULL,
+    signature TEXT,
+    confirmed_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX raffle_purchases_blink_idx ON raffle_purchases (blink_id, wallet);
+
+-- Winning tickets in the order they were drawn.
+CREATE TABLE raffle_winners (
+    blink_id UUID NOT NULL REFERENCES raffles(blink_id) ON DELETE CASCADE,
+    place INTEGER NOT NULL,
+    ticket INTEGER NOT NULL,
+    wallet TEXT NOT NULL,
+    PRIMARY KEY (blink_id, place)
+);
+
+ALTER TABLE raffles ENABLE ROW LEVEL SECURITY;
+ALTER TABLE raffle_purchases ENABLE ROW LEVEL SECURITY;
+ALTER TABLE raffle_winners ENABLE ROW LEVEL SECURITY;
+
+CREATE POLICY "Allow all" ON raffles FOR ALL USING (true);
+CREATE POLICY "Allow all" ON raffle_purchases FOR ALL USING (true);
+CREATE POLICY "Allow all" ON raffle_winners FOR ALL USING (true);
@@ -0,0 +1,20 @@
+ALTER TYPE blink_type ADD VALUE 'message';
+
+-- Messages posted to message blinks, recorded once the author reports the
+-- confirmed transaction carrying the memo.
+CREATE TABLE messages (
+    signature TEXT PRIMARY KEY,
+    blink_id UUID NOT NULL REFERENCES blinks(id) ON DELETE CASCADE,
+    author TEXT NOT NULL,
+    body TEXT NOT NULL,
+    -- In lamports, when a tip was sent along.
+    tip BIGINT,
+    slot BIGINT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX messages_blink_idx ON messages (blink_id, created_at DESC);
+
+ALTER TABLE messages ENABLE ROW LEVEL SECURITY;
+
+CREATE POLICY "Allow all" ON messages FOR ALL USING (true);
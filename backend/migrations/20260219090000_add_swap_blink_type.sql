@@ -0,0 +1 @@
+ALTER TYPE blink_type ADD VALUE 'swap';
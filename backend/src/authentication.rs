@@ -0,0 +1,203 @@
+use crate::models::ApiKeyScope;
+use axum::http::{HeaderMap, StatusCode, header};
+use rand::{Rng, distributions::Alphanumeric};
+use secrecy::{ExposeSecret, SecretString};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+const API_KEY_PREFIX: &str = "bz_";
+const API_KEY_SECRET_LENGTH: usize = 40;
+/// Characters of the secret kept in plain text to tell keys apart.
+const DISPLAY_PREFIX_LENGTH: usize = 8;
+
+/// A valid, unexpired and unrevoked API key that presented itself on a
+/// request.
+#[derive(Debug, Clone)]
+pub struct AuthenticatedKey {
+    pub id: Uuid,
+    pub owner: String,
+    pub scopes: Vec<String>,
+}
+
+impl AuthenticatedKey {
+    pub fn has_scope(&self, scope: ApiKeyScope) -> bool {
+        self.scopes.iter().any(|s| s == scope.as_str())
+    }
+}
+
+/// A freshly generated key and the values stored for it.
+pub struct GeneratedApiKey {
+    pub key: String,
+    pub prefix: String,
+    pub hash: String,
+}
+
+pub fn generate_api_key() -> GeneratedApiKey {
+    let secret: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(API_KEY_SECRET_LENGTH)
+        .map(char::from)
+        .collect();
+    let key = format!("{}{}", API_KEY_PREFIX, secret);
+
+    GeneratedApiKey {
+        prefix: key[..API_KEY_PREFIX.len() + DISPLAY_PREFIX_LENGTH].to_string(),
+        hash: hash_api_key(&key),
+        key,
+    }
+}
+
+/// API keys are long random strings, so a fast unsalted hash is enough to keep
+/// them unusable if the table leaks.
+pub fn hash_api_key(key: &str) -> String {
+    format!("{:x}", Sha256::digest(key.as_bytes()))
+}
+
+pub fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(str::trim)
+        .filter(|token| !token.is_empty())
+}
+
+/// Looks up the key presented in `token`, records its use and checks it holds
+/// `scope`.
+pub async fn authenticate(
+    pool: &PgPool,
+    token: &str,
+    scope: ApiKeyScope,
+) -> Result<AuthenticatedKey, (StatusCode, String)> {
+    let key = sqlx::query_as!(
+        AuthenticatedKey,
+        r#"
+        UPDATE api_keys
+        SET last_used_at = now()
+        WHERE key_hash = $1
+            AND revoked_at IS NULL
+            AND (expires_at IS NULL OR expires_at > now())
+        RETURNING id, owner, scopes
+        "#,
+        hash_api_key(token)
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .ok_or((
+        StatusCode::UNAUTHORIZED,
+        "Invalid or expired API key".to_string(),
+    ))?;
+
+    if !key.has_scope(scope) {
+        return Err((
+            StatusCode::FORBIDDEN,
+            format!("API key is missing the '{}' scope", scope.as_str()),
+        ));
+    }
+
+    Ok(key)
+}
+
+/// Whether `token` is a valid, unexpired and unrevoked API key. Unlike
+/// `authenticate`, it does not record a use of the key.
+pub async fn is_active_api_key(pool: &PgPool, token: &str) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"
+        SELECT EXISTS (
+            SELECT 1 FROM api_keys
+            WHERE key_hash = $1
+                AND revoked_at IS NULL
+                AND (expires_at IS NULL OR expires_at > now())
+        ) AS "active!"
+        "#,
+        hash_api_key(token)
+    )
+    .fetch_one(pool)
+    .await
+}
+
+/// Authenticates the bearer token of a request that requires an API key.
+pub async fn require_api_key(
+    pool: &PgPool,
+    headers: &HeaderMap,
+    scope: ApiKeyScope,
+) -> Result<AuthenticatedKey, (StatusCode, String)> {
+    let token =
+        bearer_token(headers).ok_or((StatusCode::UNAUTHORIZED, "Missing API key".to_string()))?;
+    authenticate(pool, token, scope).await
+}
+
+/// Authenticates the bearer token of a request where an API key is optional.
+/// A present but invalid key is still rejected.
+pub async fn optional_api_key(
+    pool: &PgPool,
+    headers: &HeaderMap,
+    scope: ApiKeyScope,
+) -> Result<Option<AuthenticatedKey>, (StatusCode, String)> {
+    match bearer_token(headers) {
+        Some(token) => authenticate(pool, token, scope).await.map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Token guarding the key management routes. An empty token disables them.
+#[derive(Clone)]
+pub struct AdminToken(pub SecretString);
+
+impl AdminToken {
+    pub fn verify(&self, headers: &HeaderMap) -> Result<(), (StatusCode, String)> {
+        let expected = self.0.expose_secret();
+        if expected.is_empty() {
+            return Err((
+                StatusCode::FORBIDDEN,
+                "API key management is disabled".to_string(),
+            ));
+        }
+
+        // Compare digests so the comparison time does not depend on how much
+        // of the token matched.
+        match bearer_token(headers) {
+            Some(token) if hash_api_key(token) == hash_api_key(expected) => Ok(()),
+            _ => Err((StatusCode::UNAUTHORIZED, "Invalid admin token".to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_keys_are_unique_and_prefixed() {
+        let first = generate_api_key();
+        let second = generate_api_key();
+
+        assert!(first.key.starts_with("bz_"));
+        assert_eq!(first.key.len(), 3 + API_KEY_SECRET_LENGTH);
+        assert!(first.key.starts_with(&first.prefix));
+        assert_ne!(first.key, second.key);
+        assert_eq!(first.hash, hash_api_key(&first.key));
+    }
+
+    #[test]
+    fn bearer_token_requires_bearer_scheme() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::AUTHORIZATION, "Basic abc".parse().unwrap());
+        assert_eq!(bearer_token(&headers), None);
+
+        headers.insert(header::AUTHORIZATION, "Bearer bz_abc".parse().unwrap());
+        assert_eq!(bearer_token(&headers), Some("bz_abc"));
+    }
+
+    #[test]
+    fn empty_admin_token_disables_management() {
+        let token = AdminToken(SecretString::from(""));
+        let mut headers = HeaderMap::new();
+        headers.insert(header::AUTHORIZATION, "Bearer ".parse().unwrap());
+
+        assert_eq!(token.verify(&headers).unwrap_err().0, StatusCode::FORBIDDEN);
+    }
+}
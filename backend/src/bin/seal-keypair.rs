@@ -0,0 +1,26 @@
+//! Encrypts a keypair for the signer vault.
+//!
+//! Reads a base58 keypair from stdin and the passphrase from the
+//! `SIGNER_PASSPHRASE` environment variable, and prints the sealed file:
+//!
+//! ```sh
+//! SIGNER_PASSPHRASE=... cargo run --bin seal-keypair < keypair.txt > signer.json
+//! ```
+use blinkzero::signer_vault::{DEFAULT_SEAL_ROUNDS, seal};
+use secrecy::{ExposeSecret, SecretString};
+use solana_sdk::signature::Keypair;
+use std::io::Read;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let passphrase = SecretString::from(
+        std::env::var("SIGNER_PASSPHRASE").map_err(|_| "SIGNER_PASSPHRASE is not set")?,
+    );
+    let mut input = String::new();
+    std::io::stdin().read_to_string(&mut input)?;
+    let encoded = SecretString::from(input.trim().to_string());
+    let bytes = solana_sdk::bs58::decode(encoded.expose_secret()).into_vec()?;
+    let keypair = Keypair::from_bytes(&bytes)?;
+
+    println!("{}", seal(&keypair, &passphrase, DEFAULT_SEAL_ROUNDS));
+    Ok(())
+}
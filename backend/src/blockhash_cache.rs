@@ -0,0 +1,138 @@
+use crate::rpc_pool::RpcPool;
+use solana_client::client_error::ClientError;
+use solana_sdk::hash::Hash;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+/// A recent blockhash and the last block height at which transactions using it
+/// can land.
+#[derive(Debug, Clone, Copy)]
+pub struct RecentBlockhash {
+    pub blockhash: Hash,
+    pub last_valid_block_height: u64,
+    fetched_at: Instant,
+}
+
+/// The shared RPC pool plus the latest blockhash, refreshed in the
+/// background so building a transaction does not wait on the RPC.
+///
+/// Blockhashes older than `max_age` are not handed out; a live fetch replaces
+/// them instead. Keep `max_age` well below the ~60s a blockhash stays valid so
+/// wallets have time to sign.
+pub struct BlockhashCache {
+    rpc: Arc<RpcPool>,
+    max_age: Duration,
+    latest: RwLock<Option<RecentBlockhash>>,
+}
+
+impl BlockhashCache {
+    pub fn new(rpc: Arc<RpcPool>, max_age: Duration) -> Self {
+        Self {
+            rpc,
+            max_age,
+            latest: RwLock::new(None),
+        }
+    }
+
+    /// The RPC pool shared by all transaction builders.
+    pub fn rpc(&self) -> &RpcPool {
+        &self.rpc
+    }
+
+    /// The cached blockhash, or a freshly fetched one when it is stale.
+    pub async fn get(&self) -> Result<RecentBlockhash, ClientError> {
+        let cached = *self.latest.read().unwrap();
+        match cached {
+            Some(latest) if latest.fetched_at.elapsed() < self.max_age => Ok(latest),
+            _ => self.refresh().await,
+        }
+    }
+
+    pub async fn refresh(&self) -> Result<RecentBlockhash, ClientError> {
+        let commitment = self.rpc.commitment();
+        let (blockhash, last_valid_block_height) = self
+            .rpc
+            .call(|client| async move {
+                client
+                    .get_latest_blockhash_with_commitment(commitment)
+                    .await
+            })
+            .await?;
+        let latest = RecentBlockhash {
+            blockhash,
+            last_valid_block_height,
+            fetched_at: Instant::now(),
+        };
+
+        *self.latest.write().unwrap() = Some(latest);
+        Ok(latest)
+    }
+
+    /// Refreshes the blockhash every `interval` for as long as the cache is
+    /// alive. Failures are logged; requests fall back to live fetches.
+    pub fn spawn_refresh(self: &Arc<Self>, interval: Duration) {
+        let cache = Arc::downgrade(self);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(interval);
+            loop {
+                interval.tick().await;
+                let Some(cache) = cache.upgrade() else {
+                    break;
+                };
+                if let Err(e) = cache.refresh().await {
+                    tracing::warn!("Failed to refresh recent blockhash: {}", e);
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rpc_pool::{CircuitBreaker, RpcEndpoint};
+    use solana_client::nonblocking::rpc_client::RpcClient;
+
+    fn cache(max_age: Duration) -> BlockhashCache {
+        let endpoint = RpcEndpoint::with_client(
+            "mock".to_string(),
+            1,
+            RpcClient::new_mock("succeeds".to_string()),
+        );
+        let breaker = CircuitBreaker {
+            failure_threshold: 3,
+            cooldown: Duration::from_secs(30),
+        };
+        BlockhashCache::new(
+            Arc::new(RpcPool::new(vec![endpoint], breaker, 1, 50)),
+            max_age,
+        )
+    }
+
+    #[tokio::test]
+    async fn fetches_blockhash_with_last_valid_block_height() {
+        let latest = cache(Duration::from_secs(30)).get().await.unwrap();
+
+        assert_eq!(latest.last_valid_block_height, 1234);
+    }
+
+    #[tokio::test]
+    async fn fresh_blockhash_is_served_from_cache() {
+        let cache = cache(Duration::from_secs(30));
+        let first = cache.get().await.unwrap();
+
+        let second = cache.get().await.unwrap();
+
+        assert_eq!(first.fetched_at, second.fetched_at);
+    }
+
+    #[tokio::test]
+    async fn stale_blockhash_is_fetched_again() {
+        let cache = cache(Duration::ZERO);
+        let first = cache.get().await.unwrap();
+
+        let second = cache.get().await.unwrap();
+
+        assert!(second.fetched_at > first.fetched_at);
+    }
+}
@@ -0,0 +1,286 @@
+use crate::domain::verify_allocation;
+use crate::rpc_pool::RpcPool;
+use axum::http::StatusCode;
+use solana_sdk::{hash::Hash, pubkey::Pubkey, signature::Signature};
+use sqlx::PgPool;
+use std::str::FromStr;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Signatures per `getSignatureStatuses` request.
+const MAX_SIGNATURES_PER_REQUEST: usize = 256;
+
+/// Pays allowlisted wallets of claim blinks from their distributor key.
+///
+/// An allocation is held for a claim transaction from the moment it is
+/// built, so a wallet cannot hold two claim transactions at once. Once the
+/// hold expires the distributor's signature is looked up on the cluster:
+/// landed claims are final, the others free the allocation again.
+pub struct ClaimDistributor {
+    reservation: Duration,
+}
+
+impl ClaimDistributor {
+    pub fn new(reservation: Duration) -> Self {
+        Self { reservation }
+    }
+
+    /// Holds the allocation of `wallet` and returns its lamports. Wallets
+    /// that are not allowlisted, already claimed or hold a claim in flight
+    /// get a `403`.
+    pub async fn reserve(
+        &self,
+        db: &PgPool,
+        rpc: &RpcPool,
+        blink_id: Uuid,
+        wallet: &Pubkey,
+    ) -> Result<u64, (StatusCode, String)> {
+        let mut transaction = db
+            .begin()
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        // Serializes claims of one blink.
+        sqlx::query!("SELECT id FROM blinks WHERE id = $1 FOR UPDATE", blink_id)
+            .fetch_one(&mut *transaction)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+        self.settle_expired(&mut transaction, rpc, blink_id).await?;
+
+        let allocation = sqlx::query!(
+            r#"
+            SELECT
+                a.lamports,
+                a.proof,
+                a.claimed_at IS NOT NULL AS "claimed!",
+                a.reserved_until IS NOT NULL AS "in_flight!",
+                l.merkle_root
+            FROM claim_allocations a
+            JOIN claim_allowlists l USING (blink_id)
+            WHERE a.blink_id = $1 AND a.wallet = $2
+            "#,
+            blink_id,
+            wallet.to_string()
+        )
+        .fetch_optional(&mut *transaction)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        // Settlements are kept even when this claim is refused.
+        transaction
+            .commit()
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+        let Some(allocation) = allocation else {
+            return Err((
+                StatusCode::FORBIDDEN,
+                "This wallet is not on the allowlist".to_string(),
+            ));
+        };
+        let lamports = allocation.lamports as u64;
+        if !verify_stored(&allocation.merkle_root, wallet, lamports, &allocation.proof) {
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Allocation of {} does not match the Merkle root", wallet),
+            ));
+        }
+        if allocation.claimed {
+            return Err((
+                StatusCode::FORBIDDEN,
+                "This wallet has already claimed its allocation".to_string(),
+            ));
+        }
+        if allocation.in_flight {
+            return Err((
+                StatusCode::FORBIDDEN,
+                "A claim of this wallet is in flight, try again in a few minutes".to_string(),
+            ));
+        }
+
+        // Only one concurrent request can take the hold.
+        let held = sqlx::query!(
+            r#"
+            UPDATE claim_allocations
+            SET reserved_until = now() + make_interval(secs => $3), signature = NULL
+            WHERE blink_id = $1 AND wallet = $2
+              AND reserved_until IS NULL AND claimed_at IS NULL
+            "#,
+            blink_id,
+            wallet.to_string(),
+            self.reservation.as_secs_f64()
+        )
+        .execute(db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .rows_affected();
+        if held == 0 {
+            return Err((
+                StatusCode::FORBIDDEN,
+                "A claim of this wallet is in flight, try again in a few minutes".to_string(),
+            ));
+        }
+
+        Ok(lamports)
+    }
+
+    /// Stores the distributor's signature, by which the claim is looked up
+    /// on the cluster.
+    pub async fn record_signature(
+        &self,
+        db: &PgPool,
+        blink_id: Uuid,
+        wallet: &Pubkey,
+        signature: &Signature,
+    ) -> Result<(), (StatusCode, String)> {
+        sqlx::query!(
+            "UPDATE claim_allocations SET signature = $3 WHERE blink_id = $1 AND wallet = $2",
+            blink_id,
+            wallet.to_string(),
+            signature.to_string()
+        )
+        .execute(db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        Ok(())
+    }
+
+    /// Settles expired holds of a blink: claims that landed without error are
+    /// final, the rest free their allocation.
+    async fn settle_expired(
+        &self,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+        rpc: &RpcPool,
+        blink_id: Uuid,
+    ) -> Result<(), (StatusCode, String)> {
+        let expired = sqlx::query!(
+            r#"
+            SELECT wallet, signature
+            FROM claim_allocations
+            WHERE blink_id = $1 AND claimed_at IS NULL AND reserved_until < now()
+            "#,
+            blink_id
+        )
+        .fetch_all(&mut **transaction)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        if expired.is_empty() {
+            return Ok(());
+        }
+
+        let signed = expired
+            .iter()
+            .filter_map(|row| Some((row.wallet.as_str(), row.signature.as_deref()?)))
+            .map(|(wallet, signature)| {
+                Signature::from_str(signature).map(|signature| (wallet, signature))
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| {
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("Invalid stored signature: {}", e),
+                )
+            })?;
+        let mut landed = Vec::new();
+        for chunk in signed.chunks(MAX_SIGNATURES_PER_REQUEST) {
+            let signatures = chunk
+                .iter()
+                .map(|(_, signature)| *signature)
+                .collect::<Vec<_>>();
+            let statuses = rpc
+                .call(|client| {
+                    let signatures = signatures.clone();
+                    async move {
+                        client
+                            .get_signature_statuses_with_history(&signatures)
+                            .await
+                    }
+                })
+                .await
+                .map_err(|e| {
+                    (
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        format!("RPC Error: {}", e),
+                    )
+                })?
+                .value;
+            // Failed transactions did not pay the allocation.
+            landed.extend(
+                chunk
+                    .iter()
+                    .zip(statuses)
+                    .filter(|(_, status)| status.as_ref().is_some_and(|s| s.err.is_none()))
+                    .map(|((wallet, _), _)| wallet.to_string()),
+            );
+        }
+
+        let wallets = expired
+            .iter()
+            .map(|row| row.wallet.clone())
+            .collect::<Vec<_>>();
+        sqlx::query!(
+            r#"
+            UPDATE claim_allocations
+            SET claimed_at = CASE WHEN wallet = ANY($3) THEN now() END,
+                reserved_until = NULL,
+                signature = CASE WHEN wallet = ANY($3) THEN signature END
+            WHERE blink_id = $1 AND wallet = ANY($2)
+            "#,
+            blink_id,
+            &wallets,
+            &landed
+        )
+        .execute(&mut **transaction)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+        Ok(())
+    }
+}
+
+/// Checks a stored allocation against the stored root, so edited rows
+/// cannot be claimed.
+fn verify_stored(root: &str, wallet: &Pubkey, lamports: u64, proof: &[String]) -> bool {
+    let Ok(root) = Hash::from_str(root) else {
+        return false;
+    };
+    let Ok(proof) = proof
+        .iter()
+        .map(|hash| Hash::from_str(hash))
+        .collect::<Result<Vec<_>, _>>()
+    else {
+        return false;
+    };
+    verify_allocation(&root, wallet, lamports, &proof)
+}
+
+/// Lamports of a claim blink not yet claimed or in flight, and its total.
+/// `None` until an allowlist is uploaded.
+pub async fn claim_progress(
+    db: &PgPool,
+    blink_id: Uuid,
+) -> Result<Option<(u64, u64)>, (StatusCode, String)> {
+    let progress = sqlx::query!(
+        r#"
+        SELECT
+            l.total_lamports,
+            COALESCE(SUM(a.lamports) FILTER (
+                WHERE a.claimed_at IS NOT NULL OR a.reserved_until IS NOT NULL
+            ), 0)::BIGINT AS "taken!"
+        FROM claim_allowlists l
+        LEFT JOIN claim_allocations a USING (blink_id)
+        WHERE l.blink_id = $1
+        GROUP BY l.total_lamports
+        "#,
+        blink_id
+    )
+    .fetch_optional(db)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(progress.map(|row| {
+        (
+            (row.total_lamports - row.taken) as u64,
+            row.total_lamports as u64,
+        )
+    }))
+}
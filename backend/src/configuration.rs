@@ -1,13 +1,37 @@
+use crate::blockhash_cache::BlockhashCache;
+use crate::claims::ClaimDistributor;
+use crate::domain::{ActionPathRule, ActionRuleSet};
+use crate::escrows::EscrowAgent;
+use crate::metadata_cache::MetadataCache;
+use crate::nft_minter::NftMinter;
+use crate::nonce_pool::NoncePool;
+use crate::raffles::TicketBooth;
+use crate::rpc_pool::{CircuitBreaker, RpcEndpoint, RpcPool};
+use crate::signer_vault::{self, ProgramRule, SignerVault, SigningPolicy, VaultKey};
+use crate::sponsorship::Sponsorship;
+use crate::subscriptions::SubscriptionBiller;
+use crate::swaps::{JupiterClient, QuoteProvider, SwapRouter};
 use config::ConfigError;
+use ipnet::IpNet;
 use secrecy::{ExposeSecret, SecretString};
 use serde::Deserialize;
 use serde_aux::field_attributes::deserialize_number_from_string;
+use solana_sdk::{pubkey::Pubkey, signature::Keypair};
 use sqlx::postgres::{PgConnectOptions, PgSslMode};
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
 
 #[derive(Deserialize, Clone)]
 pub struct Settings {
     pub database: DatabaseSettings,
     pub application: ApplicationSettings,
+    pub actions: ActionsSettings,
+    pub rate_limit: RateLimitSettings,
+    pub authentication: AuthenticationSettings,
+    pub metadata_cache: MetadataCacheSettings,
+    pub solana: SolanaSettings,
 }
 
 #[derive(Deserialize, Clone)]
@@ -28,6 +52,468 @@ pub struct ApplicationSettings {
     pub host: String,
 }
 
+#[derive(Deserialize, Clone)]
+pub struct ActionsSettings {
+    #[serde(default)]
+    pub rules: Vec<ActionRuleSettings>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct ActionRuleSettings {
+    pub path_pattern: String,
+    /// Omit to map the pattern onto itself (idempotent rule).
+    pub api_path: Option<String>,
+    #[serde(default)]
+    pub exclude: bool,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct AuthenticationSettings {
+    /// Bearer token for the API key management routes. Empty disables them.
+    pub admin_token: SecretString,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct SolanaSettings {
+    /// Replaced by the comma-separated URLs in the `RPC_URL` environment
+    /// variable when it is set.
+    pub rpc_endpoints: Vec<RpcEndpointSettings>,
+    pub rpc_pool: RpcPoolSettings,
+    /// How often the shared blockhash cache is refreshed. Zero disables the
+    /// background refresh.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub blockhash_refresh_ms: u64,
+    /// Cached blockhashes older than this are fetched again on demand.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub blockhash_max_age_ms: u64,
+    pub durable_nonce: DurableNonceSettings,
+    pub nft_mint: NftMintSettings,
+    pub claims: ClaimSettings,
+    pub sponsorship: SponsorshipSettings,
+    pub subscriptions: SubscriptionSettings,
+    pub escrow: EscrowSettings,
+    pub raffles: RaffleSettings,
+    pub swaps: SwapSettings,
+    /// Keys of the signer vault.
+    #[serde(default)]
+    pub signers: Vec<SignerSettings>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct DurableNonceSettings {
+    pub enabled: bool,
+    /// Base58 keypair that is the authority of every pooled nonce account.
+    pub authority_keypair: SecretString,
+    /// How long a leased nonce is reserved for the transaction it was handed
+    /// out for before the backend advances it.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub lease_secs: u64,
+    /// How often leased accounts are checked and returned to the pool.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub reclaim_interval_ms: u64,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct NftMintSettings {
+    pub enabled: bool,
+    /// Vault key that is the update authority of every mint blink's
+    /// collection.
+    pub signer: String,
+    /// How long a serial number is held for a mint transaction. Must outlast
+    /// the blockhash the transaction is built on.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub reservation_secs: u64,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct ClaimSettings {
+    /// How long an allocation is held for a claim transaction. Must outlast
+    /// the blockhash the transaction is built on.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub reservation_secs: u64,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct SponsorshipSettings {
+    pub enabled: bool,
+    /// Sponsored transactions a wallet gets per day across all blinks.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_daily_per_wallet: u32,
+    /// Age after which a sponsored transaction is looked up on the cluster.
+    /// Must outlast the blockhash the transaction is built on.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub settle_after_secs: u64,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct SubscriptionSettings {
+    /// How long a subscription payment is looked for on the cluster before
+    /// it is dropped. Must outlast the blockhash the payment is built on.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub pending_secs: u64,
+    /// How often open payments are settled and due renewals charged from
+    /// approved allowances. Zero disables background billing.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub billing_interval_ms: u64,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct EscrowSettings {
+    /// How long a deposit, release or refund of an escrow deal is looked for
+    /// on the cluster before it is dropped. Must outlast the blockhash the
+    /// transfer is built on.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub pending_secs: u64,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct RaffleSettings {
+    /// How long a ticket purchase is looked for on the cluster before it is
+    /// dropped. Must outlast the blockhash the purchase is built on; draws
+    /// wait this long after a raffle closes.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub pending_secs: u64,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct SwapSettings {
+    pub enabled: bool,
+    /// Jupiter-compatible swap APIs, tried in order.
+    pub quote_urls: Vec<String>,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub timeout_ms: u64,
+    /// Programs swap transactions may call.
+    pub allowed_programs: Vec<String>,
+}
+
+/// A vault key. Exactly one of `keypair`, `keypair_env` and `keypair_file`
+/// provides the keypair.
+#[derive(Deserialize, Clone)]
+pub struct SignerSettings {
+    pub name: String,
+    /// Base58 keypair.
+    pub keypair: Option<SecretString>,
+    /// Environment variable holding a base58 keypair.
+    pub keypair_env: Option<String>,
+    /// Keypair file written by `seal-keypair`.
+    pub keypair_file: Option<String>,
+    /// Environment variable holding the passphrase of `keypair_file`.
+    pub passphrase_env: Option<String>,
+    /// API key owners whose blinks may use the key. Omit to allow every blink.
+    pub owners: Option<Vec<String>>,
+    /// Programs the key may sign instructions for.
+    pub programs: Vec<ProgramRuleSettings>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct ProgramRuleSettings {
+    pub program_id: String,
+    /// Allowed leading bytes of the instruction data. Omit to allow every
+    /// instruction of the program.
+    pub data_prefixes: Option<Vec<Vec<u8>>>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct RpcEndpointSettings {
+    pub url: String,
+    /// Relative share of requests sent to this endpoint.
+    #[serde(default = "default_rpc_weight")]
+    pub weight: u32,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct RpcPoolSettings {
+    /// Zero disables the background health checks.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub health_check_interval_ms: u64,
+    /// Slots an endpoint may trail the most advanced endpoint and still be
+    /// considered healthy.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_slot_lag: u64,
+    /// Consecutive failures that open an endpoint's circuit.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub failure_threshold: u32,
+    /// How long an open circuit rejects requests.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub cooldown_ms: u64,
+    /// Endpoints tried for a single request.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_attempts: usize,
+}
+
+fn default_rpc_weight() -> u32 {
+    1
+}
+
+impl SolanaSettings {
+    pub fn rpc_pool(&self) -> Result<RpcPool, String> {
+        if self.rpc_endpoints.is_empty() {
+            return Err("At least one RPC endpoint is required".to_string());
+        }
+        let endpoints = self
+            .rpc_endpoints
+            .iter()
+            .map(|endpoint| match endpoint.weight {
+                0 => Err(format!(
+                    "RPC endpoint weight must be positive: {}",
+                    endpoint.url
+                )),
+                weight => Ok(RpcEndpoint::new(&endpoint.url, weight)),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(RpcPool::new(
+            endpoints,
+            CircuitBreaker {
+                failure_threshold: self.rpc_pool.failure_threshold,
+                cooldown: Duration::from_millis(self.rpc_pool.cooldown_ms),
+            },
+            self.rpc_pool.max_attempts,
+            self.rpc_pool.max_slot_lag,
+        ))
+    }
+
+    pub fn blockhash_cache(&self, rpc_pool: Arc<RpcPool>) -> BlockhashCache {
+        BlockhashCache::new(rpc_pool, Duration::from_millis(self.blockhash_max_age_ms))
+    }
+
+    /// The nonce pool, or `None` when durable nonces are disabled.
+    pub fn nonce_pool(
+        &self,
+        blockhash_cache: Arc<BlockhashCache>,
+    ) -> Result<Option<NoncePool>, String> {
+        let settings = &self.durable_nonce;
+        if !settings.enabled {
+            return Ok(None);
+        }
+        let authority = parse_keypair(settings.authority_keypair.expose_secret())?;
+
+        Ok(Some(NoncePool::new(
+            authority,
+            Duration::from_secs(settings.lease_secs),
+            blockhash_cache,
+        )))
+    }
+
+    /// The NFT minter, or `None` when mint blinks are disabled.
+    pub fn nft_minter(&self, vault: &SignerVault) -> Result<Option<NftMinter>, String> {
+        let settings = &self.nft_mint;
+        if !settings.enabled {
+            return Ok(None);
+        }
+        let authority = vault
+            .pubkey(&settings.signer)
+            .ok_or_else(|| format!("Unknown signer: {}", settings.signer))?;
+
+        Ok(Some(NftMinter::new(
+            settings.signer.clone(),
+            authority,
+            Duration::from_secs(settings.reservation_secs),
+        )))
+    }
+
+    pub fn claim_distributor(&self) -> ClaimDistributor {
+        ClaimDistributor::new(Duration::from_secs(self.claims.reservation_secs))
+    }
+
+    /// Fee sponsorship, or `None` when sponsored blinks are disabled.
+    pub fn sponsorship(&self) -> Option<Sponsorship> {
+        let settings = &self.sponsorship;
+        settings.enabled.then(|| {
+            Sponsorship::new(
+                settings.max_daily_per_wallet,
+                Duration::from_secs(settings.settle_after_secs),
+            )
+        })
+    }
+
+    pub fn subscription_biller(
+        &self,
+        blockhash_cache: Arc<BlockhashCache>,
+        signer_vault: Arc<SignerVault>,
+    ) -> SubscriptionBiller {
+        SubscriptionBiller::new(
+            blockhash_cache,
+            signer_vault,
+            Duration::from_secs(self.subscriptions.pending_secs),
+        )
+    }
+
+    pub fn escrow_agent(&self) -> EscrowAgent {
+        EscrowAgent::new(Duration::from_secs(self.escrow.pending_secs))
+    }
+
+    pub fn ticket_booth(&self) -> TicketBooth {
+        TicketBooth::new(Duration::from_secs(self.raffles.pending_secs))
+    }
+
+    /// The swap router, or `None` when swap blinks are disabled.
+    pub fn swap_router(&self) -> Result<Option<SwapRouter>, String> {
+        let settings = &self.swaps;
+        if !settings.enabled {
+            return Ok(None);
+        }
+        if settings.quote_urls.is_empty() {
+            return Err("At least one quote URL is required".to_string());
+        }
+        let timeout = Duration::from_millis(settings.timeout_ms);
+        let providers = settings
+            .quote_urls
+            .iter()
+            .map(|url| {
+                JupiterClient::new(url, timeout)
+                    .map(|client| Arc::new(client) as Arc<dyn QuoteProvider>)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let allowed_programs = settings
+            .allowed_programs
+            .iter()
+            .map(|program| {
+                Pubkey::from_str(program).map_err(|e| format!("Invalid program {}: {}", program, e))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Some(SwapRouter::new(providers, allowed_programs)))
+    }
+
+    pub fn signer_vault(&self) -> Result<SignerVault, String> {
+        let mut keys = HashMap::new();
+        for settings in &self.signers {
+            let key = settings
+                .vault_key()
+                .map_err(|e| format!("Signer {}: {}", settings.name, e))?;
+            if keys.insert(settings.name.clone(), key).is_some() {
+                return Err(format!("Duplicate signer: {}", settings.name));
+            }
+        }
+        Ok(SignerVault::new(keys))
+    }
+}
+
+impl SignerSettings {
+    fn vault_key(&self) -> Result<VaultKey, String> {
+        let keypair = match (&self.keypair, &self.keypair_env, &self.keypair_file) {
+            (Some(keypair), None, None) => parse_keypair(keypair.expose_secret())?,
+            (None, Some(var), None) => {
+                let keypair =
+                    SecretString::from(std::env::var(var).map_err(|e| format!("{}: {}", var, e))?);
+                parse_keypair(keypair.expose_secret())?
+            }
+            (None, None, Some(path)) => {
+                let var = self
+                    .passphrase_env
+                    .as_ref()
+                    .ok_or("`keypair_file` needs `passphrase_env`")?;
+                let passphrase =
+                    SecretString::from(std::env::var(var).map_err(|e| format!("{}: {}", var, e))?);
+                let contents = std::fs::read_to_string(path)
+                    .map_err(|e| format!("Failed to read {}: {}", path, e))?;
+                signer_vault::unseal(&contents, &passphrase)?
+            }
+            _ => {
+                return Err(
+                    "Set exactly one of `keypair`, `keypair_env` and `keypair_file`".to_string(),
+                );
+            }
+        };
+        let programs = self
+            .programs
+            .iter()
+            .map(|rule| {
+                Ok(ProgramRule {
+                    program_id: Pubkey::from_str(&rule.program_id)
+                        .map_err(|e| format!("Invalid program {}: {}", rule.program_id, e))?,
+                    data_prefixes: rule.data_prefixes.clone(),
+                })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        Ok(VaultKey::new(
+            keypair,
+            SigningPolicy {
+                owners: self.owners.clone(),
+                programs,
+            },
+        ))
+    }
+}
+
+#[derive(Deserialize, Clone)]
+pub struct MetadataCacheSettings {
+    /// How long rendered metadata is served from memory. Bounds staleness on
+    /// instances that did not handle an edit. Zero disables the cache.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub ttl_secs: u64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_entries: usize,
+    /// `max-age` of the `Cache-Control` header on action metadata.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_age_secs: u64,
+}
+
+impl MetadataCacheSettings {
+    pub fn cache(&self) -> MetadataCache {
+        MetadataCache::new(
+            Duration::from_secs(self.ttl_secs),
+            self.max_entries,
+            Duration::from_secs(self.max_age_secs),
+        )
+    }
+}
+
+#[derive(Deserialize, Clone)]
+pub struct RateLimitSettings {
+    pub enabled: bool,
+    /// Proxies whose `X-Forwarded-For` header is trusted by the
+    /// `forwarded_for` key.
+    #[serde(default)]
+    pub trusted_proxies: Vec<IpNet>,
+    /// Blink management: `POST /api/blinks` and the `/api/blinks/*` routes.
+    pub blinks: RouteRateLimit,
+    /// Public action endpoints: `GET` and `POST /api/actions/{id}`.
+    pub actions: RouteRateLimit,
+    /// Share pages and `actions.json`.
+    pub pages: RouteRateLimit,
+    /// Blink management requests that present an API key.
+    pub api_keys: RouteRateLimit,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct RouteRateLimit {
+    /// Interval after which one request of the quota is replenished.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub period_ms: u64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub burst_size: u32,
+    pub key: RateLimitKey,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RateLimitKey {
+    /// The address of the TCP peer.
+    PeerIp,
+    /// The client address from `X-Forwarded-For`, trusted only when the peer
+    /// is one of `trusted_proxies`.
+    ForwardedFor,
+    /// The bearer token in the `Authorization` header.
+    ApiKey,
+    /// The `account` paying for an action transaction.
+    Payer,
+}
+
+impl ActionsSettings {
+    pub fn rule_set(&self) -> Result<ActionRuleSet, String> {
+        self.rules
+            .iter()
+            .map(|rule| {
+                ActionPathRule::parse(&rule.path_pattern, rule.api_path.as_deref(), rule.exclude)
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .and_then(ActionRuleSet::new)
+    }
+}
+
 impl DatabaseSettings {
     pub fn connect_options(&self) -> PgConnectOptions {
         let ssl_mode = if self.require_ssl {
@@ -81,7 +567,20 @@ pub fn get_configuration() -> Result<Settings, ConfigError> {
         )
         .build()?;
 
-    settings.try_deserialize::<Settings>()
+    let mut settings = settings.try_deserialize::<Settings>()?;
+    if let Ok(rpc_url) = std::env::var("RPC_URL")
+        && !rpc_url.trim().is_empty()
+    {
+        settings.solana.rpc_endpoints = rpc_url
+            .split(',')
+            .map(|url| RpcEndpointSettings {
+                url: url.trim().to_string(),
+                weight: default_rpc_weight(),
+            })
+            .collect();
+    }
+
+    Ok(settings)
 }
 
 pub enum Environment {
@@ -113,3 +612,32 @@ impl TryFrom<String> for Environment {
         }
     }
 }
+
+/// Parses a base58-encoded keypair, as printed by `solana-keygen`.
+fn parse_keypair(encoded: &str) -> Result<Keypair, String> {
+    let bytes = solana_sdk::bs58::decode(encoded.trim())
+        .into_vec()
+        .map_err(|e| format!("Keypair is not valid base58: {}", e))?;
+    Keypair::from_bytes(&bytes).map_err(|e| format!("Invalid keypair: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::signature::Signer;
+
+    #[test]
+    fn parses_base58_keypair() {
+        let keypair = Keypair::new();
+
+        let parsed = parse_keypair(&keypair.to_base58_string()).unwrap();
+
+        assert_eq!(parsed.pubkey(), keypair.pubkey());
+    }
+
+    #[test]
+    fn rejects_malformed_keypairs() {
+        assert!(parse_keypair("not-base58-0OIl").is_err());
+        assert!(parse_keypair(&Pubkey::new_unique().to_string()).is_err());
+    }
+}
@@ -0,0 +1,428 @@
+use crate::models::ActionRule;
+
+/// A validated `actions.json` rule.
+///
+/// Patterns follow the Solana Actions glob semantics: `*` matches exactly one
+/// path segment and `**` matches zero or more segments and may only appear as
+/// the last segment. Wildcards in `api_path` are filled, in order, with the
+/// values captured by the wildcards of `path_pattern`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActionPathRule {
+    path_pattern: String,
+    api_path: String,
+    exclude: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Wildcard {
+    Single,
+    Many,
+}
+
+impl ActionPathRule {
+    /// Parses a rule. A missing `api_path` makes the rule idempotent (the
+    /// pattern maps onto itself). Exclusions only carry a pattern.
+    pub fn parse(
+        path_pattern: &str,
+        api_path: Option<&str>,
+        exclude: bool,
+    ) -> Result<ActionPathRule, String> {
+        if !path_pattern.starts_with('/') {
+            return Err(format!(
+                "Path pattern '{}' must be an absolute pathname",
+                path_pattern
+            ));
+        }
+        if path_pattern.contains('?') || path_pattern.contains('#') {
+            return Err(format!(
+                "Path pattern '{}' may not contain a query string or fragment",
+                path_pattern
+            ));
+        }
+        let pattern_wildcards = wildcards(path_pattern)?;
+
+        if exclude {
+            if api_path.is_some() {
+                return Err(format!(
+                    "Exclusion '{}' may not have an API path",
+                    path_pattern
+                ));
+            }
+            return Ok(Self {
+                path_pattern: path_pattern.to_string(),
+                api_path: String::new(),
+                exclude,
+            });
+        }
+
+        let api_path = api_path.unwrap_or(path_pattern);
+        let api_path_only = match api_path.split_once("://") {
+            Some((scheme, rest)) if scheme == "https" || scheme == "http" => {
+                rest.find('/').map(|i| &rest[i..]).unwrap_or("/")
+            }
+            Some(_) => {
+                return Err(format!("API path '{}' must use http or https", api_path));
+            }
+            None if api_path.starts_with('/') => api_path,
+            None => {
+                return Err(format!(
+                    "API path '{}' must be an absolute pathname or URL",
+                    api_path
+                ));
+            }
+        };
+        let api_wildcards = wildcards(api_path_only.split('?').next().unwrap_or_default())?;
+
+        if api_wildcards.len() > pattern_wildcards.len()
+            || api_wildcards
+                .iter()
+                .zip(&pattern_wildcards)
+                .any(|(api, pattern)| api != pattern)
+        {
+            return Err(format!(
+                "Wildcards in API path '{}' do not match path pattern '{}'",
+                api_path, path_pattern
+            ));
+        }
+
+        Ok(Self {
+            path_pattern: path_pattern.to_string(),
+            api_path: api_path.to_string(),
+            exclude,
+        })
+    }
+
+    pub fn is_exclusion(&self) -> bool {
+        self.exclude
+    }
+
+    /// Returns the wildcard captures if `path` matches this rule's pattern.
+    fn captures(&self, path: &str) -> Option<Vec<String>> {
+        let path = path.split(['?', '#']).next().unwrap_or_default();
+        let mut pattern = self.path_pattern.split('/');
+        let mut segments = path.split('/');
+        let mut captures = Vec::new();
+
+        loop {
+            match (pattern.next(), segments.next()) {
+                (Some("**"), Some(segment)) => {
+                    let rest: Vec<&str> = std::iter::once(segment).chain(segments).collect();
+                    captures.push(rest.join("/"));
+                    return Some(captures);
+                }
+                (Some("**"), None) => {
+                    captures.push(String::new());
+                    return Some(captures);
+                }
+                (Some("*"), Some(segment)) if !segment.is_empty() => {
+                    captures.push(segment.to_string())
+                }
+                (Some(expected), Some(segment)) if expected == segment => {}
+                (None, None) => return Some(captures),
+                _ => return None,
+            }
+        }
+    }
+
+    /// Maps a website path onto this rule's API path.
+    fn map(&self, path: &str) -> Option<String> {
+        let captures = self.captures(path)?;
+        let mut captures = captures.into_iter();
+        let mapped = self
+            .api_path
+            .split('/')
+            .map(|segment| match segment {
+                "*" | "**" => captures.next().unwrap_or_default(),
+                _ => segment.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("/");
+        Some(mapped.trim_end_matches('/').to_string())
+    }
+
+    /// Whether every path matched by `other` is also matched by this rule.
+    fn covers(&self, other: &ActionPathRule) -> bool {
+        let mut pattern = self.path_pattern.split('/');
+        let mut others = other.path_pattern.split('/');
+
+        loop {
+            match (pattern.next(), others.next()) {
+                (Some("**"), _) => return true,
+                (_, Some("**")) => return false,
+                (Some("*"), Some(other)) if !other.is_empty() => {}
+                (Some(expected), Some(other)) if expected == other => {}
+                (None, None) => return true,
+                _ => return false,
+            }
+        }
+    }
+
+    /// Whether some path is matched by both this rule and `other`.
+    fn overlaps(&self, other: &ActionPathRule) -> bool {
+        let mut pattern = self.path_pattern.split('/');
+        let mut others = other.path_pattern.split('/');
+
+        loop {
+            match (pattern.next(), others.next()) {
+                (Some("**"), _) | (_, Some("**")) => return true,
+                (Some("*"), Some("*")) => {}
+                (Some("*"), Some(segment)) | (Some(segment), Some("*")) if !segment.is_empty() => {}
+                (Some(expected), Some(segment)) if expected == segment => {}
+                (None, None) => return true,
+                _ => return false,
+            }
+        }
+    }
+
+    /// The rule as served in `actions.json`. Relative API paths are made
+    /// absolute against `base_url`.
+    pub fn to_action_rule(&self, base_url: &str) -> ActionRule {
+        let api_path = if self.api_path.starts_with('/') {
+            format!("{}{}", base_url.trim_end_matches('/'), self.api_path)
+        } else {
+            self.api_path.clone()
+        };
+        ActionRule {
+            path_pattern: self.path_pattern.clone(),
+            api_path,
+        }
+    }
+}
+
+/// An ordered list of rules, evaluated first match wins.
+///
+/// `actions.json` has no notion of exclusions, so an exclusion may only
+/// precede rules it either covers entirely or does not overlap at all: those
+/// rules can then be dropped from `actions.json` without changing which paths
+/// it maps.
+#[derive(Debug, Clone, Default)]
+pub struct ActionRuleSet(Vec<ActionPathRule>);
+
+impl ActionRuleSet {
+    pub fn new(rules: Vec<ActionPathRule>) -> Result<Self, String> {
+        let mut set = Self::default();
+        for rule in rules {
+            set.push(rule)?;
+        }
+        Ok(set)
+    }
+
+    /// Appends a rule, rejecting it when an earlier exclusion covers only
+    /// part of its paths.
+    pub fn push(&mut self, rule: ActionPathRule) -> Result<(), String> {
+        if !rule.is_exclusion()
+            && let Some(exclusion) = self.0.iter().find(|earlier| {
+                earlier.is_exclusion() && earlier.overlaps(&rule) && !earlier.covers(&rule)
+            })
+        {
+            return Err(format!(
+                "Exclusion '{}' covers only part of '{}', which actions.json cannot express",
+                exclusion.path_pattern, rule.path_pattern
+            ));
+        }
+        self.0.push(rule);
+        Ok(())
+    }
+
+    /// Resolves a website path to its action API path, or `None` when the
+    /// path is excluded or not covered by any rule.
+    pub fn resolve(&self, path: &str) -> Option<String> {
+        self.0
+            .iter()
+            .find(|rule| rule.captures(path).is_some())
+            .filter(|rule| !rule.is_exclusion())
+            .and_then(|rule| rule.map(path))
+    }
+
+    /// The rules to publish in `actions.json`. Exclusions are applied by
+    /// dropping the later rules they cover and are not published themselves.
+    pub fn to_action_rules(&self, base_url: &str) -> Vec<ActionRule> {
+        self.0
+            .iter()
+            .enumerate()
+            .filter(|(index, rule)| {
+                !rule.is_exclusion()
+                    && !self.0[..*index]
+                        .iter()
+                        .any(|earlier| earlier.is_exclusion() && earlier.covers(rule))
+            })
+            .map(|(_, rule)| rule.to_action_rule(base_url))
+            .collect()
+    }
+}
+
+fn wildcards(path: &str) -> Result<Vec<Wildcard>, String> {
+    let segments: Vec<&str> = path.split('/').collect();
+    let mut wildcards = Vec::new();
+
+    for (index, segment) in segments.iter().enumerate() {
+        match *segment {
+            "*" => wildcards.push(Wildcard::Single),
+            "**" if index == segments.len() - 1 => wildcards.push(Wildcard::Many),
+            "**" => return Err(format!("'**' must be the last segment in '{}'", path)),
+            s if s.contains('*') => {
+                return Err(format!(
+                    "Wildcards must span a whole path segment in '{}'",
+                    path
+                ));
+            }
+            _ => {}
+        }
+    }
+
+    Ok(wildcards)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ActionPathRule, ActionRuleSet};
+
+    fn rule(path_pattern: &str, api_path: Option<&str>) -> ActionPathRule {
+        ActionPathRule::parse(path_pattern, api_path, false).unwrap()
+    }
+
+    fn exclusion(path_pattern: &str) -> ActionPathRule {
+        ActionPathRule::parse(path_pattern, None, true).unwrap()
+    }
+
+    #[test]
+    fn literal_rules_map_exact_paths() {
+        let rules = ActionRuleSet::new(vec![rule("/donate", Some("/api/actions/coffee"))]).unwrap();
+
+        assert_eq!(
+            rules.resolve("/donate").as_deref(),
+            Some("/api/actions/coffee")
+        );
+        assert_eq!(rules.resolve("/donate/more"), None);
+    }
+
+    #[test]
+    fn single_wildcard_matches_one_segment() {
+        let rules = ActionRuleSet::new(vec![rule("/b/*", Some("/api/actions/*"))]).unwrap();
+
+        assert_eq!(
+            rules.resolve("/b/coffee").as_deref(),
+            Some("/api/actions/coffee")
+        );
+        assert_eq!(rules.resolve("/b/coffee/extra"), None);
+        assert_eq!(rules.resolve("/b/"), None);
+    }
+
+    #[test]
+    fn double_wildcard_matches_remaining_segments() {
+        let rules = ActionRuleSet::new(vec![rule(
+            "/campaigns/**",
+            Some("https://api.example.com/api/actions/**"),
+        )])
+        .unwrap();
+
+        assert_eq!(
+            rules.resolve("/campaigns/spring/coffee").as_deref(),
+            Some("https://api.example.com/api/actions/spring/coffee")
+        );
+    }
+
+    #[test]
+    fn idempotent_rules_map_onto_themselves() {
+        let rules = ActionRuleSet::new(vec![rule("/api/actions/**", None)]).unwrap();
+
+        assert_eq!(
+            rules.resolve("/api/actions/coffee?amount=1").as_deref(),
+            Some("/api/actions/coffee")
+        );
+    }
+
+    #[test]
+    fn exclusions_take_precedence_over_later_rules() {
+        let rules = ActionRuleSet::new(vec![
+            exclusion("/drafts/**"),
+            rule("/drafts/coffee", Some("/api/actions/coffee")),
+            rule("/b/*", Some("/api/actions/*")),
+        ])
+        .unwrap();
+
+        assert_eq!(rules.resolve("/drafts/coffee"), None);
+        assert_eq!(
+            rules.resolve("/b/coffee").as_deref(),
+            Some("/api/actions/coffee")
+        );
+    }
+
+    #[test]
+    fn exclusions_drop_covered_rules_from_actions_json() {
+        let rules = ActionRuleSet::new(vec![
+            exclusion("/blog/**"),
+            rule("/blog/donate", Some("/api/actions/coffee")),
+            rule("/b/*", Some("/api/actions/*")),
+        ])
+        .unwrap();
+
+        let published = rules.to_action_rules("https://blinkzero.example");
+
+        assert_eq!(published.len(), 1);
+        assert_eq!(published[0].path_pattern, "/b/*");
+        assert_eq!(
+            published[0].api_path,
+            "https://blinkzero.example/api/actions/*"
+        );
+    }
+
+    #[test]
+    fn exclusions_covering_part_of_a_later_rule_are_rejected() {
+        let cases = [
+            ("/b/about", "/b/*"),
+            ("/b/*/edit", "/b/**"),
+            ("/b/*", "/b/**"),
+        ];
+
+        for (excluded, path_pattern) in cases {
+            let rules = vec![exclusion(excluded), rule(path_pattern, None)];
+            assert!(
+                ActionRuleSet::new(rules).is_err(),
+                "{} before {}",
+                excluded,
+                path_pattern
+            );
+        }
+
+        // Rules before the exclusion already win, and rules it covers or
+        // cannot match are unaffected.
+        assert!(ActionRuleSet::new(vec![exclusion("/**"), rule("/b/*", None)]).is_ok());
+        assert!(
+            ActionRuleSet::new(vec![
+                rule("/b/*", None),
+                exclusion("/b/about"),
+                rule("/blog/*", None),
+            ])
+            .is_ok()
+        );
+    }
+
+    #[test]
+    fn invalid_patterns_are_rejected() {
+        let cases = [
+            ("b/*", Some("/api/actions/*")),
+            ("/b/**/x", Some("/api/actions/**")),
+            ("/b/pre-*", Some("/api/actions/*")),
+            ("/b?x=1", Some("/api/actions/x")),
+            ("/b/*", Some("api/actions/*")),
+            ("/b/*", Some("ftp://example.com/*")),
+            ("/b/*", Some("/api/actions/*/*")),
+            ("/b/*", Some("/api/actions/**")),
+        ];
+
+        for (path_pattern, api_path) in cases {
+            assert!(
+                ActionPathRule::parse(path_pattern, api_path, false).is_err(),
+                "{} -> {:?}",
+                path_pattern,
+                api_path
+            );
+        }
+    }
+
+    #[test]
+    fn exclusions_may_not_have_an_api_path() {
+        assert!(ActionPathRule::parse("/b/*", Some("/api/actions/*"), true).is_err());
+    }
+}
@@ -0,0 +1,173 @@
+use solana_sdk::{
+    hash::{Hash, hashv},
+    pubkey::Pubkey,
+};
+
+/// Most wallets one allowlist can hold.
+pub const MAX_ALLOWLIST_ENTRIES: usize = 10_000;
+
+/// Wallets of a claim blink and the lamports each may claim, committed to by
+/// a Merkle root.
+///
+/// Leaves hash `0x00 || wallet || lamports` (little-endian) and inner nodes
+/// hash `0x01` followed by their two children in byte order, so proofs need
+/// no left/right flags. A node without a sibling moves up unchanged.
+#[derive(Debug)]
+pub struct Allowlist {
+    /// Sorted by wallet.
+    entries: Vec<(Pubkey, u64)>,
+    /// Leaves first, root last.
+    levels: Vec<Vec<Hash>>,
+}
+
+impl Allowlist {
+    pub fn new(mut entries: Vec<(Pubkey, u64)>) -> Result<Allowlist, String> {
+        if entries.is_empty() {
+            return Err("The allowlist is empty".to_string());
+        }
+        if entries.len() > MAX_ALLOWLIST_ENTRIES {
+            return Err(format!(
+                "The allowlist holds more than {} wallets",
+                MAX_ALLOWLIST_ENTRIES
+            ));
+        }
+        entries.sort_by_key(|(wallet, _)| *wallet);
+        if let Some(pair) = entries.windows(2).find(|pair| pair[0].0 == pair[1].0) {
+            return Err(format!("Duplicate wallet: {}", pair[0].0));
+        }
+        if let Some((wallet, _)) = entries.iter().find(|(_, lamports)| *lamports == 0) {
+            return Err(format!("Allocation of {} must be positive", wallet));
+        }
+        entries
+            .iter()
+            .try_fold(0i64, |total, (_, lamports)| {
+                i64::try_from(*lamports)
+                    .ok()
+                    .and_then(|lamports| total.checked_add(lamports))
+            })
+            .ok_or("The allowlist total is too large")?;
+
+        let mut levels = vec![
+            entries
+                .iter()
+                .map(|(wallet, lamports)| leaf(wallet, *lamports))
+                .collect::<Vec<_>>(),
+        ];
+        while levels.last().unwrap().len() > 1 {
+            let next = levels
+                .last()
+                .unwrap()
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => node(left, right),
+                    [single] => *single,
+                    _ => unreachable!(),
+                })
+                .collect();
+            levels.push(next);
+        }
+
+        Ok(Allowlist { entries, levels })
+    }
+
+    pub fn root(&self) -> Hash {
+        self.levels.last().unwrap()[0]
+    }
+
+    /// Entries sorted by wallet.
+    pub fn entries(&self) -> &[(Pubkey, u64)] {
+        &self.entries
+    }
+
+    pub fn total_lamports(&self) -> u64 {
+        self.entries.iter().map(|(_, lamports)| lamports).sum()
+    }
+
+    /// Sibling hashes from the leaf of entry `index` up to the root.
+    pub fn proof(&self, index: usize) -> Vec<Hash> {
+        let mut proof = Vec::new();
+        let mut index = index;
+        for level in &self.levels[..self.levels.len() - 1] {
+            if let Some(sibling) = level.get(index ^ 1) {
+                proof.push(*sibling);
+            }
+            index /= 2;
+        }
+        proof
+    }
+}
+
+/// Whether `proof` shows that `wallet` may claim `lamports` under `root`.
+pub fn verify_allocation(root: &Hash, wallet: &Pubkey, lamports: u64, proof: &[Hash]) -> bool {
+    proof
+        .iter()
+        .fold(leaf(wallet, lamports), |hash, sibling| node(&hash, sibling))
+        == *root
+}
+
+fn leaf(wallet: &Pubkey, lamports: u64) -> Hash {
+    hashv(&[&[0], wallet.as_ref(), &lamports.to_le_bytes()])
+}
+
+fn node(a: &Hash, b: &Hash) -> Hash {
+    let (left, right) = if a.as_ref() <= b.as_ref() {
+        (a, b)
+    } else {
+        (b, a)
+    };
+    hashv(&[&[1], left.as_ref(), right.as_ref()])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allowlist(size: u64) -> Allowlist {
+        Allowlist::new(
+            (1..=size)
+                .map(|lamports| (Pubkey::new_unique(), lamports))
+                .collect(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn every_entry_proves_against_the_root() {
+        for size in [1, 2, 5, 8] {
+            let allowlist = allowlist(size);
+            let root = allowlist.root();
+
+            for (index, (wallet, lamports)) in allowlist.entries().iter().enumerate() {
+                let proof = allowlist.proof(index);
+                assert!(verify_allocation(&root, wallet, *lamports, &proof));
+                assert!(!verify_allocation(&root, wallet, lamports + 1, &proof));
+            }
+        }
+    }
+
+    #[test]
+    fn root_does_not_depend_on_upload_order() {
+        let entries = vec![
+            (Pubkey::new_unique(), 1),
+            (Pubkey::new_unique(), 2),
+            (Pubkey::new_unique(), 3),
+        ];
+        let mut reversed = entries.clone();
+        reversed.reverse();
+
+        assert_eq!(
+            Allowlist::new(entries).unwrap().root(),
+            Allowlist::new(reversed).unwrap().root()
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_allowlists() {
+        let wallet = Pubkey::new_unique();
+
+        assert!(Allowlist::new(vec![]).is_err());
+        assert!(Allowlist::new(vec![(wallet, 1), (wallet, 2)]).is_err());
+        assert!(Allowlist::new(vec![(wallet, 0)]).is_err());
+        assert!(Allowlist::new(vec![(wallet, u64::MAX)]).is_err());
+    }
+}
@@ -0,0 +1,123 @@
+use super::contains_profanity;
+
+const MIN_LENGTH: usize = 3;
+const MAX_LENGTH: usize = 48;
+
+/// Path segments and words that would collide with our own routes or be
+/// mistaken for official pages.
+const RESERVED_SLUGS: &[&str] = &[
+    "actions",
+    "admin",
+    "api",
+    "b",
+    "blink",
+    "blinks",
+    "blinkzero",
+    "dashboard",
+    "health",
+    "help",
+    "login",
+    "new",
+    "official",
+    "settings",
+    "solana",
+    "support",
+    "well-known",
+];
+
+/// A validated, owner-chosen blink slug: lowercase ASCII letters, digits and
+/// single hyphens.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlinkSlug(String);
+
+impl BlinkSlug {
+    pub fn parse(s: String) -> Result<BlinkSlug, String> {
+        let slug = s.trim().to_lowercase();
+
+        if slug.len() < MIN_LENGTH || slug.len() > MAX_LENGTH {
+            return Err(format!(
+                "Slug must be between {} and {} characters long",
+                MIN_LENGTH, MAX_LENGTH
+            ));
+        }
+
+        if !slug
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        {
+            return Err("Slug may only contain letters, digits and hyphens".to_string());
+        }
+
+        if slug.starts_with('-') || slug.ends_with('-') || slug.contains("--") {
+            return Err("Slug may not start or end with a hyphen or contain '--'".to_string());
+        }
+
+        if uuid::Uuid::parse_str(&slug).is_ok() {
+            return Err("Slug may not be a UUID".to_string());
+        }
+
+        if RESERVED_SLUGS.contains(&slug.as_str()) {
+            return Err(format!("Slug '{}' is reserved", slug));
+        }
+
+        if contains_profanity(&slug) {
+            return Err("Slug contains disallowed words".to_string());
+        }
+
+        Ok(Self(slug))
+    }
+}
+
+impl AsRef<str> for BlinkSlug {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BlinkSlug;
+
+    #[test]
+    fn valid_slugs_are_accepted_and_lowercased() {
+        let slug = BlinkSlug::parse("Coffee-For-Devs-2025".to_string()).unwrap();
+        assert_eq!(slug.as_ref(), "coffee-for-devs-2025");
+    }
+
+    #[test]
+    fn slugs_with_invalid_length_are_rejected() {
+        assert!(BlinkSlug::parse("ab".to_string()).is_err());
+        assert!(BlinkSlug::parse("a".repeat(49)).is_err());
+    }
+
+    #[test]
+    fn slugs_with_invalid_characters_are_rejected() {
+        for slug in ["with space", "under_score", "emoji-🚀", "a/b", "q?x=1"] {
+            assert!(BlinkSlug::parse(slug.to_string()).is_err(), "{}", slug);
+        }
+    }
+
+    #[test]
+    fn badly_placed_hyphens_are_rejected() {
+        for slug in ["-leading", "trailing-", "double--hyphen"] {
+            assert!(BlinkSlug::parse(slug.to_string()).is_err(), "{}", slug);
+        }
+    }
+
+    #[test]
+    fn reserved_words_are_rejected() {
+        assert!(BlinkSlug::parse("api".to_string()).is_err());
+        assert!(BlinkSlug::parse("Admin".to_string()).is_err());
+    }
+
+    #[test]
+    fn uuids_are_rejected() {
+        let id = uuid::Uuid::new_v4().to_string();
+        assert!(BlinkSlug::parse(id).is_err());
+    }
+
+    #[test]
+    fn profane_slugs_are_rejected() {
+        assert!(BlinkSlug::parse("fuck-this".to_string()).is_err());
+    }
+}
@@ -0,0 +1,41 @@
+use serde::Deserialize;
+
+/// Config of a claim blink:
+///
+/// ```json
+/// { "distributor": "airdrop" }
+/// ```
+///
+/// Allowlisted wallets claim their allocation from the vault key
+/// `distributor`, which also pays the transaction fee so wallets without
+/// SOL can claim.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClaimConfig {
+    pub distributor: String,
+}
+
+#[derive(Deserialize)]
+struct RawClaimConfig {
+    distributor: String,
+    #[serde(default)]
+    durable_nonce: bool,
+}
+
+impl ClaimConfig {
+    pub fn from_config(config: &serde_json::Value) -> Result<ClaimConfig, String> {
+        let raw = RawClaimConfig::deserialize(config)
+            .map_err(|e| format!("Invalid claim config: {}", e))?;
+
+        // Claims are only released once their blockhash has expired.
+        if raw.durable_nonce {
+            return Err("Claim blinks cannot use durable nonces".to_string());
+        }
+        if config.get("sponsor").is_some() {
+            return Err("Claim blinks are paid for by their distributor".to_string());
+        }
+
+        Ok(ClaimConfig {
+            distributor: raw.distributor,
+        })
+    }
+}
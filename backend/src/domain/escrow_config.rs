@@ -0,0 +1,124 @@
+use crate::invoices::SOL;
+use crate::models::EscrowStep;
+use serde::Deserialize;
+use solana_sdk::pubkey::Pubkey;
+use std::str::FromStr;
+
+const MAX_TIMEOUT_DAYS: u32 = 365;
+
+/// Config of an escrow blink:
+///
+/// ```json
+/// { "amount": 2, "currency": "SOL", "escrow": "escrow", "timeout_days": 14, "on_timeout": "release" }
+/// ```
+///
+/// A buyer deposits `amount` of `currency` (`SOL` or a token mint) with the
+/// vault key `escrow`. The buyer can release the deposit to the seller, the
+/// blink's wallet, and the seller can refund it. Once `timeout_days` have
+/// passed since the deposit, either party can take the `on_timeout` step.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EscrowConfig {
+    pub amount: f64,
+    pub currency: String,
+    pub escrow: String,
+    pub timeout_days: u32,
+    pub on_timeout: EscrowStep,
+}
+
+#[derive(Deserialize)]
+struct RawEscrowConfig {
+    amount: f64,
+    #[serde(default = "default_currency")]
+    currency: String,
+    escrow: String,
+    #[serde(default = "default_timeout_days")]
+    timeout_days: u32,
+    #[serde(default = "default_on_timeout")]
+    on_timeout: EscrowStep,
+    #[serde(default)]
+    durable_nonce: bool,
+}
+
+fn default_currency() -> String {
+    SOL.to_string()
+}
+
+fn default_timeout_days() -> u32 {
+    14
+}
+
+fn default_on_timeout() -> EscrowStep {
+    EscrowStep::Release
+}
+
+impl EscrowConfig {
+    pub fn from_config(config: &serde_json::Value) -> Result<EscrowConfig, String> {
+        let raw = RawEscrowConfig::deserialize(config)
+            .map_err(|e| format!("Invalid escrow config: {}", e))?;
+
+        if !(raw.amount.is_finite() && raw.amount > 0.0) {
+            return Err("Escrow amount must be positive".to_string());
+        }
+        if raw.currency != SOL && Pubkey::from_str(&raw.currency).is_err() {
+            return Err(format!(
+                "Currency must be SOL or a token mint: {}",
+                raw.currency
+            ));
+        }
+        if !(1..=MAX_TIMEOUT_DAYS).contains(&raw.timeout_days) {
+            return Err(format!(
+                "Escrow timeout must be 1 to {} days",
+                MAX_TIMEOUT_DAYS
+            ));
+        }
+        if raw.on_timeout == EscrowStep::Deposit {
+            return Err("on_timeout must be release or refund".to_string());
+        }
+        // Only one transfer of a deal may be in flight, which the expiry of
+        // its blockhash bounds.
+        if raw.durable_nonce {
+            return Err("Escrow blinks cannot use durable nonces".to_string());
+        }
+        if config.get("sponsor").is_some() {
+            return Err("Escrow blinks cannot be sponsored".to_string());
+        }
+
+        Ok(EscrowConfig {
+            amount: raw.amount,
+            currency: raw.currency,
+            escrow: raw.escrow,
+            timeout_days: raw.timeout_days,
+            on_timeout: raw.on_timeout,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn releases_to_the_seller_after_two_weeks_by_default() {
+        let config =
+            EscrowConfig::from_config(&json!({ "amount": 2, "escrow": "escrow" })).unwrap();
+
+        assert_eq!(config.currency, "SOL");
+        assert_eq!(config.timeout_days, 14);
+        assert_eq!(config.on_timeout, EscrowStep::Release);
+    }
+
+    #[test]
+    fn rejects_invalid_configs() {
+        for config in [
+            json!({ "amount": 0, "escrow": "escrow" }),
+            json!({ "amount": 1 }),
+            json!({ "amount": 1, "escrow": "escrow", "currency": "DOGE" }),
+            json!({ "amount": 1, "escrow": "escrow", "timeout_days": 0 }),
+            json!({ "amount": 1, "escrow": "escrow", "on_timeout": "deposit" }),
+            json!({ "amount": 1, "escrow": "escrow", "durable_nonce": true }),
+        ] {
+            assert!(EscrowConfig::from_config(&config).is_err(), "{}", config);
+        }
+    }
+}
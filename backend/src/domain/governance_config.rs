@@ -0,0 +1,126 @@
+use serde::Deserialize;
+use solana_sdk::pubkey::Pubkey;
+use std::str::FromStr;
+
+/// The SPL Governance program deployed by Realms.
+pub const GOVERNANCE_PROGRAM_ID: Pubkey =
+    solana_sdk::pubkey!("GovER5Lthms3bLBqWub97yVrMPEFH8H6yHbzpDHp6ibx");
+
+/// Config of a governance blink:
+///
+/// ```json
+/// { "realm": "<realm>", "governance": "<governance>", "proposal": "<proposal>" }
+/// ```
+///
+/// Votes are cast on `proposal` with the governance program, by default the
+/// one Realms uses. Realms running their own deployment set `program_id`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GovernanceConfig {
+    pub program_id: Pubkey,
+    pub realm: Pubkey,
+    pub governance: Pubkey,
+    pub proposal: Pubkey,
+}
+
+#[derive(Deserialize)]
+struct RawGovernanceConfig {
+    program_id: Option<String>,
+    realm: String,
+    governance: String,
+    proposal: String,
+}
+
+impl GovernanceConfig {
+    pub fn from_config(config: &serde_json::Value) -> Result<GovernanceConfig, String> {
+        let raw = RawGovernanceConfig::deserialize(config)
+            .map_err(|e| format!("Invalid governance config: {}", e))?;
+        let key = |value: &str, name: &str| {
+            Pubkey::from_str(value).map_err(|e| format!("Invalid {}: {}", name, e))
+        };
+
+        Ok(GovernanceConfig {
+            program_id: match &raw.program_id {
+                Some(program_id) => key(program_id, "program_id")?,
+                None => GOVERNANCE_PROGRAM_ID,
+            },
+            realm: key(&raw.realm, "realm")?,
+            governance: key(&raw.governance, "governance")?,
+            proposal: key(&raw.proposal, "proposal")?,
+        })
+    }
+}
+
+/// A vote on a single-choice proposal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GovernanceVote {
+    Yes,
+    No,
+    Abstain,
+}
+
+impl GovernanceVote {
+    pub const ALL: [GovernanceVote; 3] = [
+        GovernanceVote::Yes,
+        GovernanceVote::No,
+        GovernanceVote::Abstain,
+    ];
+
+    pub fn parse(value: &str) -> Result<GovernanceVote, String> {
+        match value {
+            "yes" => Ok(GovernanceVote::Yes),
+            "no" => Ok(GovernanceVote::No),
+            "abstain" => Ok(GovernanceVote::Abstain),
+            _ => Err(format!("Vote must be yes, no or abstain: {}", value)),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GovernanceVote::Yes => "yes",
+            GovernanceVote::No => "no",
+            GovernanceVote::Abstain => "abstain",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            GovernanceVote::Yes => "Yes",
+            GovernanceVote::No => "No",
+            GovernanceVote::Abstain => "Abstain",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn defaults_to_the_realms_program() {
+        let config = GovernanceConfig::from_config(&json!({
+            "realm": Pubkey::new_unique().to_string(),
+            "governance": Pubkey::new_unique().to_string(),
+            "proposal": Pubkey::new_unique().to_string()
+        }))
+        .unwrap();
+
+        assert_eq!(config.program_id, GOVERNANCE_PROGRAM_ID);
+    }
+
+    #[test]
+    fn rejects_invalid_addresses() {
+        let key = Pubkey::new_unique().to_string();
+        for config in [
+            json!({ "realm": key, "governance": key }),
+            json!({ "realm": "nope", "governance": key, "proposal": key }),
+            json!({ "program_id": "nope", "realm": key, "governance": key, "proposal": key }),
+        ] {
+            assert!(
+                GovernanceConfig::from_config(&config).is_err(),
+                "{}",
+                config
+            );
+        }
+    }
+}
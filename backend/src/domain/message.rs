@@ -0,0 +1,141 @@
+use serde::Deserialize;
+use std::fmt;
+use uuid::Uuid;
+
+use super::contains_profanity;
+
+/// Longest message, in characters, a blink may allow.
+const MAX_MESSAGE_LENGTH: usize = 280;
+
+/// Longest message in bytes, so the memo fits a transaction whatever the
+/// script it is written in.
+const MAX_MESSAGE_BYTES: usize = 560;
+
+/// Config of a message blink:
+///
+/// ```json
+/// { "max_length": 280, "filter_profanity": true, "tips": true }
+/// ```
+///
+/// Users write up to `max_length` characters, which are posted as a memo.
+/// With `tips`, a SOL tip to the blink's wallet can be sent along.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MessageConfig {
+    pub max_length: usize,
+    pub filter_profanity: bool,
+    pub tips: bool,
+}
+
+#[derive(Deserialize)]
+struct RawMessageConfig {
+    #[serde(default = "default_max_length")]
+    max_length: usize,
+    #[serde(default = "default_filter_profanity")]
+    filter_profanity: bool,
+    #[serde(default)]
+    tips: bool,
+}
+
+fn default_max_length() -> usize {
+    MAX_MESSAGE_LENGTH
+}
+
+fn default_filter_profanity() -> bool {
+    true
+}
+
+impl MessageConfig {
+    pub fn from_config(config: &serde_json::Value) -> Result<MessageConfig, String> {
+        let raw = RawMessageConfig::deserialize(config)
+            .map_err(|e| format!("Invalid message config: {}", e))?;
+
+        if !(1..=MAX_MESSAGE_LENGTH).contains(&raw.max_length) {
+            return Err(format!(
+                "max_length must be 1 to {} characters",
+                MAX_MESSAGE_LENGTH
+            ));
+        }
+
+        Ok(MessageConfig {
+            max_length: raw.max_length,
+            filter_profanity: raw.filter_profanity,
+            tips: raw.tips,
+        })
+    }
+
+    /// Checks a message entered by the user and returns it trimmed.
+    pub fn check_message(&self, text: &str) -> Result<String, String> {
+        let text = text.trim();
+        if text.is_empty() {
+            return Err("Message is empty".to_string());
+        }
+        if text.chars().count() > self.max_length || text.len() > MAX_MESSAGE_BYTES {
+            return Err(format!(
+                "Messages are at most {} characters",
+                self.max_length
+            ));
+        }
+        if text.chars().any(|c| c.is_control() && c != '\n') {
+            return Err("Message contains control characters".to_string());
+        }
+        if self.filter_profanity && contains_profanity(text) {
+            return Err("Message contains a blocked word".to_string());
+        }
+        Ok(text.to_string())
+    }
+}
+
+/// Memo of a message: `msg:<blink>:<text>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MessageMemo {
+    pub blink_id: Uuid,
+    pub text: String,
+}
+
+impl MessageMemo {
+    pub fn parse(memo: &str) -> Option<MessageMemo> {
+        let (blink_id, text) = memo.strip_prefix("msg:")?.split_once(':')?;
+        Some(MessageMemo {
+            blink_id: Uuid::parse_str(blink_id).ok()?,
+            text: text.to_string(),
+        })
+    }
+}
+
+impl fmt::Display for MessageMemo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "msg:{}:{}", self.blink_id, self.text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn checks_messages() {
+        let config = MessageConfig::from_config(&json!({ "max_length": 10 })).unwrap();
+
+        assert_eq!(config.check_message("  gm ☀️ \n").unwrap(), "gm ☀️");
+        assert!(config.check_message("   ").is_err());
+        assert!(config.check_message("hello world!").is_err());
+        assert!(config.check_message("a\u{7}b").is_err());
+        assert!(config.check_message("sh1t").is_err());
+
+        let unfiltered = MessageConfig::from_config(&json!({ "filter_profanity": false })).unwrap();
+        assert!(unfiltered.check_message("sh1t").is_ok());
+        assert!(MessageConfig::from_config(&json!({ "max_length": 0 })).is_err());
+    }
+
+    #[test]
+    fn memos_round_trip_text_with_colons() {
+        let memo = MessageMemo {
+            blink_id: Uuid::new_v4(),
+            text: "Signed: see you at 10:00".to_string(),
+        };
+
+        assert_eq!(MessageMemo::parse(&memo.to_string()), Some(memo));
+        assert_eq!(MessageMemo::parse("vote:x:y"), None);
+    }
+}
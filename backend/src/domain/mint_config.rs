@@ -0,0 +1,152 @@
+use serde::Deserialize;
+use solana_sdk::pubkey::Pubkey;
+use std::str::FromStr;
+
+/// Metaplex limits on metadata fields, in bytes.
+const MAX_NAME_LEN: usize = 32;
+const MAX_SYMBOL_LEN: usize = 10;
+const MAX_URI_LEN: usize = 200;
+
+/// Config of a mint blink:
+///
+/// ```json
+/// {
+///   "collection": "<collection mint>",
+///   "uri": "https://example.com/passes/{number}.json",
+///   "symbol": "PASS",
+///   "price": 0.5,
+///   "supply": 1000,
+///   "per_wallet_limit": 2
+/// }
+/// ```
+///
+/// Every mint is a one-of-one NFT named after the blink title and its
+/// serial number; `{number}` in the URI is replaced by that number.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MintConfig {
+    pub collection: Pubkey,
+    pub uri_template: String,
+    pub symbol: String,
+    /// SOL paid to the blink's wallet per mint.
+    pub price: f64,
+    pub supply: u32,
+    pub per_wallet_limit: Option<u32>,
+}
+
+#[derive(Deserialize)]
+struct RawMintConfig {
+    collection: String,
+    uri: String,
+    #[serde(default)]
+    symbol: String,
+    #[serde(default)]
+    price: f64,
+    supply: u32,
+    per_wallet_limit: Option<u32>,
+    #[serde(default)]
+    durable_nonce: bool,
+}
+
+impl MintConfig {
+    pub fn from_config(config: &serde_json::Value) -> Result<MintConfig, String> {
+        let raw = RawMintConfig::deserialize(config)
+            .map_err(|e| format!("Invalid mint config: {}", e))?;
+        let collection =
+            Pubkey::from_str(&raw.collection).map_err(|e| format!("Invalid collection: {}", e))?;
+
+        if !raw.price.is_finite() || raw.price < 0.0 {
+            return Err("Price must be a non-negative amount of SOL".to_string());
+        }
+        if raw.supply == 0 {
+            return Err("Supply must be at least 1".to_string());
+        }
+        if raw.per_wallet_limit == Some(0) {
+            return Err("Per-wallet limit must be at least 1".to_string());
+        }
+        if raw.symbol.len() > MAX_SYMBOL_LEN {
+            return Err(format!("Symbol is longer than {} bytes", MAX_SYMBOL_LEN));
+        }
+        // Reservations are only released once their blockhash has expired.
+        if raw.durable_nonce {
+            return Err("Mint blinks cannot use durable nonces".to_string());
+        }
+
+        let config = MintConfig {
+            collection,
+            uri_template: raw.uri,
+            symbol: raw.symbol,
+            price: raw.price,
+            supply: raw.supply,
+            per_wallet_limit: raw.per_wallet_limit,
+        };
+        if config.uri(config.supply).len() > MAX_URI_LEN {
+            return Err(format!("URI is longer than {} bytes", MAX_URI_LEN));
+        }
+        Ok(config)
+    }
+
+    pub fn uri(&self, number: u32) -> String {
+        self.uri_template.replace("{number}", &number.to_string())
+    }
+
+    /// `<title> #<number>`, with the title shortened to fit the metadata.
+    pub fn name(&self, title: &str, number: u32) -> String {
+        let suffix = format!(" #{}", number);
+        let mut end = title.len().min(MAX_NAME_LEN - suffix.len());
+        while !title.is_char_boundary(end) {
+            end -= 1;
+        }
+        format!("{}{}", title[..end].trim_end(), suffix)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn config() -> serde_json::Value {
+        json!({
+            "collection": Pubkey::new_unique().to_string(),
+            "uri": "https://example.com/{number}.json",
+            "price": 0.5,
+            "supply": 100
+        })
+    }
+
+    #[test]
+    fn fills_in_the_serial_number() {
+        let config = MintConfig::from_config(&config()).unwrap();
+
+        assert_eq!(config.uri(7), "https://example.com/7.json");
+        assert_eq!(config.name("Pass", 7), "Pass #7");
+        assert_eq!(config.per_wallet_limit, None);
+    }
+
+    #[test]
+    fn shortens_long_titles_to_fit_the_name() {
+        let config = MintConfig::from_config(&config()).unwrap();
+
+        let name = config.name("Ünïcödé titles are much longer than names", 1000);
+
+        assert!(name.len() <= MAX_NAME_LEN);
+        assert!(name.ends_with(" #1000"));
+    }
+
+    #[test]
+    fn rejects_invalid_configs() {
+        for (key, value) in [
+            ("collection", json!("nope")),
+            ("price", json!(-1)),
+            ("supply", json!(0)),
+            ("per_wallet_limit", json!(0)),
+            ("symbol", json!("WAY_TOO_LONG")),
+            ("uri", json!("x".repeat(201))),
+            ("durable_nonce", json!(true)),
+        ] {
+            let mut config = config();
+            config[key] = value;
+            assert!(MintConfig::from_config(&config).is_err(), "{}", key);
+        }
+    }
+}
@@ -0,0 +1,35 @@
+mod action_path_rule;
+mod allowlist;
+mod blink_slug;
+mod claim_config;
+mod escrow_config;
+mod governance_config;
+mod message;
+mod mint_config;
+mod poll;
+mod profanity;
+mod raffle;
+mod sponsor_config;
+mod stake_config;
+mod subscription_config;
+mod swap_config;
+mod token_gate;
+mod vote_weighting;
+
+pub use action_path_rule::{ActionPathRule, ActionRuleSet};
+pub use allowlist::{Allowlist, MAX_ALLOWLIST_ENTRIES, verify_allocation};
+pub use blink_slug::BlinkSlug;
+pub use claim_config::ClaimConfig;
+pub use escrow_config::EscrowConfig;
+pub use governance_config::{GOVERNANCE_PROGRAM_ID, GovernanceConfig, GovernanceVote};
+pub use message::{MessageConfig, MessageMemo};
+pub use mint_config::MintConfig;
+pub use poll::{BallotKind, OptionTally, Poll, PollOption, Round, Tally};
+pub use profanity::contains_profanity;
+pub use raffle::{RaffleConfig, Ticket, draw_winners, ticket_list_hash};
+pub use sponsor_config::SponsorConfig;
+pub use stake_config::StakeConfig;
+pub use subscription_config::SubscriptionConfig;
+pub use swap_config::{SwapConfig, WRAPPED_SOL_MINT};
+pub use token_gate::{GateRequirement, TokenGate};
+pub use vote_weighting::{SnapshotPolicy, VoteMemo, VoteWeighting, WeightClaim};
@@ -0,0 +1,499 @@
+use serde::Deserialize;
+use std::collections::HashSet;
+
+/// The options and ballot of a Vote blink, read from its config:
+///
+/// ```json
+/// {
+///   "options": [{ "id": "a", "label": "Option A" }, { "id": "b", "label": "Option B" }],
+///   "ballot": { "kind": "ranked", "max": 2 }
+/// }
+/// ```
+///
+/// Options given as plain strings use the text as their id. They predate
+/// option ids and are only accepted for single-choice polls.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Poll {
+    pub options: Vec<PollOption>,
+    pub ballot: BallotKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PollOption {
+    pub id: String,
+    pub label: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BallotKind {
+    Single,
+    /// Approval voting: pick between `min` and `max` options.
+    Multi {
+        min: usize,
+        max: usize,
+    },
+    /// Instant-runoff: rank up to `max` options.
+    Ranked {
+        max: usize,
+    },
+}
+
+/// Longest option id. Ids end up in every memo of the poll.
+const MAX_OPTION_ID_LEN: usize = 16;
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawOption {
+    Text(String),
+    Identified { id: String, label: String },
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+enum RawBallot {
+    Single,
+    Multi {
+        min: Option<usize>,
+        max: Option<usize>,
+    },
+    Ranked {
+        max: Option<usize>,
+    },
+}
+
+impl Poll {
+    pub fn from_config(config: &serde_json::Value) -> Result<Poll, String> {
+        let raw_options = config.get("options").ok_or("Vote blinks need options")?;
+        let raw_options = Vec::<RawOption>::deserialize(raw_options)
+            .map_err(|e| format!("Invalid options: {}", e))?;
+        let ballot = match config.get("ballot") {
+            Some(ballot) => {
+                RawBallot::deserialize(ballot).map_err(|e| format!("Invalid ballot: {}", e))?
+            }
+            None => RawBallot::Single,
+        };
+
+        let mut options = Vec::with_capacity(raw_options.len());
+        let mut identified = true;
+        for option in raw_options {
+            let option = match option {
+                RawOption::Text(text) => {
+                    identified = false;
+                    PollOption {
+                        id: text.clone(),
+                        label: text,
+                    }
+                }
+                RawOption::Identified { id, label } => {
+                    let valid = !id.is_empty()
+                        && id.len() <= MAX_OPTION_ID_LEN
+                        && id
+                            .chars()
+                            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+                    if !valid {
+                        return Err(format!(
+                            "Invalid option id '{}': use up to {} letters, digits, '-' or '_'",
+                            id, MAX_OPTION_ID_LEN
+                        ));
+                    }
+                    PollOption { id, label }
+                }
+            };
+            if option.id.is_empty() || option.label.trim().is_empty() {
+                return Err("Options cannot be empty".to_string());
+            }
+            options.push(option);
+        }
+        if options.is_empty() {
+            return Err("Vote blinks need options".to_string());
+        }
+        let mut ids = HashSet::new();
+        if let Some(duplicate) = options.iter().find(|option| !ids.insert(&option.id)) {
+            return Err(format!("Duplicate option '{}'", duplicate.id));
+        }
+
+        let count = options.len();
+        let ballot = match ballot {
+            RawBallot::Single => BallotKind::Single,
+            RawBallot::Multi { min, max } => {
+                let (min, max) = (min.unwrap_or(1), max.unwrap_or(count));
+                if min == 0 || min > max || max > count {
+                    return Err(format!(
+                        "Multi-select polls need 1 <= min <= max <= {} options",
+                        count
+                    ));
+                }
+                BallotKind::Multi { min, max }
+            }
+            RawBallot::Ranked { max } => {
+                let max = max.unwrap_or(count);
+                if max == 0 || max > count {
+                    return Err(format!("Ranked polls rank between 1 and {} options", count));
+                }
+                BallotKind::Ranked { max }
+            }
+        };
+        if !identified && ballot != BallotKind::Single {
+            return Err("Multi-select and ranked polls need options with ids".to_string());
+        }
+
+        Ok(Poll { options, ballot })
+    }
+
+    /// Reads the picks of a `selection` query parameter: a single option id,
+    /// or comma-separated ids in order of preference for multi-select and
+    /// ranked polls. Blank entries, from ranks left open, are ignored.
+    pub fn parse_selection(&self, selection: &str) -> Result<Vec<usize>, String> {
+        let ids: Vec<&str> = match self.ballot {
+            BallotKind::Single => vec![selection],
+            BallotKind::Multi { .. } | BallotKind::Ranked { .. } => selection
+                .split(',')
+                .map(str::trim)
+                .filter(|id| !id.is_empty())
+                .collect(),
+        };
+        let mut picks = ids
+            .into_iter()
+            .map(|id| {
+                self.options
+                    .iter()
+                    .position(|option| option.id == id)
+                    .ok_or_else(|| format!("'{}' is not an option of this poll", id))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        // Approval ballots are unordered.
+        if let BallotKind::Multi { .. } = self.ballot {
+            picks.sort_unstable();
+        }
+        self.check_picks(&picks)?;
+        Ok(picks)
+    }
+
+    /// Encodes picks for the vote memo: option ids joined by `,` for
+    /// multi-select and by `>` in order of preference for ranked polls.
+    pub fn encode(&self, picks: &[usize]) -> String {
+        let separator = match self.ballot {
+            BallotKind::Ranked { .. } => ">",
+            _ => ",",
+        };
+        picks
+            .iter()
+            .map(|&pick| self.options[pick].id.as_str())
+            .collect::<Vec<_>>()
+            .join(separator)
+    }
+
+    /// Reads picks encoded by `encode`, or `None` if they are not a valid
+    /// ballot for this poll.
+    pub fn decode(&self, encoded: &str) -> Option<Vec<usize>> {
+        let ids: Vec<&str> = match self.ballot {
+            BallotKind::Single => vec![encoded],
+            BallotKind::Multi { .. } => encoded.split(',').collect(),
+            BallotKind::Ranked { .. } => encoded.split('>').collect(),
+        };
+        let picks = ids
+            .into_iter()
+            .map(|id| self.options.iter().position(|option| option.id == id))
+            .collect::<Option<Vec<_>>>()?;
+        self.check_picks(&picks).ok()?;
+        Some(picks)
+    }
+
+    /// Human-readable ballot, e.g. `1. Option A, 2. Option B` for a ranking.
+    pub fn describe(&self, picks: &[usize]) -> String {
+        let labels = picks.iter().map(|&pick| self.options[pick].label.as_str());
+        match self.ballot {
+            BallotKind::Ranked { .. } => labels
+                .enumerate()
+                .map(|(rank, label)| format!("{}. {}", rank + 1, label))
+                .collect::<Vec<_>>()
+                .join(", "),
+            _ => labels.collect::<Vec<_>>().join(", "),
+        }
+    }
+
+    fn check_picks(&self, picks: &[usize]) -> Result<(), String> {
+        let unique: HashSet<_> = picks.iter().collect();
+        if unique.len() != picks.len() {
+            return Err("An option can only be picked once".to_string());
+        }
+        match self.ballot {
+            BallotKind::Single if picks.len() != 1 => Err("Pick one option".to_string()),
+            BallotKind::Multi { min, max } if picks.len() < min || picks.len() > max => {
+                Err(format!("Pick between {} and {} options", min, max))
+            }
+            BallotKind::Ranked { max } if picks.is_empty() || picks.len() > max => {
+                Err(format!("Rank between 1 and {} options", max))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Counts ballots, given as `(encoded picks, ballots, summed weight)`.
+    /// Weighted polls decide runoffs by weight, others by ballot count.
+    ///
+    /// Single and multi-select polls credit every picked option. Ranked
+    /// polls report first preferences per option, then run instant-runoff
+    /// rounds: each ballot counts for its highest-ranked remaining option
+    /// and the weakest option is eliminated until one holds a majority of
+    /// the ballots still in play. Ties for last place eliminate the option
+    /// listed last.
+    pub fn tally(&self, ballots: &[(String, i64, u128)], weighted: bool) -> Tally {
+        let ballots: Vec<(Vec<usize>, i64, u128)> = ballots
+            .iter()
+            .filter_map(|(encoded, votes, weight)| {
+                self.decode(encoded).map(|picks| (picks, *votes, *weight))
+            })
+            .collect();
+
+        let mut tally = Tally {
+            options: vec![OptionTally::default(); self.options.len()],
+            ballots: 0,
+            weight: 0,
+            rounds: None,
+            winner: None,
+        };
+        for (picks, votes, weight) in &ballots {
+            tally.ballots += votes;
+            tally.weight += weight;
+            let credited = match self.ballot {
+                BallotKind::Ranked { .. } => &picks[..1],
+                _ => &picks[..],
+            };
+            for &pick in credited {
+                tally.options[pick].votes += votes;
+                tally.options[pick].weight += weight;
+            }
+        }
+
+        if let BallotKind::Ranked { .. } = self.ballot {
+            let (rounds, winner) = self.instant_runoff(&ballots, weighted);
+            tally.rounds = Some(rounds);
+            tally.winner = winner;
+        }
+        tally
+    }
+
+    fn instant_runoff(
+        &self,
+        ballots: &[(Vec<usize>, i64, u128)],
+        weighted: bool,
+    ) -> (Vec<Round>, Option<usize>) {
+        let mut remaining: Vec<usize> = (0..self.options.len()).collect();
+        let mut rounds = Vec::new();
+
+        loop {
+            let mut counts = vec![OptionTally::default(); self.options.len()];
+            for (picks, votes, weight) in ballots {
+                if let Some(&pick) = picks.iter().find(|pick| remaining.contains(pick)) {
+                    counts[pick].votes += votes;
+                    counts[pick].weight += weight;
+                }
+            }
+            let score = |option: usize| {
+                if weighted {
+                    counts[option].weight
+                } else {
+                    counts[option].votes as u128
+                }
+            };
+            let in_play: u128 = remaining.iter().map(|&option| score(option)).sum();
+            let options = remaining
+                .iter()
+                .map(|&option| (option, counts[option].clone()))
+                .collect();
+
+            let leader = remaining
+                .iter()
+                .copied()
+                .max_by_key(|&option| score(option));
+            let winner = leader.filter(|&leader| in_play > 0 && score(leader) * 2 > in_play);
+            if winner.is_some() || remaining.len() <= 1 || in_play == 0 {
+                rounds.push(Round {
+                    options,
+                    eliminated: None,
+                });
+                return (rounds, winner);
+            }
+
+            // `min_by_key` keeps the first minimum, so scan from the end.
+            let weakest = remaining
+                .iter()
+                .rev()
+                .copied()
+                .min_by_key(|&option| score(option))
+                .expect("at least two options remain");
+            remaining.retain(|&option| option != weakest);
+            rounds.push(Round {
+                options,
+                eliminated: Some(weakest),
+            });
+        }
+    }
+}
+
+/// Outcome of `Poll::tally`. Options are indexed like `Poll::options`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tally {
+    pub options: Vec<OptionTally>,
+    pub ballots: i64,
+    pub weight: u128,
+    /// Instant-runoff rounds of a ranked poll.
+    pub rounds: Option<Vec<Round>>,
+    pub winner: Option<usize>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OptionTally {
+    pub votes: i64,
+    pub weight: u128,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Round {
+    /// Options still in the running and the ballots they hold.
+    pub options: Vec<(usize, OptionTally)>,
+    pub eliminated: Option<usize>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn ranked_poll() -> Poll {
+        Poll::from_config(&json!({
+            "options": [
+                { "id": "a", "label": "Alice" },
+                { "id": "b", "label": "Bob" },
+                { "id": "c", "label": "Carol" }
+            ],
+            "ballot": { "kind": "ranked" }
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn plain_string_options_use_their_text_as_id() {
+        let poll = Poll::from_config(&json!({ "options": ["Yes & No", "Maybe, later"] })).unwrap();
+
+        assert_eq!(poll.ballot, BallotKind::Single);
+        assert_eq!(poll.parse_selection("Maybe, later"), Ok(vec![1]));
+        assert_eq!(poll.decode("Yes & No"), Some(vec![0]));
+    }
+
+    #[test]
+    fn rejects_invalid_polls() {
+        for config in [
+            json!({}),
+            json!({ "options": [] }),
+            json!({ "options": ["Yes", "Yes"] }),
+            json!({ "options": [{ "id": "a b", "label": "A" }] }),
+            json!({ "options": [{ "id": "a", "label": " " }] }),
+            json!({ "options": ["Yes", "No"], "ballot": { "kind": "ranked" } }),
+            json!({
+                "options": [{ "id": "a", "label": "A" }],
+                "ballot": { "kind": "multi", "min": 1, "max": 2 }
+            }),
+            json!({
+                "options": [{ "id": "a", "label": "A" }],
+                "ballot": { "kind": "approval" }
+            }),
+        ] {
+            assert!(Poll::from_config(&config).is_err(), "{}", config);
+        }
+    }
+
+    #[test]
+    fn multi_select_enforces_pick_limits() {
+        let poll = Poll::from_config(&json!({
+            "options": [
+                { "id": "a", "label": "A" },
+                { "id": "b", "label": "B" },
+                { "id": "c", "label": "C" }
+            ],
+            "ballot": { "kind": "multi", "min": 1, "max": 2 }
+        }))
+        .unwrap();
+
+        assert_eq!(poll.parse_selection("c,a"), Ok(vec![0, 2]));
+        assert!(poll.parse_selection("a,b,c").is_err());
+        assert!(poll.parse_selection("").is_err());
+        assert!(poll.parse_selection("a,a").is_err());
+        assert_eq!(poll.encode(&[0, 2]), "a,c");
+        assert_eq!(poll.decode("a,c"), Some(vec![0, 2]));
+    }
+
+    #[test]
+    fn ranked_ballots_keep_their_order() {
+        let poll = ranked_poll();
+
+        let picks = poll.parse_selection("c,a,").unwrap();
+
+        assert_eq!(picks, vec![2, 0]);
+        assert_eq!(poll.encode(&picks), "c>a");
+        assert_eq!(poll.decode("c>a"), Some(vec![2, 0]));
+        assert_eq!(poll.describe(&picks), "1. Carol, 2. Alice");
+    }
+
+    #[test]
+    fn approval_credits_every_pick() {
+        let poll = Poll::from_config(&json!({
+            "options": [{ "id": "a", "label": "A" }, { "id": "b", "label": "B" }],
+            "ballot": { "kind": "multi" }
+        }))
+        .unwrap();
+
+        let tally = poll.tally(&[("a,b".to_string(), 2, 0), ("b".to_string(), 1, 0)], false);
+
+        assert_eq!(tally.ballots, 3);
+        assert_eq!(tally.options[0].votes, 2);
+        assert_eq!(tally.options[1].votes, 3);
+        assert_eq!(tally.rounds, None);
+    }
+
+    #[test]
+    fn instant_runoff_transfers_eliminated_ballots() {
+        let poll = ranked_poll();
+
+        let tally = poll.tally(
+            &[
+                ("a>b".to_string(), 4, 0),
+                ("b>a".to_string(), 3, 0),
+                ("c>b".to_string(), 2, 0),
+            ],
+            false,
+        );
+
+        let rounds = tally.rounds.unwrap();
+        assert_eq!(rounds.len(), 2);
+        assert_eq!(rounds[0].eliminated, Some(2));
+        assert_eq!(tally.winner, Some(1));
+        assert_eq!(tally.options[0].votes, 4);
+    }
+
+    #[test]
+    fn weighted_runoffs_are_decided_by_weight() {
+        let poll = ranked_poll();
+
+        let tally = poll.tally(
+            &[
+                ("a".to_string(), 3, 30),
+                ("b".to_string(), 1, 100),
+                ("c".to_string(), 1, 5),
+            ],
+            true,
+        );
+
+        assert_eq!(tally.winner, Some(1));
+        assert_eq!(tally.rounds.unwrap().len(), 1);
+    }
+
+    #[test]
+    fn runoff_without_ballots_has_no_winner() {
+        let tally = ranked_poll().tally(&[], false);
+
+        assert_eq!(tally.winner, None);
+        assert_eq!(tally.rounds.unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,52 @@
+/// Words rejected wherever they appear inside a token.
+const BLOCKED_STEMS: &[&str] = &["fuck", "shit", "cunt", "nigg", "fagg", "whore", "wank"];
+
+/// Words rejected only as whole tokens, since they are common substrings of
+/// harmless words ("cocktail", "dickens", "assess").
+const BLOCKED_WORDS: &[&str] = &[
+    "ass", "asshole", "bastard", "bitch", "cock", "dick", "porn", "pussy", "rape", "slut", "tits",
+];
+
+/// Returns `true` if `text` contains a blocked word, ignoring case, common
+/// letter substitutions and separators between words.
+pub fn contains_profanity(text: &str) -> bool {
+    let normalized: String = text.to_lowercase().chars().map(deobfuscate).collect();
+
+    normalized
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|token| !token.is_empty())
+        .any(|token| {
+            BLOCKED_WORDS.contains(&token) || BLOCKED_STEMS.iter().any(|stem| token.contains(stem))
+        })
+}
+
+fn deobfuscate(c: char) -> char {
+    match c {
+        '0' => 'o',
+        '1' | '!' => 'i',
+        '3' => 'e',
+        '4' | '@' => 'a',
+        '5' | '$' => 's',
+        '7' => 't',
+        _ => c,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::contains_profanity;
+
+    #[test]
+    fn clean_text_is_accepted() {
+        assert!(!contains_profanity("community-cocktail-night"));
+        assert!(!contains_profanity("Charles Dickens reading club"));
+        assert!(!contains_profanity("assess the 2024 grants"));
+    }
+
+    #[test]
+    fn blocked_words_are_rejected() {
+        assert!(contains_profanity("what-the-fuck"));
+        assert!(contains_profanity("Total BITCH move"));
+        assert!(contains_profanity("sh1t-happens"));
+    }
+}
@@ -0,0 +1,178 @@
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+const MAX_TICKETS_PER_WALLET: u32 = 1000;
+const MAX_WINNERS: u32 = 100;
+
+/// Config of a raffle blink:
+///
+/// ```json
+/// { "ticket_price": 0.1, "max_per_wallet": 10, "closes_at": "2026-03-01T18:00:00Z", "winners": 1 }
+/// ```
+///
+/// Tickets cost `ticket_price` SOL and sell until `closes_at`, at most
+/// `max_per_wallet` to a wallet. `winners` distinct wallets are drawn.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RaffleConfig {
+    pub ticket_price: f64,
+    pub max_per_wallet: u32,
+    pub closes_at: DateTime<Utc>,
+    pub winners: u32,
+}
+
+#[derive(Deserialize)]
+struct RawRaffleConfig {
+    ticket_price: f64,
+    #[serde(default = "default_max_per_wallet")]
+    max_per_wallet: u32,
+    closes_at: DateTime<Utc>,
+    #[serde(default = "default_winners")]
+    winners: u32,
+    #[serde(default)]
+    durable_nonce: bool,
+}
+
+fn default_max_per_wallet() -> u32 {
+    10
+}
+
+fn default_winners() -> u32 {
+    1
+}
+
+impl RaffleConfig {
+    pub fn from_config(config: &serde_json::Value) -> Result<RaffleConfig, String> {
+        let raw = RawRaffleConfig::deserialize(config)
+            .map_err(|e| format!("Invalid raffle config: {}", e))?;
+
+        if !(raw.ticket_price.is_finite() && raw.ticket_price > 0.0) {
+            return Err("Ticket price must be positive".to_string());
+        }
+        if !(1..=MAX_TICKETS_PER_WALLET).contains(&raw.max_per_wallet) {
+            return Err(format!(
+                "max_per_wallet must be 1 to {}",
+                MAX_TICKETS_PER_WALLET
+            ));
+        }
+        if !(1..=MAX_WINNERS).contains(&raw.winners) {
+            return Err(format!("A raffle draws 1 to {} winners", MAX_WINNERS));
+        }
+        // Tickets must land before the raffle closes, not whenever a nonce
+        // transaction is submitted.
+        if raw.durable_nonce {
+            return Err("Raffle blinks cannot use durable nonces".to_string());
+        }
+
+        Ok(RaffleConfig {
+            ticket_price: raw.ticket_price,
+            max_per_wallet: raw.max_per_wallet,
+            closes_at: raw.closes_at,
+            winners: raw.winners,
+        })
+    }
+}
+
+/// A sold ticket, numbered from 1 in the order purchases landed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ticket {
+    pub number: u32,
+    pub wallet: String,
+    /// Signature of the purchase the ticket was bought with.
+    pub signature: String,
+}
+
+/// Hex SHA-256 of the ticket list, one `number:wallet:signature` line per
+/// ticket.
+pub fn ticket_list_hash(tickets: &[Ticket]) -> String {
+    let mut hasher = Sha256::new();
+    for ticket in tickets {
+        hasher.update(format!(
+            "{}:{}:{}\n",
+            ticket.number, ticket.wallet, ticket.signature
+        ));
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+/// Draws up to `winners` tickets of distinct wallets.
+///
+/// Draw `k` (from 0) hashes `seed:ticket_list_hash:k` with SHA-256 and picks
+/// the remaining ticket at the first eight bytes, big-endian, modulo the
+/// number of remaining tickets. The winner's other tickets are then removed.
+pub fn draw_winners(seed: &str, tickets: &[Ticket], winners: u32) -> Vec<Ticket> {
+    let list_hash = ticket_list_hash(tickets);
+    let mut remaining = tickets.to_vec();
+    let mut drawn = Vec::new();
+
+    for k in 0..winners {
+        if remaining.is_empty() {
+            break;
+        }
+        let digest = Sha256::digest(format!("{}:{}:{}", seed, list_hash, k));
+        let value = u64::from_be_bytes(digest[..8].try_into().unwrap());
+        let winner = remaining[(value % remaining.len() as u64) as usize].clone();
+        remaining.retain(|ticket| ticket.wallet != winner.wallet);
+        drawn.push(winner);
+    }
+    drawn
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn tickets(wallets: &[&str]) -> Vec<Ticket> {
+        wallets
+            .iter()
+            .enumerate()
+            .map(|(i, wallet)| Ticket {
+                number: i as u32 + 1,
+                wallet: wallet.to_string(),
+                signature: format!("sig{}", i),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn draws_are_reproducible_and_distinct() {
+        let tickets = tickets(&["a", "a", "a", "b", "c"]);
+
+        let drawn = draw_winners("seed", &tickets, 5);
+
+        assert_eq!(drawn, draw_winners("seed", &tickets, 5));
+        let mut wallets: Vec<_> = drawn.iter().map(|t| t.wallet.as_str()).collect();
+        wallets.sort();
+        assert_eq!(wallets, ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn the_list_hash_covers_every_ticket() {
+        let list = tickets(&["a", "b"]);
+        let mut changed = list.clone();
+        changed[1].wallet = "c".to_string();
+
+        assert_eq!(ticket_list_hash(&list).len(), 64);
+        assert_ne!(ticket_list_hash(&list), ticket_list_hash(&changed));
+    }
+
+    #[test]
+    fn validates_configs() {
+        let config = RaffleConfig::from_config(
+            &json!({ "ticket_price": 0.1, "closes_at": "2026-03-01T18:00:00Z" }),
+        )
+        .unwrap();
+        assert_eq!(config.max_per_wallet, 10);
+        assert_eq!(config.winners, 1);
+
+        for config in [
+            json!({ "ticket_price": 0, "closes_at": "2026-03-01T18:00:00Z" }),
+            json!({ "ticket_price": 0.1 }),
+            json!({ "ticket_price": 0.1, "closes_at": "2026-03-01T18:00:00Z", "winners": 0 }),
+            json!({ "ticket_price": 0.1, "closes_at": "2026-03-01T18:00:00Z", "max_per_wallet": 0 }),
+        ] {
+            assert!(RaffleConfig::from_config(&config).is_err(), "{}", config);
+        }
+    }
+}
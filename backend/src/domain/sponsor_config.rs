@@ -0,0 +1,88 @@
+use serde::Deserialize;
+use solana_sdk::native_token::LAMPORTS_PER_SOL;
+
+/// Fee sponsorship of a blink, declared under `config.sponsor`:
+///
+/// ```json
+/// { "signer": "gas", "budget_sol": 0.5, "daily_wallet_limit": 3 }
+/// ```
+///
+/// The vault key `signer` pays the fees of the blink's transactions until
+/// `budget_sol` is spent. Each wallet gets at most `daily_wallet_limit`
+/// sponsored transactions per day.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SponsorConfig {
+    pub signer: String,
+    pub budget_lamports: u64,
+    pub daily_wallet_limit: u32,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawSponsorConfig {
+    signer: String,
+    budget_sol: f64,
+    #[serde(default = "default_daily_wallet_limit")]
+    daily_wallet_limit: u32,
+}
+
+fn default_daily_wallet_limit() -> u32 {
+    1
+}
+
+impl SponsorConfig {
+    pub fn from_config(config: &serde_json::Value) -> Result<Option<SponsorConfig>, String> {
+        let Some(sponsor) = config.get("sponsor") else {
+            return Ok(None);
+        };
+        let raw = RawSponsorConfig::deserialize(sponsor)
+            .map_err(|e| format!("Invalid sponsor: {}", e))?;
+
+        if !(raw.budget_sol.is_finite() && raw.budget_sol > 0.0) {
+            return Err("Sponsor budget_sol must be positive".to_string());
+        }
+        if raw.daily_wallet_limit == 0 {
+            return Err("Sponsor daily_wallet_limit must be at least 1".to_string());
+        }
+        // Sponsored transactions are built on a recent blockhash so that
+        // unused ones expire and give their fee back to the budget.
+        if config.get("durable_nonce").and_then(|v| v.as_bool()) == Some(true) {
+            return Err("Sponsored blinks cannot use durable nonces".to_string());
+        }
+
+        Ok(Some(SponsorConfig {
+            signer: raw.signer,
+            budget_lamports: (raw.budget_sol * LAMPORTS_PER_SOL as f64) as u64,
+            daily_wallet_limit: raw.daily_wallet_limit,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn reads_the_budget_in_lamports() {
+        let config = json!({ "sponsor": { "signer": "gas", "budget_sol": 0.5 } });
+
+        let sponsor = SponsorConfig::from_config(&config).unwrap().unwrap();
+
+        assert_eq!(sponsor.budget_lamports, 500_000_000);
+        assert_eq!(sponsor.daily_wallet_limit, 1);
+        assert_eq!(SponsorConfig::from_config(&json!({})).unwrap(), None);
+    }
+
+    #[test]
+    fn rejects_invalid_sponsors() {
+        for config in [
+            json!({ "sponsor": { "signer": "gas", "budget_sol": 0 } }),
+            json!({ "sponsor": { "signer": "gas", "budget_sol": 1, "daily_wallet_limit": 0 } }),
+            json!({ "sponsor": { "budget_sol": 1 } }),
+            json!({ "sponsor": { "signer": "gas", "budget_sol": 1 }, "durable_nonce": true }),
+        ] {
+            assert!(SponsorConfig::from_config(&config).is_err(), "{}", config);
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use serde::Deserialize;
+use solana_sdk::pubkey::Pubkey;
+use std::str::FromStr;
+
+const MAX_PRESET_AMOUNTS: usize = 5;
+
+/// Config of a stake blink:
+///
+/// ```json
+/// { "validator": "<vote account>", "min_amount": 1, "amounts": [1, 5, 10] }
+/// ```
+///
+/// Every preset amount gets a button; any amount of at least `min_amount`
+/// SOL can be entered as well.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StakeConfig {
+    pub validator: Pubkey,
+    pub min_amount: f64,
+    pub amounts: Vec<f64>,
+}
+
+#[derive(Deserialize)]
+struct RawStakeConfig {
+    validator: String,
+    #[serde(default = "default_min_amount")]
+    min_amount: f64,
+    #[serde(default)]
+    amounts: Vec<f64>,
+}
+
+fn default_min_amount() -> f64 {
+    0.1
+}
+
+impl StakeConfig {
+    pub fn from_config(config: &serde_json::Value) -> Result<StakeConfig, String> {
+        let raw = RawStakeConfig::deserialize(config)
+            .map_err(|e| format!("Invalid stake config: {}", e))?;
+        let validator =
+            Pubkey::from_str(&raw.validator).map_err(|e| format!("Invalid validator: {}", e))?;
+
+        if !(raw.min_amount.is_finite() && raw.min_amount > 0.0) {
+            return Err("Minimum stake must be a positive amount of SOL".to_string());
+        }
+        if raw.amounts.len() > MAX_PRESET_AMOUNTS {
+            return Err(format!(
+                "At most {} preset amounts are allowed",
+                MAX_PRESET_AMOUNTS
+            ));
+        }
+        if let Some(amount) = raw
+            .amounts
+            .iter()
+            .find(|amount| !(amount.is_finite() && **amount >= raw.min_amount))
+        {
+            return Err(format!(
+                "Preset amount {} is below the minimum stake of {} SOL",
+                amount, raw.min_amount
+            ));
+        }
+
+        Ok(StakeConfig {
+            validator,
+            min_amount: raw.min_amount,
+            amounts: raw.amounts,
+        })
+    }
+
+    /// Checks an amount entered by the user.
+    pub fn check_amount(&self, amount: f64) -> Result<(), String> {
+        if !(amount.is_finite() && amount >= self.min_amount) {
+            return Err(format!("Minimum stake is {} SOL", self.min_amount));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn config(extra: serde_json::Value) -> serde_json::Value {
+        let mut config = json!({ "validator": Pubkey::new_unique().to_string() });
+        for (key, value) in extra.as_object().unwrap() {
+            config[key] = value.clone();
+        }
+        config
+    }
+
+    #[test]
+    fn enforces_the_minimum_stake() {
+        let stake = StakeConfig::from_config(&config(json!({ "min_amount": 1 }))).unwrap();
+
+        assert!(stake.check_amount(1.0).is_ok());
+        assert!(stake.check_amount(0.5).is_err());
+        assert!(stake.check_amount(f64::NAN).is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_configs() {
+        for extra in [
+            json!({ "validator": "nope" }),
+            json!({ "min_amount": 0 }),
+            json!({ "min_amount": 1, "amounts": [0.5] }),
+            json!({ "amounts": [1, 2, 3, 4, 5, 6] }),
+        ] {
+            assert!(
+                StakeConfig::from_config(&config(extra.clone())).is_err(),
+                "{}",
+                extra
+            );
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use crate::invoices::SOL;
+use serde::Deserialize;
+use solana_sdk::pubkey::Pubkey;
+use std::str::FromStr;
+
+const MAX_PERIOD_DAYS: u32 = 366;
+const MAX_APPROVE_PERIODS: u32 = 60;
+
+/// Config of a subscription blink:
+///
+/// ```json
+/// { "price": 5, "currency": "<mint>", "period_days": 30, "delegate": "billing" }
+/// ```
+///
+/// Each payment of `price` extends the subscription by `period_days`.
+/// `currency` is `SOL` or the mint of an SPL token. With `delegate`, a vault
+/// key, token subscribers also approve it to charge `approve_periods`
+/// renewals from their token account, which the backend charges when they
+/// fall due.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SubscriptionConfig {
+    pub price: f64,
+    pub currency: String,
+    pub period_days: u32,
+    pub delegate: Option<String>,
+    pub approve_periods: u32,
+}
+
+#[derive(Deserialize)]
+struct RawSubscriptionConfig {
+    price: f64,
+    #[serde(default = "default_currency")]
+    currency: String,
+    #[serde(default = "default_period_days")]
+    period_days: u32,
+    delegate: Option<String>,
+    approve_periods: Option<u32>,
+    #[serde(default)]
+    durable_nonce: bool,
+}
+
+fn default_currency() -> String {
+    SOL.to_string()
+}
+
+fn default_period_days() -> u32 {
+    30
+}
+
+impl SubscriptionConfig {
+    pub fn from_config(config: &serde_json::Value) -> Result<SubscriptionConfig, String> {
+        let raw = RawSubscriptionConfig::deserialize(config)
+            .map_err(|e| format!("Invalid subscription config: {}", e))?;
+
+        if !(raw.price.is_finite() && raw.price > 0.0) {
+            return Err("Subscription price must be positive".to_string());
+        }
+        if raw.currency != SOL && Pubkey::from_str(&raw.currency).is_err() {
+            return Err(format!(
+                "Currency must be SOL or a token mint: {}",
+                raw.currency
+            ));
+        }
+        if !(1..=MAX_PERIOD_DAYS).contains(&raw.period_days) {
+            return Err(format!(
+                "Subscription period must be 1 to {} days",
+                MAX_PERIOD_DAYS
+            ));
+        }
+        // Only token accounts can approve a delegate.
+        if raw.delegate.is_some() && raw.currency == SOL {
+            return Err("Automatic renewals need a token currency".to_string());
+        }
+        if raw.approve_periods.is_some() && raw.delegate.is_none() {
+            return Err("approve_periods needs a delegate".to_string());
+        }
+        let approve_periods = raw.approve_periods.unwrap_or(12);
+        if !(1..=MAX_APPROVE_PERIODS).contains(&approve_periods) {
+            return Err(format!(
+                "approve_periods must be 1 to {}",
+                MAX_APPROVE_PERIODS
+            ));
+        }
+        // Renewals are dated from when their transaction is built.
+        if raw.durable_nonce {
+            return Err("Subscription blinks cannot use durable nonces".to_string());
+        }
+
+        Ok(SubscriptionConfig {
+            price: raw.price,
+            currency: raw.currency,
+            period_days: raw.period_days,
+            delegate: raw.delegate,
+            approve_periods,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn defaults_to_monthly_sol() {
+        let config = SubscriptionConfig::from_config(&json!({ "price": 0.5 })).unwrap();
+
+        assert_eq!(config.currency, "SOL");
+        assert_eq!(config.period_days, 30);
+        assert_eq!(config.delegate, None);
+    }
+
+    #[test]
+    fn delegates_need_a_token_currency() {
+        let mint = Pubkey::new_unique().to_string();
+
+        for config in [
+            json!({ "price": 1, "delegate": "billing" }),
+            json!({ "price": 1, "currency": mint, "approve_periods": 3 }),
+            json!({ "price": 1, "currency": mint, "delegate": "billing", "approve_periods": 0 }),
+            json!({ "price": 1, "period_days": 0 }),
+            json!({ "price": 0 }),
+        ] {
+            assert!(
+                SubscriptionConfig::from_config(&config).is_err(),
+                "{}",
+                config
+            );
+        }
+        assert!(
+            SubscriptionConfig::from_config(
+                &json!({ "price": 1, "currency": mint, "delegate": "billing" })
+            )
+            .is_ok()
+        );
+    }
+}
@@ -0,0 +1,162 @@
+use serde::Deserialize;
+use solana_sdk::pubkey::Pubkey;
+use std::str::FromStr;
+
+/// Wrapped SOL, the input of every swap.
+pub const WRAPPED_SOL_MINT: Pubkey =
+    solana_sdk::pubkey!("So11111111111111111111111111111111111111112");
+
+/// Highest slippage a blink may allow: 50%.
+const MAX_SLIPPAGE_BPS: u16 = 5_000;
+
+/// Highest referral fee a blink may take: 10%.
+const MAX_FEE_BPS: u16 = 1_000;
+
+/// Config of a swap blink:
+///
+/// ```json
+/// { "output_mint": "<mint>", "slippage_bps": 50, "max_slippage_bps": 300,
+///   "fee_bps": 20, "fee_account": "<token account>" }
+/// ```
+///
+/// Users swap an amount of SOL they enter for `output_mint`. Their slippage
+/// defaults to `slippage_bps` and may not exceed `max_slippage_bps`. With
+/// `fee_bps`, the quote provider sends that share of the swap to
+/// `fee_account` as a referral fee.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SwapConfig {
+    pub output_mint: Pubkey,
+    pub slippage_bps: u16,
+    pub max_slippage_bps: u16,
+    pub fee_bps: u16,
+    pub fee_account: Option<Pubkey>,
+}
+
+#[derive(Deserialize)]
+struct RawSwapConfig {
+    output_mint: String,
+    #[serde(default = "default_slippage_bps")]
+    slippage_bps: u16,
+    #[serde(default = "default_max_slippage_bps")]
+    max_slippage_bps: u16,
+    #[serde(default)]
+    fee_bps: u16,
+    fee_account: Option<String>,
+    #[serde(default)]
+    durable_nonce: bool,
+}
+
+fn default_slippage_bps() -> u16 {
+    50
+}
+
+fn default_max_slippage_bps() -> u16 {
+    300
+}
+
+impl SwapConfig {
+    pub fn from_config(config: &serde_json::Value) -> Result<SwapConfig, String> {
+        let raw = RawSwapConfig::deserialize(config)
+            .map_err(|e| format!("Invalid swap config: {}", e))?;
+
+        let output_mint = Pubkey::from_str(&raw.output_mint)
+            .map_err(|e| format!("Invalid output_mint: {}", e))?;
+        if output_mint == WRAPPED_SOL_MINT {
+            return Err("output_mint must not be SOL".to_string());
+        }
+        if !(1..=MAX_SLIPPAGE_BPS).contains(&raw.max_slippage_bps) {
+            return Err(format!(
+                "max_slippage_bps must be 1 to {}",
+                MAX_SLIPPAGE_BPS
+            ));
+        }
+        if !(1..=raw.max_slippage_bps).contains(&raw.slippage_bps) {
+            return Err("slippage_bps must be 1 to max_slippage_bps".to_string());
+        }
+        if raw.fee_bps > MAX_FEE_BPS {
+            return Err(format!("fee_bps must be at most {}", MAX_FEE_BPS));
+        }
+        let fee_account = raw
+            .fee_account
+            .map(|account| {
+                Pubkey::from_str(&account).map_err(|e| format!("Invalid fee_account: {}", e))
+            })
+            .transpose()?;
+        if raw.fee_bps > 0 && fee_account.is_none() {
+            return Err("fee_bps needs a fee_account".to_string());
+        }
+        // Quote providers prepare the whole transaction, which the user signs
+        // alone.
+        if raw.durable_nonce {
+            return Err("Swap blinks cannot use durable nonces".to_string());
+        }
+
+        Ok(SwapConfig {
+            output_mint,
+            slippage_bps: raw.slippage_bps,
+            max_slippage_bps: raw.max_slippage_bps,
+            fee_bps: raw.fee_bps,
+            fee_account,
+        })
+    }
+
+    /// Slippage in basis points for a percentage entered by the user, or the
+    /// default when none was entered.
+    pub fn slippage(&self, percent: Option<&str>) -> Result<u16, String> {
+        let Some(percent) = percent.filter(|percent| !percent.is_empty()) else {
+            return Ok(self.slippage_bps);
+        };
+        let bps = percent
+            .trim_end_matches('%')
+            .parse::<f64>()
+            .ok()
+            .filter(|percent| percent.is_finite() && *percent > 0.0)
+            .map(|percent| (percent * 100.0).round())
+            .ok_or_else(|| format!("Invalid slippage: {}", percent))?;
+        if bps < 1.0 || bps > f64::from(self.max_slippage_bps) {
+            return Err(format!(
+                "Slippage must be 0.01% to {}%",
+                f64::from(self.max_slippage_bps) / 100.0
+            ));
+        }
+        Ok(bps as u16)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn caps_the_slippage_users_enter() {
+        let config = SwapConfig::from_config(&json!({
+            "output_mint": Pubkey::new_unique().to_string(),
+            "max_slippage_bps": 100
+        }))
+        .unwrap();
+
+        assert_eq!(config.slippage(None), Ok(50));
+        assert_eq!(config.slippage(Some("")), Ok(50));
+        assert_eq!(config.slippage(Some("0.3")), Ok(30));
+        assert_eq!(config.slippage(Some("1%")), Ok(100));
+        assert!(config.slippage(Some("1.5")).is_err());
+        assert!(config.slippage(Some("0")).is_err());
+        assert!(config.slippage(Some("NaN")).is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_configs() {
+        let mint = Pubkey::new_unique().to_string();
+        for config in [
+            json!({ "output_mint": WRAPPED_SOL_MINT.to_string() }),
+            json!({ "output_mint": mint, "max_slippage_bps": 6000 }),
+            json!({ "output_mint": mint, "slippage_bps": 500 }),
+            json!({ "output_mint": mint, "fee_bps": 20 }),
+            json!({ "output_mint": mint, "fee_bps": 2000, "fee_account": mint }),
+            json!({ "output_mint": mint, "durable_nonce": true }),
+        ] {
+            assert!(SwapConfig::from_config(&config).is_err(), "{}", config);
+        }
+    }
+}
@@ -0,0 +1,188 @@
+use serde::Deserialize;
+use solana_sdk::pubkey::Pubkey;
+use std::str::FromStr;
+
+/// What the requesting wallet must hold to use a blink, declared under
+/// `config.gate`:
+///
+/// ```json
+/// { "kind": "token", "mint": "...", "min_amount": 100 }
+/// { "kind": "collection", "collection": "...", "discount_percent": 20 }
+/// ```
+///
+/// Without `discount_percent` wallets that do not qualify are turned away;
+/// with it they pay full price and holders get the discount.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenGate {
+    pub requirement: GateRequirement,
+    pub discount_percent: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GateRequirement {
+    /// A balance of at least `min_amount` (in whole tokens) of an SPL mint.
+    Token { mint: Pubkey, min_amount: f64 },
+    /// An NFT from a verified Metaplex collection.
+    Collection { collection: Pubkey },
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+enum RawRequirement {
+    Token {
+        mint: String,
+        #[serde(default = "default_min_amount")]
+        min_amount: f64,
+        discount_percent: Option<f64>,
+    },
+    Collection {
+        collection: String,
+        discount_percent: Option<f64>,
+    },
+}
+
+fn default_min_amount() -> f64 {
+    1.0
+}
+
+impl TokenGate {
+    /// Reads the gate of a blink config. Blinks without a `gate` key are
+    /// open to everyone.
+    pub fn from_config(config: &serde_json::Value) -> Result<Option<TokenGate>, String> {
+        let Some(gate) = config.get("gate") else {
+            return Ok(None);
+        };
+        let raw = RawRequirement::deserialize(gate).map_err(|e| format!("Invalid gate: {}", e))?;
+
+        let (requirement, discount_percent) = match raw {
+            RawRequirement::Token {
+                mint,
+                min_amount,
+                discount_percent,
+            } => {
+                if !(min_amount.is_finite() && min_amount > 0.0) {
+                    return Err("Gate min_amount must be positive".to_string());
+                }
+                let mint = parse_pubkey(&mint, "mint")?;
+                (
+                    GateRequirement::Token { mint, min_amount },
+                    discount_percent,
+                )
+            }
+            RawRequirement::Collection {
+                collection,
+                discount_percent,
+            } => {
+                let collection = parse_pubkey(&collection, "collection")?;
+                (GateRequirement::Collection { collection }, discount_percent)
+            }
+        };
+
+        if let Some(discount) = discount_percent
+            && !(discount > 0.0 && discount <= 100.0)
+        {
+            return Err("Gate discount_percent must be between 0 and 100".to_string());
+        }
+
+        Ok(Some(TokenGate {
+            requirement,
+            discount_percent,
+        }))
+    }
+
+    /// Whether wallets that do not qualify may still use the blink.
+    pub fn is_discount(&self) -> bool {
+        self.discount_percent.is_some()
+    }
+
+    /// Applies the holder discount to a SOL amount.
+    pub fn discounted(&self, amount: f64) -> f64 {
+        match self.discount_percent {
+            Some(discount) => amount * (100.0 - discount) / 100.0,
+            None => amount,
+        }
+    }
+
+    /// The `ActionError` message for wallets that do not qualify.
+    pub fn denial_message(&self) -> String {
+        match &self.requirement {
+            GateRequirement::Token { mint, min_amount } => format!(
+                "This action is only available to wallets holding at least {} of token {}",
+                min_amount, mint
+            ),
+            GateRequirement::Collection { collection } => format!(
+                "This action is only available to holders of an NFT from collection {}",
+                collection
+            ),
+        }
+    }
+}
+
+fn parse_pubkey(value: &str, name: &str) -> Result<Pubkey, String> {
+    Pubkey::from_str(value).map_err(|e| format!("Invalid gate {}: {}", name, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn blinks_without_gate_are_open() {
+        assert_eq!(TokenGate::from_config(&json!({ "amount": 1 })), Ok(None));
+    }
+
+    #[test]
+    fn parses_token_gate_with_default_min_amount() {
+        let mint = Pubkey::new_unique();
+
+        let gate = TokenGate::from_config(&json!({
+            "gate": { "kind": "token", "mint": mint.to_string() }
+        }))
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(
+            gate.requirement,
+            GateRequirement::Token {
+                mint,
+                min_amount: 1.0
+            }
+        );
+        assert!(!gate.is_discount());
+    }
+
+    #[test]
+    fn collection_discount_applies_to_amounts() {
+        let gate = TokenGate::from_config(&json!({
+            "gate": {
+                "kind": "collection",
+                "collection": Pubkey::new_unique().to_string(),
+                "discount_percent": 20
+            }
+        }))
+        .unwrap()
+        .unwrap();
+
+        assert!(gate.is_discount());
+        assert!((gate.discounted(0.5) - 0.4).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn rejects_invalid_gates() {
+        let mint = Pubkey::new_unique().to_string();
+        for gate in [
+            json!({ "kind": "token", "mint": "not-a-key" }),
+            json!({ "kind": "token", "mint": mint, "min_amount": 0 }),
+            json!({ "kind": "token", "mint": mint, "discount_percent": 120 }),
+            json!({ "kind": "staked", "mint": mint }),
+            json!({ "kind": "collection" }),
+        ] {
+            assert!(
+                TokenGate::from_config(&json!({ "gate": gate })).is_err(),
+                "{}",
+                gate
+            );
+        }
+    }
+}
@@ -0,0 +1,191 @@
+use serde::Deserialize;
+use solana_sdk::pubkey::Pubkey;
+use std::fmt;
+use std::str::FromStr;
+use uuid::Uuid;
+
+/// Token weighting of a Vote blink, declared under `config.weighting`:
+///
+/// ```json
+/// { "mint": "...", "snapshot": "creation" }
+/// { "mint": "...", "snapshot": { "slot": 300000000 } }
+/// ```
+///
+/// Each wallet votes with its balance of `mint` at the snapshot.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VoteWeighting {
+    pub mint: Pubkey,
+    pub snapshot: SnapshotPolicy,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SnapshotPolicy {
+    /// Balances when the weighting is set on the blink.
+    Creation,
+    /// Balances once the cluster reaches this slot. Voting opens then.
+    Slot(u64),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawWeighting {
+    mint: String,
+    snapshot: SnapshotPolicy,
+}
+
+impl VoteWeighting {
+    /// Reads the weighting of a blink config. Polls without a `weighting`
+    /// key count one vote per wallet.
+    pub fn from_config(config: &serde_json::Value) -> Result<Option<VoteWeighting>, String> {
+        let Some(weighting) = config.get("weighting") else {
+            return Ok(None);
+        };
+        let raw = RawWeighting::deserialize(weighting)
+            .map_err(|e| format!("Invalid weighting: {}", e))?;
+        let mint =
+            Pubkey::from_str(&raw.mint).map_err(|e| format!("Invalid weighting mint: {}", e))?;
+
+        Ok(Some(VoteWeighting {
+            mint,
+            snapshot: raw.snapshot,
+        }))
+    }
+}
+
+/// The memo a vote transaction carries.
+///
+/// Unweighted votes keep the original `vote:<blink>:<selection>` format.
+/// Weighted votes name the mint, snapshot slot and weight they claim, so
+/// anyone can check the claim against the chain:
+/// `wvote:<blink>:<mint>:<slot>:<weight>:<selection>`. The selection is last
+/// because it may contain colons.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VoteMemo {
+    pub blink_id: Uuid,
+    pub selection: String,
+    pub weight: Option<WeightClaim>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WeightClaim {
+    pub mint: Pubkey,
+    pub slot: u64,
+    pub weight: u64,
+}
+
+impl VoteMemo {
+    pub fn parse(memo: &str) -> Option<VoteMemo> {
+        if let Some(rest) = memo.strip_prefix("vote:") {
+            let (blink_id, selection) = rest.split_once(':')?;
+            return Some(VoteMemo {
+                blink_id: Uuid::parse_str(blink_id).ok()?,
+                selection: selection.to_string(),
+                weight: None,
+            });
+        }
+
+        let mut parts = memo.strip_prefix("wvote:")?.splitn(5, ':');
+        let blink_id = Uuid::parse_str(parts.next()?).ok()?;
+        let weight = WeightClaim {
+            mint: Pubkey::from_str(parts.next()?).ok()?,
+            slot: parts.next()?.parse().ok()?,
+            weight: parts.next()?.parse().ok()?,
+        };
+        Some(VoteMemo {
+            blink_id,
+            selection: parts.next()?.to_string(),
+            weight: Some(weight),
+        })
+    }
+}
+
+impl fmt::Display for VoteMemo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.weight {
+            None => write!(f, "vote:{}:{}", self.blink_id, self.selection),
+            Some(claim) => write!(
+                f,
+                "wvote:{}:{}:{}:{}:{}",
+                self.blink_id, claim.mint, claim.slot, claim.weight, self.selection
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn parses_weighting_policies() {
+        let mint = Pubkey::new_unique();
+
+        let creation = VoteWeighting::from_config(&json!({
+            "weighting": { "mint": mint.to_string(), "snapshot": "creation" }
+        }));
+        let slot = VoteWeighting::from_config(&json!({
+            "weighting": { "mint": mint.to_string(), "snapshot": { "slot": 42 } }
+        }));
+
+        assert_eq!(
+            creation.unwrap().unwrap().snapshot,
+            SnapshotPolicy::Creation
+        );
+        assert_eq!(slot.unwrap().unwrap().snapshot, SnapshotPolicy::Slot(42));
+    }
+
+    #[test]
+    fn rejects_invalid_weightings() {
+        for weighting in [
+            json!({ "mint": "nope", "snapshot": "creation" }),
+            json!({ "mint": Pubkey::new_unique().to_string(), "snapshot": "later" }),
+            json!({ "mint": Pubkey::new_unique().to_string() }),
+        ] {
+            assert!(
+                VoteWeighting::from_config(&json!({ "weighting": weighting })).is_err(),
+                "{}",
+                weighting
+            );
+        }
+    }
+
+    #[test]
+    fn unweighted_memo_keeps_original_format() {
+        let blink_id = Uuid::new_v4();
+        let memo = VoteMemo {
+            blink_id,
+            selection: "Yes".to_string(),
+            weight: None,
+        };
+
+        assert_eq!(memo.to_string(), format!("vote:{}:Yes", blink_id));
+        assert_eq!(VoteMemo::parse(&memo.to_string()), Some(memo));
+    }
+
+    #[test]
+    fn weighted_memo_round_trips_selections_with_colons() {
+        let memo = VoteMemo {
+            blink_id: Uuid::new_v4(),
+            selection: "Option: B".to_string(),
+            weight: Some(WeightClaim {
+                mint: Pubkey::new_unique(),
+                slot: 1000,
+                weight: 18_000_000_000_000_000_000,
+            }),
+        };
+
+        assert_eq!(VoteMemo::parse(&memo.to_string()), Some(memo));
+    }
+
+    #[test]
+    fn rejects_foreign_memos() {
+        assert_eq!(VoteMemo::parse("gm"), None);
+        assert_eq!(VoteMemo::parse("vote:not-a-uuid:Yes"), None);
+        assert_eq!(
+            VoteMemo::parse(&format!("wvote:{}:nope:1:1:Yes", Uuid::new_v4())),
+            None
+        );
+    }
+}
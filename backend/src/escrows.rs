@@ -0,0 +1,419 @@
+use crate::domain::EscrowConfig;
+use crate::invoices::Currency;
+use crate::models::{EscrowStatus, EscrowStep};
+use crate::rpc_pool::RpcPool;
+use axum::http::StatusCode;
+use chrono::{DateTime, Duration as TimeDelta, Utc};
+use solana_sdk::{
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+use sqlx::PgPool;
+use std::str::FromStr;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Runs the deals of escrow blinks.
+///
+/// Deposits are held by a key of the signer vault rather than a program
+/// account: the buyer pays the escrow key, and releases and refunds are
+/// transfers out of it that the vault signs. Every transfer carries a fresh
+/// reference key and moves the deal on once it is found on the cluster.
+/// A deal has at most one transfer in flight, so a deposit cannot be paid
+/// out twice; transfers not found within `pending` are dropped.
+pub struct EscrowAgent {
+    pending: Duration,
+}
+
+/// The deal of an escrow blink as stored.
+#[derive(Debug)]
+pub struct Deal {
+    pub blink_id: Uuid,
+    pub status: EscrowStatus,
+    pub escrow: String,
+    pub escrow_key: String,
+    pub currency: String,
+    pub decimals: i16,
+    pub amount: i64,
+    pub timeout_days: i32,
+    pub on_timeout: EscrowStep,
+    pub buyer: Option<String>,
+    pub seller: String,
+    pub pending_step: Option<EscrowStep>,
+    pub pending_reference: Option<String>,
+    pub pending_at: Option<DateTime<Utc>>,
+    pub deposit_signature: Option<String>,
+    pub funded_at: Option<DateTime<Utc>>,
+    pub settlement_signature: Option<String>,
+    pub closed_at: Option<DateTime<Utc>>,
+}
+
+/// Terms a deposit opens a deal on.
+pub struct Terms<'a> {
+    pub config: &'a EscrowConfig,
+    pub escrow_key: &'a Pubkey,
+    pub currency: &'a Currency,
+    pub seller: &'a Pubkey,
+}
+
+/// A transfer of a deal to be built: `amount` base units from `from` to
+/// `to`, carrying `reference`.
+#[derive(Debug)]
+pub struct EscrowTransfer {
+    pub step: EscrowStep,
+    /// Vault key that signs releases and refunds.
+    pub escrow: String,
+    pub from: Pubkey,
+    pub to: Pubkey,
+    pub currency: Currency,
+    pub amount: u64,
+    pub reference: Pubkey,
+}
+
+impl Deal {
+    pub fn currency(&self) -> Result<Currency, (StatusCode, String)> {
+        Currency::from_stored(&self.currency, self.decimals)
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))
+    }
+
+    /// When either party may take the `on_timeout` step of a funded deal.
+    pub fn timeout_at(&self) -> Option<DateTime<Utc>> {
+        self.funded_at
+            .map(|funded_at| funded_at + TimeDelta::days(i64::from(self.timeout_days)))
+    }
+
+    /// Whether `wallet` may release or refund the funded deal, with the
+    /// reason when it may not.
+    pub fn check_step(&self, step: EscrowStep, wallet: &str) -> Result<(), String> {
+        let buyer = self.buyer.as_deref() == Some(wallet);
+        let seller = self.seller == wallet;
+        let timeout_at = self.timeout_at().unwrap_or(DateTime::<Utc>::MAX_UTC);
+        let timed_out = step == self.on_timeout && timeout_at <= Utc::now();
+        match step {
+            EscrowStep::Release if buyer || (seller && timed_out) => Ok(()),
+            EscrowStep::Refund if seller || (buyer && timed_out) => Ok(()),
+            EscrowStep::Release if seller && step == self.on_timeout => Err(format!(
+                "The seller can release this deal from {}",
+                timeout_at.format("%Y-%m-%d %H:%M UTC")
+            )),
+            EscrowStep::Refund if buyer && step == self.on_timeout => Err(format!(
+                "The buyer can claim a refund from {}",
+                timeout_at.format("%Y-%m-%d %H:%M UTC")
+            )),
+            EscrowStep::Release => Err("Only the buyer can release this deal".to_string()),
+            EscrowStep::Refund => Err("Only the seller can refund this deal".to_string()),
+            EscrowStep::Deposit => Err("This deal has already been funded".to_string()),
+        }
+    }
+
+    /// Why no transfer of the deal can be built right now, if so: one is in
+    /// flight or the deal is closed.
+    pub fn unavailable(&self) -> Option<String> {
+        if let Some(pending) = self.pending_step {
+            return Some(format!(
+                "A {} of this deal is in flight, try again in a few minutes",
+                pending.as_str()
+            ));
+        }
+        match self.status {
+            EscrowStatus::Released => Some("This deal was released to the seller".to_string()),
+            EscrowStatus::Refunded => Some("This deal was refunded to the buyer".to_string()),
+            EscrowStatus::Open | EscrowStatus::Funded => None,
+        }
+    }
+
+    /// Why `wallet` cannot take `step` of the deal right now, if so.
+    fn blocker(&self, step: EscrowStep, wallet: &str) -> Option<String> {
+        self.unavailable().or_else(|| match (self.status, step) {
+            (EscrowStatus::Open, EscrowStep::Deposit) => None,
+            (EscrowStatus::Open, _) => Some("Nothing has been deposited yet".to_string()),
+            (_, step) => self.check_step(step, wallet).err(),
+        })
+    }
+}
+
+impl EscrowAgent {
+    pub fn new(pending: Duration) -> Self {
+        Self { pending }
+    }
+
+    /// The deal of a blink after settling its transfer in flight, or `None`
+    /// before the first deposit.
+    pub async fn deal(
+        &self,
+        db: &PgPool,
+        rpc: &RpcPool,
+        blink_id: Uuid,
+    ) -> Result<Option<Deal>, (StatusCode, String)> {
+        self.settle(db, rpc, blink_id).await?;
+        fetch_deal(db, blink_id).await
+    }
+
+    /// Starts `step` of the deal of a blink for `wallet` and returns the
+    /// transfer to build. Steps the deal or wallet cannot take get a `403`.
+    pub async fn open(
+        &self,
+        db: &PgPool,
+        rpc: &RpcPool,
+        blink_id: Uuid,
+        step: EscrowStep,
+        wallet: &Pubkey,
+        terms: Terms<'_>,
+    ) -> Result<EscrowTransfer, (StatusCode, String)> {
+        self.settle(db, rpc, blink_id).await?;
+
+        let mut transaction = db
+            .begin()
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        // Serializes the steps of one deal.
+        sqlx::query!("SELECT id FROM blinks WHERE id = $1 FOR UPDATE", blink_id)
+            .fetch_one(&mut *transaction)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+        let deal = fetch_deal(&mut *transaction, blink_id).await?;
+        if let Some(denial) = deal
+            .as_ref()
+            .and_then(|deal| deal.blocker(step, &wallet.to_string()))
+        {
+            return Err((StatusCode::FORBIDDEN, denial));
+        }
+        if deal.is_none() && step != EscrowStep::Deposit {
+            return Err((
+                StatusCode::FORBIDDEN,
+                "Nothing has been deposited yet".to_string(),
+            ));
+        }
+
+        let reference = Keypair::new().pubkey();
+        let transfer = if step == EscrowStep::Deposit {
+            if wallet == terms.seller {
+                return Err((
+                    StatusCode::FORBIDDEN,
+                    "Sellers cannot deposit into their own deal".to_string(),
+                ));
+            }
+            // Until it is funded the deal follows the blink's config.
+            let amount = terms.currency.base_units(terms.config.amount);
+            sqlx::query!(
+                r#"
+                INSERT INTO escrows (
+                    blink_id, escrow, escrow_key, currency, decimals, amount,
+                    timeout_days, on_timeout, seller, buyer,
+                    pending_step, pending_reference, pending_at
+                )
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, 'deposit', $11, now())
+                ON CONFLICT (blink_id) DO UPDATE SET
+                    escrow = EXCLUDED.escrow,
+                    escrow_key = EXCLUDED.escrow_key,
+                    currency = EXCLUDED.currency,
+                    decimals = EXCLUDED.decimals,
+                    amount = EXCLUDED.amount,
+                    timeout_days = EXCLUDED.timeout_days,
+                    on_timeout = EXCLUDED.on_timeout,
+                    seller = EXCLUDED.seller,
+                    buyer = EXCLUDED.buyer,
+                    pending_step = EXCLUDED.pending_step,
+                    pending_reference = EXCLUDED.pending_reference,
+                    pending_at = EXCLUDED.pending_at
+                "#,
+                blink_id,
+                terms.config.escrow,
+                terms.escrow_key.to_string(),
+                terms.currency.code(),
+                i16::from(terms.currency.decimals()),
+                amount as i64,
+                terms.config.timeout_days as i32,
+                terms.config.on_timeout as EscrowStep,
+                terms.seller.to_string(),
+                wallet.to_string(),
+                reference.to_string()
+            )
+            .execute(&mut *transaction)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+            EscrowTransfer {
+                step,
+                escrow: terms.config.escrow.clone(),
+                from: *wallet,
+                to: *terms.escrow_key,
+                currency: *terms.currency,
+                amount,
+                reference,
+            }
+        } else {
+            let deal = deal.expect("deal checked above");
+            sqlx::query!(
+                r#"
+                UPDATE escrows
+                SET pending_step = $2, pending_reference = $3, pending_at = now()
+                WHERE blink_id = $1
+                "#,
+                blink_id,
+                step as EscrowStep,
+                reference.to_string()
+            )
+            .execute(&mut *transaction)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+            EscrowTransfer {
+                step,
+                from: parse_stored(&deal.escrow_key, "escrow key")?,
+                to: recipient(&deal, step)?,
+                currency: deal.currency()?,
+                amount: deal.amount as u64,
+                escrow: deal.escrow,
+                reference,
+            }
+        };
+
+        transaction
+            .commit()
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        Ok(transfer)
+    }
+
+    /// Looks the transfer in flight of a deal up on the cluster. A landed
+    /// transfer moves the deal on, one not found in time is dropped. When
+    /// the cluster cannot be reached the transfer stays in flight.
+    pub async fn settle(
+        &self,
+        db: &PgPool,
+        rpc: &RpcPool,
+        blink_id: Uuid,
+    ) -> Result<(), (StatusCode, String)> {
+        let Some(deal) = fetch_deal(db, blink_id).await? else {
+            return Ok(());
+        };
+        let (Some(step), Some(pending_reference), Some(pending_at)) =
+            (deal.pending_step, &deal.pending_reference, deal.pending_at)
+        else {
+            return Ok(());
+        };
+        let expired = pending_at + self.pending < Utc::now();
+
+        let reference = parse_stored(pending_reference, "reference")?;
+        let landed = match deal
+            .currency()?
+            .find_payment(
+                rpc,
+                &recipient(&deal, step)?,
+                deal.amount as u64,
+                &reference,
+            )
+            .await
+        {
+            Ok(landed) => landed,
+            Err(e) => {
+                tracing::warn!(blink_id = %blink_id, "Failed to settle escrow: {}", e);
+                return Ok(());
+            }
+        };
+
+        match (landed, step) {
+            (Some(landed), EscrowStep::Deposit) => {
+                sqlx::query!(
+                    r#"
+                    UPDATE escrows
+                    SET status = 'funded', buyer = $3, deposit_signature = $4,
+                        funded_at = now(), pending_step = NULL,
+                        pending_reference = NULL, pending_at = NULL
+                    WHERE blink_id = $1 AND pending_reference = $2
+                    "#,
+                    blink_id,
+                    pending_reference,
+                    landed.payer.to_string(),
+                    landed.signature.to_string()
+                )
+                .execute(db)
+                .await
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+            }
+            (Some(landed), step) => {
+                let status = if step == EscrowStep::Release {
+                    EscrowStatus::Released
+                } else {
+                    EscrowStatus::Refunded
+                };
+                sqlx::query!(
+                    r#"
+                    UPDATE escrows
+                    SET status = $3, settlement_signature = $4, closed_at = now(),
+                        pending_step = NULL, pending_reference = NULL, pending_at = NULL
+                    WHERE blink_id = $1 AND pending_reference = $2
+                    "#,
+                    blink_id,
+                    pending_reference,
+                    status as EscrowStatus,
+                    landed.signature.to_string()
+                )
+                .execute(db)
+                .await
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+            }
+            (None, _) if expired => {
+                // An expired deposit frees the deal for other buyers.
+                sqlx::query!(
+                    r#"
+                    UPDATE escrows
+                    SET buyer = CASE WHEN status = 'open' THEN NULL ELSE buyer END,
+                        pending_step = NULL, pending_reference = NULL, pending_at = NULL
+                    WHERE blink_id = $1 AND pending_reference = $2
+                    "#,
+                    blink_id,
+                    pending_reference
+                )
+                .execute(db)
+                .await
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+            }
+            (None, _) => {}
+        }
+        Ok(())
+    }
+}
+
+async fn fetch_deal<'e, E>(db: E, blink_id: Uuid) -> Result<Option<Deal>, (StatusCode, String)>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    sqlx::query_as!(
+        Deal,
+        r#"
+        SELECT
+            blink_id, status as "status: EscrowStatus", escrow, escrow_key,
+            currency, decimals, amount, timeout_days,
+            on_timeout as "on_timeout: EscrowStep", buyer, seller,
+            pending_step as "pending_step: EscrowStep", pending_reference, pending_at,
+            deposit_signature,
+            funded_at, settlement_signature, closed_at
+        FROM escrows
+        WHERE blink_id = $1
+        "#,
+        blink_id
+    )
+    .fetch_optional(db)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
+/// The wallet a step of the deal pays: the escrow key for deposits, the
+/// seller for releases and the buyer for refunds.
+fn recipient(deal: &Deal, step: EscrowStep) -> Result<Pubkey, (StatusCode, String)> {
+    match step {
+        EscrowStep::Deposit => parse_stored(&deal.escrow_key, "escrow key"),
+        EscrowStep::Release => parse_stored(&deal.seller, "seller"),
+        EscrowStep::Refund => parse_stored(deal.buyer.as_deref().unwrap_or_default(), "buyer"),
+    }
+}
+
+fn parse_stored(value: &str, what: &str) -> Result<Pubkey, (StatusCode, String)> {
+    Pubkey::from_str(value).map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Invalid stored {}: {}", what, e),
+        )
+    })
+}
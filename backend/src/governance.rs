@@ -0,0 +1,370 @@
+use crate::domain::{GovernanceConfig, GovernanceVote};
+use crate::holdings::BorshReader;
+use crate::rpc_pool::RpcPool;
+use solana_sdk::{
+    account::Account,
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    system_program,
+};
+
+/// `GovernanceAccountType` tags of the accounts read here.
+const PROPOSAL_V2: u8 = 14;
+const TOKEN_OWNER_RECORD_V1: u8 = 2;
+const TOKEN_OWNER_RECORD_V2: u8 = 17;
+
+/// `GovernanceInstruction::CastVote`.
+const CAST_VOTE: u8 = 13;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProposalState {
+    Draft,
+    SigningOff,
+    Voting,
+    Succeeded,
+    Executing,
+    Completed,
+    Cancelled,
+    Defeated,
+    ExecutingWithErrors,
+    Vetoed,
+}
+
+impl ProposalState {
+    fn from_tag(tag: u8) -> Option<ProposalState> {
+        Some(match tag {
+            0 => ProposalState::Draft,
+            1 => ProposalState::SigningOff,
+            2 => ProposalState::Voting,
+            3 => ProposalState::Succeeded,
+            4 => ProposalState::Executing,
+            5 => ProposalState::Completed,
+            6 => ProposalState::Cancelled,
+            7 => ProposalState::Defeated,
+            8 => ProposalState::ExecutingWithErrors,
+            9 => ProposalState::Vetoed,
+            _ => return None,
+        })
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ProposalState::Draft => "Draft",
+            ProposalState::SigningOff => "Signing off",
+            ProposalState::Voting => "Voting",
+            ProposalState::Succeeded => "Succeeded",
+            ProposalState::Executing => "Executing",
+            ProposalState::Completed => "Completed",
+            ProposalState::Cancelled => "Cancelled",
+            ProposalState::Defeated => "Defeated",
+            ProposalState::ExecutingWithErrors => "Executing with errors",
+            ProposalState::Vetoed => "Vetoed",
+        }
+    }
+}
+
+/// The fields of a `ProposalV2` account a vote needs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Proposal {
+    pub governance: Pubkey,
+    pub governing_token_mint: Pubkey,
+    pub state: ProposalState,
+    /// Token owner record of the proposal's author.
+    pub token_owner_record: Pubkey,
+    /// Number of options. Yes/No proposals have one.
+    pub options: usize,
+    /// Raw token amounts voted for the first option, against and abstaining.
+    pub yes_weight: u64,
+    pub no_weight: u64,
+    pub abstain_weight: u64,
+    pub name: String,
+}
+
+impl Proposal {
+    /// Reads a `ProposalV2` account.
+    ///
+    /// Walks the Borsh layout up to the `name` field.
+    pub fn parse(data: &[u8]) -> Option<Proposal> {
+        let mut reader = BorshReader::new(data);
+        if reader.u8()? != PROPOSAL_V2 {
+            return None;
+        }
+        let governance = pubkey(&mut reader)?;
+        let governing_token_mint = pubkey(&mut reader)?;
+        let state = ProposalState::from_tag(reader.u8()?)?;
+        let token_owner_record = pubkey(&mut reader)?;
+        reader.skip(2)?; // signatories, signed off
+        match reader.u8()? {
+            0 => {}               // single choice
+            1 => reader.skip(4)?, // multi choice and its limits
+            _ => return None,
+        }
+
+        let options = reader.u32()? as usize;
+        let mut yes_weight = 0;
+        for index in 0..options {
+            let label = reader.u32()? as usize;
+            reader.skip(label)?;
+            let weight = reader.u64()?;
+            if index == 0 {
+                yes_weight = weight;
+            }
+            reader.skip(1 + 2 + 2 + 2)?; // result, transaction counters
+        }
+        let no_weight = option_u64(&mut reader)?.unwrap_or(0);
+        reader.skip(1)?; // reserved
+        let abstain_weight = option_u64(&mut reader)?.unwrap_or(0);
+
+        option_u64(&mut reader)?; // start voting at
+        reader.skip(8)?; // draft at
+        for _ in 0..6 {
+            // signing off, voting, voting slot, completed, executing, closed
+            option_u64(&mut reader)?;
+        }
+        reader.skip(1)?; // execution flags
+        option_u64(&mut reader)?; // max vote weight
+        if reader.u8()? == 1 {
+            reader.skip(4)?; // max voting time
+        }
+        if reader.u8()? == 1 {
+            // vote threshold: percentages carry a value, disabled does not
+            if reader.u8()? != 2 {
+                reader.skip(1)?;
+            }
+        }
+        reader.skip(64)?; // reserved
+        let name = reader.u32()? as usize;
+        let name = String::from_utf8(reader.take(name)?.to_vec()).ok()?;
+
+        Some(Proposal {
+            governance,
+            governing_token_mint,
+            state,
+            token_owner_record,
+            options,
+            yes_weight,
+            no_weight,
+            abstain_weight,
+            name,
+        })
+    }
+}
+
+/// The proposal of a governance blink, checked to belong to its governance.
+pub async fn fetch_proposal(rpc: &RpcPool, config: &GovernanceConfig) -> Result<Proposal, String> {
+    let account = fetch_account(rpc, &config.proposal)
+        .await?
+        .filter(|account| account.owner == config.program_id)
+        .ok_or_else(|| format!("{} is not a governance proposal", config.proposal))?;
+    let proposal = Proposal::parse(&account.data)
+        .ok_or_else(|| format!("{} is not a governance proposal", config.proposal))?;
+    if proposal.governance != config.governance {
+        return Err(format!(
+            "Proposal {} belongs to another governance",
+            config.proposal
+        ));
+    }
+    Ok(proposal)
+}
+
+/// Governing tokens of `mint` that `owner` deposited in the realm, or `None`
+/// when it has no token owner record.
+pub async fn fetch_deposit(
+    rpc: &RpcPool,
+    config: &GovernanceConfig,
+    mint: &Pubkey,
+    owner: &Pubkey,
+) -> Result<Option<u64>, String> {
+    let record = token_owner_record_address(config, mint, owner);
+    let Some(account) = fetch_account(rpc, &record)
+        .await?
+        .filter(|account| account.owner == config.program_id)
+    else {
+        return Ok(None);
+    };
+    Ok(deposit_amount(&account.data))
+}
+
+/// Casts `vote` on the proposal as the token owner `voter`, who also pays
+/// for the vote record.
+pub fn cast_vote_instruction(
+    config: &GovernanceConfig,
+    proposal: &Proposal,
+    voter: &Pubkey,
+    vote: GovernanceVote,
+) -> Instruction {
+    let program_id = &config.program_id;
+    let mint = &proposal.governing_token_mint;
+    let voter_record = token_owner_record_address(config, mint, voter);
+    let vote_record = Pubkey::find_program_address(
+        &[
+            b"governance",
+            config.proposal.as_ref(),
+            voter_record.as_ref(),
+        ],
+        program_id,
+    )
+    .0;
+    let realm_config =
+        Pubkey::find_program_address(&[b"realm-config", config.realm.as_ref()], program_id).0;
+
+    let mut data = vec![CAST_VOTE];
+    match vote {
+        // Approve: one choice with rank 0 and the full weight.
+        GovernanceVote::Yes => {
+            data.push(0);
+            data.extend_from_slice(&1u32.to_le_bytes());
+            data.extend_from_slice(&[0, 100]);
+        }
+        GovernanceVote::No => data.push(1),
+        GovernanceVote::Abstain => data.push(2),
+    }
+
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(config.realm, false),
+            AccountMeta::new(config.governance, false),
+            AccountMeta::new(config.proposal, false),
+            AccountMeta::new(proposal.token_owner_record, false),
+            AccountMeta::new(voter_record, false),
+            AccountMeta::new_readonly(*voter, true),
+            AccountMeta::new(vote_record, false),
+            AccountMeta::new_readonly(*mint, false),
+            AccountMeta::new(*voter, true),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(realm_config, false),
+        ],
+        data,
+    }
+}
+
+fn token_owner_record_address(config: &GovernanceConfig, mint: &Pubkey, owner: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            b"governance",
+            config.realm.as_ref(),
+            mint.as_ref(),
+            owner.as_ref(),
+        ],
+        &config.program_id,
+    )
+    .0
+}
+
+/// The deposit of a token owner record: after the account type, realm, mint
+/// and owner.
+fn deposit_amount(data: &[u8]) -> Option<u64> {
+    let mut reader = BorshReader::new(data);
+    if !matches!(reader.u8()?, TOKEN_OWNER_RECORD_V1 | TOKEN_OWNER_RECORD_V2) {
+        return None;
+    }
+    reader.skip(32 * 3)?;
+    reader.u64()
+}
+
+async fn fetch_account(rpc: &RpcPool, address: &Pubkey) -> Result<Option<Account>, String> {
+    let commitment = rpc.commitment();
+    rpc.call(|client| async move {
+        client
+            .get_account_with_commitment(address, commitment)
+            .await
+    })
+    .await
+    .map(|response| response.value)
+    .map_err(|e| format!("RPC Error: {}", e))
+}
+
+fn pubkey(reader: &mut BorshReader) -> Option<Pubkey> {
+    Pubkey::try_from(reader.take(32)?).ok()
+}
+
+fn option_u64(reader: &mut BorshReader) -> Option<Option<u64>> {
+    match reader.u8()? {
+        0 => Some(None),
+        1 => Some(Some(reader.u64()?)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A `ProposalV2` in `state` with one option.
+    fn proposal_data(governance: &Pubkey, state: u8) -> Vec<u8> {
+        let mut data = vec![PROPOSAL_V2];
+        data.extend_from_slice(governance.as_ref());
+        data.extend_from_slice(Pubkey::new_unique().as_ref());
+        data.push(state);
+        data.extend_from_slice(Pubkey::new_unique().as_ref());
+        data.extend_from_slice(&[1, 1, 0]);
+        data.extend_from_slice(&1u32.to_le_bytes());
+        data.extend_from_slice(&7u32.to_le_bytes());
+        data.extend_from_slice(b"Approve");
+        data.extend_from_slice(&700u64.to_le_bytes());
+        data.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0]);
+        data.push(1);
+        data.extend_from_slice(&200u64.to_le_bytes());
+        data.push(0);
+        data.push(1);
+        data.extend_from_slice(&100u64.to_le_bytes());
+        data.push(0);
+        data.extend_from_slice(&1_700_000_000i64.to_le_bytes());
+        data.extend_from_slice(&[0, 1]);
+        data.extend_from_slice(&1_700_000_100i64.to_le_bytes());
+        data.extend_from_slice(&[0, 0, 0, 0]);
+        data.push(0);
+        data.extend_from_slice(&[0, 0]);
+        data.extend_from_slice(&[1, 0, 60]);
+        data.extend_from_slice(&[0; 64]);
+        data.extend_from_slice(&14u32.to_le_bytes());
+        data.extend_from_slice(b"Fund the grant");
+        data
+    }
+
+    #[test]
+    fn reads_proposals() {
+        let governance = Pubkey::new_unique();
+        let data = proposal_data(&governance, 2);
+
+        let proposal = Proposal::parse(&data).unwrap();
+
+        assert_eq!(proposal.governance, governance);
+        assert_eq!(proposal.state, ProposalState::Voting);
+        assert_eq!(proposal.options, 1);
+        assert_eq!(
+            (
+                proposal.yes_weight,
+                proposal.no_weight,
+                proposal.abstain_weight
+            ),
+            (700, 200, 100)
+        );
+        assert_eq!(proposal.name, "Fund the grant");
+        assert_eq!(Proposal::parse(&data[..data.len() - 1]), None);
+    }
+
+    #[test]
+    fn encodes_votes() {
+        let config = GovernanceConfig {
+            program_id: crate::domain::GOVERNANCE_PROGRAM_ID,
+            realm: Pubkey::new_unique(),
+            governance: Pubkey::new_unique(),
+            proposal: Pubkey::new_unique(),
+        };
+        let proposal = Proposal::parse(&proposal_data(&config.governance, 2)).unwrap();
+        let voter = Pubkey::new_unique();
+
+        let yes = cast_vote_instruction(&config, &proposal, &voter, GovernanceVote::Yes);
+        let no = cast_vote_instruction(&config, &proposal, &voter, GovernanceVote::No);
+
+        assert_eq!(yes.data, [13, 0, 1, 0, 0, 0, 0, 100]);
+        assert_eq!(no.data, [13, 1]);
+        assert_eq!(yes.accounts.len(), 11);
+        assert_eq!(
+            yes.accounts[4].pubkey,
+            token_owner_record_address(&config, &proposal.governing_token_mint, &voter)
+        );
+    }
+}
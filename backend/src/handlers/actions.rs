@@ -1,48 +1,348 @@
 use axum::{
     Json,
-    extract::{Path, Query, State},
-    http::{HeaderMap, StatusCode},
-    response::IntoResponse,
+    extract::{Path, Query, RawQuery, State},
+    http::{HeaderMap, HeaderValue, StatusCode, Uri, header},
+    response::{IntoResponse, Redirect, Response},
 };
 use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
-use solana_client::nonblocking::rpc_client::RpcClient;
+use percent_encoding::{NON_ALPHANUMERIC, utf8_percent_encode};
 use solana_sdk::{
-    commitment_config::CommitmentConfig,
     compute_budget::ComputeBudgetInstruction,
     instruction::{AccountMeta, Instruction},
     message::Message,
     native_token::LAMPORTS_PER_SOL,
     pubkey::Pubkey,
+    rent::Rent,
+    stake::{
+        self,
+        state::{Authorized, Lockup, StakeStateV2},
+    },
     system_instruction,
     transaction::Transaction,
 };
 use sqlx::PgPool;
 use std::str::FromStr;
+use std::sync::Arc;
 use uuid::Uuid;
 
+use super::claims::describe_claim_progress;
+use super::escrows::escrow_metadata;
+use super::governance::governance_metadata;
+use super::invoices::{
+    currency_label, fetch_blink_invoice, invoice_metadata, reconcile, stored_currency,
+};
+use super::messages::{confirm_message, message_metadata};
+use super::nonces::require_nonce_pool;
+use super::raffles::raffle_metadata;
+use super::subscriptions::{price_label, renewal_metadata};
+use super::swaps::swap_action;
+use super::votes::{SnapshotState, confirm_vote, ensure_snapshot, voting_power};
+use crate::blockhash_cache::BlockhashCache;
+use crate::claims::ClaimDistributor;
+use crate::domain::{
+    ActionPathRule, ActionRuleSet, BallotKind, ClaimConfig, EscrowConfig, GovernanceConfig,
+    GovernanceVote, MessageConfig, MessageMemo, MintConfig, Poll, RaffleConfig, SponsorConfig,
+    StakeConfig, SubscriptionConfig, SwapConfig, TokenGate, VoteMemo, VoteWeighting, WeightClaim,
+};
+use crate::escrows::{EscrowAgent, Terms};
+use crate::governance::{ProposalState, cast_vote_instruction, fetch_deposit, fetch_proposal};
+use crate::holdings;
+use crate::invoices::Currency;
+use crate::metadata_cache::{MetadataCache, etag_matches};
 use crate::models::{
-    ActionLinks, ActionMetadata, ActionParameter, ActionPostRequest, ActionPostResponse,
-    ActionQueryParams, ActionRule, ActionsJson, Blink, BlinkType, LinkedAction,
+    ActionError, ActionGetQuery, ActionLinks, ActionMetadata, ActionParameter,
+    ActionParameterOption, ActionPostLinks, ActionPostRequest, ActionPostResponse,
+    ActionQueryParams, ActionsJson, Blink, BlinkType, EscrowStep, InvoiceStatus, LinkedAction,
+    NextActionLink, NextActionPostRequest,
 };
+use crate::nft_minter::NftMinter;
+use crate::nonce_pool::NoncePool;
+use crate::raffles::TicketBooth;
+use crate::rpc_pool::RpcPool;
+use crate::signer_vault::{SignerVault, blink_signers};
+use crate::sponsorship::{LAMPORTS_PER_SIGNATURE, Sponsorship};
+use crate::subscriptions::{Approval, SubscriptionBiller};
+use crate::swaps::SwapRouter;
 
-const MEMO_PROGRAM_ID: &str = "MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr";
+pub(crate) const MEMO_PROGRAM_ID: &str = "MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr";
 const SOLANA_DEVNET_CHAIN_ID: &str = "solana:EtWTRABZaYq6iMfeYKouRu166VU2xqa1";
 
 #[tracing::instrument(
     name = "Fetching action metadata",
-    skip(pool),
-    fields(blink_id = %id)
+    skip(
+        pool,
+        cache,
+        rpc_pool,
+        biller,
+        escrow_agent,
+        ticket_booth,
+        params,
+        query,
+        headers
+    ),
+    fields(blink_key = %key)
 )]
+#[allow(clippy::too_many_arguments)]
 pub async fn get_action_metadata(
     State(pool): State<PgPool>,
-    Path(id): Path<Uuid>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
+    State(cache): State<Arc<MetadataCache>>,
+    State(rpc_pool): State<Arc<RpcPool>>,
+    State(biller): State<Arc<SubscriptionBiller>>,
+    State(escrow_agent): State<Arc<EscrowAgent>>,
+    State(ticket_booth): State<Arc<TicketBooth>>,
+    Path(key): Path<String>,
+    Query(params): Query<ActionGetQuery>,
+    RawQuery(query): RawQuery,
+    headers: HeaderMap,
+) -> Result<Response, (StatusCode, String)> {
+    // Answers for a specific wallet depend on its holdings, subscription or
+    // part in a deal and bypass the shared cache.
+    if let Some(subscriber) = &params.subscriber
+        && let Some(response) = subscriber_metadata(
+            &pool,
+            &rpc_pool,
+            &cache,
+            &biller,
+            &escrow_agent,
+            &ticket_booth,
+            &key,
+            query.clone(),
+            subscriber,
+        )
+        .await?
+    {
+        return Ok(response);
+    }
+    let account = params
+        .account
+        .as_deref()
+        .and_then(|account| Pubkey::from_str(account).ok());
+    if let Some(account) = account
+        && let Some(response) = account_metadata(
+            &pool,
+            &rpc_pool,
+            &cache,
+            &escrow_agent,
+            &ticket_booth,
+            &key,
+            query.clone(),
+            &account,
+        )
+        .await?
+    {
+        return Ok(response);
+    }
+
+    let metadata = match cache.get(&key) {
+        Some(metadata) => metadata,
+        None => {
+            let mut blink = match resolve_blink(&pool, &key).await? {
+                BlinkLookup::Found(blink) => *blink,
+                BlinkLookup::Moved(public_id) => {
+                    return Ok(redirect_to_action(&public_id, query));
+                }
+            };
+            let metadata = render_live_metadata(
+                &pool,
+                &rpc_pool,
+                &cache,
+                &escrow_agent,
+                &ticket_booth,
+                &mut blink,
+                None,
+                None,
+            )
+            .await?;
+            let body = serde_json::to_vec(&metadata)
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+            cache.insert(&key, blink.id, blink.revision, body.into())
+        }
+    };
+
+    let mut response_headers = action_headers();
+    response_headers.insert(header::ETAG, metadata.etag.parse().unwrap());
+    response_headers.insert(
+        header::CACHE_CONTROL,
+        cache.cache_control().parse().unwrap(),
+    );
+
+    let not_modified = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| etag_matches(value, &metadata.etag));
+    if not_modified {
+        return Ok((StatusCode::NOT_MODIFIED, response_headers).into_response());
+    }
+
+    response_headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/json"),
+    );
+    Ok((response_headers, metadata.body).into_response())
+}
+
+/// Metadata of a gated or escrow blink as seen by `account`, or `None` for
+/// other blinks. Gated blinks also get `None` when the holdings cannot be
+/// checked right now; the gate is enforced again when the transaction is
+/// built.
+#[allow(clippy::too_many_arguments)]
+async fn account_metadata(
+    pool: &PgPool,
+    rpc_pool: &RpcPool,
+    cache: &MetadataCache,
+    escrow_agent: &EscrowAgent,
+    ticket_booth: &TicketBooth,
+    key: &str,
+    query: Option<String>,
+    account: &Pubkey,
+) -> Result<Option<Response>, (StatusCode, String)> {
+    let mut blink = match resolve_blink(pool, key).await? {
+        BlinkLookup::Found(blink) => *blink,
+        BlinkLookup::Moved(public_id) => return Ok(Some(redirect_to_action(&public_id, query))),
+    };
+    let escrow = matches!(blink.r#type, BlinkType::Escrow);
+    let gate = TokenGate::from_config(&blink.config)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    let qualifies = match &gate {
+        Some(gate) => {
+            match holdings::meets_requirement(rpc_pool, &gate.requirement, account).await {
+                Ok(qualifies) => Some(qualifies),
+                Err(e) => {
+                    tracing::warn!("Failed to check holdings of {}: {}", account, e);
+                    None
+                }
+            }
+        }
+        None => None,
+    };
+    if qualifies.is_none() && !escrow {
+        return Ok(None);
+    }
+
+    let gate = gate.as_ref().zip(qualifies);
+    let mut response_headers = action_headers();
+    response_headers.insert(
+        header::CACHE_CONTROL,
+        HeaderValue::from_static("private, no-store"),
+    );
+    Ok(Some(
+        (
+            response_headers,
+            Json(
+                render_live_metadata(
+                    pool,
+                    rpc_pool,
+                    cache,
+                    escrow_agent,
+                    ticket_booth,
+                    &mut blink,
+                    gate,
+                    Some(account),
+                )
+                .await?,
+            ),
+        )
+            .into_response(),
+    ))
+}
+
+/// Metadata of a subscription blink as the renewal link of `subscriber`, or
+/// `None` when the blink is not a subscription.
+#[allow(clippy::too_many_arguments)]
+async fn subscriber_metadata(
+    pool: &PgPool,
+    rpc_pool: &RpcPool,
+    cache: &MetadataCache,
+    biller: &SubscriptionBiller,
+    escrow_agent: &EscrowAgent,
+    ticket_booth: &TicketBooth,
+    key: &str,
+    query: Option<String>,
+    subscriber: &str,
+) -> Result<Option<Response>, (StatusCode, String)> {
+    let mut blink = match resolve_blink(pool, key).await? {
+        BlinkLookup::Found(blink) => *blink,
+        BlinkLookup::Moved(public_id) => return Ok(Some(redirect_to_action(&public_id, query))),
+    };
+    if !matches!(blink.r#type, BlinkType::Subscription) {
+        return Ok(None);
+    }
+    let subscriber = parse_pubkey(subscriber, "subscriber")?;
+    let mut metadata = render_live_metadata(
+        pool,
+        rpc_pool,
+        cache,
+        escrow_agent,
+        ticket_booth,
+        &mut blink,
+        None,
+        None,
+    )
+    .await?;
+    renewal_metadata(pool, biller, &blink, &subscriber, &mut metadata).await?;
+
+    let mut response_headers = action_headers();
+    response_headers.insert(
+        header::CACHE_CONTROL,
+        HeaderValue::from_static("private, no-store"),
+    );
+    Ok(Some((response_headers, Json(metadata)).into_response()))
+}
+
+fn action_headers() -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert("x-blockchain-ids", SOLANA_DEVNET_CHAIN_ID.parse().unwrap());
+    headers.insert("x-action-version", "2.1.3".parse().unwrap());
+    headers
+}
+
+/// Renders the metadata of a blink with the current state of its claims,
+/// invoice, deal, raffle, message feed or proposal. `viewer` is the viewing wallet, when it is known.
+#[allow(clippy::too_many_arguments)]
+async fn render_live_metadata(
+    pool: &PgPool,
+    rpc_pool: &RpcPool,
+    cache: &MetadataCache,
+    escrow_agent: &EscrowAgent,
+    ticket_booth: &TicketBooth,
+    blink: &mut Blink,
+    gate: Option<(&TokenGate, bool)>,
+    viewer: Option<&Pubkey>,
+) -> Result<ActionMetadata, (StatusCode, String)> {
+    describe_claim_progress(pool, blink).await?;
+    let mut metadata = render_metadata(blink, gate)?;
+    if matches!(blink.r#type, BlinkType::Payment)
+        && let Some(invoice) = fetch_blink_invoice(pool, blink.id).await?
+    {
+        let invoice = reconcile(pool, rpc_pool, cache, invoice).await?;
+        invoice_metadata(&invoice, blink, &mut metadata)?;
+    }
+    if matches!(blink.r#type, BlinkType::Escrow) {
+        escrow_metadata(pool, rpc_pool, escrow_agent, blink, viewer, &mut metadata).await?;
+    }
+    if matches!(blink.r#type, BlinkType::Raffle) {
+        raffle_metadata(pool, rpc_pool, ticket_booth, blink, &mut metadata).await?;
+    }
+    if matches!(blink.r#type, BlinkType::Message) {
+        message_metadata(pool, blink, &mut metadata).await?;
+    }
+    if matches!(blink.r#type, BlinkType::Governance) {
+        governance_metadata(rpc_pool, blink, &mut metadata).await?;
+    }
+    Ok(metadata)
+}
+
+/// Renders the metadata of a blink. `gate` is the blink's gate and whether
+/// the viewing wallet qualifies, when the wallet is known.
+fn render_metadata(
+    blink: &Blink,
+    gate: Option<(&TokenGate, bool)>,
+) -> Result<ActionMetadata, (StatusCode, String)> {
     let backend_url =
         std::env::var("BACKEND_URL").unwrap_or_else(|_| "http://localhost:8000".to_string());
+    let id = blink.public_id();
 
-    let blink = fetch_blink(&pool, id).await?;
-
-    let actions = match blink.r#type {
+    let mut actions = match blink.r#type {
         BlinkType::Donation => vec![LinkedAction {
             label: blink.label.clone(),
             href: format!(
@@ -64,71 +364,410 @@ pub async fn get_action_metadata(
                 name: "amount".to_string(),
                 label: Some("Enter SOL amount".to_string()),
                 required: Some(true),
+                r#type: None,
+                options: None,
+                min: None,
+                max: None,
             }]),
         }],
         BlinkType::Vote => {
-            let options = blink
-                .config
-                .get("options")
-                .and_then(|v| v.as_array())
-                .ok_or((
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    "Invalid vote config".to_string(),
-                ))?;
-
-            options
+            let poll = Poll::from_config(&blink.config)
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+            vote_actions(&poll, &format!("{}/api/actions/{}", backend_url, id))
+        }
+        BlinkType::Mint | BlinkType::Claim => vec![LinkedAction {
+            label: blink.label.clone(),
+            href: format!("{}/api/actions/{}", backend_url, id),
+            parameters: None,
+        }],
+        BlinkType::Stake => {
+            let config = StakeConfig::from_config(&blink.config)
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+            let mut actions = config
+                .amounts
                 .iter()
-                .map(|opt| {
-                    let val = opt.as_str().unwrap_or("Unknown");
-                    LinkedAction {
-                        label: format!("Vote {}", val),
-                        href: format!("{}/api/actions/{}?selection={}", backend_url, id, val),
-                        parameters: None,
-                    }
+                .map(|amount| LinkedAction {
+                    label: format!("Stake {} SOL", amount),
+                    href: format!("{}/api/actions/{}?amount={}", backend_url, id, amount),
+                    parameters: None,
                 })
-                .collect()
+                .collect::<Vec<_>>();
+            actions.push(LinkedAction {
+                label: blink.label.clone(),
+                href: format!("{}/api/actions/{}?amount={{amount}}", backend_url, id),
+                parameters: Some(vec![ActionParameter {
+                    name: "amount".to_string(),
+                    label: Some(format!("Enter SOL amount (min {})", config.min_amount)),
+                    required: Some(true),
+                    r#type: None,
+                    options: None,
+                    min: None,
+                    max: None,
+                }]),
+            });
+            actions
+        }
+        BlinkType::Subscription => {
+            let config = SubscriptionConfig::from_config(&blink.config)
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+            vec![LinkedAction {
+                label: format!("{} ({})", blink.label, price_label(&config)),
+                href: format!("{}/api/actions/{}", backend_url, id),
+                parameters: None,
+            }]
+        }
+        BlinkType::Escrow => {
+            let config = EscrowConfig::from_config(&blink.config)
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+            vec![LinkedAction {
+                label: format!(
+                    "Deposit {} {}",
+                    config.amount,
+                    currency_label(&config.currency)
+                ),
+                href: format!("{}/api/actions/{}?step=deposit", backend_url, id),
+                parameters: None,
+            }]
+        }
+        BlinkType::Message => {
+            let config = MessageConfig::from_config(&blink.config)
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+            let mut href = format!("{}/api/actions/{}?message={{message}}", backend_url, id);
+            let mut parameters = vec![ActionParameter {
+                name: "message".to_string(),
+                label: Some(format!(
+                    "Your message (max {} characters)",
+                    config.max_length
+                )),
+                required: Some(true),
+                r#type: Some("textarea".to_string()),
+                options: None,
+                min: None,
+                max: None,
+            }];
+            if config.tips {
+                href.push_str("&amount={amount}");
+                parameters.push(ActionParameter {
+                    name: "amount".to_string(),
+                    label: Some("Tip in SOL (optional)".to_string()),
+                    required: Some(false),
+                    r#type: None,
+                    options: None,
+                    min: None,
+                    max: None,
+                });
+            }
+            vec![LinkedAction {
+                label: blink.label.clone(),
+                href,
+                parameters: Some(parameters),
+            }]
+        }
+        BlinkType::Raffle => {
+            let config = RaffleConfig::from_config(&blink.config)
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+            vec![
+                LinkedAction {
+                    label: format!("Buy 1 ticket ({} SOL)", config.ticket_price),
+                    href: format!("{}/api/actions/{}?tickets=1", backend_url, id),
+                    parameters: None,
+                },
+                LinkedAction {
+                    label: blink.label.clone(),
+                    href: format!("{}/api/actions/{}?tickets={{tickets}}", backend_url, id),
+                    parameters: Some(vec![ActionParameter {
+                        name: "tickets".to_string(),
+                        label: Some(format!("Number of tickets (max {})", config.max_per_wallet)),
+                        required: Some(true),
+                        r#type: None,
+                        options: None,
+                        min: None,
+                        max: None,
+                    }]),
+                },
+            ]
+        }
+        BlinkType::Governance => GovernanceVote::ALL
+            .iter()
+            .map(|vote| LinkedAction {
+                label: vote.label().to_string(),
+                href: format!("{}/api/actions/{}?vote={}", backend_url, id, vote.as_str()),
+                parameters: None,
+            })
+            .collect(),
+        BlinkType::Swap => {
+            let config = SwapConfig::from_config(&blink.config)
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+            vec![LinkedAction {
+                label: blink.label.clone(),
+                href: format!(
+                    "{}/api/actions/{}?amount={{amount}}&slippage={{slippage}}",
+                    backend_url, id
+                ),
+                parameters: Some(vec![
+                    ActionParameter {
+                        name: "amount".to_string(),
+                        label: Some("Enter SOL amount".to_string()),
+                        required: Some(true),
+                        r#type: None,
+                        options: None,
+                        min: None,
+                        max: None,
+                    },
+                    ActionParameter {
+                        name: "slippage".to_string(),
+                        label: Some(format!(
+                            "Slippage % (default {}, max {})",
+                            f64::from(config.slippage_bps) / 100.0,
+                            f64::from(config.max_slippage_bps) / 100.0
+                        )),
+                        required: Some(false),
+                        r#type: None,
+                        options: None,
+                        min: None,
+                        max: None,
+                    },
+                ]),
+            }]
         }
     };
 
-    let response_body = ActionMetadata {
-        icon: blink.icon_url,
+    let mut error = None;
+    match gate {
+        Some((gate, false)) if !gate.is_discount() => {
+            error = Some(ActionError {
+                message: gate.denial_message(),
+            });
+        }
+        Some((gate, true))
+            if !matches!(
+                blink.r#type,
+                BlinkType::Vote
+                    | BlinkType::Stake
+                    | BlinkType::Claim
+                    | BlinkType::Subscription
+                    | BlinkType::Escrow
+                    | BlinkType::Raffle
+                    | BlinkType::Message
+                    | BlinkType::Governance
+                    | BlinkType::Swap
+            ) =>
+        {
+            if let Some(discount) = gate.discount_percent {
+                for action in &mut actions {
+                    action.label = format!("{} ({}% holder discount)", action.label, discount);
+                }
+            }
+        }
+        _ => {}
+    }
+
+    Ok(ActionMetadata {
+        icon: blink.icon_url.clone(),
         label: blink.label.clone(),
-        title: blink.title,
-        description: blink.description,
+        title: blink.title.clone(),
+        description: blink.description.clone(),
         links: Some(ActionLinks { actions }),
-        disabled: None,
-    };
+        disabled: error.as_ref().map(|_| true),
+        error,
+    })
+}
 
-    let mut headers = HeaderMap::new();
-    headers.insert("x-blockchain-ids", SOLANA_DEVNET_CHAIN_ID.parse().unwrap());
-    headers.insert("x-action-version", "2.1.3".parse().unwrap());
+/// Buttons of a poll. Hrefs only carry option ids, percent-encoded since
+/// plain-text options use their text as id. Multi-select polls are one
+/// checkbox group and ranked polls one select per rank; clients join the
+/// picks into a comma-separated `selection`.
+fn vote_actions(poll: &Poll, action_url: &str) -> Vec<LinkedAction> {
+    let choices = || {
+        poll.options
+            .iter()
+            .map(|option| ActionParameterOption {
+                label: option.label.clone(),
+                value: option.id.clone(),
+            })
+            .collect()
+    };
 
-    Ok((headers, Json(response_body)))
+    match poll.ballot {
+        BallotKind::Single => poll
+            .options
+            .iter()
+            .map(|option| LinkedAction {
+                label: format!("Vote {}", option.label),
+                href: format!(
+                    "{}?selection={}",
+                    action_url,
+                    utf8_percent_encode(&option.id, NON_ALPHANUMERIC)
+                ),
+                parameters: None,
+            })
+            .collect(),
+        BallotKind::Multi { min, max } => vec![LinkedAction {
+            label: "Vote".to_string(),
+            href: format!("{}?selection={{selection}}", action_url),
+            parameters: Some(vec![ActionParameter {
+                name: "selection".to_string(),
+                label: Some(if min == max {
+                    format!("Pick {}", min)
+                } else {
+                    format!("Pick {} to {}", min, max)
+                }),
+                required: Some(true),
+                r#type: Some("checkbox".to_string()),
+                options: Some(choices()),
+                min: Some(min),
+                max: Some(max),
+            }]),
+        }],
+        BallotKind::Ranked { max } => {
+            let ranks: Vec<String> = (1..=max).map(|rank| format!("{{rank{}}}", rank)).collect();
+            vec![LinkedAction {
+                label: "Submit ranking".to_string(),
+                href: format!("{}?selection={}", action_url, ranks.join(",")),
+                parameters: Some(
+                    (1..=max)
+                        .map(|rank| ActionParameter {
+                            name: format!("rank{}", rank),
+                            label: Some(format!("Choice {}", rank)),
+                            required: Some(rank == 1),
+                            r#type: Some("select".to_string()),
+                            options: Some(choices()),
+                            min: None,
+                            max: None,
+                        })
+                        .collect(),
+                ),
+            }]
+        }
+    }
 }
 
 #[tracing::instrument(
     name = "Building action transaction",
-    skip(pool, params, payload),
-    fields(blink_id = %id, account = %payload.account)
+    skip(
+        pool,
+        blockhash_cache,
+        nonce_pool,
+        nft_minter,
+        signer_vault,
+        claim_distributor,
+        subscription_biller,
+        escrow_agent,
+        ticket_booth,
+        swap_router,
+        cache,
+        sponsorship,
+        uri,
+        payload
+    ),
+    fields(blink_key = %key, account = %payload.account)
 )]
+#[allow(clippy::too_many_arguments)]
 pub async fn post_action_transaction(
     State(pool): State<PgPool>,
-    Path(id): Path<Uuid>,
-    Query(params): Query<ActionQueryParams>,
+    State(blockhash_cache): State<Arc<BlockhashCache>>,
+    State(nonce_pool): State<Option<Arc<NoncePool>>>,
+    State(nft_minter): State<Option<Arc<NftMinter>>>,
+    State(signer_vault): State<Arc<SignerVault>>,
+    State(claim_distributor): State<Arc<ClaimDistributor>>,
+    State(subscription_biller): State<Arc<SubscriptionBiller>>,
+    State(escrow_agent): State<Arc<EscrowAgent>>,
+    State(ticket_booth): State<Arc<TicketBooth>>,
+    State(swap_router): State<Option<Arc<SwapRouter>>>,
+    State(cache): State<Arc<MetadataCache>>,
+    State(sponsorship): State<Option<Arc<Sponsorship>>>,
+    Path(key): Path<String>,
+    uri: Uri,
     Json(payload): Json<ActionPostRequest>,
-) -> Result<Json<ActionPostResponse>, (StatusCode, String)> {
-    let blink = fetch_blink(&pool, id).await?;
+) -> Result<Response, (StatusCode, String)> {
+    let Query(params) =
+        Query::<ActionQueryParams>::try_from_uri(&uri).map_err(|e| (e.status(), e.body_text()))?;
+    let query = uri.query().map(str::to_string);
+    let blink = match resolve_blink(&pool, &key).await? {
+        BlinkLookup::Found(blink) => *blink,
+        BlinkLookup::Moved(public_id) => return Ok(redirect_to_action(&public_id, query)),
+    };
     let user_pubkey = parse_pubkey(&payload.account, "user wallet")?;
 
-    let client = get_rpc_client()?;
-    let recent_blockhash = client.get_latest_blockhash().await.map_err(|e| {
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            format!("RPC Error: {}", e),
-        )
-    })?;
+    // Gates are evaluated before any builder runs. Wallets that miss a
+    // discount gate still pay full price.
+    let gate = TokenGate::from_config(&blink.config)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    let mut holder_discount = None;
+    if let Some(gate) = &gate {
+        let qualifies =
+            holdings::meets_requirement(blockhash_cache.rpc(), &gate.requirement, &user_pubkey)
+                .await
+                .map_err(|e| {
+                    (
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        format!("RPC Error: {}", e),
+                    )
+                })?;
+        if !qualifies && !gate.is_discount() {
+            return Ok(action_error(StatusCode::FORBIDDEN, gate.denial_message()));
+        }
+        if qualifies {
+            holder_discount = gate.discount_percent.map(|discount| (gate, discount));
+        }
+    }
+
+    let sponsor = SponsorConfig::from_config(&blink.config)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+
+    // New mint accounts and the mint authority sign server-side, as do the
+    // distributor of a claim, which also pays its fee, and the escrow key
+    // paying out a deal.
+    let mut mint = None;
+    let mut claim = None;
+    let mut escrow = None;
+    // Invoices fix the amount and carry a reference to find the payment by.
+    let mut invoice = match blink.r#type {
+        BlinkType::Payment => fetch_blink_invoice(&pool, blink.id).await?,
+        _ => None,
+    };
+    let (mut instructions, message) = match blink.r#type {
+        BlinkType::Payment if invoice.is_some() => {
+            let invoice = invoice.take().unwrap();
+            let invoice = reconcile(&pool, blockhash_cache.rpc(), &cache, invoice).await?;
+            match invoice.status {
+                InvoiceStatus::Open => {}
+                InvoiceStatus::Paid => {
+                    return Ok(action_error(
+                        StatusCode::FORBIDDEN,
+                        "This invoice has been paid".to_string(),
+                    ));
+                }
+                InvoiceStatus::Cancelled => {
+                    return Ok(action_error(
+                        StatusCode::FORBIDDEN,
+                        "This invoice was cancelled".to_string(),
+                    ));
+                }
+            }
+            let currency = stored_currency(&invoice)?;
+            let destination_pubkey = parse_pubkey(&blink.wallet_address, "destination wallet")?;
+            let reference = Pubkey::from_str(&invoice.reference).map_err(|e| {
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("Invalid stored reference: {}", e),
+                )
+            })?;
 
-    let (transaction, message) = match blink.r#type {
+            let ixs = currency.payment_instructions(
+                &user_pubkey,
+                &destination_pubkey,
+                invoice.amount as u64,
+                &reference,
+            );
+            let msg = format!(
+                "Pay invoice {}: {} {}",
+                invoice.number,
+                currency.format(invoice.amount as u64),
+                currency_label(&currency.code())
+            );
+            (ixs, msg)
+        }
         BlinkType::Donation | BlinkType::Payment => {
             let destination_pubkey = parse_pubkey(&blink.wallet_address, "destination wallet")?;
 
@@ -142,127 +781,884 @@ pub async fn post_action_transaction(
                     "Missing or invalid amount".to_string(),
                 ))?;
 
-            let tx = build_transfer_transaction(
-                &user_pubkey,
-                &destination_pubkey,
-                amount,
-                recent_blockhash,
-            )?;
-            let msg = format!("Send {} SOL to {}", amount, blink.title);
-            (tx, msg)
+            let (amount, msg) = match holder_discount {
+                Some((gate, discount)) => {
+                    let amount = gate.discounted(amount);
+                    let msg = format!(
+                        "Send {} SOL to {} ({}% holder discount)",
+                        amount, blink.title, discount
+                    );
+                    (amount, msg)
+                }
+                None => (amount, format!("Send {} SOL to {}", amount, blink.title)),
+            };
+
+            let ixs = transfer_instructions(&user_pubkey, &destination_pubkey, amount);
+            (ixs, msg)
         }
         BlinkType::Vote => {
             let selection = params
                 .selection
                 .as_ref()
                 .ok_or((StatusCode::BAD_REQUEST, "Missing selection".to_string()))?;
+            let poll = Poll::from_config(&blink.config)
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+            let picks = poll
+                .parse_selection(selection)
+                .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
 
-            let tx = build_memo_transaction(&user_pubkey, id, selection, recent_blockhash)?;
-            let msg = format!("Vote for: {}", selection);
-            (tx, msg)
+            // Weighted votes carry the voter's snapshot balance so the claim
+            // can be checked against the chain.
+            let weighting = VoteWeighting::from_config(&blink.config)
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+            let weight = match weighting {
+                None => None,
+                Some(weighting) => {
+                    let slot =
+                        match ensure_snapshot(&pool, blockhash_cache.rpc(), blink.id, &weighting)
+                            .await?
+                        {
+                            SnapshotState::Ready { slot } => slot,
+                            SnapshotState::Pending { opens_at } => {
+                                return Ok(action_error(
+                                    StatusCode::FORBIDDEN,
+                                    format!("Voting opens at slot {}", opens_at),
+                                ));
+                            }
+                        };
+                    let weight = voting_power(&pool, blink.id, &user_pubkey).await?;
+                    if weight == 0 {
+                        return Ok(action_error(
+                            StatusCode::FORBIDDEN,
+                            format!(
+                                "This wallet held no {} at snapshot slot {}",
+                                weighting.mint, slot
+                            ),
+                        ));
+                    }
+                    Some(WeightClaim {
+                        mint: weighting.mint,
+                        slot,
+                        weight,
+                    })
+                }
+            };
+
+            let msg = match &weight {
+                Some(claim) => format!(
+                    "Vote for: {} (weight {})",
+                    poll.describe(&picks),
+                    claim.weight
+                ),
+                None => format!("Vote for: {}", poll.describe(&picks)),
+            };
+            let memo = VoteMemo {
+                blink_id: blink.id,
+                selection: poll.encode(&picks),
+                weight,
+            };
+            let ixs = memo_instructions(&user_pubkey, &memo.to_string())?;
+            (ixs, msg)
+        }
+        BlinkType::Mint => {
+            let minter = nft_minter.ok_or((
+                StatusCode::SERVICE_UNAVAILABLE,
+                "NFT minting is not enabled".to_string(),
+            ))?;
+            let config = MintConfig::from_config(&blink.config)
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+            let destination_pubkey = parse_pubkey(&blink.wallet_address, "destination wallet")?;
+            let price = match holder_discount {
+                Some((gate, _)) => gate.discounted(config.price),
+                None => config.price,
+            };
+
+            let reserved = match minter
+                .reserve(
+                    &pool,
+                    blockhash_cache.rpc(),
+                    blink.id,
+                    &config,
+                    &user_pubkey,
+                )
+                .await
+            {
+                Ok(reserved) => reserved,
+                Err((StatusCode::FORBIDDEN, message)) => {
+                    return Ok(action_error(StatusCode::FORBIDDEN, message));
+                }
+                Err(e) => return Err(e),
+            };
+
+            let mut ixs = vec![ComputeBudgetInstruction::set_compute_unit_price(50_000)];
+            ixs.extend(minter.instructions(
+                &config,
+                &blink.title,
+                &reserved,
+                &user_pubkey,
+                &destination_pubkey,
+                (price * LAMPORTS_PER_SOL as f64) as u64,
+            ));
+            let mut msg = format!(
+                "Mint {} for {} SOL",
+                config.name(&blink.title, reserved.number),
+                price
+            );
+            if let Some((_, discount)) = holder_discount {
+                msg = format!("{} ({}% holder discount)", msg, discount);
+            }
+            mint = Some((minter, reserved));
+            (ixs, msg)
+        }
+        BlinkType::Stake => {
+            let config = StakeConfig::from_config(&blink.config)
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+            let amount: f64 = params.amount.as_ref().and_then(|a| a.parse().ok()).ok_or((
+                StatusCode::BAD_REQUEST,
+                "Missing or invalid amount".to_string(),
+            ))?;
+            config
+                .check_amount(amount)
+                .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
+            let ixs = stake_instructions(&user_pubkey, &config.validator, amount);
+            let msg = format!("Stake {} SOL with {}", amount, config.validator);
+            (ixs, msg)
+        }
+        BlinkType::Claim => {
+            let config = ClaimConfig::from_config(&blink.config)
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+            let distributor = signer_vault.pubkey(&config.distributor).ok_or((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Unknown signer: {}", config.distributor),
+            ))?;
+
+            let lamports = match claim_distributor
+                .reserve(&pool, blockhash_cache.rpc(), blink.id, &user_pubkey)
+                .await
+            {
+                Ok(lamports) => lamports,
+                Err((StatusCode::FORBIDDEN, message)) => {
+                    return Ok(action_error(StatusCode::FORBIDDEN, message));
+                }
+                Err(e) => return Err(e),
+            };
+            cache.invalidate(blink.id);
+
+            let ixs = claim_instructions(&distributor, &user_pubkey, lamports, blink.id)?;
+            let msg = format!("Claim {} SOL", lamports as f64 / LAMPORTS_PER_SOL as f64);
+            claim = Some((config.distributor, distributor));
+            (ixs, msg)
+        }
+        BlinkType::Subscription => {
+            if params
+                .subscriber
+                .as_ref()
+                .is_some_and(|subscriber| *subscriber != payload.account)
+            {
+                return Ok(action_error(
+                    StatusCode::FORBIDDEN,
+                    "This renewal link belongs to another wallet".to_string(),
+                ));
+            }
+            let config = SubscriptionConfig::from_config(&blink.config)
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+            let destination_pubkey = parse_pubkey(&blink.wallet_address, "destination wallet")?;
+            let currency = Currency::resolve(blockhash_cache.rpc(), &config.currency)
+                .await
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+            let amount = currency.base_units(config.price);
+            let approval = match &config.delegate {
+                Some(name) => Some(Approval {
+                    delegate: signer_vault.pubkey(name).ok_or((
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        format!("Unknown signer: {}", name),
+                    ))?,
+                    amount: amount.saturating_mul(u64::from(config.approve_periods)),
+                }),
+                None => None,
+            };
+
+            let reference = subscription_biller
+                .open_payment(
+                    &pool,
+                    blink.id,
+                    &user_pubkey,
+                    &currency,
+                    amount,
+                    config.period_days,
+                    approval,
+                )
+                .await?;
+            let mut ixs = currency.payment_instructions(
+                &user_pubkey,
+                &destination_pubkey,
+                amount,
+                &reference,
+            );
+            let label = currency_label(&currency.code());
+            let mut msg = format!(
+                "Subscribe to {} for {} days: {} {}",
+                blink.title,
+                config.period_days,
+                currency.format(amount),
+                label
+            );
+            if let Some(approval) = approval {
+                ixs.extend(currency.approve_instruction(
+                    &user_pubkey,
+                    &approval.delegate,
+                    approval.amount,
+                ));
+                msg = format!(
+                    "{}, approving {} {} for automatic renewals",
+                    msg,
+                    currency.format(approval.amount),
+                    label
+                );
+            }
+            (ixs, msg)
+        }
+        BlinkType::Escrow => {
+            let step = match params.step.as_deref() {
+                Some(step) => EscrowStep::parse(step).map_err(|e| (StatusCode::BAD_REQUEST, e))?,
+                None => EscrowStep::Deposit,
+            };
+            let config = EscrowConfig::from_config(&blink.config)
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+            let escrow_key = signer_vault.pubkey(&config.escrow).ok_or((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Unknown signer: {}", config.escrow),
+            ))?;
+            let seller = parse_pubkey(&blink.wallet_address, "destination wallet")?;
+            let currency = Currency::resolve(blockhash_cache.rpc(), &config.currency)
+                .await
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+
+            let terms = Terms {
+                config: &config,
+                escrow_key: &escrow_key,
+                currency: &currency,
+                seller: &seller,
+            };
+            let transfer = match escrow_agent
+                .open(
+                    &pool,
+                    blockhash_cache.rpc(),
+                    blink.id,
+                    step,
+                    &user_pubkey,
+                    terms,
+                )
+                .await
+            {
+                Ok(transfer) => transfer,
+                Err((StatusCode::FORBIDDEN, message)) => {
+                    return Ok(action_error(StatusCode::FORBIDDEN, message));
+                }
+                Err(e) => return Err(e),
+            };
+            cache.invalidate(blink.id);
+
+            // The wallet taking the step pays the fee; payouts leave the
+            // escrow key.
+            let ixs = transfer.currency.transfer_instructions(
+                &user_pubkey,
+                &transfer.from,
+                &transfer.to,
+                transfer.amount,
+                &transfer.reference,
+            );
+            let amount = format!(
+                "{} {}",
+                transfer.currency.format(transfer.amount),
+                currency_label(&transfer.currency.code())
+            );
+            let msg = match step {
+                EscrowStep::Deposit => {
+                    format!("Deposit {} into escrow for {}", amount, blink.title)
+                }
+                EscrowStep::Release => {
+                    escrow = Some(transfer.escrow);
+                    format!("Release {} to the seller of {}", amount, blink.title)
+                }
+                EscrowStep::Refund => {
+                    escrow = Some(transfer.escrow);
+                    format!("Refund {} to the buyer of {}", amount, blink.title)
+                }
+            };
+            (ixs, msg)
+        }
+        BlinkType::Message => {
+            let config = MessageConfig::from_config(&blink.config)
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+            let text = params
+                .message
+                .as_deref()
+                .ok_or((StatusCode::BAD_REQUEST, "Missing message".to_string()))?;
+            let text = match config.check_message(text) {
+                Ok(text) => text,
+                Err(message) => return Ok(action_error(StatusCode::BAD_REQUEST, message)),
+            };
+            // Clients fill an empty tip in as an empty string.
+            let tip = match params.amount.as_deref().filter(|amount| !amount.is_empty()) {
+                None => None,
+                Some(_) if !config.tips => {
+                    return Err((
+                        StatusCode::BAD_REQUEST,
+                        "This blink takes no tips".to_string(),
+                    ));
+                }
+                Some(amount) => Some(
+                    amount
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|amount| amount.is_finite() && *amount > 0.0)
+                        .ok_or((StatusCode::BAD_REQUEST, "Invalid tip amount".to_string()))?,
+                ),
+            };
+
+            let memo = MessageMemo {
+                blink_id: blink.id,
+                text,
+            };
+            let mut ixs = memo_instructions(&user_pubkey, &memo.to_string())?;
+            let msg = match tip {
+                Some(tip) => {
+                    let destination_pubkey =
+                        parse_pubkey(&blink.wallet_address, "destination wallet")?;
+                    ixs.push(system_instruction::transfer(
+                        &user_pubkey,
+                        &destination_pubkey,
+                        (tip * LAMPORTS_PER_SOL as f64) as u64,
+                    ));
+                    format!("Post a message to {} with a {} SOL tip", blink.title, tip)
+                }
+                None => format!("Post a message to {}", blink.title),
+            };
+            (ixs, msg)
+        }
+        BlinkType::Raffle => {
+            let tickets: u32 = params
+                .tickets
+                .as_deref()
+                .unwrap_or("1")
+                .parse()
+                .map_err(|_| (StatusCode::BAD_REQUEST, "Invalid ticket count".to_string()))?;
+            let config = RaffleConfig::from_config(&blink.config)
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+            let seller = parse_pubkey(&blink.wallet_address, "destination wallet")?;
+            let (reference, lamports) = match ticket_booth
+                .buy(
+                    &pool,
+                    blockhash_cache.rpc(),
+                    blink.id,
+                    &config,
+                    &seller,
+                    &user_pubkey,
+                    tickets,
+                )
+                .await
+            {
+                Ok(purchase) => purchase,
+                Err((StatusCode::FORBIDDEN, message)) => {
+                    return Ok(action_error(StatusCode::FORBIDDEN, message));
+                }
+                Err(e) => return Err(e),
+            };
+            cache.invalidate(blink.id);
+
+            let ixs =
+                Currency::Sol.payment_instructions(&user_pubkey, &seller, lamports, &reference);
+            let msg = format!(
+                "Buy {} ticket{} for {}: {} SOL",
+                tickets,
+                if tickets == 1 { "" } else { "s" },
+                blink.title,
+                Currency::Sol.format(lamports)
+            );
+            (ixs, msg)
+        }
+        BlinkType::Governance => {
+            let vote = params
+                .vote
+                .as_deref()
+                .ok_or((StatusCode::BAD_REQUEST, "Missing vote".to_string()))
+                .and_then(|vote| {
+                    GovernanceVote::parse(vote).map_err(|e| (StatusCode::BAD_REQUEST, e))
+                })?;
+            let config = GovernanceConfig::from_config(&blink.config)
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+            let rpc = blockhash_cache.rpc();
+            let proposal = fetch_proposal(rpc, &config)
+                .await
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+            if proposal.state != ProposalState::Voting {
+                return Ok(action_error(
+                    StatusCode::FORBIDDEN,
+                    "This proposal is not open for voting".to_string(),
+                ));
+            }
+            if vote == GovernanceVote::Yes && proposal.options != 1 {
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    "Only single-option proposals can be voted on".to_string(),
+                ));
+            }
+            let deposit = fetch_deposit(rpc, &config, &proposal.governing_token_mint, &user_pubkey)
+                .await
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+            if deposit.unwrap_or(0) == 0 {
+                return Ok(action_error(
+                    StatusCode::FORBIDDEN,
+                    "This wallet has no governing tokens deposited in the realm".to_string(),
+                ));
+            }
+
+            let ixs = vec![
+                ComputeBudgetInstruction::set_compute_unit_price(50_000),
+                cast_vote_instruction(&config, &proposal, &user_pubkey, vote),
+            ];
+            let msg = format!("Vote {} on {}", vote.label(), proposal.name);
+            (ixs, msg)
+        }
+        // Quote providers prepare the whole transaction, so it skips the
+        // fee payer, nonce and signing steps below.
+        BlinkType::Swap => {
+            return swap_action(
+                &pool,
+                blockhash_cache.rpc(),
+                swap_router.as_deref(),
+                &blink,
+                &params,
+                &user_pubkey,
+            )
+            .await;
         }
     };
 
-    let serialized = bincode::serialize(&transaction).map_err(|e| {
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            format!("Serialization error: {}", e),
+    // Sponsored blinks get a vault key as fee payer, which must not be able
+    // to lose more than the fee.
+    let sponsored = match &sponsor {
+        Some(config) => {
+            let sponsorship = sponsorship.ok_or((
+                StatusCode::SERVICE_UNAVAILABLE,
+                "Sponsored transactions are not enabled".to_string(),
+            ))?;
+            let payer = signer_vault.pubkey(&config.signer).ok_or((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Unknown signer: {}", config.signer),
+            ))?;
+            Sponsorship::strip_priority_fees(&mut instructions);
+            if let Err(e) = Sponsorship::check_instructions(&instructions, &payer) {
+                return Ok(action_error(StatusCode::FORBIDDEN, e));
+            }
+            Some((sponsorship, config, payer))
+        }
+        None => None,
+    };
+    let fee_payer = match (&sponsored, &claim) {
+        (Some((_, _, payer)), _) | (None, Some((_, payer))) => *payer,
+        (None, None) => user_pubkey,
+    };
+
+    let uses_durable_nonce = blink
+        .config
+        .get("durable_nonce")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    let (transaction, leased) = if uses_durable_nonce {
+        let nonce_pool = require_nonce_pool(nonce_pool)?;
+        let (transaction, lease) = nonce_pool
+            .build_transaction(&pool, &user_pubkey, &instructions)
+            .await?;
+        (transaction, Some((nonce_pool, lease.account)))
+    } else {
+        let recent_blockhash = blockhash_cache
+            .get()
+            .await
+            .map_err(|e| {
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("RPC Error: {}", e),
+                )
+            })?
+            .blockhash;
+        let message =
+            Message::new_with_blockhash(&instructions, Some(&fee_payer), &recent_blockhash);
+        (Transaction::new_unsigned(message), None)
+    };
+    let nonce_account = leased.as_ref().map(|(_, account)| account.to_string());
+    let mut transaction = transaction;
+    let mut sponsored_id = None;
+    // A reservation of the sponsor's budget and a leased nonce account are
+    // given back when the transaction is not handed out.
+    let signed = async {
+        let mut signers =
+            blink_signers(&blink.config).map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+        if let Some((minter, reserved)) = &mint {
+            let blockhash = transaction.message.recent_blockhash;
+            minter.sign(&mut transaction, reserved, blockhash)?;
+            signers.push(minter.signer().to_string());
+        }
+        if let Some((distributor, _)) = &claim {
+            signers.push(distributor.clone());
+        }
+        signers.extend(escrow);
+        if let Some((sponsorship, config, _)) = &sponsored {
+            let fee = LAMPORTS_PER_SIGNATURE
+                * u64::from(transaction.message.header.num_required_signatures);
+            let id = sponsorship
+                .reserve(
+                    &pool,
+                    blockhash_cache.rpc(),
+                    blink.id,
+                    config,
+                    &user_pubkey,
+                    fee,
+                )
+                .await?;
+            sponsored_id = Some(id);
+            signers.push(config.signer.clone());
+        }
+        signer_vault
+            .sign(
+                &pool,
+                &signers,
+                blink.id,
+                blink.owner.as_deref(),
+                &mut transaction,
+            )
+            .await?;
+        if let (Some((sponsorship, _, _)), Some(id)) = (&sponsored, sponsored_id) {
+            sponsorship
+                .record_signature(&pool, id, &transaction.signatures[0])
+                .await?;
+        }
+        if claim.is_some() {
+            claim_distributor
+                .record_signature(&pool, blink.id, &user_pubkey, &transaction.signatures[0])
+                .await?;
+        }
+
+        let serialized = bincode::serialize(&transaction).map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Serialization error: {}", e),
+            )
+        })?;
+        let transaction = BASE64.encode(&serialized);
+
+        record_action_build(
+            &pool,
+            &blink,
+            &payload.account,
+            &transaction,
+            &message,
+            nonce_account.as_deref(),
         )
-    })?;
+        .await?;
+        Ok::<_, (StatusCode, String)>(transaction)
+    }
+    .await;
+    let transaction = match signed {
+        Ok(transaction) => transaction,
+        Err(e) => {
+            if let (Some((sponsorship, _, _)), Some(id)) = (&sponsored, sponsored_id) {
+                sponsorship.cancel(&pool, id).await?;
+            }
+            if let Some((nonce_pool, account)) = &leased {
+                nonce_pool.release(&pool, account).await?;
+            }
+            return match e {
+                (StatusCode::FORBIDDEN, message) => {
+                    Ok(action_error(StatusCode::FORBIDDEN, message))
+                }
+                e => Err(e),
+            };
+        }
+    };
+
+    // Votes and messages are recorded once the client reports the confirmed
+    // transaction.
+    let links =
+        matches!(blink.r#type, BlinkType::Vote | BlinkType::Message).then(|| ActionPostLinks {
+            next: NextActionLink {
+                r#type: "post".to_string(),
+                href: format!("/api/actions/{}/confirm", blink.public_id()),
+            },
+        });
 
     Ok(Json(ActionPostResponse {
-        transaction: BASE64.encode(&serialized),
+        transaction,
         message: Some(message),
-    }))
+        links,
+    })
+    .into_response())
+}
+
+/// Callback for votes and messages, which are recorded once the client
+/// reports the confirmed transaction.
+#[tracing::instrument(
+    name = "Confirming an action",
+    skip(pool, rpc_pool, cache, payload),
+    fields(blink_key = %key, account = %payload.account, signature = %payload.signature)
+)]
+pub async fn confirm_action(
+    State(pool): State<PgPool>,
+    State(rpc_pool): State<Arc<RpcPool>>,
+    State(cache): State<Arc<MetadataCache>>,
+    Path(key): Path<String>,
+    Json(payload): Json<NextActionPostRequest>,
+) -> Result<Response, (StatusCode, String)> {
+    let blink = match resolve_blink(&pool, &key).await? {
+        BlinkLookup::Found(blink) => *blink,
+        BlinkLookup::Moved(public_id) => {
+            let location = format!("/api/actions/{}/confirm", public_id);
+            return Ok(Redirect::permanent(&location).into_response());
+        }
+    };
+    match blink.r#type {
+        BlinkType::Vote => confirm_vote(&pool, &rpc_pool, blink, &payload).await,
+        BlinkType::Message => confirm_message(&pool, &rpc_pool, &cache, blink, &payload).await,
+        _ => Err((
+            StatusCode::BAD_REQUEST,
+            "Blink has no transactions to confirm".to_string(),
+        )),
+    }
+}
+
+/// An error shown to the user by the Actions client.
+pub(super) fn action_error(status: StatusCode, message: String) -> Response {
+    (status, Json(ActionError { message })).into_response()
 }
 
-pub async fn get_action_json() -> impl IntoResponse {
+#[tracing::instrument(name = "Serving actions.json", skip(pool, rules))]
+pub async fn get_action_json(
+    State(pool): State<PgPool>,
+    State(rules): State<Arc<ActionRuleSet>>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
     let backend_url =
         std::env::var("BACKEND_URL").unwrap_or_else(|_| "http://localhost:8000".to_string());
 
+    let rules = action_rule_set(&pool, &rules).await?;
+
     let mut headers = HeaderMap::new();
     headers.insert("x-blockchain-ids", SOLANA_DEVNET_CHAIN_ID.parse().unwrap());
 
-    (
+    Ok((
         headers,
         Json(ActionsJson {
-            rules: vec![ActionRule {
-                path_pattern: "/api/actions/*".to_string(),
-                api_path: format!("{}/api/actions/*", backend_url),
-            }],
+            rules: rules.to_action_rules(&backend_url),
         }),
+    ))
+}
+
+/// The configured rules followed by the rules managed in the database. Rows
+/// that fail validation are skipped so a bad row cannot take down
+/// `actions.json` for every other rule.
+pub(super) async fn action_rule_set(
+    pool: &PgPool,
+    configured: &ActionRuleSet,
+) -> Result<ActionRuleSet, (StatusCode, String)> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT id, path_pattern, api_path, exclude
+        FROM action_rules
+        ORDER BY position, created_at
+        "#
     )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let mut rules = configured.clone();
+    for row in rows {
+        let rule = ActionPathRule::parse(&row.path_pattern, row.api_path.as_deref(), row.exclude)
+            .and_then(|rule| rules.push(rule));
+        if let Err(e) = rule {
+            tracing::warn!(rule_id = %row.id, "Skipping invalid action rule: {}", e);
+        }
+    }
+    Ok(rules)
 }
 
-fn get_rpc_client() -> Result<RpcClient, (StatusCode, String)> {
-    let rpc_url = std::env::var("RPC_URL").map_err(|_| {
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            "RPC_URL not set".to_string(),
-        )
-    })?;
-    Ok(RpcClient::new_with_commitment(
-        rpc_url,
-        CommitmentConfig::confirmed(),
-    ))
+/// Records which revision a served transaction was built from.
+pub(super) async fn record_action_build(
+    pool: &PgPool,
+    blink: &Blink,
+    account: &str,
+    transaction: &str,
+    message: &str,
+    nonce_account: Option<&str>,
+) -> Result<(), (StatusCode, String)> {
+    sqlx::query!(
+        r#"
+        INSERT INTO action_builds (blink_id, revision, account, transaction, message, nonce_account)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        "#,
+        blink.id,
+        blink.revision,
+        account,
+        transaction,
+        message,
+        nonce_account
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(())
 }
 
-async fn fetch_blink(pool: &PgPool, id: Uuid) -> Result<Blink, (StatusCode, String)> {
-    {
-        sqlx::sqlx_macros::expand_query!(
-            record = Blink,
-            source = r#"
-        SELECT 
-            id, 
+/// Permanent redirect from an old slug to the blink's current action URL.
+/// 308 keeps the method and body, so POSTs follow it too.
+fn redirect_to_action(public_id: &str, query: Option<String>) -> Response {
+    let location = match query {
+        Some(query) => format!("/api/actions/{}?{}", public_id, query),
+        None => format!("/api/actions/{}", public_id),
+    };
+    Redirect::permanent(&location).into_response()
+}
+
+pub(super) async fn fetch_blink(pool: &PgPool, id: Uuid) -> Result<Blink, (StatusCode, String)> {
+    sqlx::query_as!(
+        Blink,
+        r#"
+        SELECT
+            id,
             created_at as "created_at!",
-            title, 
-            icon_url, 
-            description, 
-            label, 
-            wallet_address, 
-            type as "type: BlinkType", 
-            config 
-        FROM blinks 
+            title,
+            icon_url,
+            description,
+            label,
+            wallet_address,
+            type as "type: BlinkType",
+            config,
+            slug,
+            owner,
+            revision
+        FROM blinks
         WHERE id = $1
         "#,
-            args = [id]
-        )
-    }
+        id
+    )
     .fetch_optional(pool)
     .await
     .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
     .ok_or((StatusCode::NOT_FOUND, "Blink not found".to_string()))
 }
 
+/// Outcome of looking a blink up by the identifier used in a public URL.
+pub(super) enum BlinkLookup {
+    Found(Box<Blink>),
+    /// The identifier is an old slug; the blink now lives under this one.
+    Moved(String),
+}
+
+/// Resolves a blink by UUID, current slug or any slug it used in the past.
+pub(super) async fn resolve_blink(
+    pool: &PgPool,
+    key: &str,
+) -> Result<BlinkLookup, (StatusCode, String)> {
+    if let Ok(id) = Uuid::parse_str(key) {
+        return fetch_blink(pool, id)
+            .await
+            .map(|blink| BlinkLookup::Found(Box::new(blink)));
+    }
+    let key = key.to_lowercase();
+
+    let blink = sqlx::query_as!(
+        Blink,
+        r#"
+        SELECT
+            b.id,
+            b.created_at as "created_at!",
+            b.title,
+            b.icon_url,
+            b.description,
+            b.label,
+            b.wallet_address,
+            b.type as "type: BlinkType",
+            b.config,
+            b.slug,
+            b.owner,
+            b.revision
+        FROM blink_slugs s
+        JOIN blinks b ON b.id = s.blink_id
+        WHERE s.slug = $1
+        "#,
+        key
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .ok_or((StatusCode::NOT_FOUND, "Blink not found".to_string()))?;
+
+    if blink.slug.as_deref() == Some(key.as_str()) {
+        Ok(BlinkLookup::Found(Box::new(blink)))
+    } else {
+        Ok(BlinkLookup::Moved(blink.public_id()))
+    }
+}
+
 fn parse_pubkey(address: &str, name: &str) -> Result<Pubkey, (StatusCode, String)> {
     Pubkey::from_str(address)
         .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid {}: {}", name, e)))
 }
 
-fn build_transfer_transaction(
-    from: &Pubkey,
-    to: &Pubkey,
-    amount_sol: f64,
-    recent_blockhash: solana_sdk::hash::Hash,
-) -> Result<Transaction, (StatusCode, String)> {
+fn transfer_instructions(from: &Pubkey, to: &Pubkey, amount_sol: f64) -> Vec<Instruction> {
     let lamports = (amount_sol * LAMPORTS_PER_SOL as f64) as u64;
     let transfer_ix = system_instruction::transfer(from, to, lamports);
     let priority_fee_ix = ComputeBudgetInstruction::set_compute_unit_price(50_000);
 
-    let message = Message::new_with_blockhash(
-        &[priority_fee_ix, transfer_ix],
-        Some(from),
-        &recent_blockhash,
+    vec![priority_fee_ix, transfer_ix]
+}
+
+/// Creates a stake account derived from `from` with a random seed, so it
+/// needs no signature of its own, and delegates it to `vote`. The user is
+/// staker and withdrawer; the rent-exempt reserve is added to the amount.
+fn stake_instructions(from: &Pubkey, vote: &Pubkey, amount_sol: f64) -> Vec<Instruction> {
+    let seed = format!("blinkzero:{:016x}", rand::random::<u64>());
+    let stake_pubkey = Pubkey::create_with_seed(from, &seed, &stake::program::id())
+        .expect("seed is shorter than the maximum seed length");
+    let lamports = (amount_sol * LAMPORTS_PER_SOL as f64) as u64
+        + Rent::default().minimum_balance(StakeStateV2::size_of());
+    let authorized = Authorized {
+        staker: *from,
+        withdrawer: *from,
+    };
+
+    let mut ixs = vec![ComputeBudgetInstruction::set_compute_unit_price(50_000)];
+    ixs.extend(
+        stake::instruction::create_account_with_seed_and_delegate_stake(
+            from,
+            &stake_pubkey,
+            from,
+            &seed,
+            vote,
+            &authorized,
+            &Lockup::default(),
+            lamports,
+        ),
     );
-    Ok(Transaction::new_unsigned(message))
+    ixs
 }
 
-fn build_memo_transaction(
-    from: &Pubkey,
+/// Pays an allocation from the distributor. The memo makes the claiming
+/// wallet a signer, so only it can submit its claim.
+fn claim_instructions(
+    distributor: &Pubkey,
+    wallet: &Pubkey,
+    lamports: u64,
     blink_id: Uuid,
-    selection: &str,
-    blockhash: solana_sdk::hash::Hash,
-) -> Result<Transaction, (StatusCode, String)> {
+) -> Result<Vec<Instruction>, (StatusCode, String)> {
+    let mut ixs = memo_instructions(wallet, &format!("claim:{}", blink_id))?;
+    ixs.push(system_instruction::transfer(distributor, wallet, lamports));
+    Ok(ixs)
+}
+
+fn memo_instructions(from: &Pubkey, memo: &str) -> Result<Vec<Instruction>, (StatusCode, String)> {
     let memo_program_id = Pubkey::from_str(MEMO_PROGRAM_ID).map_err(|e| {
         (
             StatusCode::INTERNAL_SERVER_ERROR,
@@ -270,16 +1666,13 @@ fn build_memo_transaction(
         )
     })?;
 
-    let memo_data = format!("vote:{}:{}", blink_id, selection);
-
     let memo_ix = Instruction {
         program_id: memo_program_id,
         accounts: vec![AccountMeta::new_readonly(*from, true)],
-        data: memo_data.into_bytes(),
+        data: memo.as_bytes().to_vec(),
     };
 
     let priority_fee_ix = ComputeBudgetInstruction::set_compute_unit_price(50_000);
 
-    let message = Message::new_with_blockhash(&[priority_fee_ix, memo_ix], Some(from), &blockhash);
-    Ok(Transaction::new_unsigned(message))
+    Ok(vec![priority_fee_ix, memo_ix])
 }
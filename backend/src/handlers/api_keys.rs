@@ -0,0 +1,198 @@
+use crate::authentication::{AdminToken, generate_api_key};
+use crate::models::{
+    ApiKeyResponse, CreateApiKeyRequest, CreatedApiKeyResponse, ListApiKeysQuery,
+    RotateApiKeyRequest,
+};
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[tracing::instrument(
+    name = "Creating an API key",
+    skip(pool, admin, headers, payload),
+    fields(key_name = %payload.name, owner = %payload.owner)
+)]
+pub async fn create_api_key(
+    State(pool): State<PgPool>,
+    State(admin): State<AdminToken>,
+    headers: HeaderMap,
+    Json(payload): Json<CreateApiKeyRequest>,
+) -> Result<Json<CreatedApiKeyResponse>, (StatusCode, String)> {
+    admin.verify(&headers)?;
+
+    if payload.name.trim().is_empty() || payload.owner.trim().is_empty() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Name and owner must not be empty".to_string(),
+        ));
+    }
+    if payload.scopes.is_empty() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "At least one scope is required".to_string(),
+        ));
+    }
+
+    let generated = generate_api_key();
+    let scopes: Vec<String> = payload
+        .scopes
+        .iter()
+        .map(|scope| scope.as_str().to_string())
+        .collect();
+
+    let api_key = sqlx::query_as!(
+        ApiKeyResponse,
+        r#"
+        INSERT INTO api_keys (name, owner, prefix, key_hash, scopes, expires_at)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING id, created_at, name, owner, prefix, scopes, expires_at, last_used_at, revoked_at
+        "#,
+        payload.name.trim(),
+        payload.owner.trim(),
+        generated.prefix,
+        generated.hash,
+        &scopes,
+        payload.expires_at
+    )
+    .fetch_one(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(CreatedApiKeyResponse {
+        key: generated.key,
+        api_key,
+    }))
+}
+
+#[tracing::instrument(name = "Listing API keys", skip(pool, admin, headers))]
+pub async fn list_api_keys(
+    State(pool): State<PgPool>,
+    State(admin): State<AdminToken>,
+    headers: HeaderMap,
+    Query(query): Query<ListApiKeysQuery>,
+) -> Result<Json<Vec<ApiKeyResponse>>, (StatusCode, String)> {
+    admin.verify(&headers)?;
+
+    let keys = sqlx::query_as!(
+        ApiKeyResponse,
+        r#"
+        SELECT id, created_at, name, owner, prefix, scopes, expires_at, last_used_at, revoked_at
+        FROM api_keys
+        WHERE $1::TEXT IS NULL OR owner = $1
+        ORDER BY created_at DESC
+        "#,
+        query.owner
+    )
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(keys))
+}
+
+#[tracing::instrument(
+    name = "Rotating an API key",
+    skip(pool, admin, headers, payload),
+    fields(api_key_id = %id)
+)]
+pub async fn rotate_api_key(
+    State(pool): State<PgPool>,
+    State(admin): State<AdminToken>,
+    headers: HeaderMap,
+    Path(id): Path<Uuid>,
+    payload: Option<Json<RotateApiKeyRequest>>,
+) -> Result<Json<CreatedApiKeyResponse>, (StatusCode, String)> {
+    admin.verify(&headers)?;
+
+    let grace_period_secs = payload.map(|Json(p)| p.grace_period_secs).unwrap_or(0);
+    let generated = generate_api_key();
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let api_key = sqlx::query_as!(
+        ApiKeyResponse,
+        r#"
+        INSERT INTO api_keys (name, owner, prefix, key_hash, scopes, expires_at)
+        SELECT name, owner, $2, $3, scopes, expires_at
+        FROM api_keys
+        WHERE id = $1 AND revoked_at IS NULL AND rotated_to IS NULL
+        RETURNING id, created_at, name, owner, prefix, scopes, expires_at, last_used_at, revoked_at
+        "#,
+        id,
+        generated.prefix,
+        generated.hash
+    )
+    .fetch_optional(&mut *transaction)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .ok_or((
+        StatusCode::NOT_FOUND,
+        "Active API key not found".to_string(),
+    ))?;
+
+    sqlx::query!(
+        r#"
+        UPDATE api_keys
+        SET rotated_to = $2,
+            expires_at = LEAST(
+                COALESCE(expires_at, 'infinity'),
+                now() + make_interval(secs => $3)
+            )
+        WHERE id = $1
+        "#,
+        id,
+        api_key.id,
+        grace_period_secs as f64
+    )
+    .execute(&mut *transaction)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    transaction
+        .commit()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(CreatedApiKeyResponse {
+        key: generated.key,
+        api_key,
+    }))
+}
+
+#[tracing::instrument(
+    name = "Revoking an API key",
+    skip(pool, admin, headers),
+    fields(api_key_id = %id)
+)]
+pub async fn revoke_api_key(
+    State(pool): State<PgPool>,
+    State(admin): State<AdminToken>,
+    headers: HeaderMap,
+    Path(id): Path<Uuid>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    admin.verify(&headers)?;
+
+    let result = sqlx::query!(
+        "UPDATE api_keys SET revoked_at = now() WHERE id = $1 AND revoked_at IS NULL",
+        id
+    )
+    .execute(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    if result.rows_affected() == 0 {
+        return Err((
+            StatusCode::NOT_FOUND,
+            "Active API key not found".to_string(),
+        ));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
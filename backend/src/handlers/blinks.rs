@@ -1,10 +1,31 @@
-use crate::models::{Blink, BlinkType, CreateBlinkRequest, CreateBlinkResponse};
-use axum::{Json, extract::State, http::StatusCode};
-use sqlx::PgPool;
+use super::revisions::snapshot_revision;
+use super::votes::{store_snapshot, stored_snapshot, take_snapshot};
+use crate::authentication::{optional_api_key, require_api_key};
+use crate::domain::{
+    BlinkSlug, ClaimConfig, EscrowConfig, GovernanceConfig, MessageConfig, MintConfig, Poll,
+    RaffleConfig, SnapshotPolicy, SponsorConfig, StakeConfig, SubscriptionConfig, SwapConfig,
+    TokenGate, VoteWeighting,
+};
+use crate::holdings::BalanceSnapshot;
+use crate::metadata_cache::MetadataCache;
+use crate::models::{
+    ApiKeyScope, Blink, BlinkRevision, BlinkType, CreateBlinkRequest, CreateBlinkResponse,
+    UpdateBlinkRequest, UpdateSlugRequest, UpdateSlugResponse,
+};
+use crate::rpc_pool::RpcPool;
+use crate::signer_vault::{SignerVault, blink_signers};
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+};
+use sqlx::{PgPool, Postgres, Transaction};
+use std::sync::Arc;
+use uuid::Uuid;
 
 #[tracing::instrument(
     name = "Creating a new blink",
-    skip(pool),
+    skip(pool, rpc_pool, signer_vault, headers),
     fields(
         blink_title = %payload.title,
         wallet = %payload.wallet_address
@@ -12,26 +33,59 @@ use sqlx::PgPool;
 )]
 pub async fn create_blink(
     State(pool): State<PgPool>,
+    State(rpc_pool): State<Arc<RpcPool>>,
+    State(signer_vault): State<Arc<SignerVault>>,
+    headers: HeaderMap,
     Json(payload): Json<CreateBlinkRequest>,
 ) -> Result<Json<CreateBlinkResponse>, (StatusCode, String)> {
     let backend_url =
         std::env::var("BACKEND_URL").unwrap_or_else(|_| "http://localhost:8000".to_string());
 
+    // Blinks created with an API key belong to the key's owner; the no-code
+    // builder creates anonymous blinks.
+    let key = optional_api_key(&pool, &headers, ApiKeyScope::Create).await?;
+
+    let slug = payload
+        .slug
+        .map(BlinkSlug::parse)
+        .transpose()
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    TokenGate::from_config(&payload.config).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let weighting = validate_type_config(&payload.r#type, &payload.config)?;
+    validate_signers(
+        &signer_vault,
+        &payload.r#type,
+        &payload.config,
+        key.as_ref().map(|key| key.owner.as_str()),
+    )?;
+    let snapshot = match &weighting {
+        Some(weighting) => creation_snapshot(&rpc_pool, weighting).await?,
+        None => None,
+    };
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
     let blink = sqlx::query_as!(
         Blink,
         r#"
-        INSERT INTO blinks (title, icon_url, description, label, wallet_address, type, config)
-        VALUES ($1, $2, $3, $4, $5, $6, $7)
-        RETURNING 
-            id, 
+        INSERT INTO blinks (title, icon_url, description, label, wallet_address, type, config, owner)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        RETURNING
+            id,
             created_at as "created_at!",
-            title, 
-            icon_url, 
-            description, 
-            label, 
-            wallet_address, 
-            type as "type: BlinkType", 
-            config
+            title,
+            icon_url,
+            description,
+            label,
+            wallet_address,
+            type as "type: BlinkType",
+            config,
+            slug,
+            owner,
+            revision
         "#,
         payload.title,
         payload.icon_url,
@@ -39,14 +93,360 @@ pub async fn create_blink(
         payload.label,
         payload.wallet_address,
         payload.r#type as BlinkType,
-        payload.config
+        payload.config,
+        key.as_ref().map(|key| key.owner.as_str())
     )
-    .fetch_one(&pool)
+    .fetch_one(&mut *transaction)
     .await
     .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
+    snapshot_revision(&mut transaction, blink.id, key.as_ref(), None).await?;
+
+    if let (Some(weighting), Some(snapshot)) = (&weighting, &snapshot) {
+        store_snapshot(&mut transaction, blink.id, weighting, snapshot).await?;
+    }
+
+    if let Some(slug) = &slug {
+        assign_slug(&mut transaction, blink.id, slug).await?;
+    }
+
+    transaction
+        .commit()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let public_id = slug
+        .as_ref()
+        .map(|s| s.as_ref().to_string())
+        .unwrap_or_else(|| blink.id.to_string());
+
     Ok(Json(CreateBlinkResponse {
         id: blink.id,
-        action_url: format!("{}/api/actions/{}", backend_url, blink.id),
+        action_url: format!("{}/api/actions/{}", backend_url, public_id),
+        slug: slug.map(|s| s.as_ref().to_string()),
+    }))
+}
+
+#[tracing::instrument(
+    name = "Updating a blink",
+    skip(pool, cache, rpc_pool, signer_vault, headers, payload),
+    fields(blink_id = %id, blink_title = %payload.title)
+)]
+pub async fn update_blink(
+    State(pool): State<PgPool>,
+    State(cache): State<Arc<MetadataCache>>,
+    State(rpc_pool): State<Arc<RpcPool>>,
+    State(signer_vault): State<Arc<SignerVault>>,
+    headers: HeaderMap,
+    Path(id): Path<Uuid>,
+    Json(payload): Json<UpdateBlinkRequest>,
+) -> Result<Json<BlinkRevision>, (StatusCode, String)> {
+    let key = require_api_key(&pool, &headers, ApiKeyScope::Update).await?;
+    let snapshot = validate_update(
+        &pool,
+        &rpc_pool,
+        &signer_vault,
+        id,
+        &payload.r#type,
+        &payload.config,
+        &key.owner,
+    )
+    .await?;
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    lock_owned_blink(&mut transaction, id, &key.owner).await?;
+
+    if let Some((weighting, snapshot)) = &snapshot {
+        store_snapshot(&mut transaction, id, weighting, snapshot).await?;
+    }
+
+    sqlx::query!(
+        r#"
+        UPDATE blinks
+        SET title = $2,
+            icon_url = $3,
+            description = $4,
+            label = $5,
+            type = $6,
+            config = $7,
+            revision = revision + 1
+        WHERE id = $1
+        "#,
+        id,
+        payload.title,
+        payload.icon_url,
+        payload.description,
+        payload.label,
+        payload.r#type as BlinkType,
+        payload.config
+    )
+    .execute(&mut *transaction)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let revision = snapshot_revision(&mut transaction, id, Some(&key), None).await?;
+
+    transaction
+        .commit()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    cache.invalidate(id);
+
+    Ok(Json(revision))
+}
+
+#[tracing::instrument(
+    name = "Updating blink slug",
+    skip(pool, cache, headers, payload),
+    fields(blink_id = %id, slug = %payload.slug)
+)]
+pub async fn update_blink_slug(
+    State(pool): State<PgPool>,
+    State(cache): State<Arc<MetadataCache>>,
+    headers: HeaderMap,
+    Path(id): Path<Uuid>,
+    Json(payload): Json<UpdateSlugRequest>,
+) -> Result<Json<UpdateSlugResponse>, (StatusCode, String)> {
+    let backend_url =
+        std::env::var("BACKEND_URL").unwrap_or_else(|_| "http://localhost:8000".to_string());
+
+    let key = require_api_key(&pool, &headers, ApiKeyScope::Update).await?;
+    let slug = BlinkSlug::parse(payload.slug).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    lock_owned_blink(&mut transaction, id, &key.owner).await?;
+
+    assign_slug(&mut transaction, id, &slug).await?;
+
+    transaction
+        .commit()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    cache.invalidate(id);
+
+    Ok(Json(UpdateSlugResponse {
+        id,
+        slug: slug.as_ref().to_string(),
+        action_url: format!("{}/api/actions/{}", backend_url, slug.as_ref()),
     }))
 }
+
+/// Runs the checks of updating a blink to `type` and `config`. Returns the
+/// balances to snapshot when the update sets a vote weighting the blink has
+/// no snapshot for.
+#[allow(clippy::too_many_arguments)]
+pub(super) async fn validate_update(
+    pool: &PgPool,
+    rpc_pool: &RpcPool,
+    signer_vault: &SignerVault,
+    id: Uuid,
+    r#type: &BlinkType,
+    config: &serde_json::Value,
+    owner: &str,
+) -> Result<Option<(VoteWeighting, BalanceSnapshot)>, (StatusCode, String)> {
+    TokenGate::from_config(config).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let weighting = validate_type_config(r#type, config)?;
+    validate_signers(signer_vault, r#type, config, Some(owner))?;
+
+    // Balances are only read again when the weighting itself changes.
+    match weighting {
+        Some(weighting) if stored_snapshot(pool, id, &weighting).await?.is_none() => {
+            Ok(creation_snapshot(rpc_pool, &weighting)
+                .await?
+                .map(|snapshot| (weighting, snapshot)))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Checks the type-specific config of a blink and returns the vote
+/// weighting. Only vote blinks can be weighted.
+fn validate_type_config(
+    r#type: &BlinkType,
+    config: &serde_json::Value,
+) -> Result<Option<VoteWeighting>, (StatusCode, String)> {
+    match r#type {
+        BlinkType::Vote => {
+            Poll::from_config(config).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+        }
+        BlinkType::Mint => {
+            MintConfig::from_config(config).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+        }
+        BlinkType::Stake => {
+            StakeConfig::from_config(config).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+        }
+        BlinkType::Claim => {
+            ClaimConfig::from_config(config).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+        }
+        BlinkType::Subscription => {
+            SubscriptionConfig::from_config(config).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+        }
+        BlinkType::Escrow => {
+            EscrowConfig::from_config(config).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+        }
+        BlinkType::Raffle => {
+            RaffleConfig::from_config(config).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+        }
+        BlinkType::Message => {
+            MessageConfig::from_config(config).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+        }
+        BlinkType::Governance => {
+            GovernanceConfig::from_config(config).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+        }
+        BlinkType::Swap => {
+            SwapConfig::from_config(config).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+        }
+        BlinkType::Donation | BlinkType::Payment => {}
+    }
+    let weighting = VoteWeighting::from_config(config).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    if weighting.is_some() && !matches!(r#type, BlinkType::Vote) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Only vote blinks can be weighted".to_string(),
+        ));
+    }
+    let sponsor = SponsorConfig::from_config(config).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    // Mint transactions fund new accounts from the fee payer, and sponsors
+    // only pay for memos and transfers.
+    match (&sponsor, r#type) {
+        (Some(_), BlinkType::Mint) => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "Mint blinks cannot be sponsored".to_string(),
+            ));
+        }
+        (Some(_), BlinkType::Stake) => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "Stake blinks cannot be sponsored".to_string(),
+            ));
+        }
+        (Some(_), BlinkType::Swap) => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "Swap blinks cannot be sponsored".to_string(),
+            ));
+        }
+        _ => {}
+    }
+    Ok(weighting)
+}
+
+/// Checks that every vault key in `config.signers`, the sponsor, the
+/// distributor of a claim blink, the delegate of a subscription blink and the
+/// escrow key of an escrow blink may be used by blinks of `owner`.
+fn validate_signers(
+    signer_vault: &SignerVault,
+    r#type: &BlinkType,
+    config: &serde_json::Value,
+    owner: Option<&str>,
+) -> Result<(), (StatusCode, String)> {
+    let mut names = blink_signers(config).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    if let Some(sponsor) =
+        SponsorConfig::from_config(config).map_err(|e| (StatusCode::BAD_REQUEST, e))?
+    {
+        names.push(sponsor.signer);
+    }
+    if matches!(r#type, BlinkType::Claim) {
+        let claim = ClaimConfig::from_config(config).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+        names.push(claim.distributor);
+    }
+    if matches!(r#type, BlinkType::Subscription)
+        && let Some(delegate) = SubscriptionConfig::from_config(config)
+            .map_err(|e| (StatusCode::BAD_REQUEST, e))?
+            .delegate
+    {
+        names.push(delegate);
+    }
+    if matches!(r#type, BlinkType::Escrow) {
+        let escrow = EscrowConfig::from_config(config).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+        names.push(escrow.escrow);
+    }
+    for name in names {
+        signer_vault
+            .check_owner(&name, owner)
+            .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    }
+    Ok(())
+}
+
+/// Balances for a weighting snapshotted when it is set on the blink. Slot
+/// snapshots are taken once voting opens.
+async fn creation_snapshot(
+    rpc_pool: &RpcPool,
+    weighting: &VoteWeighting,
+) -> Result<Option<BalanceSnapshot>, (StatusCode, String)> {
+    match weighting.snapshot {
+        SnapshotPolicy::Creation => take_snapshot(rpc_pool, weighting).await,
+        SnapshotPolicy::Slot(_) => Ok(None),
+    }
+}
+
+/// Makes `slug` the current slug of a blink and records it in the slug
+/// history. A slug can only ever belong to one blink, so a blink may move back
+/// to one of its own old slugs but never take over another blink's.
+async fn assign_slug(
+    transaction: &mut Transaction<'_, Postgres>,
+    blink_id: Uuid,
+    slug: &BlinkSlug,
+) -> Result<(), (StatusCode, String)> {
+    let slug_blink_id = sqlx::query_scalar!(
+        r#"
+        INSERT INTO blink_slugs (slug, blink_id)
+        VALUES ($1, $2)
+        ON CONFLICT (slug) DO UPDATE SET slug = EXCLUDED.slug
+        RETURNING blink_id
+        "#,
+        slug.as_ref(),
+        blink_id
+    )
+    .fetch_one(&mut **transaction)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    if slug_blink_id != blink_id {
+        return Err((
+            StatusCode::CONFLICT,
+            format!("Slug '{}' is already taken", slug.as_ref()),
+        ));
+    }
+
+    sqlx::query!(
+        "UPDATE blinks SET slug = $1 WHERE id = $2",
+        slug.as_ref(),
+        blink_id
+    )
+    .execute(&mut **transaction)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(())
+}
+
+/// Locks a blink for an update made with an API key. Blinks of other owners
+/// are reported as missing so keys cannot probe for them.
+pub(super) async fn lock_owned_blink(
+    transaction: &mut Transaction<'_, Postgres>,
+    blink_id: Uuid,
+    owner: &str,
+) -> Result<(), (StatusCode, String)> {
+    sqlx::query_scalar!(
+        "SELECT id FROM blinks WHERE id = $1 AND owner = $2 FOR UPDATE",
+        blink_id,
+        owner
+    )
+    .fetch_optional(&mut **transaction)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .ok_or((StatusCode::NOT_FOUND, "Blink not found".to_string()))?;
+
+    Ok(())
+}
@@ -0,0 +1,220 @@
+use super::blinks::lock_owned_blink;
+use crate::authentication::require_api_key;
+use crate::claims::claim_progress;
+use crate::domain::Allowlist;
+use crate::metadata_cache::MetadataCache;
+use crate::models::{
+    AllowlistSummary, ApiKeyScope, Blink, BlinkType, ClaimAllocation, UploadAllowlistRequest,
+};
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+};
+use solana_sdk::{native_token::LAMPORTS_PER_SOL, pubkey::Pubkey};
+use sqlx::PgPool;
+use std::str::FromStr;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Replaces the allowlist of a claim blink. Allowlists are frozen once the
+/// first claim was built.
+#[tracing::instrument(
+    name = "Uploading claim allowlist",
+    skip(pool, cache, headers, payload),
+    fields(blink_id = %id, wallets = payload.entries.len())
+)]
+pub async fn upload_allowlist(
+    State(pool): State<PgPool>,
+    State(cache): State<Arc<MetadataCache>>,
+    headers: HeaderMap,
+    Path(id): Path<Uuid>,
+    Json(payload): Json<UploadAllowlistRequest>,
+) -> Result<Json<AllowlistSummary>, (StatusCode, String)> {
+    let key = require_api_key(&pool, &headers, ApiKeyScope::Update).await?;
+
+    let entries = payload
+        .entries
+        .iter()
+        .map(|entry| {
+            let wallet = Pubkey::from_str(&entry.wallet)
+                .map_err(|e| format!("Invalid wallet {}: {}", entry.wallet, e))?;
+            if !(entry.amount.is_finite() && entry.amount > 0.0) {
+                return Err(format!("Allocation of {} must be positive", entry.wallet));
+            }
+            Ok((
+                wallet,
+                (entry.amount * LAMPORTS_PER_SOL as f64).round() as u64,
+            ))
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let allowlist = Allowlist::new(entries).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    lock_owned_blink(&mut transaction, id, &key.owner).await?;
+    let r#type = sqlx::query_scalar!(
+        r#"SELECT type as "type: BlinkType" FROM blinks WHERE id = $1"#,
+        id
+    )
+    .fetch_one(&mut *transaction)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if !matches!(r#type, BlinkType::Claim) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Only claim blinks have an allowlist".to_string(),
+        ));
+    }
+
+    let started = sqlx::query_scalar!(
+        r#"
+        SELECT EXISTS (
+            SELECT 1 FROM claim_allocations
+            WHERE blink_id = $1
+              AND (reserved_until IS NOT NULL OR claimed_at IS NOT NULL)
+        ) AS "started!"
+        "#,
+        id
+    )
+    .fetch_one(&mut *transaction)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if started {
+        return Err((
+            StatusCode::CONFLICT,
+            "The allowlist cannot change once claims have started".to_string(),
+        ));
+    }
+
+    let root = allowlist.root().to_string();
+    sqlx::query!(
+        r#"
+        INSERT INTO claim_allowlists (blink_id, merkle_root, total_lamports)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (blink_id) DO UPDATE
+        SET merkle_root = EXCLUDED.merkle_root,
+            total_lamports = EXCLUDED.total_lamports,
+            created_at = now()
+        "#,
+        id,
+        root,
+        allowlist.total_lamports() as i64
+    )
+    .execute(&mut *transaction)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    sqlx::query!("DELETE FROM claim_allocations WHERE blink_id = $1", id)
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    // Proofs differ in length, so each travels as one comma-separated string.
+    let wallets = allowlist
+        .entries()
+        .iter()
+        .map(|(wallet, _)| wallet.to_string())
+        .collect::<Vec<_>>();
+    let lamports = allowlist
+        .entries()
+        .iter()
+        .map(|(_, lamports)| *lamports as i64)
+        .collect::<Vec<_>>();
+    let proofs = (0..wallets.len())
+        .map(|index| {
+            allowlist
+                .proof(index)
+                .iter()
+                .map(|hash| hash.to_string())
+                .collect::<Vec<_>>()
+                .join(",")
+        })
+        .collect::<Vec<_>>();
+    sqlx::query!(
+        r#"
+        INSERT INTO claim_allocations (blink_id, wallet, lamports, proof)
+        SELECT $1, wallet, lamports, string_to_array(proof, ',')
+        FROM UNNEST($2::TEXT[], $3::BIGINT[], $4::TEXT[]) AS entries (wallet, lamports, proof)
+        "#,
+        id,
+        &wallets,
+        &lamports,
+        &proofs
+    )
+    .execute(&mut *transaction)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    transaction
+        .commit()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    cache.invalidate(id);
+
+    Ok(Json(AllowlistSummary {
+        blink_id: id,
+        merkle_root: root,
+        wallets: wallets.len(),
+        total_lamports: allowlist.total_lamports(),
+    }))
+}
+
+#[tracing::instrument(
+    name = "Fetching claim allocation",
+    skip(pool),
+    fields(blink_id = %id, wallet = %wallet)
+)]
+pub async fn get_claim_allocation(
+    State(pool): State<PgPool>,
+    Path((id, wallet)): Path<(Uuid, String)>,
+) -> Result<Json<ClaimAllocation>, (StatusCode, String)> {
+    let allocation = sqlx::query!(
+        r#"
+        SELECT a.lamports, a.proof, a.claimed_at IS NOT NULL AS "claimed!", l.merkle_root
+        FROM claim_allocations a
+        JOIN claim_allowlists l USING (blink_id)
+        WHERE a.blink_id = $1 AND a.wallet = $2
+        "#,
+        id,
+        wallet
+    )
+    .fetch_optional(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .ok_or((
+        StatusCode::NOT_FOUND,
+        "Wallet is not on the allowlist".to_string(),
+    ))?;
+
+    Ok(Json(ClaimAllocation {
+        blink_id: id,
+        merkle_root: allocation.merkle_root,
+        wallet,
+        lamports: allocation.lamports,
+        proof: allocation.proof,
+        claimed: allocation.claimed,
+    }))
+}
+
+/// Appends the unclaimed share of a claim blink's allocation to its
+/// description.
+pub(super) async fn describe_claim_progress(
+    pool: &PgPool,
+    blink: &mut Blink,
+) -> Result<(), (StatusCode, String)> {
+    if !matches!(blink.r#type, BlinkType::Claim) {
+        return Ok(());
+    }
+    if let Some((remaining, total)) = claim_progress(pool, blink.id).await? {
+        blink.description = format!(
+            "{}\n\n{} of {} SOL left to claim",
+            blink.description,
+            remaining as f64 / LAMPORTS_PER_SOL as f64,
+            total as f64 / LAMPORTS_PER_SOL as f64
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,168 @@
+use super::invoices::currency_label;
+use crate::authentication::require_api_key;
+use crate::escrows::EscrowAgent;
+use crate::models::{
+    ActionError, ActionLinks, ActionMetadata, ApiKeyScope, Blink, BlinkType, Escrow, EscrowStatus,
+    EscrowStep, LinkedAction,
+};
+use crate::rpc_pool::RpcPool;
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+};
+use solana_sdk::pubkey::Pubkey;
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// The deal of an escrow blink of the key's owner.
+#[tracing::instrument(
+    name = "Fetching escrow",
+    skip(pool, rpc_pool, agent, headers),
+    fields(blink_id = %id)
+)]
+pub async fn get_escrow(
+    State(pool): State<PgPool>,
+    State(rpc_pool): State<Arc<RpcPool>>,
+    State(agent): State<Arc<EscrowAgent>>,
+    headers: HeaderMap,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Escrow>, (StatusCode, String)> {
+    let key = require_api_key(&pool, &headers, ApiKeyScope::Read).await?;
+    let blink = sqlx::query!(
+        r#"
+        SELECT type as "type: BlinkType"
+        FROM blinks
+        WHERE id = $1 AND owner = $2
+        "#,
+        id,
+        key.owner
+    )
+    .fetch_optional(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .ok_or((StatusCode::NOT_FOUND, "Blink not found".to_string()))?;
+    if !matches!(blink.r#type, BlinkType::Escrow) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Only escrow blinks hold deposits".to_string(),
+        ));
+    }
+
+    let deal = agent.deal(&pool, &rpc_pool, id).await?.ok_or((
+        StatusCode::NOT_FOUND,
+        "Nothing has been deposited yet".to_string(),
+    ))?;
+    let currency = deal.currency()?;
+    Ok(Json(Escrow {
+        blink_id: deal.blink_id,
+        status: deal.status,
+        escrow: deal.escrow_key.clone(),
+        amount: currency.format(deal.amount as u64),
+        currency: currency.code(),
+        timeout_at: deal.timeout_at(),
+        buyer: deal.buyer,
+        seller: deal.seller,
+        pending: deal.pending_step,
+        funded_at: deal.funded_at,
+        on_timeout: deal.on_timeout,
+        deposit_signature: deal.deposit_signature,
+        settlement_signature: deal.settlement_signature,
+        closed_at: deal.closed_at,
+    }))
+}
+
+/// Sets the buttons of an escrow blink to the steps its deal is at. When
+/// the viewing wallet is known, only the steps it may take are shown.
+pub(super) async fn escrow_metadata(
+    pool: &PgPool,
+    rpc_pool: &RpcPool,
+    agent: &EscrowAgent,
+    blink: &Blink,
+    viewer: Option<&Pubkey>,
+    metadata: &mut ActionMetadata,
+) -> Result<(), (StatusCode, String)> {
+    let viewer = viewer.map(Pubkey::to_string);
+    // Open deals keep the deposit button.
+    let deal = match agent.deal(pool, rpc_pool, blink.id).await? {
+        Some(deal) if deal.status != EscrowStatus::Open => deal,
+        deal => {
+            let denial = deal.and_then(|deal| deal.unavailable()).or_else(|| {
+                (viewer.as_deref() == Some(blink.wallet_address.as_str()))
+                    .then(|| "Sellers cannot deposit into their own deal".to_string())
+            });
+            if let Some(message) = denial {
+                disable(metadata, message);
+            }
+            return Ok(());
+        }
+    };
+
+    let currency = deal.currency()?;
+    let amount = format!(
+        "{} {}",
+        currency.format(deal.amount as u64),
+        currency_label(&currency.code())
+    );
+    let summary = match deal.status {
+        EscrowStatus::Released => format!("{} was released to the seller", amount),
+        EscrowStatus::Refunded => format!("{} was refunded to the buyer", amount),
+        _ => format!(
+            "{} is held in escrow. From {} the {} opens to both parties.",
+            amount,
+            deal.timeout_at()
+                .map(|at| at.format("%Y-%m-%d").to_string())
+                .unwrap_or_default(),
+            deal.on_timeout.as_str()
+        ),
+    };
+    metadata.description = if blink.description.is_empty() {
+        summary
+    } else {
+        format!("{}\n\n{}", blink.description, summary)
+    };
+
+    let backend_url =
+        std::env::var("BACKEND_URL").unwrap_or_else(|_| "http://localhost:8000".to_string());
+    let steps = [EscrowStep::Release, EscrowStep::Refund]
+        .into_iter()
+        .filter(|step| match &viewer {
+            Some(viewer) => deal.check_step(*step, viewer).is_ok(),
+            None => true,
+        })
+        .collect::<Vec<_>>();
+    metadata.links = Some(ActionLinks {
+        actions: steps
+            .iter()
+            .map(|step| LinkedAction {
+                label: match step {
+                    EscrowStep::Refund => format!("Refund {} to buyer", amount),
+                    _ => format!("Release {} to seller", amount),
+                },
+                href: format!(
+                    "{}/api/actions/{}?step={}",
+                    backend_url,
+                    blink.public_id(),
+                    step.as_str()
+                ),
+                parameters: None,
+            })
+            .collect(),
+    });
+
+    let denial = deal.unavailable().or_else(|| {
+        steps
+            .is_empty()
+            .then(|| "This deal is between other wallets".to_string())
+    });
+    if let Some(message) = denial {
+        disable(metadata, message);
+    }
+    Ok(())
+}
+
+fn disable(metadata: &mut ActionMetadata, message: String) {
+    metadata.disabled = Some(true);
+    metadata.error = Some(ActionError { message });
+}
@@ -0,0 +1,56 @@
+use axum::http::StatusCode;
+
+use crate::domain::GovernanceConfig;
+use crate::governance::{ProposalState, fetch_proposal};
+use crate::invoices::Currency;
+use crate::models::{ActionError, ActionMetadata, Blink};
+use crate::rpc_pool::RpcPool;
+
+/// Adds the name, state and tallies of the proposal to the description of a
+/// governance blink. Proposals no longer voting on are disabled.
+pub(super) async fn governance_metadata(
+    rpc_pool: &RpcPool,
+    blink: &Blink,
+    metadata: &mut ActionMetadata,
+) -> Result<(), (StatusCode, String)> {
+    let config = GovernanceConfig::from_config(&blink.config)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    // The blink still renders when the cluster cannot be read.
+    let proposal = match fetch_proposal(rpc_pool, &config).await {
+        Ok(proposal) => proposal,
+        Err(e) => {
+            tracing::warn!(blink_id = %blink.id, "Failed to read proposal: {}", e);
+            return Ok(());
+        }
+    };
+    let currency =
+        match Currency::resolve(rpc_pool, &proposal.governing_token_mint.to_string()).await {
+            Ok(currency) => currency,
+            Err(e) => {
+                tracing::warn!(blink_id = %blink.id, "Failed to read governing token: {}", e);
+                return Ok(());
+            }
+        };
+
+    let summary = format!(
+        "{} · {}\nYes {} · No {} · Abstain {}",
+        proposal.name,
+        proposal.state.label(),
+        currency.format(proposal.yes_weight),
+        currency.format(proposal.no_weight),
+        currency.format(proposal.abstain_weight)
+    );
+    metadata.description = if blink.description.is_empty() {
+        summary
+    } else {
+        format!("{}\n\n{}", blink.description, summary)
+    };
+
+    if proposal.state != ProposalState::Voting {
+        metadata.disabled = Some(true);
+        metadata.error = Some(ActionError {
+            message: "This proposal is not open for voting".to_string(),
+        });
+    }
+    Ok(())
+}
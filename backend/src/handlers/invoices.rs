@@ -0,0 +1,514 @@
+use super::revisions::snapshot_revision;
+use crate::authentication::require_api_key;
+use crate::invoices::{Currency, SOL};
+use crate::metadata_cache::MetadataCache;
+use crate::models::{
+    ActionError, ActionLinks, ActionMetadata, ApiKeyScope, Blink, CreateInvoiceRequest, Invoice,
+    InvoiceRecord, InvoiceState, InvoiceStatus, LinkedAction,
+};
+use crate::rpc_pool::RpcPool;
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode, header},
+    response::IntoResponse,
+};
+use chrono::Utc;
+use solana_sdk::{
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+use sqlx::PgPool;
+use std::str::FromStr;
+use std::sync::Arc;
+use uuid::Uuid;
+
+const MAX_NUMBER_LEN: usize = 64;
+const MAX_NOTE_LEN: usize = 280;
+
+/// Issues an invoice as a single-use payment blink of the key's owner.
+#[tracing::instrument(
+    name = "Creating an invoice",
+    skip(pool, rpc_pool, headers, payload),
+    fields(number = %payload.number)
+)]
+pub async fn create_invoice(
+    State(pool): State<PgPool>,
+    State(rpc_pool): State<Arc<RpcPool>>,
+    headers: HeaderMap,
+    Json(payload): Json<CreateInvoiceRequest>,
+) -> Result<Json<Invoice>, (StatusCode, String)> {
+    let key = require_api_key(&pool, &headers, ApiKeyScope::Create).await?;
+
+    let number = payload.number.trim();
+    if number.is_empty() || number.len() > MAX_NUMBER_LEN {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("Invoice number must be 1 to {} bytes", MAX_NUMBER_LEN),
+        ));
+    }
+    if payload
+        .note
+        .as_ref()
+        .is_some_and(|note| note.len() > MAX_NOTE_LEN)
+    {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("Note is longer than {} bytes", MAX_NOTE_LEN),
+        ));
+    }
+    Pubkey::from_str(&payload.wallet_address)
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid wallet: {}", e)))?;
+    if !(payload.amount.is_finite() && payload.amount > 0.0) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Amount must be positive".to_string(),
+        ));
+    }
+    let currency = Currency::resolve(&rpc_pool, &payload.currency)
+        .await
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let amount = currency.base_units(payload.amount);
+    if amount == 0 || amount > i64::MAX as u64 {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!(
+                "Amount must be between 1 base unit and {} {}",
+                currency.format(i64::MAX as u64),
+                currency.code()
+            ),
+        ));
+    }
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let title = payload
+        .title
+        .clone()
+        .unwrap_or_else(|| format!("Invoice {}", number));
+    let blink_id = sqlx::query_scalar!(
+        r#"
+        INSERT INTO blinks (title, icon_url, description, label, wallet_address, type, config, owner)
+        VALUES ($1, $2, $3, 'Pay', $4, 'payment', $5, $6)
+        RETURNING id
+        "#,
+        title,
+        payload.icon_url,
+        payload.note.clone().unwrap_or_default(),
+        payload.wallet_address,
+        serde_json::json!({ "amount": payload.amount }),
+        key.owner
+    )
+    .fetch_one(&mut *transaction)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    snapshot_revision(&mut transaction, blink_id, Some(&key), None).await?;
+
+    let reference = Keypair::new().pubkey();
+    let invoice_id = sqlx::query_scalar!(
+        r#"
+        INSERT INTO invoices (
+            blink_id, owner, number, currency, decimals, amount, reference, due_date, note
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+        ON CONFLICT (owner, number) DO NOTHING
+        RETURNING id
+        "#,
+        blink_id,
+        key.owner,
+        number,
+        currency.code(),
+        i16::from(currency.decimals()),
+        amount as i64,
+        reference.to_string(),
+        payload.due_date,
+        payload.note
+    )
+    .fetch_optional(&mut *transaction)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .ok_or((
+        StatusCode::CONFLICT,
+        format!("Invoice {} already exists", number),
+    ))?;
+
+    transaction
+        .commit()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let invoice = fetch_invoice(&pool, invoice_id, &key.owner).await?;
+    Ok(Json(invoice_response(&invoice)?))
+}
+
+#[tracing::instrument(name = "Listing invoices", skip(pool, headers))]
+pub async fn list_invoices(
+    State(pool): State<PgPool>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<Invoice>>, (StatusCode, String)> {
+    let key = require_api_key(&pool, &headers, ApiKeyScope::Read).await?;
+
+    let invoices = sqlx::query_as!(
+        InvoiceRecord,
+        r#"
+        SELECT
+            i.id, i.blink_id, i.number, i.currency, i.decimals, i.amount, i.reference,
+            i.due_date, i.note, i.status as "status: InvoiceStatus", i.signature, i.payer,
+            i.paid_at, i.created_at, b.wallet_address
+        FROM invoices i
+        JOIN blinks b ON b.id = i.blink_id
+        WHERE i.owner = $1
+        ORDER BY i.created_at DESC
+        "#,
+        key.owner
+    )
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(
+        invoices
+            .iter()
+            .map(invoice_response)
+            .collect::<Result<_, _>>()?,
+    ))
+}
+
+/// An invoice, reconciled against the cluster while it is open.
+#[tracing::instrument(
+    name = "Fetching an invoice",
+    skip(pool, rpc_pool, cache, headers),
+    fields(invoice_id = %id)
+)]
+pub async fn get_invoice(
+    State(pool): State<PgPool>,
+    State(rpc_pool): State<Arc<RpcPool>>,
+    State(cache): State<Arc<MetadataCache>>,
+    headers: HeaderMap,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Invoice>, (StatusCode, String)> {
+    let key = require_api_key(&pool, &headers, ApiKeyScope::Read).await?;
+    let invoice = fetch_invoice(&pool, id, &key.owner).await?;
+    let invoice = reconcile(&pool, &rpc_pool, &cache, invoice).await?;
+
+    Ok(Json(invoice_response(&invoice)?))
+}
+
+/// Cancels an open invoice, which disables its blink.
+#[tracing::instrument(
+    name = "Cancelling an invoice",
+    skip(pool, rpc_pool, cache, headers),
+    fields(invoice_id = %id)
+)]
+pub async fn cancel_invoice(
+    State(pool): State<PgPool>,
+    State(rpc_pool): State<Arc<RpcPool>>,
+    State(cache): State<Arc<MetadataCache>>,
+    headers: HeaderMap,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Invoice>, (StatusCode, String)> {
+    let key = require_api_key(&pool, &headers, ApiKeyScope::Update).await?;
+    let invoice = fetch_invoice(&pool, id, &key.owner).await?;
+    // A payment that already landed wins over the cancellation.
+    let invoice = reconcile(&pool, &rpc_pool, &cache, invoice).await?;
+
+    let cancelled = sqlx::query!(
+        "UPDATE invoices SET status = 'cancelled' WHERE id = $1 AND status = 'open'",
+        id
+    )
+    .execute(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .rows_affected();
+    if cancelled == 0 {
+        return Err((
+            StatusCode::CONFLICT,
+            format!("Invoice {} is not open", invoice.number),
+        ));
+    }
+    cache.invalidate(invoice.blink_id);
+
+    let invoice = fetch_invoice(&pool, id, &key.owner).await?;
+    Ok(Json(invoice_response(&invoice)?))
+}
+
+/// Plain-text receipt of a paid invoice.
+#[tracing::instrument(
+    name = "Downloading an invoice receipt",
+    skip(pool, rpc_pool, cache, headers),
+    fields(invoice_id = %id)
+)]
+pub async fn get_invoice_receipt(
+    State(pool): State<PgPool>,
+    State(rpc_pool): State<Arc<RpcPool>>,
+    State(cache): State<Arc<MetadataCache>>,
+    headers: HeaderMap,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let key = require_api_key(&pool, &headers, ApiKeyScope::Read).await?;
+    let invoice = fetch_invoice(&pool, id, &key.owner).await?;
+    let invoice = reconcile(&pool, &rpc_pool, &cache, invoice).await?;
+    let (Some(signature), Some(payer), Some(paid_at)) =
+        (&invoice.signature, &invoice.payer, &invoice.paid_at)
+    else {
+        return Err((
+            StatusCode::CONFLICT,
+            format!("Invoice {} is not paid", invoice.number),
+        ));
+    };
+    let currency = stored_currency(&invoice)?;
+
+    let mut receipt = format!(
+        "Receipt for invoice {}\n\n\
+         Amount:      {} {}\n\
+         Paid to:     {}\n\
+         Paid by:     {}\n\
+         Paid at:     {}\n\
+         Transaction: {}\n\
+         Reference:   {}\n",
+        invoice.number,
+        currency.format(invoice.amount as u64),
+        currency.code(),
+        invoice.wallet_address,
+        payer,
+        paid_at.to_rfc3339(),
+        signature,
+        invoice.reference
+    );
+    if let Some(note) = &invoice.note {
+        receipt.push_str(&format!("Note:        {}\n", note));
+    }
+    let filename: String = invoice
+        .number
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+
+    Ok((
+        [
+            (
+                header::CONTENT_TYPE,
+                "text/plain; charset=utf-8".to_string(),
+            ),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"receipt-{}.txt\"", filename),
+            ),
+        ],
+        receipt,
+    ))
+}
+
+async fn fetch_invoice(
+    pool: &PgPool,
+    id: Uuid,
+    owner: &str,
+) -> Result<InvoiceRecord, (StatusCode, String)> {
+    sqlx::query_as!(
+        InvoiceRecord,
+        r#"
+        SELECT
+            i.id, i.blink_id, i.number, i.currency, i.decimals, i.amount, i.reference,
+            i.due_date, i.note, i.status as "status: InvoiceStatus", i.signature, i.payer,
+            i.paid_at, i.created_at, b.wallet_address
+        FROM invoices i
+        JOIN blinks b ON b.id = i.blink_id
+        WHERE i.id = $1 AND i.owner = $2
+        "#,
+        id,
+        owner
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .ok_or((StatusCode::NOT_FOUND, "Invoice not found".to_string()))
+}
+
+/// The invoice a blink was issued for, if any.
+pub(super) async fn fetch_blink_invoice(
+    pool: &PgPool,
+    blink_id: Uuid,
+) -> Result<Option<InvoiceRecord>, (StatusCode, String)> {
+    sqlx::query_as!(
+        InvoiceRecord,
+        r#"
+        SELECT
+            i.id, i.blink_id, i.number, i.currency, i.decimals, i.amount, i.reference,
+            i.due_date, i.note, i.status as "status: InvoiceStatus", i.signature, i.payer,
+            i.paid_at, i.created_at, b.wallet_address
+        FROM invoices i
+        JOIN blinks b ON b.id = i.blink_id
+        WHERE i.blink_id = $1
+        "#,
+        blink_id
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
+/// Marks an open invoice paid once a matching transfer carrying its
+/// reference landed. The cluster is only asked while the invoice is open,
+/// and when it cannot be reached the stored status stands.
+pub(super) async fn reconcile(
+    pool: &PgPool,
+    rpc: &RpcPool,
+    cache: &MetadataCache,
+    invoice: InvoiceRecord,
+) -> Result<InvoiceRecord, (StatusCode, String)> {
+    if invoice.status != InvoiceStatus::Open {
+        return Ok(invoice);
+    }
+    let currency = stored_currency(&invoice)?;
+    let merchant = Pubkey::from_str(&invoice.wallet_address).map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Invalid stored wallet: {}", e),
+        )
+    })?;
+    let reference = Pubkey::from_str(&invoice.reference).map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Invalid stored reference: {}", e),
+        )
+    })?;
+
+    let payment = match currency
+        .find_payment(rpc, &merchant, invoice.amount as u64, &reference)
+        .await
+    {
+        Ok(Some(payment)) => payment,
+        Ok(None) => return Ok(invoice),
+        Err(e) => {
+            tracing::warn!("Failed to reconcile invoice {}: {}", invoice.id, e);
+            return Ok(invoice);
+        }
+    };
+
+    let paid = sqlx::query!(
+        r#"
+        UPDATE invoices
+        SET status = 'paid', signature = $2, payer = $3, paid_at = now()
+        WHERE id = $1 AND status = 'open'
+        RETURNING paid_at
+        "#,
+        invoice.id,
+        payment.signature.to_string(),
+        payment.payer.to_string()
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let Some(paid) = paid else {
+        // Cancelled in the meantime.
+        return Ok(invoice);
+    };
+    cache.invalidate(invoice.blink_id);
+
+    Ok(InvoiceRecord {
+        status: InvoiceStatus::Paid,
+        signature: Some(payment.signature.to_string()),
+        payer: Some(payment.payer.to_string()),
+        paid_at: paid.paid_at,
+        ..invoice
+    })
+}
+
+/// Turns the metadata of an invoice blink into a single fixed-amount button,
+/// disabled once the invoice is paid or cancelled.
+pub(super) fn invoice_metadata(
+    invoice: &InvoiceRecord,
+    blink: &Blink,
+    metadata: &mut ActionMetadata,
+) -> Result<(), (StatusCode, String)> {
+    let backend_url =
+        std::env::var("BACKEND_URL").unwrap_or_else(|_| "http://localhost:8000".to_string());
+    let currency = stored_currency(invoice)?;
+    let amount = format!(
+        "{} {}",
+        currency.format(invoice.amount as u64),
+        currency_label(&currency.code())
+    );
+
+    let summary = format!(
+        "Invoice {}: {} due {}",
+        invoice.number, amount, invoice.due_date
+    );
+    metadata.description = if blink.description.is_empty() {
+        summary
+    } else {
+        format!("{}\n\n{}", blink.description, summary)
+    };
+    metadata.links = Some(ActionLinks {
+        actions: vec![LinkedAction {
+            label: format!("Pay {}", amount),
+            href: format!("{}/api/actions/{}", backend_url, blink.public_id()),
+            parameters: None,
+        }],
+    });
+    let closed = match invoice.status {
+        InvoiceStatus::Open => None,
+        InvoiceStatus::Paid => Some("This invoice has been paid"),
+        InvoiceStatus::Cancelled => Some("This invoice was cancelled"),
+    };
+    if let Some(message) = closed {
+        metadata.disabled = Some(true);
+        metadata.error = Some(ActionError {
+            message: message.to_string(),
+        });
+    }
+    Ok(())
+}
+
+pub(super) fn stored_currency(invoice: &InvoiceRecord) -> Result<Currency, (StatusCode, String)> {
+    Currency::from_stored(&invoice.currency, invoice.decimals)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))
+}
+
+/// Token mints are shortened in labels shown to payers.
+pub(super) fn currency_label(code: &str) -> String {
+    if code == SOL {
+        SOL.to_string()
+    } else {
+        format!("{}…", &code[..4])
+    }
+}
+
+fn invoice_response(invoice: &InvoiceRecord) -> Result<Invoice, (StatusCode, String)> {
+    let backend_url =
+        std::env::var("BACKEND_URL").unwrap_or_else(|_| "http://localhost:8000".to_string());
+    let currency = stored_currency(invoice)?;
+    let status = match invoice.status {
+        InvoiceStatus::Open if invoice.due_date < Utc::now().date_naive() => InvoiceState::Overdue,
+        InvoiceStatus::Open => InvoiceState::Open,
+        InvoiceStatus::Paid => InvoiceState::Paid,
+        InvoiceStatus::Cancelled => InvoiceState::Cancelled,
+    };
+
+    Ok(Invoice {
+        id: invoice.id,
+        blink_id: invoice.blink_id,
+        action_url: format!("{}/api/actions/{}", backend_url, invoice.blink_id),
+        number: invoice.number.clone(),
+        amount: currency.format(invoice.amount as u64),
+        currency: currency.code(),
+        due_date: invoice.due_date,
+        note: invoice.note.clone(),
+        status,
+        reference: invoice.reference.clone(),
+        wallet_address: invoice.wallet_address.clone(),
+        signature: invoice.signature.clone(),
+        payer: invoice.payer.clone(),
+        paid_at: invoice.paid_at,
+        created_at: invoice.created_at,
+    })
+}
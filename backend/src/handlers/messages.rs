@@ -0,0 +1,199 @@
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use solana_sdk::{
+    pubkey::Pubkey, system_instruction::SystemInstruction, system_program,
+    transaction::VersionedTransaction,
+};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::actions::{action_error, fetch_blink};
+use super::votes::{confirmation_signer, fetch_confirmed, memos, signed_transaction};
+use crate::domain::{MessageConfig, MessageMemo};
+use crate::invoices::Currency;
+use crate::metadata_cache::MetadataCache;
+use crate::models::{
+    ActionMetadata, Blink, BlinkType, CompletedAction, MessageEntry, MessageFeedQuery,
+    NextActionPostRequest,
+};
+use crate::rpc_pool::RpcPool;
+
+const MAX_FEED_ENTRIES: i64 = 100;
+
+/// Checks the confirmed memo of a message transaction against the blink's
+/// config and adds the message to the feed.
+pub(super) async fn confirm_message(
+    pool: &PgPool,
+    rpc_pool: &RpcPool,
+    cache: &MetadataCache,
+    blink: Blink,
+    payload: &NextActionPostRequest,
+) -> Result<Response, (StatusCode, String)> {
+    let (author, signature) = confirmation_signer(payload)?;
+    let confirmed = fetch_confirmed(rpc_pool, &signature).await?;
+    if confirmed.is_null() {
+        return Ok(action_error(
+            StatusCode::NOT_FOUND,
+            "Message transaction is not confirmed yet".to_string(),
+        ));
+    }
+    if !confirmed["meta"]["err"].is_null() {
+        return Ok(action_error(
+            StatusCode::BAD_REQUEST,
+            "Message transaction failed".to_string(),
+        ));
+    }
+
+    let Some((transaction, memo)) =
+        signed_transaction(&confirmed, &signature, &author).and_then(|transaction| {
+            let memo = memos(&transaction)
+                .find_map(MessageMemo::parse)
+                .filter(|memo| memo.blink_id == blink.id)?;
+            Some((transaction, memo))
+        })
+    else {
+        return Ok(action_error(
+            StatusCode::BAD_REQUEST,
+            "Transaction does not carry a message signed by this wallet".to_string(),
+        ));
+    };
+    // Anyone can write the memo without the blink, so it is checked again.
+    let config = MessageConfig::from_config(&blink.config)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    let body = match config.check_message(&memo.text) {
+        Ok(body) => body,
+        Err(message) => return Ok(action_error(StatusCode::BAD_REQUEST, message)),
+    };
+    let tip = match Pubkey::try_from(blink.wallet_address.as_str()) {
+        Ok(wallet) if config.tips => tip(&transaction, &author, &wallet),
+        _ => None,
+    };
+
+    sqlx::query!(
+        r#"
+        INSERT INTO messages (signature, blink_id, author, body, tip, slot)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        ON CONFLICT DO NOTHING
+        "#,
+        signature.to_string(),
+        blink.id,
+        author.to_string(),
+        body,
+        tip.map(|tip| tip as i64),
+        confirmed["slot"].as_i64().unwrap_or_default()
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    cache.invalidate(blink.id);
+
+    Ok(Json(CompletedAction {
+        r#type: "completed".to_string(),
+        icon: blink.icon_url,
+        title: blink.title,
+        description: "Your message was posted.".to_string(),
+        label: "Message posted".to_string(),
+    })
+    .into_response())
+}
+
+/// The feed of a message blink, newest first.
+#[tracing::instrument(name = "Listing messages", skip(pool, query), fields(blink_id = %id))]
+pub async fn list_messages(
+    State(pool): State<PgPool>,
+    Path(id): Path<Uuid>,
+    Query(query): Query<MessageFeedQuery>,
+) -> Result<Json<Vec<MessageEntry>>, (StatusCode, String)> {
+    let blink = fetch_blink(&pool, id).await?;
+    if !matches!(blink.r#type, BlinkType::Message) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Blink is not a message blink".to_string(),
+        ));
+    }
+
+    let rows = sqlx::query!(
+        r#"
+        SELECT signature, author, body, tip, slot, created_at
+        FROM messages
+        WHERE blink_id = $1 AND ($2::TIMESTAMPTZ IS NULL OR created_at < $2)
+        ORDER BY created_at DESC, signature
+        LIMIT $3
+        "#,
+        id,
+        query.before,
+        query.limit.unwrap_or(50).clamp(1, MAX_FEED_ENTRIES)
+    )
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(
+        rows.into_iter()
+            .map(|row| MessageEntry {
+                signature: row.signature,
+                author: row.author,
+                body: row.body,
+                tip: row.tip.map(|tip| Currency::Sol.format(tip as u64)),
+                slot: row.slot,
+                created_at: row.created_at,
+            })
+            .collect(),
+    ))
+}
+
+/// Adds the number of messages posted to the description of a message
+/// blink.
+pub(super) async fn message_metadata(
+    pool: &PgPool,
+    blink: &Blink,
+    metadata: &mut ActionMetadata,
+) -> Result<(), (StatusCode, String)> {
+    let posted = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) AS "posted!" FROM messages WHERE blink_id = $1"#,
+        blink.id
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let summary = format!(
+        "{} message{} posted",
+        posted,
+        if posted == 1 { "" } else { "s" }
+    );
+    metadata.description = if blink.description.is_empty() {
+        summary
+    } else {
+        format!("{}\n\n{}", blink.description, summary)
+    };
+    Ok(())
+}
+
+/// Lamports `author` sent to `wallet` in system transfers of a transaction.
+fn tip(transaction: &VersionedTransaction, author: &Pubkey, wallet: &Pubkey) -> Option<u64> {
+    let message = &transaction.message;
+    let keys = message.static_account_keys();
+    let lamports = message
+        .instructions()
+        .iter()
+        .filter_map(|instruction| {
+            let account = |index: usize| keys.get(usize::from(*instruction.accounts.get(index)?));
+            if keys.get(usize::from(instruction.program_id_index)) != Some(&system_program::id())
+                || account(0)? != author
+                || account(1)? != wallet
+            {
+                return None;
+            }
+            match bincode::deserialize(&instruction.data).ok()? {
+                SystemInstruction::Transfer { lamports } => Some(lamports),
+                _ => None,
+            }
+        })
+        .sum();
+    (lamports > 0).then_some(lamports)
+}
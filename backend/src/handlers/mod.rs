@@ -1,7 +1,37 @@
 mod actions;
+mod api_keys;
 mod blinks;
+mod claims;
+mod escrows;
+mod governance;
 mod health;
+mod invoices;
+mod messages;
+mod nonces;
+mod raffles;
+mod revisions;
+mod rpc;
+mod share;
+mod signers;
+mod sponsorship;
+mod subscriptions;
+mod swaps;
+mod votes;
 
 pub use actions::*;
+pub use api_keys::*;
 pub use blinks::*;
+pub use claims::*;
+pub use escrows::*;
 pub use health::*;
+pub use invoices::*;
+pub use messages::*;
+pub use nonces::*;
+pub use raffles::*;
+pub use revisions::*;
+pub use rpc::*;
+pub use share::*;
+pub use signers::*;
+pub use sponsorship::*;
+pub use subscriptions::*;
+pub use votes::*;
@@ -0,0 +1,62 @@
+use crate::authentication::AdminToken;
+use crate::models::{NonceAccount, RegisterNonceAccountRequest};
+use crate::nonce_pool::NoncePool;
+use axum::{
+    Json,
+    extract::State,
+    http::{HeaderMap, StatusCode},
+};
+use sqlx::PgPool;
+use std::sync::Arc;
+
+#[tracing::instrument(
+    name = "Registering nonce account",
+    skip(pool, admin, nonce_pool, headers, payload),
+    fields(pubkey = %payload.pubkey)
+)]
+pub async fn register_nonce_account(
+    State(pool): State<PgPool>,
+    State(admin): State<AdminToken>,
+    State(nonce_pool): State<Option<Arc<NoncePool>>>,
+    headers: HeaderMap,
+    Json(payload): Json<RegisterNonceAccountRequest>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    admin.verify(&headers)?;
+    let nonce_pool = require_nonce_pool(nonce_pool)?;
+
+    nonce_pool.register(&pool, &payload.pubkey).await?;
+
+    Ok(StatusCode::CREATED)
+}
+
+#[tracing::instrument(name = "Listing nonce accounts", skip(pool, admin, headers))]
+pub async fn list_nonce_accounts(
+    State(pool): State<PgPool>,
+    State(admin): State<AdminToken>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<NonceAccount>>, (StatusCode, String)> {
+    admin.verify(&headers)?;
+
+    let accounts = sqlx::query_as!(
+        NonceAccount,
+        r#"
+        SELECT pubkey, created_at, leased_at, leased_until, leased_nonce
+        FROM nonce_accounts
+        ORDER BY created_at
+        "#
+    )
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(accounts))
+}
+
+pub(super) fn require_nonce_pool(
+    nonce_pool: Option<Arc<NoncePool>>,
+) -> Result<Arc<NoncePool>, (StatusCode, String)> {
+    nonce_pool.ok_or((
+        StatusCode::SERVICE_UNAVAILABLE,
+        "Durable nonces are not enabled".to_string(),
+    ))
+}
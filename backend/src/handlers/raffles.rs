@@ -0,0 +1,93 @@
+use super::actions::fetch_blink;
+use crate::domain::RaffleConfig;
+use crate::models::{ActionError, ActionMetadata, Blink, BlinkType, RaffleDraw};
+use crate::raffles::TicketBooth;
+use crate::rpc_pool::RpcPool;
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+};
+use chrono::Utc;
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// The draw of a raffle blink: the seed, the hash of the ticket list and the
+/// winners. The raffle is drawn on the first request after its draw slot.
+#[tracing::instrument(
+    name = "Fetching raffle draw",
+    skip(pool, rpc_pool, ticket_booth),
+    fields(blink_id = %id)
+)]
+pub async fn get_raffle_draw(
+    State(pool): State<PgPool>,
+    State(rpc_pool): State<Arc<RpcPool>>,
+    State(ticket_booth): State<Arc<TicketBooth>>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<RaffleDraw>, (StatusCode, String)> {
+    let blink = fetch_blink(&pool, id).await?;
+    if !matches!(blink.r#type, BlinkType::Raffle) {
+        return Err((StatusCode::BAD_REQUEST, "Blink is not a raffle".to_string()));
+    }
+    Ok(Json(ticket_booth.draw(&pool, &rpc_pool, id).await?))
+}
+
+/// Adds the tickets sold and the time left to the description of a raffle
+/// blink, and the winners once drawn. Closed raffles are disabled.
+pub(super) async fn raffle_metadata(
+    pool: &PgPool,
+    rpc_pool: &RpcPool,
+    ticket_booth: &TicketBooth,
+    blink: &Blink,
+    metadata: &mut ActionMetadata,
+) -> Result<(), (StatusCode, String)> {
+    let state = ticket_booth.state(pool, rpc_pool, blink.id).await?;
+    let (tickets_sold, closes_at, winners) = match state {
+        Some(state) => (state.tickets_sold, state.closes_at, state.winners),
+        None => {
+            let config = RaffleConfig::from_config(&blink.config)
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+            (0, config.closes_at, Vec::new())
+        }
+    };
+
+    let sold = format!(
+        "{} ticket{} sold",
+        tickets_sold,
+        if tickets_sold == 1 { "" } else { "s" }
+    );
+    let left = closes_at - Utc::now();
+    let summary = if !winners.is_empty() {
+        format!("{} · Drawn: {}", sold, winners.join(", "))
+    } else if left.num_seconds() <= 0 {
+        format!("{} · Closed, the draw is pending", sold)
+    } else if left.num_hours() >= 24 {
+        format!(
+            "{} · Closes in {}d {}h",
+            sold,
+            left.num_days(),
+            left.num_hours() % 24
+        )
+    } else {
+        format!(
+            "{} · Closes in {}h {}m",
+            sold,
+            left.num_hours(),
+            left.num_minutes() % 60
+        )
+    };
+    metadata.description = if blink.description.is_empty() {
+        summary
+    } else {
+        format!("{}\n\n{}", blink.description, summary)
+    };
+
+    if left.num_seconds() <= 0 {
+        metadata.disabled = Some(true);
+        metadata.error = Some(ActionError {
+            message: "This raffle has closed".to_string(),
+        });
+    }
+    Ok(())
+}
@@ -0,0 +1,433 @@
+use super::blinks::{lock_owned_blink, validate_update};
+use super::votes::store_snapshot;
+use crate::authentication::{AuthenticatedKey, require_api_key};
+use crate::metadata_cache::MetadataCache;
+use crate::models::{
+    ActionBuild, ApiKeyScope, BlinkRevision, BlinkType, FieldChange, RevisionDiff,
+    RevisionDiffQuery,
+};
+use crate::rpc_pool::RpcPool;
+use crate::signer_vault::SignerVault;
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+};
+use serde_json::Value;
+use sqlx::{PgPool, Postgres, Transaction};
+use std::sync::Arc;
+use uuid::Uuid;
+
+#[tracing::instrument(name = "Listing blink revisions", skip(pool, headers), fields(blink_id = %id))]
+pub async fn list_blink_revisions(
+    State(pool): State<PgPool>,
+    headers: HeaderMap,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Vec<BlinkRevision>>, (StatusCode, String)> {
+    let key = require_api_key(&pool, &headers, ApiKeyScope::Read).await?;
+    ensure_owned_blink(&pool, id, &key.owner).await?;
+
+    let revisions = sqlx::query_as!(
+        BlinkRevision,
+        r#"
+        SELECT
+            blink_id,
+            revision,
+            created_at,
+            author,
+            author_key_id,
+            rolled_back_from,
+            title,
+            icon_url,
+            description,
+            label,
+            type as "type: BlinkType",
+            config
+        FROM blink_revisions
+        WHERE blink_id = $1
+        ORDER BY revision DESC
+        "#,
+        id
+    )
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(revisions))
+}
+
+#[tracing::instrument(
+    name = "Diffing blink revisions",
+    skip(pool, headers),
+    fields(blink_id = %id, from = query.from, to = query.to)
+)]
+pub async fn diff_blink_revisions(
+    State(pool): State<PgPool>,
+    headers: HeaderMap,
+    Path(id): Path<Uuid>,
+    Query(query): Query<RevisionDiffQuery>,
+) -> Result<Json<RevisionDiff>, (StatusCode, String)> {
+    let key = require_api_key(&pool, &headers, ApiKeyScope::Read).await?;
+    ensure_owned_blink(&pool, id, &key.owner).await?;
+
+    let from = fetch_revision(&pool, id, query.from).await?;
+    let to = fetch_revision(&pool, id, query.to).await?;
+
+    Ok(Json(RevisionDiff {
+        from: query.from,
+        to: query.to,
+        changes: diff_revisions(&from, &to),
+    }))
+}
+
+#[tracing::instrument(
+    name = "Rolling back a blink",
+    skip(pool, cache, rpc_pool, signer_vault, headers),
+    fields(blink_id = %id, revision = revision)
+)]
+pub async fn rollback_blink_revision(
+    State(pool): State<PgPool>,
+    State(cache): State<Arc<MetadataCache>>,
+    State(rpc_pool): State<Arc<RpcPool>>,
+    State(signer_vault): State<Arc<SignerVault>>,
+    headers: HeaderMap,
+    Path((id, revision)): Path<(Uuid, i32)>,
+) -> Result<Json<BlinkRevision>, (StatusCode, String)> {
+    let key = require_api_key(&pool, &headers, ApiKeyScope::Update).await?;
+    ensure_owned_blink(&pool, id, &key.owner).await?;
+
+    // An old revision may no longer pass the checks of an update, e.g. when
+    // its signer was removed from the vault.
+    let target = fetch_revision(&pool, id, revision).await?;
+    let snapshot = validate_update(
+        &pool,
+        &rpc_pool,
+        &signer_vault,
+        id,
+        &target.r#type,
+        &target.config.0,
+        &key.owner,
+    )
+    .await?;
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    lock_owned_blink(&mut transaction, id, &key.owner).await?;
+
+    if let Some((weighting, snapshot)) = &snapshot {
+        store_snapshot(&mut transaction, id, weighting, snapshot).await?;
+    }
+
+    // Rolling back never rewrites history: the old content becomes a new
+    // revision on top.
+    sqlx::query!(
+        r#"
+        UPDATE blinks
+        SET title = $2,
+            icon_url = $3,
+            description = $4,
+            label = $5,
+            type = $6,
+            config = $7,
+            revision = revision + 1
+        WHERE id = $1
+        "#,
+        id,
+        target.title,
+        target.icon_url,
+        target.description,
+        target.label,
+        target.r#type as BlinkType,
+        target.config.0
+    )
+    .execute(&mut *transaction)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let revision = snapshot_revision(&mut transaction, id, Some(&key), Some(revision)).await?;
+
+    transaction
+        .commit()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    cache.invalidate(id);
+
+    Ok(Json(revision))
+}
+
+#[tracing::instrument(
+    name = "Listing action builds of a revision",
+    skip(pool, headers),
+    fields(blink_id = %id, revision = revision)
+)]
+pub async fn list_revision_builds(
+    State(pool): State<PgPool>,
+    headers: HeaderMap,
+    Path((id, revision)): Path<(Uuid, i32)>,
+) -> Result<Json<Vec<ActionBuild>>, (StatusCode, String)> {
+    let key = require_api_key(&pool, &headers, ApiKeyScope::Read).await?;
+    ensure_owned_blink(&pool, id, &key.owner).await?;
+    fetch_revision(&pool, id, revision).await?;
+
+    let builds = sqlx::query_as!(
+        ActionBuild,
+        r#"
+        SELECT id, created_at, revision, account, transaction, message, nonce_account
+        FROM action_builds
+        WHERE blink_id = $1 AND revision = $2
+        ORDER BY created_at DESC
+        "#,
+        id,
+        revision
+    )
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(builds))
+}
+
+/// Records the current state of a blink as its latest revision. Callers bump
+/// `blinks.revision` in the same transaction before taking the snapshot.
+pub(super) async fn snapshot_revision(
+    transaction: &mut Transaction<'_, Postgres>,
+    blink_id: Uuid,
+    author: Option<&AuthenticatedKey>,
+    rolled_back_from: Option<i32>,
+) -> Result<BlinkRevision, (StatusCode, String)> {
+    sqlx::query_as!(
+        BlinkRevision,
+        r#"
+        INSERT INTO blink_revisions (
+            blink_id, revision, author, author_key_id, rolled_back_from,
+            title, icon_url, description, label, type, config
+        )
+        SELECT id, revision, $2, $3, $4, title, icon_url, description, label, type, config
+        FROM blinks
+        WHERE id = $1
+        RETURNING
+            blink_id,
+            revision,
+            created_at,
+            author,
+            author_key_id,
+            rolled_back_from,
+            title,
+            icon_url,
+            description,
+            label,
+            type as "type: BlinkType",
+            config
+        "#,
+        blink_id,
+        author.map(|key| key.owner.as_str()),
+        author.map(|key| key.id),
+        rolled_back_from
+    )
+    .fetch_one(&mut **transaction)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
+/// Revision history is private to the blink's owner. Blinks of other owners
+/// are reported as missing.
+async fn ensure_owned_blink(
+    pool: &PgPool,
+    blink_id: Uuid,
+    owner: &str,
+) -> Result<(), (StatusCode, String)> {
+    sqlx::query_scalar!(
+        "SELECT id FROM blinks WHERE id = $1 AND owner = $2",
+        blink_id,
+        owner
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .ok_or((StatusCode::NOT_FOUND, "Blink not found".to_string()))?;
+
+    Ok(())
+}
+
+async fn fetch_revision(
+    pool: &PgPool,
+    blink_id: Uuid,
+    revision: i32,
+) -> Result<BlinkRevision, (StatusCode, String)> {
+    sqlx::query_as!(
+        BlinkRevision,
+        r#"
+        SELECT
+            blink_id,
+            revision,
+            created_at,
+            author,
+            author_key_id,
+            rolled_back_from,
+            title,
+            icon_url,
+            description,
+            label,
+            type as "type: BlinkType",
+            config
+        FROM blink_revisions
+        WHERE blink_id = $1 AND revision = $2
+        "#,
+        blink_id,
+        revision
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .ok_or((
+        StatusCode::NOT_FOUND,
+        format!("Revision {} not found", revision),
+    ))
+}
+
+/// Field-level changes from `from` to `to`.
+fn diff_revisions(from: &BlinkRevision, to: &BlinkRevision) -> Vec<FieldChange> {
+    let fields = [
+        (
+            "title",
+            Value::from(from.title.as_str()),
+            Value::from(to.title.as_str()),
+        ),
+        (
+            "icon_url",
+            Value::from(from.icon_url.as_str()),
+            Value::from(to.icon_url.as_str()),
+        ),
+        (
+            "description",
+            Value::from(from.description.as_str()),
+            Value::from(to.description.as_str()),
+        ),
+        (
+            "label",
+            Value::from(from.label.as_str()),
+            Value::from(to.label.as_str()),
+        ),
+        (
+            "type",
+            serde_json::to_value(&from.r#type).unwrap_or_default(),
+            serde_json::to_value(&to.r#type).unwrap_or_default(),
+        ),
+    ];
+
+    let mut changes: Vec<FieldChange> = fields
+        .into_iter()
+        .filter(|(_, from, to)| from != to)
+        .map(|(field, from, to)| FieldChange {
+            field: field.to_string(),
+            from,
+            to,
+        })
+        .collect();
+
+    match (from.config.as_object(), to.config.as_object()) {
+        (Some(from_config), Some(to_config)) => {
+            let mut keys: Vec<&String> = from_config.keys().chain(to_config.keys()).collect();
+            keys.sort();
+            keys.dedup();
+
+            changes.extend(keys.into_iter().filter_map(|key| {
+                let from = from_config.get(key).cloned().unwrap_or(Value::Null);
+                let to = to_config.get(key).cloned().unwrap_or(Value::Null);
+                (from != to).then(|| FieldChange {
+                    field: format!("config.{}", key),
+                    from,
+                    to,
+                })
+            }));
+        }
+        _ if from.config.0 != to.config.0 => changes.push(FieldChange {
+            field: "config".to_string(),
+            from: from.config.0.clone(),
+            to: to.config.0.clone(),
+        }),
+        _ => {}
+    }
+
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use serde_json::json;
+
+    fn revision(revision: i32, title: &str, config: Value) -> BlinkRevision {
+        BlinkRevision {
+            blink_id: Uuid::nil(),
+            revision,
+            created_at: Utc::now(),
+            author: None,
+            author_key_id: None,
+            rolled_back_from: None,
+            title: title.to_string(),
+            icon_url: "https://example.com/icon.png".to_string(),
+            description: "A blink".to_string(),
+            label: "Donate".to_string(),
+            r#type: BlinkType::Donation,
+            config: sqlx::types::Json(config),
+        }
+    }
+
+    #[test]
+    fn identical_revisions_have_no_changes() {
+        let a = revision(1, "Coffee", json!({ "amount": 0.1 }));
+        let b = revision(2, "Coffee", json!({ "amount": 0.1 }));
+
+        assert!(diff_revisions(&a, &b).is_empty());
+    }
+
+    #[test]
+    fn diff_reports_fields_and_individual_config_keys() {
+        let a = revision(1, "Coffee", json!({ "amount": 0.1, "memo": "hi" }));
+        let b = revision(2, "Tea", json!({ "amount": 0.5, "currency": "SOL" }));
+
+        let changes = diff_revisions(&a, &b);
+
+        assert_eq!(
+            changes,
+            vec![
+                FieldChange {
+                    field: "title".to_string(),
+                    from: json!("Coffee"),
+                    to: json!("Tea"),
+                },
+                FieldChange {
+                    field: "config.amount".to_string(),
+                    from: json!(0.1),
+                    to: json!(0.5),
+                },
+                FieldChange {
+                    field: "config.currency".to_string(),
+                    from: Value::Null,
+                    to: json!("SOL"),
+                },
+                FieldChange {
+                    field: "config.memo".to_string(),
+                    from: json!("hi"),
+                    to: Value::Null,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn non_object_configs_are_compared_whole() {
+        let a = revision(1, "Coffee", json!([1, 2]));
+        let b = revision(2, "Coffee", json!([1, 3]));
+
+        let changes = diff_revisions(&a, &b);
+
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].field, "config");
+    }
+}
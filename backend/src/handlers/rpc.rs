@@ -0,0 +1,20 @@
+use crate::authentication::AdminToken;
+use crate::models::RpcEndpointMetrics;
+use crate::rpc_pool::RpcPool;
+use axum::{
+    Json,
+    extract::State,
+    http::{HeaderMap, StatusCode},
+};
+use std::sync::Arc;
+
+#[tracing::instrument(name = "Listing RPC endpoints", skip(admin, rpc_pool, headers))]
+pub async fn list_rpc_endpoints(
+    State(admin): State<AdminToken>,
+    State(rpc_pool): State<Arc<RpcPool>>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<RpcEndpointMetrics>>, (StatusCode, String)> {
+    admin.verify(&headers)?;
+
+    Ok(Json(rpc_pool.metrics()))
+}
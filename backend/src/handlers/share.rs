@@ -0,0 +1,135 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use sqlx::PgPool;
+use std::sync::Arc;
+
+use super::actions::{BlinkLookup, action_rule_set, resolve_blink};
+use crate::domain::ActionRuleSet;
+use crate::models::Blink;
+
+const DIAL_TO_INTERSTITIAL_URL: &str = "https://dial.to/";
+
+#[tracing::instrument(
+    name = "Rendering share page",
+    skip(pool, rules),
+    fields(blink_key = %key)
+)]
+pub async fn get_share_page(
+    State(pool): State<PgPool>,
+    State(rules): State<Arc<ActionRuleSet>>,
+    Path(key): Path<String>,
+) -> Result<Response, (StatusCode, String)> {
+    let backend_url =
+        std::env::var("BACKEND_URL").unwrap_or_else(|_| "http://localhost:8000".to_string());
+
+    let blink = match resolve_blink(&pool, &key).await? {
+        BlinkLookup::Found(blink) => *blink,
+        BlinkLookup::Moved(public_id) => {
+            return Ok(Redirect::permanent(&format!("/b/{}", public_id)).into_response());
+        }
+    };
+
+    // The page links the action `actions.json` maps it to, and none when
+    // its path is excluded or unmapped.
+    let share_path = format!("/b/{}", blink.public_id());
+    let Some(api_path) = action_rule_set(&pool, &rules).await?.resolve(&share_path) else {
+        return Err((StatusCode::NOT_FOUND, "Blink not found".to_string()));
+    };
+    let share_url = format!("{}{}", backend_url, share_path);
+    let action_url = if api_path.starts_with('/') {
+        format!("{}{}", backend_url, api_path)
+    } else {
+        api_path
+    };
+
+    Ok(Html(render_share_page(&blink, &share_url, &action_url)).into_response())
+}
+
+fn render_share_page(blink: &Blink, share_url: &str, action_url: &str) -> String {
+    let title = escape_html(&blink.title);
+    let description = escape_html(&blink.description);
+    let icon = escape_html(&blink.icon_url);
+    let label = escape_html(&blink.label);
+    let share_url = escape_html(share_url);
+
+    let solana_action = format!("solana-action:{}", action_url);
+    let interstitial_url = reqwest::Url::parse_with_params(
+        DIAL_TO_INTERSTITIAL_URL,
+        &[("action", solana_action.as_str())],
+    )
+    .map(|url| url.to_string())
+    .unwrap_or_else(|_| solana_action.clone());
+
+    let interstitial_url = escape_html(&interstitial_url);
+    let solana_action = escape_html(&solana_action);
+    let action_url = escape_html(action_url);
+
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>{title}</title>
+<meta name="description" content="{description}">
+<meta property="og:type" content="website">
+<meta property="og:url" content="{share_url}">
+<meta property="og:title" content="{title}">
+<meta property="og:description" content="{description}">
+<meta property="og:image" content="{icon}">
+<meta name="twitter:card" content="summary_large_image">
+<meta name="twitter:title" content="{title}">
+<meta name="twitter:description" content="{description}">
+<meta name="twitter:image" content="{icon}">
+<link rel="canonical" href="{share_url}">
+<link rel="alternate" type="application/json" href="{action_url}">
+</head>
+<body>
+<main>
+<img src="{icon}" alt="{title}" width="320">
+<h1>{title}</h1>
+<p>{description}</p>
+<p><a href="{interstitial_url}">{label}</a></p>
+<p><a href="{solana_action}">Open in a blink-enabled wallet</a></p>
+</main>
+</body>
+</html>
+"#
+    )
+}
+
+fn escape_html(input: &str) -> String {
+    let mut escaped = String::with_capacity(input.len());
+    for c in input.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_html_escapes_markup_characters() {
+        assert_eq!(
+            escape_html(r#"<script>alert("x" & 'y')</script>"#),
+            "&lt;script&gt;alert(&quot;x&quot; &amp; &#39;y&#39;)&lt;/script&gt;"
+        );
+    }
+
+    #[test]
+    fn escape_html_leaves_plain_text_untouched() {
+        assert_eq!(escape_html("Buy me a coffee"), "Buy me a coffee");
+    }
+}
@@ -0,0 +1,72 @@
+use crate::authentication::AdminToken;
+use crate::models::{SignerAuditEntry, SignerAuditQuery, SignerProgramRule, SignerSummary};
+use crate::signer_vault::SignerVault;
+use axum::{
+    Json,
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+};
+use sqlx::PgPool;
+use std::sync::Arc;
+
+const MAX_AUDIT_ENTRIES: i64 = 500;
+
+#[tracing::instrument(name = "Listing signers", skip(admin, signer_vault, headers))]
+pub async fn list_signers(
+    State(admin): State<AdminToken>,
+    State(signer_vault): State<Arc<SignerVault>>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<SignerSummary>>, (StatusCode, String)> {
+    admin.verify(&headers)?;
+
+    let mut signers = signer_vault
+        .keys()
+        .map(|(name, key)| SignerSummary {
+            name: name.to_string(),
+            pubkey: key.pubkey().to_string(),
+            owners: key.policy.owners.clone(),
+            programs: key
+                .policy
+                .programs
+                .iter()
+                .map(|rule| SignerProgramRule {
+                    program_id: rule.program_id.to_string(),
+                    data_prefixes: rule.data_prefixes.clone(),
+                })
+                .collect(),
+        })
+        .collect::<Vec<_>>();
+    signers.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(Json(signers))
+}
+
+#[tracing::instrument(name = "Listing signer audit log", skip(pool, admin, headers))]
+pub async fn list_signer_audit_log(
+    State(pool): State<PgPool>,
+    State(admin): State<AdminToken>,
+    headers: HeaderMap,
+    Query(query): Query<SignerAuditQuery>,
+) -> Result<Json<Vec<SignerAuditEntry>>, (StatusCode, String)> {
+    admin.verify(&headers)?;
+
+    let entries = sqlx::query_as!(
+        SignerAuditEntry,
+        r#"
+        SELECT signer, pubkey, blink_id, payer, signature, programs, created_at
+        FROM signer_audit_log
+        WHERE ($1::TEXT IS NULL OR signer = $1)
+          AND ($2::UUID IS NULL OR blink_id = $2)
+        ORDER BY id DESC
+        LIMIT $3
+        "#,
+        query.signer,
+        query.blink_id,
+        query.limit.unwrap_or(100).clamp(1, MAX_AUDIT_ENTRIES)
+    )
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(entries))
+}
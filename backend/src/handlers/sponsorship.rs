@@ -0,0 +1,47 @@
+use super::actions::fetch_blink;
+use crate::domain::SponsorConfig;
+use crate::models::SponsorshipUsage;
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[tracing::instrument(name = "Fetching sponsorship usage", skip(pool), fields(blink_id = %id))]
+pub async fn get_sponsorship_usage(
+    State(pool): State<PgPool>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<SponsorshipUsage>, (StatusCode, String)> {
+    let blink = fetch_blink(&pool, id).await?;
+    let sponsor = SponsorConfig::from_config(&blink.config)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?
+        .ok_or((
+            StatusCode::BAD_REQUEST,
+            "Blink is not sponsored".to_string(),
+        ))?;
+
+    let usage = sqlx::query!(
+        r#"
+        SELECT
+            COALESCE(SUM(fee_lamports) FILTER (WHERE status <> 'expired'), 0)::BIGINT AS "charged!",
+            COUNT(*) FILTER (WHERE status = 'pending') AS "pending!",
+            COUNT(*) FILTER (WHERE status = 'landed') AS "landed!"
+        FROM sponsored_transactions
+        WHERE blink_id = $1
+        "#,
+        id
+    )
+    .fetch_one(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(SponsorshipUsage {
+        blink_id: id,
+        budget_lamports: sponsor.budget_lamports,
+        charged_lamports: usage.charged,
+        pending_transactions: usage.pending,
+        landed_transactions: usage.landed,
+    }))
+}
@@ -0,0 +1,228 @@
+use super::invoices::currency_label;
+use crate::authentication::require_api_key;
+use crate::domain::SubscriptionConfig;
+use crate::models::{
+    ActionError, ActionLinks, ActionMetadata, ApiKeyScope, Blink, BlinkType, LinkedAction,
+    Subscriber, SubscriberState, SubscriptionStatus,
+};
+use crate::subscriptions::SubscriptionBiller;
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+};
+use chrono::{DateTime, Utc};
+use solana_sdk::pubkey::Pubkey;
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Subscribers of a subscription blink whose first payment landed, with
+/// their renewal links.
+#[tracing::instrument(
+    name = "Listing subscribers",
+    skip(pool, biller, headers),
+    fields(blink_id = %id)
+)]
+pub async fn list_subscribers(
+    State(pool): State<PgPool>,
+    State(biller): State<Arc<SubscriptionBiller>>,
+    headers: HeaderMap,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Vec<Subscriber>>, (StatusCode, String)> {
+    let key = require_api_key(&pool, &headers, ApiKeyScope::Read).await?;
+    let public_id = owned_subscription_blink(&pool, id, &key.owner).await?;
+    biller.settle(&pool, Some(id)).await?;
+
+    let subscribers = sqlx::query_as!(
+        SubscriberRow,
+        r#"
+        SELECT
+            subscriber, status as "status: SubscriptionStatus",
+            paid_through as "paid_through!", allowance, created_at
+        FROM subscriptions
+        WHERE blink_id = $1 AND paid_through IS NOT NULL
+        ORDER BY created_at
+        "#,
+        id
+    )
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(
+        subscribers
+            .into_iter()
+            .map(|row| row.into_subscriber(&public_id))
+            .collect(),
+    ))
+}
+
+/// Cancels a subscription. Its allowance is no longer charged; a payment of
+/// the subscriber reactivates it.
+#[tracing::instrument(
+    name = "Cancelling a subscription",
+    skip(pool, headers),
+    fields(blink_id = %id, wallet = %wallet)
+)]
+pub async fn cancel_subscription(
+    State(pool): State<PgPool>,
+    headers: HeaderMap,
+    Path((id, wallet)): Path<(Uuid, String)>,
+) -> Result<Json<Subscriber>, (StatusCode, String)> {
+    let key = require_api_key(&pool, &headers, ApiKeyScope::Update).await?;
+    let public_id = owned_subscription_blink(&pool, id, &key.owner).await?;
+
+    let subscription = sqlx::query_as!(
+        SubscriberRow,
+        r#"
+        UPDATE subscriptions
+        SET status = 'cancelled', allowance = 0
+        WHERE blink_id = $1 AND subscriber = $2 AND paid_through IS NOT NULL
+        RETURNING
+            subscriber, status as "status: SubscriptionStatus",
+            paid_through as "paid_through!", allowance, created_at
+        "#,
+        id,
+        wallet
+    )
+    .fetch_optional(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .ok_or((StatusCode::NOT_FOUND, "Subscriber not found".to_string()))?;
+
+    Ok(Json(subscription.into_subscriber(&public_id)))
+}
+
+/// Turns the metadata of a subscription blink into the renewal link of
+/// `subscriber`, showing how long the subscription runs.
+pub(super) async fn renewal_metadata(
+    pool: &PgPool,
+    biller: &SubscriptionBiller,
+    blink: &Blink,
+    subscriber: &Pubkey,
+    metadata: &mut ActionMetadata,
+) -> Result<(), (StatusCode, String)> {
+    biller.settle(pool, Some(blink.id)).await?;
+    let subscription = sqlx::query!(
+        r#"
+        SELECT status as "status: SubscriptionStatus", paid_through as "paid_through!"
+        FROM subscriptions
+        WHERE blink_id = $1 AND subscriber = $2 AND paid_through IS NOT NULL
+        "#,
+        blink.id,
+        subscriber.to_string()
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let Some(subscription) = subscription else {
+        metadata.disabled = Some(true);
+        metadata.error = Some(ActionError {
+            message: "This wallet has not subscribed yet".to_string(),
+        });
+        return Ok(());
+    };
+
+    let paid_through = subscription.paid_through.format("%Y-%m-%d");
+    let summary = match state(subscription.status, &subscription.paid_through) {
+        SubscriberState::Active => format!("Subscribed until {}", paid_through),
+        SubscriberState::Lapsed => format!("Subscription lapsed on {}", paid_through),
+        SubscriberState::Cancelled => {
+            format!("Subscription cancelled, paid until {}", paid_through)
+        }
+    };
+    metadata.description = if blink.description.is_empty() {
+        summary
+    } else {
+        format!("{}\n\n{}", blink.description, summary)
+    };
+    let config = SubscriptionConfig::from_config(&blink.config)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    metadata.links = Some(ActionLinks {
+        actions: vec![LinkedAction {
+            label: format!("Renew ({})", price_label(&config)),
+            href: renewal_url(&blink.public_id(), &subscriber.to_string()),
+            parameters: None,
+        }],
+    });
+    Ok(())
+}
+
+/// Price of a subscription as shown on its buttons.
+pub(super) fn price_label(config: &SubscriptionConfig) -> String {
+    format!(
+        "{} {} every {} days",
+        config.price,
+        currency_label(&config.currency),
+        config.period_days
+    )
+}
+
+fn renewal_url(public_id: &str, subscriber: &str) -> String {
+    let backend_url =
+        std::env::var("BACKEND_URL").unwrap_or_else(|_| "http://localhost:8000".to_string());
+    format!(
+        "{}/api/actions/{}?subscriber={}",
+        backend_url, public_id, subscriber
+    )
+}
+
+fn state(status: SubscriptionStatus, paid_through: &DateTime<Utc>) -> SubscriberState {
+    match status {
+        SubscriptionStatus::Cancelled => SubscriberState::Cancelled,
+        SubscriptionStatus::Active if *paid_through > Utc::now() => SubscriberState::Active,
+        SubscriptionStatus::Active => SubscriberState::Lapsed,
+    }
+}
+
+/// The public id of a subscription blink of `owner`. Blinks of other owners
+/// are reported as missing.
+async fn owned_subscription_blink(
+    pool: &PgPool,
+    id: Uuid,
+    owner: &str,
+) -> Result<String, (StatusCode, String)> {
+    let blink = sqlx::query!(
+        r#"
+        SELECT type as "type: BlinkType", slug
+        FROM blinks
+        WHERE id = $1 AND owner = $2
+        "#,
+        id,
+        owner
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .ok_or((StatusCode::NOT_FOUND, "Blink not found".to_string()))?;
+    if !matches!(blink.r#type, BlinkType::Subscription) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Only subscription blinks have subscribers".to_string(),
+        ));
+    }
+    Ok(blink.slug.unwrap_or_else(|| id.to_string()))
+}
+
+struct SubscriberRow {
+    subscriber: String,
+    status: SubscriptionStatus,
+    paid_through: DateTime<Utc>,
+    allowance: i64,
+    created_at: DateTime<Utc>,
+}
+
+impl SubscriberRow {
+    fn into_subscriber(self, public_id: &str) -> Subscriber {
+        let status = state(self.status, &self.paid_through);
+        Subscriber {
+            renewal_url: renewal_url(public_id, &self.subscriber),
+            wallet: self.subscriber,
+            status,
+            renews_at: self.paid_through,
+            auto_renew: status != SubscriberState::Cancelled && self.allowance > 0,
+            subscribed_at: self.created_at,
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use axum::{
+    Json,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use solana_sdk::{native_token::LAMPORTS_PER_SOL, pubkey::Pubkey};
+use sqlx::PgPool;
+
+use super::actions::{action_error, record_action_build};
+use crate::domain::{SwapConfig, WRAPPED_SOL_MINT};
+use crate::invoices::Currency;
+use crate::models::{ActionPostResponse, ActionQueryParams, Blink};
+use crate::rpc_pool::RpcPool;
+use crate::swaps::{SwapRequest, SwapRouter};
+
+/// Serves the transaction of a swap blink as prepared by the quote
+/// providers. It is signed by the user alone, so it skips sponsorship,
+/// durable nonces and the signer vault.
+pub(super) async fn swap_action(
+    pool: &PgPool,
+    rpc_pool: &RpcPool,
+    swap_router: Option<&SwapRouter>,
+    blink: &Blink,
+    params: &ActionQueryParams,
+    user: &Pubkey,
+) -> Result<Response, (StatusCode, String)> {
+    let swap_router = swap_router.ok_or((
+        StatusCode::SERVICE_UNAVAILABLE,
+        "Swaps are not enabled".to_string(),
+    ))?;
+    let config = SwapConfig::from_config(&blink.config)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    let amount = params
+        .amount
+        .as_deref()
+        .and_then(|amount| amount.parse::<f64>().ok())
+        .filter(|amount| amount.is_finite() && *amount > 0.0)
+        .ok_or((
+            StatusCode::BAD_REQUEST,
+            "Missing or invalid amount".to_string(),
+        ))?;
+    let slippage_bps = match config.slippage(params.slippage.as_deref()) {
+        Ok(slippage_bps) => slippage_bps,
+        Err(message) => return Ok(action_error(StatusCode::BAD_REQUEST, message)),
+    };
+
+    let request = SwapRequest {
+        input_mint: WRAPPED_SOL_MINT,
+        output_mint: config.output_mint,
+        amount: (amount * LAMPORTS_PER_SOL as f64) as u64,
+        slippage_bps,
+        fee_bps: config.fee_bps,
+        fee_account: config.fee_account,
+        user: *user,
+    };
+    let swap = match swap_router.prepare(&request).await {
+        Ok(swap) => swap,
+        Err(e) => {
+            return Ok(action_error(
+                StatusCode::BAD_GATEWAY,
+                format!("No swap route available: {}", e),
+            ));
+        }
+    };
+
+    let slippage = format!("{}% max slippage", f64::from(slippage_bps) / 100.0);
+    let message = match Currency::resolve(rpc_pool, &config.output_mint.to_string()).await {
+        Ok(currency) => format!(
+            "Swap {} SOL for about {} tokens, at least {} ({})",
+            amount,
+            currency.format(swap.out_amount),
+            currency.format(swap.min_out_amount),
+            slippage
+        ),
+        Err(_) => format!(
+            "Swap {} SOL for {} ({})",
+            amount, config.output_mint, slippage
+        ),
+    };
+
+    let serialized = bincode::serialize(&swap.transaction).map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Serialization error: {}", e),
+        )
+    })?;
+    let transaction = BASE64.encode(&serialized);
+    record_action_build(pool, blink, &user.to_string(), &transaction, &message, None).await?;
+
+    Ok(Json(ActionPostResponse {
+        transaction,
+        message: Some(message),
+        links: None,
+    })
+    .into_response())
+}
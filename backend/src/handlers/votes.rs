@@ -0,0 +1,511 @@
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use serde_json::{Value, json};
+use solana_client::rpc_request::RpcRequest;
+use solana_sdk::{pubkey::Pubkey, signature::Signature, transaction::VersionedTransaction};
+use sqlx::{PgPool, Postgres, Transaction};
+use std::str::FromStr;
+use uuid::Uuid;
+
+use super::actions::{MEMO_PROGRAM_ID, action_error, fetch_blink};
+use crate::domain::{BallotKind, OptionTally, Poll, SnapshotPolicy, VoteMemo, VoteWeighting};
+use crate::holdings::{self, BalanceSnapshot};
+use crate::models::{
+    Blink, BlinkType, CompletedAction, NextActionPostRequest, VoteOptionResult, VoteResults,
+    VoteRound, VoteWeightingSummary,
+};
+use crate::rpc_pool::RpcPool;
+
+/// Whether a weighted poll can take votes yet.
+pub(super) enum SnapshotState {
+    Ready {
+        slot: u64,
+    },
+    /// The snapshot slot has not been reached.
+    Pending {
+        opens_at: u64,
+    },
+}
+
+/// Returns the snapshot slot of a weighted poll, taking the snapshot first
+/// if the policy calls for one that has not been stored yet.
+pub(super) async fn ensure_snapshot(
+    pool: &PgPool,
+    rpc: &RpcPool,
+    blink_id: Uuid,
+    weighting: &VoteWeighting,
+) -> Result<SnapshotState, (StatusCode, String)> {
+    if let Some(slot) = stored_snapshot(pool, blink_id, weighting).await? {
+        return Ok(SnapshotState::Ready { slot });
+    }
+    let Some(snapshot) = take_snapshot(rpc, weighting).await? else {
+        let SnapshotPolicy::Slot(opens_at) = weighting.snapshot else {
+            unreachable!("creation snapshots are always available");
+        };
+        return Ok(SnapshotState::Pending { opens_at });
+    };
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    // Concurrent voters may all reach the slot at once; the first snapshot
+    // stored wins.
+    sqlx::query!("SELECT id FROM blinks WHERE id = $1 FOR UPDATE", blink_id)
+        .fetch_one(&mut *transaction)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if let Some(slot) = stored_snapshot(&mut *transaction, blink_id, weighting).await? {
+        return Ok(SnapshotState::Ready { slot });
+    }
+    store_snapshot(&mut transaction, blink_id, weighting, &snapshot).await?;
+    transaction
+        .commit()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(SnapshotState::Ready {
+        slot: snapshot.slot,
+    })
+}
+
+/// Reads the balances the policy asks for, or `None` while the cluster has
+/// not reached the snapshot slot. Balances cannot be read at a past slot, so
+/// a slot snapshot is taken by the first request at or after it.
+pub(super) async fn take_snapshot(
+    rpc: &RpcPool,
+    weighting: &VoteWeighting,
+) -> Result<Option<BalanceSnapshot>, (StatusCode, String)> {
+    let min_slot = match weighting.snapshot {
+        SnapshotPolicy::Creation => None,
+        SnapshotPolicy::Slot(slot) => {
+            let commitment = rpc.commitment();
+            let current = rpc
+                .call(|client| async move { client.get_slot_with_commitment(commitment).await })
+                .await
+                .map_err(rpc_error)?;
+            if current < slot {
+                return Ok(None);
+            }
+            Some(slot)
+        }
+    };
+
+    holdings::snapshot_balances(rpc, &weighting.mint, min_slot)
+        .await
+        .map(Some)
+        .map_err(rpc_error)
+}
+
+/// Slot of the stored snapshot, if it was taken for this weighting.
+pub(super) async fn stored_snapshot<'e>(
+    executor: impl sqlx::PgExecutor<'e>,
+    blink_id: Uuid,
+    weighting: &VoteWeighting,
+) -> Result<Option<u64>, (StatusCode, String)> {
+    let slot = sqlx::query_scalar!(
+        r#"
+        SELECT slot
+        FROM vote_snapshots
+        WHERE blink_id = $1 AND mint = $2 AND requested_slot IS NOT DISTINCT FROM $3
+        "#,
+        blink_id,
+        weighting.mint.to_string(),
+        requested_slot(weighting)
+    )
+    .fetch_optional(executor)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(slot.map(|slot| slot as u64))
+}
+
+/// Replaces the snapshot of a blink.
+pub(super) async fn store_snapshot(
+    transaction: &mut Transaction<'_, Postgres>,
+    blink_id: Uuid,
+    weighting: &VoteWeighting,
+    snapshot: &BalanceSnapshot,
+) -> Result<(), (StatusCode, String)> {
+    sqlx::query!("DELETE FROM vote_snapshots WHERE blink_id = $1", blink_id)
+        .execute(&mut **transaction)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO vote_snapshots (blink_id, mint, requested_slot, slot)
+        VALUES ($1, $2, $3, $4)
+        "#,
+        blink_id,
+        weighting.mint.to_string(),
+        requested_slot(weighting),
+        snapshot.slot as i64
+    )
+    .execute(&mut **transaction)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let (wallets, balances): (Vec<String>, Vec<String>) = snapshot
+        .balances
+        .iter()
+        .map(|(wallet, balance)| (wallet.to_string(), balance.to_string()))
+        .unzip();
+    sqlx::query!(
+        r#"
+        INSERT INTO vote_snapshot_balances (blink_id, wallet, balance)
+        SELECT $1, wallet, balance::NUMERIC
+        FROM UNNEST($2::TEXT[], $3::TEXT[]) AS b(wallet, balance)
+        "#,
+        blink_id,
+        &wallets,
+        &balances
+    )
+    .execute(&mut **transaction)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(())
+}
+
+/// Raw token balance of `wallet` in the stored snapshot.
+pub(super) async fn voting_power(
+    pool: &PgPool,
+    blink_id: Uuid,
+    wallet: &Pubkey,
+) -> Result<u64, (StatusCode, String)> {
+    let balance = sqlx::query_scalar!(
+        r#"
+        SELECT balance::TEXT AS "balance!"
+        FROM vote_snapshot_balances
+        WHERE blink_id = $1 AND wallet = $2
+        "#,
+        blink_id,
+        wallet.to_string()
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    match balance {
+        Some(balance) => balance.parse().map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Invalid stored balance: {}", e),
+            )
+        }),
+        None => Ok(0),
+    }
+}
+
+/// Checks the confirmed memo of a vote transaction against the poll and
+/// records the vote.
+pub(super) async fn confirm_vote(
+    pool: &PgPool,
+    rpc_pool: &RpcPool,
+    blink: Blink,
+    payload: &NextActionPostRequest,
+) -> Result<Response, (StatusCode, String)> {
+    let (voter, signature) = confirmation_signer(payload)?;
+    let confirmed = fetch_confirmed(rpc_pool, &signature).await?;
+    if confirmed.is_null() {
+        return Ok(action_error(
+            StatusCode::NOT_FOUND,
+            "Vote transaction is not confirmed yet".to_string(),
+        ));
+    }
+    if !confirmed["meta"]["err"].is_null() {
+        return Ok(action_error(
+            StatusCode::BAD_REQUEST,
+            "Vote transaction failed".to_string(),
+        ));
+    }
+
+    let Some(memo) = signed_transaction(&confirmed, &signature, &voter)
+        .and_then(|transaction| memos(&transaction).find_map(VoteMemo::parse))
+        .filter(|memo| memo.blink_id == blink.id)
+    else {
+        return Ok(action_error(
+            StatusCode::BAD_REQUEST,
+            "Transaction does not carry a vote signed by this wallet".to_string(),
+        ));
+    };
+    let poll =
+        Poll::from_config(&blink.config).map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    if poll.decode(&memo.selection).is_none() {
+        return Ok(action_error(
+            StatusCode::BAD_REQUEST,
+            format!("'{}' is not a valid ballot for this poll", memo.selection),
+        ));
+    }
+
+    let weighting = VoteWeighting::from_config(&blink.config)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    let weight = match (&weighting, &memo.weight) {
+        (None, None) => None,
+        (Some(weighting), Some(claim)) => {
+            let slot = stored_snapshot(pool, blink.id, weighting).await?;
+            let power = voting_power(pool, blink.id, &voter).await?;
+            if claim.mint != weighting.mint || Some(claim.slot) != slot || claim.weight != power {
+                return Ok(action_error(
+                    StatusCode::BAD_REQUEST,
+                    "Vote weight does not match the poll's snapshot".to_string(),
+                ));
+            }
+            Some(claim.weight)
+        }
+        _ => {
+            return Ok(action_error(
+                StatusCode::BAD_REQUEST,
+                "Vote weighting does not match the poll".to_string(),
+            ));
+        }
+    };
+
+    let inserted = sqlx::query!(
+        r#"
+        INSERT INTO votes (blink_id, voter, selection, weight, signature, slot)
+        VALUES ($1, $2, $3, $4::TEXT::NUMERIC, $5, $6)
+        ON CONFLICT DO NOTHING
+        "#,
+        blink.id,
+        voter.to_string(),
+        memo.selection,
+        weight.map(|weight| weight.to_string()),
+        signature.to_string(),
+        confirmed["slot"].as_i64().unwrap_or_default()
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    // Confirming the same transaction twice is fine; a second vote is not.
+    if inserted.rows_affected() == 0 {
+        let recorded = sqlx::query_scalar!(
+            "SELECT signature FROM votes WHERE blink_id = $1 AND voter = $2",
+            blink.id,
+            voter.to_string()
+        )
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        if recorded != Some(signature.to_string()) {
+            return Ok(action_error(
+                StatusCode::CONFLICT,
+                "This wallet has already voted".to_string(),
+            ));
+        }
+    }
+
+    let description = match weight {
+        Some(weight) => format!(
+            "Your vote for {} was counted with a weight of {}.",
+            memo.selection, weight
+        ),
+        None => format!("Your vote for {} was counted.", memo.selection),
+    };
+    Ok(Json(CompletedAction {
+        r#type: "completed".to_string(),
+        icon: blink.icon_url,
+        title: blink.title,
+        description,
+        label: "Vote counted".to_string(),
+    })
+    .into_response())
+}
+
+#[tracing::instrument(name = "Fetching vote results", skip(pool), fields(blink_id = %id))]
+pub async fn get_vote_results(
+    State(pool): State<PgPool>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<VoteResults>, (StatusCode, String)> {
+    let blink = fetch_blink(&pool, id).await?;
+    if !matches!(blink.r#type, BlinkType::Vote) {
+        return Err((StatusCode::BAD_REQUEST, "Blink is not a vote".to_string()));
+    }
+    let poll =
+        Poll::from_config(&blink.config).map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    let weighting = VoteWeighting::from_config(&blink.config)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+
+    // Identical ballots are counted together.
+    let rows = sqlx::query!(
+        r#"
+        SELECT selection, COUNT(*) AS "votes!", COALESCE(SUM(weight), 0)::TEXT AS "weight!"
+        FROM votes
+        WHERE blink_id = $1
+        GROUP BY selection
+        "#,
+        id
+    )
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let ballots = rows
+        .into_iter()
+        .map(|row| {
+            let weight = row.weight.parse().map_err(|e| {
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("Invalid vote weight: {}", e),
+                )
+            })?;
+            Ok((row.selection, row.votes, weight))
+        })
+        .collect::<Result<Vec<_>, (StatusCode, String)>>()?;
+    let tally = poll.tally(&ballots, weighting.is_some());
+
+    // Options are reported in poll order, including those without votes.
+    let weighted = weighting.is_some();
+    let options = tally
+        .options
+        .iter()
+        .enumerate()
+        .map(|(index, tally)| option_result(&poll, index, tally, weighted))
+        .collect();
+    let rounds = tally.rounds.as_ref().map(|rounds| {
+        rounds
+            .iter()
+            .map(|round| VoteRound {
+                options: round
+                    .options
+                    .iter()
+                    .map(|(index, tally)| option_result(&poll, *index, tally, weighted))
+                    .collect(),
+                eliminated: round.eliminated.map(|index| poll.options[index].id.clone()),
+            })
+            .collect()
+    });
+    let ballot = match poll.ballot {
+        BallotKind::Single => "single",
+        BallotKind::Multi { .. } => "multi",
+        BallotKind::Ranked { .. } => "ranked",
+    };
+
+    let weighting = match weighting {
+        Some(weighting) => Some(VoteWeightingSummary {
+            mint: weighting.mint.to_string(),
+            snapshot_slot: stored_snapshot(&pool, id, &weighting).await?,
+        }),
+        None => None,
+    };
+
+    Ok(Json(VoteResults {
+        blink_id: id,
+        ballot: ballot.to_string(),
+        total_weight: weighting.as_ref().map(|_| tally.weight.to_string()),
+        weighting,
+        options,
+        total_votes: tally.ballots,
+        rounds,
+        winner: tally.winner.map(|index| poll.options[index].id.clone()),
+    }))
+}
+
+/// The wallet and signature a confirmation callback reports.
+pub(super) fn confirmation_signer(
+    payload: &NextActionPostRequest,
+) -> Result<(Pubkey, Signature), (StatusCode, String)> {
+    let signer = Pubkey::from_str(&payload.account).map_err(|e| {
+        (
+            StatusCode::BAD_REQUEST,
+            format!("Invalid user wallet: {}", e),
+        )
+    })?;
+    let signature = Signature::from_str(&payload.signature)
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid signature: {}", e)))?;
+    Ok((signer, signature))
+}
+
+/// The `getTransaction` result of `signature`, `null` while it is not
+/// confirmed.
+pub(super) async fn fetch_confirmed(
+    rpc_pool: &RpcPool,
+    signature: &Signature,
+) -> Result<Value, (StatusCode, String)> {
+    let params = json!([
+        signature.to_string(),
+        {
+            "encoding": "base64",
+            "commitment": "confirmed",
+            "maxSupportedTransactionVersion": 0
+        }
+    ]);
+    rpc_pool
+        .call(|client| {
+            let params = params.clone();
+            async move { client.send(RpcRequest::GetTransaction, params).await }
+        })
+        .await
+        .map_err(rpc_error)
+}
+
+/// The transaction of a `getTransaction` result, if it is `signature` and
+/// `signer` signed it.
+pub(super) fn signed_transaction(
+    confirmed: &Value,
+    signature: &Signature,
+    signer: &Pubkey,
+) -> Option<VersionedTransaction> {
+    let encoded = confirmed["transaction"][0].as_str()?;
+    let transaction: VersionedTransaction =
+        bincode::deserialize(&BASE64.decode(encoded).ok()?).ok()?;
+    if transaction.signatures.first() != Some(signature) {
+        return None;
+    }
+
+    let message = &transaction.message;
+    let keys = message.static_account_keys();
+    let signers = &keys[..usize::from(message.header().num_required_signatures).min(keys.len())];
+    signers.contains(signer).then_some(transaction)
+}
+
+/// The UTF-8 memos of a transaction.
+pub(super) fn memos(transaction: &VersionedTransaction) -> impl Iterator<Item = &str> {
+    let message = &transaction.message;
+    let keys = message.static_account_keys();
+    let memo_program = Pubkey::from_str(MEMO_PROGRAM_ID).ok();
+
+    message
+        .instructions()
+        .iter()
+        .filter_map(move |instruction| {
+            if keys.get(usize::from(instruction.program_id_index)) != memo_program.as_ref() {
+                return None;
+            }
+            std::str::from_utf8(&instruction.data).ok()
+        })
+}
+
+fn option_result(
+    poll: &Poll,
+    index: usize,
+    tally: &OptionTally,
+    weighted: bool,
+) -> VoteOptionResult {
+    VoteOptionResult {
+        option: poll.options[index].id.clone(),
+        label: poll.options[index].label.clone(),
+        votes: tally.votes,
+        weight: weighted.then(|| tally.weight.to_string()),
+    }
+}
+
+fn requested_slot(weighting: &VoteWeighting) -> Option<i64> {
+    match weighting.snapshot {
+        SnapshotPolicy::Creation => None,
+        SnapshotPolicy::Slot(slot) => Some(slot as i64),
+    }
+}
+
+fn rpc_error(e: impl std::fmt::Display) -> (StatusCode, String) {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        format!("RPC Error: {}", e),
+    )
+}
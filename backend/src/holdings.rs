@@ -0,0 +1,317 @@
+use crate::domain::GateRequirement;
+use crate::rpc_pool::RpcPool;
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use serde_json::json;
+use solana_client::client_error::ClientError;
+use solana_client::rpc_request::{RpcRequest, TokenAccountsFilter};
+use solana_client::rpc_response::{Response, RpcKeyedAccount};
+use solana_sdk::pubkey::Pubkey;
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+
+/// Metaplex Token Metadata program.
+pub(crate) const METADATA_PROGRAM_ID: Pubkey =
+    solana_sdk::pubkey!("metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s");
+pub(crate) const SPL_TOKEN_PROGRAM_ID: Pubkey =
+    solana_sdk::pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+/// Size of an SPL token account.
+const TOKEN_ACCOUNT_LEN: u64 = 165;
+/// `getMultipleAccounts` accepts at most 100 keys per request.
+pub(crate) const MAX_ACCOUNTS_PER_REQUEST: usize = 100;
+
+/// Whether `owner` meets `requirement`, checked against the chain.
+pub async fn meets_requirement(
+    rpc: &RpcPool,
+    requirement: &GateRequirement,
+    owner: &Pubkey,
+) -> Result<bool, ClientError> {
+    match requirement {
+        GateRequirement::Token { mint, min_amount } => {
+            let accounts = token_accounts(rpc, owner, TokenAccountsFilter::Mint, *mint).await?;
+            let balance: f64 = accounts
+                .iter()
+                .filter_map(|account| token_amount(account).map(|amount| amount.ui_amount))
+                .sum();
+            Ok(balance >= *min_amount)
+        }
+        GateRequirement::Collection { collection } => {
+            holds_collection_nft(rpc, owner, collection).await
+        }
+    }
+}
+
+/// Balances of every holder of a mint at one slot.
+#[derive(Debug)]
+pub struct BalanceSnapshot {
+    pub slot: u64,
+    /// Raw token amounts, summed over each owner's token accounts.
+    pub balances: HashMap<Pubkey, u64>,
+}
+
+/// Reads the balance of every SPL token account of `mint`. With `min_slot`,
+/// only a node that has reached that slot may answer.
+pub async fn snapshot_balances(
+    rpc: &RpcPool,
+    mint: &Pubkey,
+    min_slot: Option<u64>,
+) -> Result<BalanceSnapshot, ClientError> {
+    let params = json!([
+        SPL_TOKEN_PROGRAM_ID.to_string(),
+        {
+            "encoding": "base64",
+            "commitment": rpc.commitment().commitment,
+            // Owner and amount only
+            "dataSlice": { "offset": 32, "length": 40 },
+            "filters": [
+                { "dataSize": TOKEN_ACCOUNT_LEN },
+                { "memcmp": { "offset": 0, "bytes": mint.to_string() } }
+            ],
+            "withContext": true,
+            "minContextSlot": min_slot
+        }
+    ]);
+    let response: Response<Vec<RpcKeyedAccount>> = rpc
+        .call(|client| {
+            let params = params.clone();
+            async move { client.send(RpcRequest::GetProgramAccounts, params).await }
+        })
+        .await?;
+
+    let mut balances = HashMap::new();
+    for account in &response.value {
+        if let Some((owner, amount)) = owner_and_amount(account)
+            && amount > 0
+        {
+            *balances.entry(owner).or_insert(0u64) += amount;
+        }
+    }
+    Ok(BalanceSnapshot {
+        slot: response.context.slot,
+        balances,
+    })
+}
+
+/// Decodes the owner/amount slice of a base64 token account.
+fn owner_and_amount(account: &RpcKeyedAccount) -> Option<(Pubkey, u64)> {
+    let data = serde_json::to_value(&account.account.data).ok()?;
+    let bytes = BASE64.decode(data[0].as_str()?).ok()?;
+    if bytes.len() != 40 {
+        return None;
+    }
+    let owner = Pubkey::try_from(&bytes[..32]).ok()?;
+    let amount = u64::from_le_bytes(bytes[32..].try_into().ok()?);
+    Some((owner, amount))
+}
+
+/// Looks for a verified member of `collection` among the NFTs of `owner`:
+/// SPL token accounts holding exactly one token of a zero-decimal mint,
+/// whose Metaplex metadata names the collection.
+async fn holds_collection_nft(
+    rpc: &RpcPool,
+    owner: &Pubkey,
+    collection: &Pubkey,
+) -> Result<bool, ClientError> {
+    let accounts = token_accounts(
+        rpc,
+        owner,
+        TokenAccountsFilter::ProgramId,
+        SPL_TOKEN_PROGRAM_ID,
+    )
+    .await?;
+    let metadata_addresses: Vec<Pubkey> = accounts
+        .iter()
+        .filter_map(token_amount)
+        .filter(|amount| amount.decimals == 0 && amount.raw == "1")
+        .map(|amount| amount.mint)
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .map(|mint| metadata_address(&mint))
+        .collect();
+
+    for chunk in metadata_addresses.chunks(MAX_ACCOUNTS_PER_REQUEST) {
+        let metadata = rpc
+            .call(|client| async move { client.get_multiple_accounts(chunk).await })
+            .await?;
+        let found = metadata
+            .iter()
+            .flatten()
+            .filter(|account| account.owner == METADATA_PROGRAM_ID)
+            .filter_map(|account| verified_collection(&account.data))
+            .any(|key| key == *collection);
+        if found {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+async fn token_accounts(
+    rpc: &RpcPool,
+    owner: &Pubkey,
+    filter: fn(Pubkey) -> TokenAccountsFilter,
+    key: Pubkey,
+) -> Result<Vec<RpcKeyedAccount>, ClientError> {
+    rpc.call(|client| async move { client.get_token_accounts_by_owner(owner, filter(key)).await })
+        .await
+}
+
+struct TokenAmount {
+    mint: Pubkey,
+    raw: String,
+    decimals: u64,
+    ui_amount: f64,
+}
+
+/// Reads the balance of a `jsonParsed` token account.
+fn token_amount(account: &RpcKeyedAccount) -> Option<TokenAmount> {
+    let data = serde_json::to_value(&account.account.data).ok()?;
+    let info = &data["parsed"]["info"];
+    let amount = &info["tokenAmount"];
+
+    Some(TokenAmount {
+        mint: Pubkey::from_str(info["mint"].as_str()?).ok()?,
+        raw: amount["amount"].as_str()?.to_string(),
+        decimals: amount["decimals"].as_u64()?,
+        ui_amount: amount["uiAmountString"].as_str()?.parse().ok()?,
+    })
+}
+
+fn metadata_address(mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"metadata", METADATA_PROGRAM_ID.as_ref(), mint.as_ref()],
+        &METADATA_PROGRAM_ID,
+    )
+    .0
+}
+
+/// The verified collection of a Metaplex metadata account, if any.
+///
+/// Walks the Borsh layout up to the `collection` field. Accounts written
+/// before the field existed end early and have no collection.
+fn verified_collection(data: &[u8]) -> Option<Pubkey> {
+    let mut reader = BorshReader { data };
+    reader.skip(1 + 32 + 32)?; // key, update authority, mint
+    for _ in 0..3 {
+        // name, symbol, uri
+        let len = reader.u32()? as usize;
+        reader.skip(len)?;
+    }
+    reader.skip(2)?; // seller fee basis points
+    if reader.u8()? == 1 {
+        let creators = reader.u32()? as usize;
+        reader.skip(creators * (32 + 1 + 1))?;
+    }
+    reader.skip(2)?; // primary sale happened, is mutable
+    for _ in 0..2 {
+        // edition nonce, token standard
+        if reader.u8()? == 1 {
+            reader.skip(1)?;
+        }
+    }
+    if reader.u8()? != 1 {
+        return None;
+    }
+    let verified = reader.u8()? == 1;
+    let key = Pubkey::try_from(reader.take(32)?).ok()?;
+
+    verified.then_some(key)
+}
+
+/// Reads Borsh-encoded account data field by field.
+pub(crate) struct BorshReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> BorshReader<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    pub(crate) fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.data.len() < len {
+            return None;
+        }
+        let (head, tail) = self.data.split_at(len);
+        self.data = tail;
+        Some(head)
+    }
+
+    pub(crate) fn skip(&mut self, len: usize) -> Option<()> {
+        self.take(len).map(|_| ())
+    }
+
+    pub(crate) fn u8(&mut self) -> Option<u8> {
+        self.take(1).map(|bytes| bytes[0])
+    }
+
+    pub(crate) fn u32(&mut self) -> Option<u32> {
+        self.take(4)
+            .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    pub(crate) fn u64(&mut self) -> Option<u64> {
+        self.take(8)
+            .map(|bytes| u64::from_le_bytes(bytes.try_into().unwrap()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata(creators: usize, collection: Option<(bool, Pubkey)>) -> Vec<u8> {
+        let mut data = vec![4];
+        data.extend_from_slice(Pubkey::new_unique().as_ref());
+        data.extend_from_slice(Pubkey::new_unique().as_ref());
+        for field in ["Blink #1", "BLNK", "https://example.com/1.json"] {
+            data.extend_from_slice(&(field.len() as u32).to_le_bytes());
+            data.extend_from_slice(field.as_bytes());
+        }
+        data.extend_from_slice(&500u16.to_le_bytes());
+        data.push(1);
+        data.extend_from_slice(&(creators as u32).to_le_bytes());
+        for _ in 0..creators {
+            data.extend_from_slice(Pubkey::new_unique().as_ref());
+            data.extend_from_slice(&[1, 100]);
+        }
+        data.extend_from_slice(&[1, 1]);
+        data.extend_from_slice(&[1, 255]);
+        data.extend_from_slice(&[1, 0]);
+        match collection {
+            Some((verified, key)) => {
+                data.extend_from_slice(&[1, verified as u8]);
+                data.extend_from_slice(key.as_ref());
+            }
+            None => data.push(0),
+        }
+        data
+    }
+
+    #[test]
+    fn reads_verified_collection() {
+        let collection = Pubkey::new_unique();
+
+        assert_eq!(
+            verified_collection(&metadata(2, Some((true, collection)))),
+            Some(collection)
+        );
+    }
+
+    #[test]
+    fn ignores_unverified_and_missing_collections() {
+        let collection = Pubkey::new_unique();
+
+        assert_eq!(
+            verified_collection(&metadata(1, Some((false, collection)))),
+            None
+        );
+        assert_eq!(verified_collection(&metadata(0, None)), None);
+    }
+
+    #[test]
+    fn truncated_metadata_has_no_collection() {
+        let data = metadata(1, Some((true, Pubkey::new_unique())));
+
+        assert_eq!(verified_collection(&data[..data.len() - 40]), None);
+    }
+}
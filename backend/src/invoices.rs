@@ -0,0 +1,363 @@
+use crate::holdings::SPL_TOKEN_PROGRAM_ID;
+use crate::nft_minter::{associated_token_address, create_token_account};
+use crate::rpc_pool::RpcPool;
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use serde_json::{Value, json};
+use solana_client::rpc_request::RpcRequest;
+use solana_sdk::{
+    compute_budget::ComputeBudgetInstruction,
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    signature::Signature,
+    system_instruction::{self, SystemInstruction},
+    system_program,
+    transaction::VersionedTransaction,
+};
+use std::str::FromStr;
+
+/// Currency code of invoices paid in SOL. Invoices in SPL tokens use the
+/// mint address as code.
+pub const SOL: &str = "SOL";
+/// Size of an SPL token mint account and the offset of its decimals.
+const MINT_LEN: usize = 82;
+const MINT_DECIMALS_OFFSET: usize = 44;
+/// `TokenInstruction::TransferChecked` and `TokenInstruction::ApproveChecked`.
+const TRANSFER_CHECKED: u8 = 12;
+const APPROVE_CHECKED: u8 = 13;
+
+/// What an invoice is paid in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Currency {
+    Sol,
+    Token { mint: Pubkey, decimals: u8 },
+}
+
+/// A transfer that settles an invoice.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Payment {
+    pub signature: Signature,
+    pub payer: Pubkey,
+}
+
+impl Currency {
+    /// The currency of a code, reading the decimals of token mints from the
+    /// cluster.
+    pub async fn resolve(rpc: &RpcPool, code: &str) -> Result<Currency, String> {
+        if code == SOL {
+            return Ok(Currency::Sol);
+        }
+        let mint = Pubkey::from_str(code)
+            .map_err(|_| format!("Currency must be SOL or a token mint: {}", code))?;
+        let commitment = rpc.commitment();
+        let account = rpc
+            .call(
+                |client| async move { client.get_account_with_commitment(&mint, commitment).await },
+            )
+            .await
+            .map_err(|e| format!("RPC Error: {}", e))?
+            .value
+            .filter(|account| {
+                account.owner == SPL_TOKEN_PROGRAM_ID && account.data.len() == MINT_LEN
+            })
+            .ok_or_else(|| format!("{} is not a token mint", mint))?;
+
+        Ok(Currency::Token {
+            mint,
+            decimals: account.data[MINT_DECIMALS_OFFSET],
+        })
+    }
+
+    /// The currency of a stored invoice.
+    pub fn from_stored(code: &str, decimals: i16) -> Result<Currency, String> {
+        if code == SOL {
+            return Ok(Currency::Sol);
+        }
+        Ok(Currency::Token {
+            mint: Pubkey::from_str(code).map_err(|e| format!("Invalid stored mint: {}", e))?,
+            decimals: u8::try_from(decimals).map_err(|e| format!("Invalid decimals: {}", e))?,
+        })
+    }
+
+    pub fn code(&self) -> String {
+        match self {
+            Currency::Sol => SOL.to_string(),
+            Currency::Token { mint, .. } => mint.to_string(),
+        }
+    }
+
+    pub fn decimals(&self) -> u8 {
+        match self {
+            Currency::Sol => 9,
+            Currency::Token { decimals, .. } => *decimals,
+        }
+    }
+
+    /// Base units of a decimal amount.
+    pub fn base_units(&self, amount: f64) -> u64 {
+        (amount * 10f64.powi(i32::from(self.decimals()))).round() as u64
+    }
+
+    /// A base-unit amount in decimal notation.
+    pub fn format(&self, units: u64) -> String {
+        let decimals = usize::from(self.decimals());
+        let scale = 10u64.pow(u32::from(self.decimals()));
+        let fraction = format!("{:0width$}", units % scale, width = decimals);
+        let fraction = fraction.trim_end_matches('0');
+        if fraction.is_empty() {
+            (units / scale).to_string()
+        } else {
+            format!("{}.{}", units / scale, fraction)
+        }
+    }
+
+    /// Instructions paying `amount` base units from `payer` to `merchant`.
+    /// `reference` rides along as a read-only account of the transfer, so the
+    /// payment can be found by it on the cluster.
+    pub fn payment_instructions(
+        &self,
+        payer: &Pubkey,
+        merchant: &Pubkey,
+        amount: u64,
+        reference: &Pubkey,
+    ) -> Vec<Instruction> {
+        self.transfer_instructions(payer, payer, merchant, amount, reference)
+    }
+
+    /// Instructions paying `amount` base units from `owner` to `recipient`,
+    /// where `fee_payer` funds the token account of `recipient` if it is
+    /// missing. Both `fee_payer` and `owner` sign.
+    pub fn transfer_instructions(
+        &self,
+        fee_payer: &Pubkey,
+        owner: &Pubkey,
+        recipient: &Pubkey,
+        amount: u64,
+        reference: &Pubkey,
+    ) -> Vec<Instruction> {
+        let mut ixs = vec![ComputeBudgetInstruction::set_compute_unit_price(50_000)];
+        let mut transfer = match self {
+            Currency::Sol => system_instruction::transfer(owner, recipient, amount),
+            Currency::Token { mint, decimals } => {
+                ixs.push(create_token_account(fee_payer, recipient, mint));
+                token_instruction(
+                    TRANSFER_CHECKED,
+                    amount,
+                    *decimals,
+                    vec![
+                        AccountMeta::new(associated_token_address(owner, mint), false),
+                        AccountMeta::new_readonly(*mint, false),
+                        AccountMeta::new(associated_token_address(recipient, mint), false),
+                        AccountMeta::new_readonly(*owner, true),
+                    ],
+                )
+            }
+        };
+        transfer
+            .accounts
+            .push(AccountMeta::new_readonly(*reference, false));
+        ixs.push(transfer);
+        ixs
+    }
+
+    /// Instructions by which `delegate` pays `amount` base units from the
+    /// token account of `owner` to `merchant`, with `delegate` as fee payer.
+    /// `None` for SOL, which cannot be delegated.
+    pub fn delegated_payment_instructions(
+        &self,
+        delegate: &Pubkey,
+        owner: &Pubkey,
+        merchant: &Pubkey,
+        amount: u64,
+        reference: &Pubkey,
+    ) -> Option<Vec<Instruction>> {
+        let Currency::Token { mint, decimals } = self else {
+            return None;
+        };
+        let mut transfer = token_instruction(
+            TRANSFER_CHECKED,
+            amount,
+            *decimals,
+            vec![
+                AccountMeta::new(associated_token_address(owner, mint), false),
+                AccountMeta::new_readonly(*mint, false),
+                AccountMeta::new(associated_token_address(merchant, mint), false),
+                AccountMeta::new_readonly(*delegate, true),
+            ],
+        );
+        transfer
+            .accounts
+            .push(AccountMeta::new_readonly(*reference, false));
+        Some(vec![
+            ComputeBudgetInstruction::set_compute_unit_price(50_000),
+            create_token_account(delegate, merchant, mint),
+            transfer,
+        ])
+    }
+
+    /// Approves `delegate` to spend up to `amount` base units from the token
+    /// account of `owner`. `None` for SOL.
+    pub fn approve_instruction(
+        &self,
+        owner: &Pubkey,
+        delegate: &Pubkey,
+        amount: u64,
+    ) -> Option<Instruction> {
+        let Currency::Token { mint, decimals } = self else {
+            return None;
+        };
+        Some(token_instruction(
+            APPROVE_CHECKED,
+            amount,
+            *decimals,
+            vec![
+                AccountMeta::new(associated_token_address(owner, mint), false),
+                AccountMeta::new_readonly(*mint, false),
+                AccountMeta::new_readonly(*delegate, false),
+                AccountMeta::new_readonly(*owner, true),
+            ],
+        ))
+    }
+
+    /// The first successful transaction carrying `reference` that pays
+    /// `amount` to `merchant`, if one landed.
+    pub async fn find_payment(
+        &self,
+        rpc: &RpcPool,
+        merchant: &Pubkey,
+        amount: u64,
+        reference: &Pubkey,
+    ) -> Result<Option<Payment>, String> {
+        let mut signatures = rpc
+            .call(|client| async move { client.get_signatures_for_address(reference).await })
+            .await
+            .map_err(|e| format!("RPC Error: {}", e))?;
+        // Oldest first: the earliest matching transfer settled the invoice.
+        signatures.reverse();
+
+        for status in signatures.iter().filter(|status| status.err.is_none()) {
+            let signature = Signature::from_str(&status.signature)
+                .map_err(|e| format!("Invalid signature: {}", e))?;
+            let params = json!([
+                status.signature,
+                {
+                    "encoding": "base64",
+                    "commitment": "confirmed",
+                    "maxSupportedTransactionVersion": 0
+                }
+            ]);
+            let confirmed: Value = rpc
+                .call(|client| {
+                    let params = params.clone();
+                    async move { client.send(RpcRequest::GetTransaction, params).await }
+                })
+                .await
+                .map_err(|e| format!("RPC Error: {}", e))?;
+            if confirmed.is_null() || !confirmed["meta"]["err"].is_null() {
+                continue;
+            }
+            if let Some(payer) = self.paying_signer(&confirmed, merchant, amount, reference) {
+                return Ok(Some(Payment { signature, payer }));
+            }
+        }
+        Ok(None)
+    }
+
+    /// The signer of a `getTransaction` result that paid the invoice.
+    fn paying_signer(
+        &self,
+        confirmed: &Value,
+        merchant: &Pubkey,
+        amount: u64,
+        reference: &Pubkey,
+    ) -> Option<Pubkey> {
+        let encoded = confirmed["transaction"][0].as_str()?;
+        let transaction: VersionedTransaction =
+            bincode::deserialize(&BASE64.decode(encoded).ok()?).ok()?;
+        let message = &transaction.message;
+        let keys = message.static_account_keys();
+        let signers =
+            &keys[..usize::from(message.header().num_required_signatures).min(keys.len())];
+
+        message.instructions().iter().find_map(|instruction| {
+            let program = keys.get(usize::from(instruction.program_id_index))?;
+            let account = |index: usize| keys.get(usize::from(*instruction.accounts.get(index)?));
+            if !instruction
+                .accounts
+                .iter()
+                .any(|index| keys.get(usize::from(*index)) == Some(reference))
+            {
+                return None;
+            }
+            let payer = match self {
+                Currency::Sol if *program == system_program::id() => {
+                    let SystemInstruction::Transfer { lamports } =
+                        bincode::deserialize(&instruction.data).ok()?
+                    else {
+                        return None;
+                    };
+                    (lamports == amount && account(1)? == merchant).then_some(*account(0)?)?
+                }
+                Currency::Token { mint, decimals } if *program == SPL_TOKEN_PROGRAM_ID => {
+                    let expected = token_instruction(TRANSFER_CHECKED, amount, *decimals, vec![]);
+                    (instruction.data == expected.data
+                        && account(1)? == mint
+                        && *account(2)? == associated_token_address(merchant, mint))
+                    .then_some(*account(3)?)?
+                }
+                _ => return None,
+            };
+            signers.contains(&payer).then_some(payer)
+        })
+    }
+}
+
+/// A checked SPL token instruction: `tag`, amount and decimals.
+fn token_instruction(
+    tag: u8,
+    amount: u64,
+    decimals: u8,
+    accounts: Vec<AccountMeta>,
+) -> Instruction {
+    let mut data = vec![tag];
+    data.extend_from_slice(&amount.to_le_bytes());
+    data.push(decimals);
+    Instruction {
+        program_id: SPL_TOKEN_PROGRAM_ID,
+        accounts,
+        data,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_base_units() {
+        let usdc = Currency::Token {
+            mint: Pubkey::new_unique(),
+            decimals: 6,
+        };
+
+        assert_eq!(Currency::Sol.format(1_500_000_000), "1.5");
+        assert_eq!(Currency::Sol.format(2_000_000_000), "2");
+        assert_eq!(usdc.format(12_340_000), "12.34");
+        assert_eq!(usdc.base_units(12.34), 12_340_000);
+    }
+
+    #[test]
+    fn payments_carry_the_reference() {
+        let payer = Pubkey::new_unique();
+        let merchant = Pubkey::new_unique();
+        let reference = Pubkey::new_unique();
+
+        let ixs = Currency::Sol.payment_instructions(&payer, &merchant, 5, &reference);
+
+        let transfer = ixs.last().unwrap();
+        assert_eq!(transfer.program_id, system_program::id());
+        assert_eq!(
+            transfer.accounts[2],
+            AccountMeta::new_readonly(reference, false)
+        );
+    }
+}
@@ -1,5 +1,23 @@
+pub mod authentication;
+pub mod blockhash_cache;
+pub mod claims;
 pub mod configuration;
+pub mod domain;
+pub mod escrows;
+pub mod governance;
 pub mod handlers;
+pub mod holdings;
+pub mod invoices;
+pub mod metadata_cache;
 pub mod models;
+pub mod nft_minter;
+pub mod nonce_pool;
+pub mod raffles;
+pub mod rate_limit;
+pub mod rpc_pool;
+pub mod signer_vault;
+pub mod sponsorship;
 pub mod startup;
+pub mod subscriptions;
+pub mod swaps;
 pub mod telemetry;
@@ -0,0 +1,203 @@
+use axum::body::Bytes;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+/// Rendered action metadata of one blink revision.
+#[derive(Debug, Clone)]
+pub struct CachedMetadata {
+    pub blink_id: Uuid,
+    pub revision: i32,
+    /// Strong validator: the revision plus a digest of the rendered body.
+    pub etag: String,
+    pub body: Bytes,
+    cached_at: Instant,
+}
+
+/// In-process cache of rendered `ActionMetadata`, so unfurl spikes are served
+/// without touching Postgres.
+///
+/// Entries are keyed by blink id and remember the revision they were rendered
+/// from. The identifiers used in URLs (UUID or current slug) are aliases of
+/// the blink id. Edits made through this instance invalidate the blink
+/// immediately; `ttl` bounds how long other instances serve a stale revision.
+pub struct MetadataCache {
+    ttl: Duration,
+    max_entries: usize,
+    cache_control: String,
+    state: Mutex<CacheState>,
+}
+
+#[derive(Default)]
+struct CacheState {
+    entries: HashMap<Uuid, CachedMetadata>,
+    aliases: HashMap<String, Uuid>,
+}
+
+impl MetadataCache {
+    /// `max_age` is how long clients and CDNs may reuse a response before
+    /// revalidating it with `If-None-Match`.
+    pub fn new(ttl: Duration, max_entries: usize, max_age: Duration) -> Self {
+        Self {
+            ttl,
+            max_entries,
+            cache_control: format!("public, max-age={}", max_age.as_secs()),
+            state: Mutex::new(CacheState::default()),
+        }
+    }
+
+    /// The `Cache-Control` value sent with action metadata.
+    pub fn cache_control(&self) -> &str {
+        &self.cache_control
+    }
+
+    /// The cached metadata for a URL identifier, unless missing or expired.
+    pub fn get(&self, key: &str) -> Option<CachedMetadata> {
+        let state = self.state.lock().unwrap();
+        let blink_id = state.aliases.get(key)?;
+        state
+            .entries
+            .get(blink_id)
+            .filter(|entry| entry.cached_at.elapsed() < self.ttl)
+            .cloned()
+    }
+
+    pub fn insert(&self, key: &str, blink_id: Uuid, revision: i32, body: Bytes) -> CachedMetadata {
+        let entry = CachedMetadata {
+            blink_id,
+            revision,
+            etag: format!("\"{}-{}\"", revision, &digest(&body)[..16]),
+            body,
+            cached_at: Instant::now(),
+        };
+        if self.max_entries == 0 {
+            return entry;
+        }
+
+        let mut state = self.state.lock().unwrap();
+        if !state.entries.contains_key(&blink_id) && state.entries.len() >= self.max_entries {
+            self.evict(&mut state);
+        }
+        state.aliases.insert(key.to_string(), blink_id);
+        state.entries.insert(blink_id, entry.clone());
+        entry
+    }
+
+    /// Drops a blink and every identifier pointing to it. Called whenever its
+    /// metadata or slug changes.
+    pub fn invalidate(&self, blink_id: Uuid) {
+        let mut state = self.state.lock().unwrap();
+        state.entries.remove(&blink_id);
+        state.aliases.retain(|_, id| *id != blink_id);
+    }
+
+    /// Makes room for one entry: expired entries go first, then the oldest.
+    fn evict(&self, state: &mut CacheState) {
+        state
+            .entries
+            .retain(|_, entry| entry.cached_at.elapsed() < self.ttl);
+
+        if state.entries.len() >= self.max_entries
+            && let Some(oldest) = state
+                .entries
+                .values()
+                .min_by_key(|entry| entry.cached_at)
+                .map(|entry| entry.blink_id)
+        {
+            state.entries.remove(&oldest);
+        }
+
+        let CacheState { entries, aliases } = state;
+        aliases.retain(|_, id| entries.contains_key(id));
+    }
+}
+
+/// Whether an `If-None-Match` header value matches `etag`. Uses the weak
+/// comparison RFC 9110 prescribes for `If-None-Match`.
+pub fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    let etag = etag.trim_start_matches("W/");
+    if_none_match
+        .split(',')
+        .map(str::trim)
+        .any(|candidate| candidate == "*" || candidate.trim_start_matches("W/") == etag)
+}
+
+fn digest(body: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cache() -> MetadataCache {
+        MetadataCache::new(Duration::from_secs(60), 2, Duration::from_secs(30))
+    }
+
+    #[test]
+    fn entries_are_found_by_every_alias() {
+        let cache = cache();
+        let id = Uuid::new_v4();
+        cache.insert(&id.to_string(), id, 1, Bytes::from_static(b"{}"));
+        cache.insert("coffee", id, 1, Bytes::from_static(b"{}"));
+
+        assert_eq!(cache.get("coffee").unwrap().blink_id, id);
+        assert_eq!(cache.get(&id.to_string()).unwrap().revision, 1);
+    }
+
+    #[test]
+    fn invalidate_drops_entry_and_aliases() {
+        let cache = cache();
+        let id = Uuid::new_v4();
+        cache.insert("coffee", id, 1, Bytes::from_static(b"{}"));
+
+        cache.invalidate(id);
+
+        assert!(cache.get("coffee").is_none());
+    }
+
+    #[test]
+    fn expired_entries_are_not_served() {
+        let cache = MetadataCache::new(Duration::ZERO, 2, Duration::ZERO);
+        let id = Uuid::new_v4();
+        cache.insert("coffee", id, 1, Bytes::from_static(b"{}"));
+
+        assert!(cache.get("coffee").is_none());
+    }
+
+    #[test]
+    fn full_cache_evicts_the_oldest_entry() {
+        let cache = cache();
+        let ids: Vec<Uuid> = (0..3).map(|_| Uuid::new_v4()).collect();
+        for (i, id) in ids.iter().enumerate() {
+            cache.insert(&i.to_string(), *id, 1, Bytes::from_static(b"{}"));
+        }
+
+        assert!(cache.get("0").is_none());
+        assert!(cache.get("1").is_some());
+        assert!(cache.get("2").is_some());
+    }
+
+    #[test]
+    fn etag_changes_with_revision_and_body() {
+        let cache = cache();
+        let id = Uuid::new_v4();
+        let first = cache.insert("a", id, 1, Bytes::from_static(b"{\"title\":\"a\"}"));
+        let second = cache.insert("a", id, 2, Bytes::from_static(b"{\"title\":\"a\"}"));
+        let third = cache.insert("a", id, 2, Bytes::from_static(b"{\"title\":\"b\"}"));
+
+        assert_ne!(first.etag, second.etag);
+        assert_ne!(second.etag, third.etag);
+        assert!(first.etag.starts_with("\"1-"));
+    }
+
+    #[test]
+    fn if_none_match_accepts_lists_weak_tags_and_wildcards() {
+        assert!(etag_matches("\"1-abc\"", "\"1-abc\""));
+        assert!(etag_matches("\"0-xyz\", W/\"1-abc\"", "\"1-abc\""));
+        assert!(etag_matches("*", "\"1-abc\""));
+        assert!(!etag_matches("\"2-abc\"", "\"1-abc\""));
+    }
+}
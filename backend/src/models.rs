@@ -1,4 +1,4 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::prelude::FromRow;
 use sqlx::types::Json;
@@ -11,6 +11,15 @@ pub enum BlinkType {
     Donation,
     Payment,
     Vote,
+    Mint,
+    Stake,
+    Claim,
+    Subscription,
+    Escrow,
+    Raffle,
+    Message,
+    Governance,
+    Swap,
 }
 
 #[derive(Debug, FromRow, Serialize)]
@@ -24,6 +33,17 @@ pub struct Blink {
     pub wallet_address: String,
     pub r#type: BlinkType,
     pub config: Json<serde_json::Value>,
+    pub slug: Option<String>,
+    pub owner: Option<String>,
+    pub revision: i32,
+}
+
+impl Blink {
+    /// The identifier used in public URLs: the slug when one is set,
+    /// otherwise the UUID.
+    pub fn public_id(&self) -> String {
+        self.slug.clone().unwrap_or_else(|| self.id.to_string())
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -35,12 +55,167 @@ pub struct CreateBlinkRequest {
     pub wallet_address: String,
     pub r#type: BlinkType,
     pub config: serde_json::Value,
+    pub slug: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
 pub struct CreateBlinkResponse {
     pub id: Uuid,
     pub action_url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub slug: Option<String>,
+}
+
+/// Replaces a blink's metadata. The wallet address is not revisioned and
+/// cannot be changed.
+#[derive(Debug, Deserialize)]
+pub struct UpdateBlinkRequest {
+    pub title: String,
+    pub icon_url: String,
+    pub description: String,
+    pub label: String,
+    pub r#type: BlinkType,
+    pub config: serde_json::Value,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BlinkRevision {
+    pub blink_id: Uuid,
+    pub revision: i32,
+    pub created_at: DateTime<Utc>,
+    pub author: Option<String>,
+    pub author_key_id: Option<Uuid>,
+    pub rolled_back_from: Option<i32>,
+    pub title: String,
+    pub icon_url: String,
+    pub description: String,
+    pub label: String,
+    pub r#type: BlinkType,
+    pub config: Json<serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RevisionDiffQuery {
+    pub from: i32,
+    pub to: i32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RevisionDiff {
+    pub from: i32,
+    pub to: i32,
+    pub changes: Vec<FieldChange>,
+}
+
+/// A field that differs between two revisions. Config keys are reported
+/// individually as `config.<key>`; a missing value is `null`.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct FieldChange {
+    pub field: String,
+    pub from: serde_json::Value,
+    pub to: serde_json::Value,
+}
+
+/// A transaction served by the action API, kept to answer what a blink
+/// looked like when a payer signed.
+#[derive(Debug, Serialize)]
+pub struct ActionBuild {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub revision: i32,
+    pub account: String,
+    pub transaction: String,
+    pub message: Option<String>,
+    /// Durable nonce account the transaction was built on, if any.
+    pub nonce_account: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateSlugRequest {
+    pub slug: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UpdateSlugResponse {
+    pub id: Uuid,
+    pub slug: String,
+    pub action_url: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ApiKeyScope {
+    Create,
+    Read,
+    Update,
+    Analytics,
+}
+
+impl ApiKeyScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApiKeyScope::Create => "create",
+            ApiKeyScope::Read => "read",
+            ApiKeyScope::Update => "update",
+            ApiKeyScope::Analytics => "analytics",
+        }
+    }
+}
+
+impl TryFrom<&str> for ApiKeyScope {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "create" => Ok(Self::Create),
+            "read" => Ok(Self::Read),
+            "update" => Ok(Self::Update),
+            "analytics" => Ok(Self::Analytics),
+            other => Err(format!("{} is not a valid API key scope", other)),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateApiKeyRequest {
+    pub name: String,
+    pub owner: String,
+    pub scopes: Vec<ApiKeyScope>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RotateApiKeyRequest {
+    /// Seconds the replaced key keeps working, so deployments can switch over.
+    #[serde(default)]
+    pub grace_period_secs: u32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListApiKeysQuery {
+    pub owner: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ApiKeyResponse {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub name: String,
+    pub owner: String,
+    pub prefix: String,
+    pub scopes: Vec<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+/// Returned once, when a key is created or rotated. The plaintext key cannot
+/// be recovered afterwards.
+#[derive(Debug, Serialize)]
+pub struct CreatedApiKeyResponse {
+    pub key: String,
+    #[serde(flatten)]
+    pub api_key: ApiKeyResponse,
 }
 
 #[derive(Debug, Serialize)]
@@ -52,6 +227,8 @@ pub struct ActionMetadata {
     pub links: Option<ActionLinks>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub disabled: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<ActionError>,
 }
 
 #[derive(Debug, Serialize)]
@@ -72,6 +249,21 @@ pub struct ActionParameter {
     pub name: String,
     pub label: Option<String>,
     pub required: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub r#type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub options: Option<Vec<ActionParameterOption>>,
+    /// Fewest and most values a `checkbox` parameter takes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ActionParameterOption {
+    pub label: String,
+    pub value: String,
 }
 
 #[derive(Debug, Deserialize)]
@@ -79,16 +271,427 @@ pub struct ActionPostRequest {
     pub account: String,
 }
 
+/// Clients may identify the wallet viewing an action, so gated blinks can
+/// tell it up front whether it qualifies.
+#[derive(Debug, Deserialize)]
+pub struct ActionGetQuery {
+    pub account: Option<String>,
+    /// Wallet of a subscriber whose renewal link is shown.
+    pub subscriber: Option<String>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct ActionQueryParams {
     pub amount: Option<String>,
     pub selection: Option<String>,
+    /// Renewal links only build transactions for this wallet.
+    pub subscriber: Option<String>,
+    /// Linked action of an escrow blink: `deposit`, `release` or `refund`.
+    pub step: Option<String>,
+    /// Number of raffle tickets to buy.
+    pub tickets: Option<String>,
+    /// Text posted to a message blink.
+    pub message: Option<String>,
+    /// Vote cast by a governance blink: `yes`, `no` or `abstain`.
+    pub vote: Option<String>,
+    /// Slippage in percent entered for a swap blink.
+    pub slippage: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
 pub struct ActionPostResponse {
     pub transaction: String,
     pub message: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub links: Option<ActionPostLinks>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ActionPostLinks {
+    pub next: NextActionLink,
+}
+
+/// Callback the client POSTs the confirmed transaction signature to.
+#[derive(Debug, Serialize)]
+pub struct NextActionLink {
+    pub r#type: String,
+    pub href: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NextActionPostRequest {
+    pub account: String,
+    pub signature: String,
+}
+
+/// Final action shown to the user once a chain of actions is done.
+#[derive(Debug, Serialize)]
+pub struct CompletedAction {
+    pub r#type: String,
+    pub icon: String,
+    pub title: String,
+    pub description: String,
+    pub label: String,
+}
+
+/// Spending of a sponsored blink. Pending transactions are charged until
+/// they are found to have expired.
+#[derive(Debug, Serialize)]
+pub struct SponsorshipUsage {
+    pub blink_id: Uuid,
+    pub budget_lamports: u64,
+    pub charged_lamports: i64,
+    pub pending_transactions: i64,
+    pub landed_transactions: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UploadAllowlistRequest {
+    pub entries: Vec<AllowlistEntry>,
+}
+
+/// A wallet and the SOL it may claim.
+#[derive(Debug, Deserialize)]
+pub struct AllowlistEntry {
+    pub wallet: String,
+    pub amount: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AllowlistSummary {
+    pub blink_id: Uuid,
+    pub merkle_root: String,
+    pub wallets: usize,
+    pub total_lamports: u64,
+}
+
+/// An allowlisted wallet with the proof of its allocation against the
+/// blink's Merkle root.
+#[derive(Debug, Serialize)]
+pub struct ClaimAllocation {
+    pub blink_id: Uuid,
+    pub merkle_root: String,
+    pub wallet: String,
+    pub lamports: i64,
+    pub proof: Vec<String>,
+    pub claimed: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "invoice_status", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum InvoiceStatus {
+    Open,
+    Paid,
+    Cancelled,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateInvoiceRequest {
+    pub number: String,
+    /// In units of `currency`.
+    pub amount: f64,
+    /// `SOL` or the mint of an SPL token.
+    pub currency: String,
+    pub due_date: NaiveDate,
+    pub note: Option<String>,
+    /// Wallet the invoice is paid to.
+    pub wallet_address: String,
+    pub icon_url: String,
+    pub title: Option<String>,
+}
+
+/// An invoice with the wallet it is paid to.
+#[derive(Debug, Clone, FromRow)]
+pub struct InvoiceRecord {
+    pub id: Uuid,
+    pub blink_id: Uuid,
+    pub number: String,
+    pub currency: String,
+    pub decimals: i16,
+    pub amount: i64,
+    pub reference: String,
+    pub due_date: NaiveDate,
+    pub note: Option<String>,
+    pub status: InvoiceStatus,
+    pub signature: Option<String>,
+    pub payer: Option<String>,
+    pub paid_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub wallet_address: String,
+}
+
+/// Status reported by the invoice API: open invoices past their due date
+/// are overdue.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InvoiceState {
+    Open,
+    Overdue,
+    Paid,
+    Cancelled,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Invoice {
+    pub id: Uuid,
+    pub blink_id: Uuid,
+    pub action_url: String,
+    pub number: String,
+    /// In units of `currency`, as a decimal string.
+    pub amount: String,
+    pub currency: String,
+    pub due_date: NaiveDate,
+    pub note: Option<String>,
+    pub status: InvoiceState,
+    pub reference: String,
+    pub wallet_address: String,
+    pub signature: Option<String>,
+    pub payer: Option<String>,
+    pub paid_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "subscription_status", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum SubscriptionStatus {
+    Active,
+    Cancelled,
+}
+
+/// Status reported to creators: active subscriptions whose paid period has
+/// ended are lapsed.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SubscriberState {
+    Active,
+    Lapsed,
+    Cancelled,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Subscriber {
+    pub wallet: String,
+    pub status: SubscriberState,
+    /// End of the paid period, when the subscription renews.
+    pub renews_at: DateTime<Utc>,
+    /// Renewals are charged from an allowance the subscriber approved.
+    pub auto_renew: bool,
+    /// Action URL the subscriber renews with.
+    pub renewal_url: String,
+    pub subscribed_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "escrow_status", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum EscrowStatus {
+    Open,
+    Funded,
+    Released,
+    Refunded,
+}
+
+/// A linked action of an escrow blink.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "escrow_step", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum EscrowStep {
+    Deposit,
+    Release,
+    Refund,
+}
+
+impl EscrowStep {
+    pub fn parse(step: &str) -> Result<EscrowStep, String> {
+        match step {
+            "deposit" => Ok(EscrowStep::Deposit),
+            "release" => Ok(EscrowStep::Release),
+            "refund" => Ok(EscrowStep::Refund),
+            _ => Err(format!("Unknown escrow step: {}", step)),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EscrowStep::Deposit => "deposit",
+            EscrowStep::Release => "release",
+            EscrowStep::Refund => "refund",
+        }
+    }
+}
+
+/// The deal of an escrow blink as reported to its creator.
+#[derive(Debug, Serialize)]
+pub struct Escrow {
+    pub blink_id: Uuid,
+    pub status: EscrowStatus,
+    /// Address of the vault key holding the deposit.
+    pub escrow: String,
+    /// In units of `currency`, as a decimal string.
+    pub amount: String,
+    pub currency: String,
+    pub buyer: Option<String>,
+    pub seller: String,
+    /// Transfer built but not yet found on the cluster.
+    pub pending: Option<EscrowStep>,
+    pub funded_at: Option<DateTime<Utc>>,
+    /// When either party may take the `on_timeout` step.
+    pub timeout_at: Option<DateTime<Utc>>,
+    pub on_timeout: EscrowStep,
+    pub deposit_signature: Option<String>,
+    pub settlement_signature: Option<String>,
+    pub closed_at: Option<DateTime<Utc>>,
+}
+
+/// The draw of a raffle blink. Anyone can check it: the winners follow from
+/// `seed`, the blockhash of slot `seed_slot`, and the ticket list hashing to
+/// `ticket_hash`.
+#[derive(Debug, Serialize)]
+pub struct RaffleDraw {
+    pub blink_id: Uuid,
+    pub closes_at: DateTime<Utc>,
+    /// Slot committed when the first ticket was sold.
+    pub draw_slot: u64,
+    /// First produced slot at or after `draw_slot`.
+    pub seed_slot: u64,
+    pub seed: String,
+    pub ticket_hash: String,
+    pub tickets: Vec<RaffleTicket>,
+    /// In the order they were drawn.
+    pub winners: Vec<RaffleTicket>,
+    pub drawn_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RaffleTicket {
+    pub number: u32,
+    pub wallet: String,
+    pub signature: String,
+}
+
+/// Tally of a Vote blink. Weights are raw token amounts as strings and are
+/// `null` for unweighted polls.
+#[derive(Debug, Serialize)]
+pub struct VoteResults {
+    pub blink_id: Uuid,
+    /// `single`, `multi` or `ranked`.
+    pub ballot: String,
+    pub weighting: Option<VoteWeightingSummary>,
+    /// Votes per option; first preferences for ranked polls.
+    pub options: Vec<VoteOptionResult>,
+    pub total_votes: i64,
+    pub total_weight: Option<String>,
+    /// Instant-runoff rounds of a ranked poll.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rounds: Option<Vec<VoteRound>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub winner: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct VoteRound {
+    pub options: Vec<VoteOptionResult>,
+    pub eliminated: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct VoteWeightingSummary {
+    pub mint: String,
+    /// `null` until the snapshot is taken.
+    pub snapshot_slot: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct VoteOptionResult {
+    pub option: String,
+    pub label: String,
+    pub votes: i64,
+    pub weight: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ActionError {
+    pub message: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RpcEndpointMetrics {
+    pub endpoint: String,
+    pub weight: u32,
+    pub healthy: bool,
+    pub circuit_open: bool,
+    pub slot: Option<u64>,
+    pub requests: u64,
+    pub errors: u64,
+    pub average_latency_ms: Option<f64>,
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RegisterNonceAccountRequest {
+    pub pubkey: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct NonceAccount {
+    pub pubkey: String,
+    pub created_at: DateTime<Utc>,
+    pub leased_at: Option<DateTime<Utc>>,
+    pub leased_until: Option<DateTime<Utc>>,
+    pub leased_nonce: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SignerSummary {
+    pub name: String,
+    pub pubkey: String,
+    pub owners: Option<Vec<String>>,
+    pub programs: Vec<SignerProgramRule>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SignerProgramRule {
+    pub program_id: String,
+    pub data_prefixes: Option<Vec<Vec<u8>>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SignerAuditQuery {
+    pub signer: Option<String>,
+    pub blink_id: Option<Uuid>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SignerAuditEntry {
+    pub signer: String,
+    pub pubkey: String,
+    pub blink_id: Uuid,
+    pub payer: String,
+    pub signature: String,
+    pub programs: Vec<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Page of a message feed, newest first. `before` continues from the
+/// oldest message of the previous page.
+#[derive(Debug, Deserialize)]
+pub struct MessageFeedQuery {
+    pub before: Option<DateTime<Utc>>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MessageEntry {
+    pub signature: String,
+    pub author: String,
+    pub body: String,
+    /// In SOL, when a tip was sent along.
+    pub tip: Option<String>,
+    pub slot: i64,
+    pub created_at: DateTime<Utc>,
 }
 
 #[derive(Debug, Serialize)]
@@ -97,6 +700,7 @@ pub struct ActionsJson {
 }
 
 #[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct ActionRule {
     pub path_pattern: String,
     pub api_path: String,
@@ -0,0 +1,506 @@
+use crate::domain::MintConfig;
+use crate::holdings::{MAX_ACCOUNTS_PER_REQUEST, METADATA_PROGRAM_ID, SPL_TOKEN_PROGRAM_ID};
+use crate::rpc_pool::RpcPool;
+use axum::http::StatusCode;
+use solana_sdk::{
+    hash::Hash,
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    rent::Rent,
+    signature::{Keypair, Signer},
+    system_instruction, system_program,
+    transaction::Transaction,
+};
+use sqlx::PgPool;
+use std::str::FromStr;
+use std::time::Duration;
+use uuid::Uuid;
+
+const ASSOCIATED_TOKEN_PROGRAM_ID: Pubkey =
+    solana_sdk::pubkey!("ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL");
+/// Size of an SPL token mint account.
+const MINT_LEN: usize = 82;
+
+// Instruction discriminators.
+const INITIALIZE_MINT2: u8 = 20;
+const MINT_TO: u8 = 7;
+const CREATE_IDEMPOTENT: u8 = 1;
+const CREATE_METADATA_ACCOUNT_V3: u8 = 33;
+const CREATE_MASTER_EDITION_V3: u8 = 17;
+const VERIFY_SIZED_COLLECTION_ITEM: u8 = 30;
+
+/// Mints one-of-one Metaplex NFTs into a collection whose update authority
+/// is held by the backend.
+///
+/// Every transaction creates a fresh mint. The backend signs for the new
+/// mint account, and the vault key `signer` as mint, update and collection
+/// authority; the buyer signs as fee payer. Serial numbers are reserved when a transaction is
+/// built, so the supply cap holds even while transactions are in flight.
+pub struct NftMinter {
+    signer: String,
+    authority: Pubkey,
+    reservation: Duration,
+}
+
+/// A serial number held for one mint transaction.
+pub struct Reservation {
+    pub mint: Keypair,
+    pub number: u32,
+}
+
+impl NftMinter {
+    pub fn new(signer: String, authority: Pubkey, reservation: Duration) -> Self {
+        Self {
+            signer,
+            authority,
+            reservation,
+        }
+    }
+
+    /// Name of the vault key holding the collection authority.
+    pub fn signer(&self) -> &str {
+        &self.signer
+    }
+
+    /// Reserves the lowest free serial number of a blink for `buyer`.
+    /// Sold-out blinks and wallets at their limit get a `403`.
+    pub async fn reserve(
+        &self,
+        db: &PgPool,
+        rpc: &RpcPool,
+        blink_id: Uuid,
+        config: &MintConfig,
+        buyer: &Pubkey,
+    ) -> Result<Reservation, (StatusCode, String)> {
+        let mut transaction = db
+            .begin()
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        // Serializes reservations of one blink.
+        sqlx::query!("SELECT id FROM blinks WHERE id = $1 FOR UPDATE", blink_id)
+            .fetch_one(&mut *transaction)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+        self.settle_expired(&mut transaction, rpc, blink_id).await?;
+
+        if let Some(limit) = config.per_wallet_limit {
+            let minted = sqlx::query_scalar!(
+                r#"
+                SELECT COUNT(*) AS "count!"
+                FROM nft_mints
+                WHERE blink_id = $1 AND buyer = $2
+                "#,
+                blink_id,
+                buyer.to_string()
+            )
+            .fetch_one(&mut *transaction)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+            if minted >= i64::from(limit) {
+                return Err((
+                    StatusCode::FORBIDDEN,
+                    format!("This wallet has reached the limit of {} mints", limit),
+                ));
+            }
+        }
+
+        let number = sqlx::query_scalar!(
+            r#"
+            SELECT n AS "number!"
+            FROM generate_series(1, $2::INT) AS n
+            WHERE NOT EXISTS (
+                SELECT 1 FROM nft_mints WHERE blink_id = $1 AND number = n
+            )
+            ORDER BY n
+            LIMIT 1
+            "#,
+            blink_id,
+            config.supply as i32
+        )
+        .fetch_optional(&mut *transaction)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or((StatusCode::FORBIDDEN, "Sold out".to_string()))?;
+
+        let mint = Keypair::new();
+        sqlx::query!(
+            r#"
+            INSERT INTO nft_mints (mint, blink_id, number, buyer, reserved_until)
+            VALUES ($1, $2, $3, $4, now() + make_interval(secs => $5))
+            "#,
+            mint.pubkey().to_string(),
+            blink_id,
+            number,
+            buyer.to_string(),
+            self.reservation.as_secs_f64()
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+        transaction
+            .commit()
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+        Ok(Reservation {
+            mint,
+            number: number as u32,
+        })
+    }
+
+    /// Settles expired reservations of a blink: those whose mint account
+    /// exists landed and are kept, the rest free their serial number.
+    async fn settle_expired(
+        &self,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+        rpc: &RpcPool,
+        blink_id: Uuid,
+    ) -> Result<(), (StatusCode, String)> {
+        let expired = sqlx::query_scalar!(
+            r#"
+            SELECT mint
+            FROM nft_mints
+            WHERE blink_id = $1 AND reserved_until < now()
+            "#,
+            blink_id
+        )
+        .fetch_all(&mut **transaction)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        if expired.is_empty() {
+            return Ok(());
+        }
+
+        let mints = expired
+            .iter()
+            .map(|mint| Pubkey::from_str(mint))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| {
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("Invalid stored mint: {}", e),
+                )
+            })?;
+        let commitment = rpc.commitment();
+        let mut landed = Vec::new();
+        for chunk in mints.chunks(MAX_ACCOUNTS_PER_REQUEST) {
+            let accounts = rpc
+                .call(|client| async move {
+                    client
+                        .get_multiple_accounts_with_commitment(chunk, commitment)
+                        .await
+                })
+                .await
+                .map_err(|e| {
+                    (
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        format!("RPC Error: {}", e),
+                    )
+                })?
+                .value;
+            landed.extend(
+                chunk
+                    .iter()
+                    .zip(accounts)
+                    .filter(|(_, account)| account.is_some())
+                    .map(|(mint, _)| mint.to_string()),
+            );
+        }
+
+        sqlx::query!(
+            r#"
+            UPDATE nft_mints
+            SET reserved_until = NULL
+            WHERE blink_id = $1 AND mint = ANY($2)
+            "#,
+            blink_id,
+            &landed
+        )
+        .execute(&mut **transaction)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        sqlx::query!(
+            r#"
+            DELETE FROM nft_mints
+            WHERE blink_id = $1 AND reserved_until < now()
+            "#,
+            blink_id
+        )
+        .execute(&mut **transaction)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+        Ok(())
+    }
+
+    /// Instructions minting the reserved NFT to `buyer`, who pays the rent
+    /// and `price_lamports` to `destination`.
+    pub fn instructions(
+        &self,
+        config: &MintConfig,
+        title: &str,
+        reservation: &Reservation,
+        buyer: &Pubkey,
+        destination: &Pubkey,
+        price_lamports: u64,
+    ) -> Vec<Instruction> {
+        let authority = self.authority;
+        let mint = reservation.mint.pubkey();
+        let token_account = associated_token_address(buyer, &mint);
+        let metadata = metadata_address(&mint);
+        let edition = edition_address(&mint);
+
+        let mut instructions = Vec::new();
+        if price_lamports > 0 {
+            instructions.push(system_instruction::transfer(
+                buyer,
+                destination,
+                price_lamports,
+            ));
+        }
+        instructions.extend([
+            system_instruction::create_account(
+                buyer,
+                &mint,
+                Rent::default().minimum_balance(MINT_LEN),
+                MINT_LEN as u64,
+                &SPL_TOKEN_PROGRAM_ID,
+            ),
+            initialize_mint(&mint, &authority),
+            create_token_account(buyer, buyer, &mint),
+            mint_one(&mint, &token_account, &authority),
+            Instruction {
+                program_id: METADATA_PROGRAM_ID,
+                accounts: vec![
+                    AccountMeta::new(metadata, false),
+                    AccountMeta::new_readonly(mint, false),
+                    AccountMeta::new_readonly(authority, true),
+                    AccountMeta::new(*buyer, true),
+                    AccountMeta::new_readonly(authority, true),
+                    AccountMeta::new_readonly(system_program::id(), false),
+                ],
+                data: metadata_data(
+                    &config.name(title, reservation.number),
+                    &config.symbol,
+                    &config.uri(reservation.number),
+                    &config.collection,
+                ),
+            },
+            Instruction {
+                program_id: METADATA_PROGRAM_ID,
+                accounts: vec![
+                    AccountMeta::new(edition, false),
+                    AccountMeta::new(mint, false),
+                    AccountMeta::new_readonly(authority, true),
+                    AccountMeta::new_readonly(authority, true),
+                    AccountMeta::new(*buyer, true),
+                    AccountMeta::new(metadata, false),
+                    AccountMeta::new_readonly(SPL_TOKEN_PROGRAM_ID, false),
+                    AccountMeta::new_readonly(system_program::id(), false),
+                ],
+                // Max supply: Some(0), no prints.
+                data: [&[CREATE_MASTER_EDITION_V3, 1][..], &0u64.to_le_bytes()].concat(),
+            },
+            Instruction {
+                program_id: METADATA_PROGRAM_ID,
+                accounts: vec![
+                    AccountMeta::new(metadata, false),
+                    AccountMeta::new_readonly(authority, true),
+                    AccountMeta::new(*buyer, true),
+                    AccountMeta::new_readonly(config.collection, false),
+                    AccountMeta::new(metadata_address(&config.collection), false),
+                    AccountMeta::new_readonly(edition_address(&config.collection), false),
+                ],
+                data: vec![VERIFY_SIZED_COLLECTION_ITEM],
+            },
+        ]);
+        instructions
+    }
+
+    /// Adds the signature of the new mint account.
+    pub fn sign(
+        &self,
+        transaction: &mut Transaction,
+        reservation: &Reservation,
+        blockhash: Hash,
+    ) -> Result<(), (StatusCode, String)> {
+        transaction
+            .try_partial_sign(&[&reservation.mint], blockhash)
+            .map_err(|e| {
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("Failed to sign with mint account: {}", e),
+                )
+            })
+    }
+}
+
+fn initialize_mint(mint: &Pubkey, authority: &Pubkey) -> Instruction {
+    // Zero decimals, freeze authority set.
+    let mut data = vec![INITIALIZE_MINT2, 0];
+    data.extend_from_slice(authority.as_ref());
+    data.push(1);
+    data.extend_from_slice(authority.as_ref());
+
+    Instruction {
+        program_id: SPL_TOKEN_PROGRAM_ID,
+        accounts: vec![AccountMeta::new(*mint, false)],
+        data,
+    }
+}
+
+fn mint_one(mint: &Pubkey, token_account: &Pubkey, authority: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: SPL_TOKEN_PROGRAM_ID,
+        accounts: vec![
+            AccountMeta::new(*mint, false),
+            AccountMeta::new(*token_account, false),
+            AccountMeta::new_readonly(*authority, true),
+        ],
+        data: [&[MINT_TO][..], &1u64.to_le_bytes()].concat(),
+    }
+}
+
+/// Borsh-encoded `CreateMetadataAccountV3` arguments: no creators, royalties
+/// or uses, mutable, with an unverified collection that the
+/// `VerifySizedCollectionItem` instruction then verifies.
+fn metadata_data(name: &str, symbol: &str, uri: &str, collection: &Pubkey) -> Vec<u8> {
+    let mut data = vec![CREATE_METADATA_ACCOUNT_V3];
+    for field in [name, symbol, uri] {
+        data.extend_from_slice(&(field.len() as u32).to_le_bytes());
+        data.extend_from_slice(field.as_bytes());
+    }
+    // Seller fee basis points, no creators.
+    data.extend_from_slice(&[0, 0, 0]);
+    // Some(Collection { verified: false, key })
+    data.extend_from_slice(&[1, 0]);
+    data.extend_from_slice(collection.as_ref());
+    // No uses, mutable, no collection details.
+    data.extend_from_slice(&[0, 1, 0]);
+    data
+}
+
+/// Creates the associated token account of `owner` for `mint` unless it
+/// exists, funded by `payer`.
+pub(crate) fn create_token_account(payer: &Pubkey, owner: &Pubkey, mint: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: ASSOCIATED_TOKEN_PROGRAM_ID,
+        accounts: vec![
+            AccountMeta::new(*payer, true),
+            AccountMeta::new(associated_token_address(owner, mint), false),
+            AccountMeta::new_readonly(*owner, false),
+            AccountMeta::new_readonly(*mint, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(SPL_TOKEN_PROGRAM_ID, false),
+        ],
+        data: vec![CREATE_IDEMPOTENT],
+    }
+}
+
+pub fn associated_token_address(owner: &Pubkey, mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[owner.as_ref(), SPL_TOKEN_PROGRAM_ID.as_ref(), mint.as_ref()],
+        &ASSOCIATED_TOKEN_PROGRAM_ID,
+    )
+    .0
+}
+
+fn metadata_address(mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"metadata", METADATA_PROGRAM_ID.as_ref(), mint.as_ref()],
+        &METADATA_PROGRAM_ID,
+    )
+    .0
+}
+
+fn edition_address(mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            b"metadata",
+            METADATA_PROGRAM_ID.as_ref(),
+            mint.as_ref(),
+            b"edition",
+        ],
+        &METADATA_PROGRAM_ID,
+    )
+    .0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn metadata_follows_the_v3_layout() {
+        let collection = Pubkey::new_unique();
+
+        let data = metadata_data("Pass #1", "PASS", "https://x.io/1", &collection);
+
+        assert_eq!(data[0], CREATE_METADATA_ACCOUNT_V3);
+        assert_eq!(&data[1..5], &7u32.to_le_bytes());
+        assert_eq!(&data[5..12], b"Pass #1");
+        let collection_at = data.len() - 3 - 32;
+        assert_eq!(&data[collection_at..data.len() - 3], collection.as_ref());
+        assert_eq!(&data[collection_at - 2..collection_at], &[1, 0]);
+    }
+
+    #[test]
+    fn initializes_a_zero_decimal_mint() {
+        let mint = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+
+        let instruction = initialize_mint(&mint, &authority);
+
+        assert_eq!(instruction.data.len(), 67);
+        assert_eq!(&instruction.data[..2], &[INITIALIZE_MINT2, 0]);
+        assert_eq!(&instruction.data[2..34], authority.as_ref());
+    }
+
+    #[test]
+    fn mint_transactions_need_the_buyer_and_authority_signatures() {
+        let authority = Pubkey::new_unique();
+        let minter = NftMinter::new(
+            "collection".to_string(),
+            authority,
+            Duration::from_secs(300),
+        );
+        let config = MintConfig::from_config(&serde_json::json!({
+            "collection": Pubkey::new_unique().to_string(),
+            "uri": "https://example.com/{number}.json",
+            "price": 0.1,
+            "supply": 10
+        }))
+        .unwrap();
+        let reservation = Reservation {
+            mint: Keypair::new(),
+            number: 1,
+        };
+        let buyer = Pubkey::new_unique();
+        let instructions = minter.instructions(
+            &config,
+            "Pass",
+            &reservation,
+            &buyer,
+            &Pubkey::new_unique(),
+            100_000_000,
+        );
+        let mut transaction = Transaction::new_unsigned(solana_sdk::message::Message::new(
+            &instructions,
+            Some(&buyer),
+        ));
+
+        minter
+            .sign(&mut transaction, &reservation, Hash::default())
+            .unwrap();
+
+        let message = &transaction.message;
+        assert_eq!(message.header.num_required_signatures, 3);
+        for (key, signature) in message.account_keys.iter().zip(&transaction.signatures) {
+            let signed = *signature != Default::default();
+            assert_eq!(signed, *key == reservation.mint.pubkey());
+        }
+        assert!(message.account_keys[..3].contains(&authority));
+    }
+}
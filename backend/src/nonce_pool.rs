@@ -0,0 +1,340 @@
+use crate::blockhash_cache::BlockhashCache;
+use axum::http::StatusCode;
+use solana_client::nonce_utils;
+use solana_sdk::{
+    hash::Hash,
+    instruction::Instruction,
+    message::Message,
+    nonce::state::Data,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    system_instruction,
+    transaction::Transaction,
+};
+use sqlx::PgPool;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Durable nonce accounts whose authority is held by the backend.
+///
+/// Transactions built from a leased account start with
+/// `advance_nonce_account` and use the stored nonce instead of a recent
+/// blockhash, so they stay valid until signed. An account is leased to one
+/// transaction at a time and only returns to the pool once its nonce has
+/// advanced: either the transaction landed, or the lease expired and the
+/// sweep advanced the nonce itself so the stale transaction can no longer
+/// land.
+pub struct NoncePool {
+    authority: Keypair,
+    lease: Duration,
+    blockhash_cache: Arc<BlockhashCache>,
+}
+
+/// A nonce account leased to a single transaction.
+#[derive(Debug)]
+pub struct NonceLease {
+    pub account: Pubkey,
+    pub nonce: Hash,
+}
+
+impl NoncePool {
+    pub fn new(authority: Keypair, lease: Duration, blockhash_cache: Arc<BlockhashCache>) -> Self {
+        Self {
+            authority,
+            lease,
+            blockhash_cache,
+        }
+    }
+
+    pub fn authority(&self) -> Pubkey {
+        self.authority.pubkey()
+    }
+
+    /// Builds a transaction for `payer` on a freshly leased nonce account.
+    /// The nonce authority's signature is already attached.
+    pub async fn build_transaction(
+        &self,
+        db: &PgPool,
+        payer: &Pubkey,
+        instructions: &[Instruction],
+    ) -> Result<(Transaction, NonceLease), (StatusCode, String)> {
+        let lease = self.lease(db).await?;
+
+        let mut nonced = vec![system_instruction::advance_nonce_account(
+            &lease.account,
+            &self.authority.pubkey(),
+        )];
+        nonced.extend_from_slice(instructions);
+        let message = Message::new_with_blockhash(&nonced, Some(payer), &lease.nonce);
+        let mut transaction = Transaction::new_unsigned(message);
+
+        if let Err(e) = transaction.try_partial_sign(&[&self.authority], lease.nonce) {
+            self.release(db, &lease.account).await?;
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to sign with nonce authority: {}", e),
+            ));
+        }
+
+        Ok((transaction, lease))
+    }
+
+    /// Leases an available account and records the nonce handed out with it.
+    /// `SKIP LOCKED` keeps concurrent requests from picking the same account.
+    pub async fn lease(&self, db: &PgPool) -> Result<NonceLease, (StatusCode, String)> {
+        let leased = sqlx::query!(
+            r#"
+            UPDATE nonce_accounts
+            SET leased_at = now(), leased_until = now() + make_interval(secs => $1)
+            WHERE pubkey = (
+                SELECT pubkey
+                FROM nonce_accounts
+                WHERE leased_at IS NULL
+                ORDER BY created_at
+                LIMIT 1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING pubkey, leased_at AS "leased_at!"
+            "#,
+            self.lease.as_secs_f64()
+        )
+        .fetch_optional(db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or((
+            StatusCode::SERVICE_UNAVAILABLE,
+            "No durable nonce account is available, try again later".to_string(),
+        ))?;
+
+        let account = parse_account(&leased.pubkey)?;
+        let nonce = match self.fetch_nonce(&account).await {
+            Ok(data) => data.blockhash(),
+            Err(e) => {
+                self.release(db, &account).await?;
+                return Err(e);
+            }
+        };
+
+        // The sweep frees leases that expire before their nonce is recorded;
+        // the lease timestamp tells whether this one is still ours.
+        let recorded = sqlx::query!(
+            r#"
+            UPDATE nonce_accounts
+            SET leased_nonce = $3
+            WHERE pubkey = $1 AND leased_at = $2
+            "#,
+            leased.pubkey,
+            leased.leased_at,
+            nonce.to_string()
+        )
+        .execute(db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        if recorded.rows_affected() == 0 {
+            return Err((
+                StatusCode::SERVICE_UNAVAILABLE,
+                "Durable nonce lease expired, try again".to_string(),
+            ));
+        }
+
+        Ok(NonceLease { account, nonce })
+    }
+
+    /// Returns an account to the pool.
+    pub async fn release(&self, db: &PgPool, account: &Pubkey) -> Result<(), (StatusCode, String)> {
+        sqlx::query!(
+            r#"
+            UPDATE nonce_accounts
+            SET leased_at = NULL, leased_until = NULL, leased_nonce = NULL
+            WHERE pubkey = $1
+            "#,
+            account.to_string()
+        )
+        .execute(db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+        Ok(())
+    }
+
+    /// Adds an initialized nonce account whose authority is ours to the pool.
+    pub async fn register(&self, db: &PgPool, pubkey: &str) -> Result<(), (StatusCode, String)> {
+        let account = Pubkey::from_str(pubkey).map_err(|e| {
+            (
+                StatusCode::BAD_REQUEST,
+                format!("Invalid nonce account: {}", e),
+            )
+        })?;
+        let data = self.fetch_nonce(&account).await?;
+        if data.authority != self.authority.pubkey() {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!(
+                    "Nonce authority of {} is {}, expected {}",
+                    account,
+                    data.authority,
+                    self.authority.pubkey()
+                ),
+            ));
+        }
+
+        let inserted = sqlx::query!(
+            "INSERT INTO nonce_accounts (pubkey) VALUES ($1) ON CONFLICT DO NOTHING",
+            account.to_string()
+        )
+        .execute(db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+        if inserted.rows_affected() == 0 {
+            return Err((
+                StatusCode::CONFLICT,
+                "Nonce account is already registered".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Returns leased accounts whose nonce has advanced to the pool, and
+    /// advances the nonce of expired leases that were never used.
+    pub async fn reclaim(&self, db: &PgPool) -> Result<usize, (StatusCode, String)> {
+        let leases = sqlx::query!(
+            r#"
+            SELECT pubkey, leased_nonce, leased_until < now() AS "expired!"
+            FROM nonce_accounts
+            WHERE leased_at IS NOT NULL
+            "#
+        )
+        .fetch_all(db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+        let mut released = 0;
+        for lease in leases {
+            let account = parse_account(&lease.pubkey)?;
+            // Leased but never handed out, e.g. the request failed midway.
+            let Some(leased_nonce) = lease.leased_nonce else {
+                if lease.expired {
+                    released += sqlx::query!(
+                        r#"
+                        UPDATE nonce_accounts
+                        SET leased_at = NULL, leased_until = NULL
+                        WHERE pubkey = $1 AND leased_nonce IS NULL
+                        "#,
+                        lease.pubkey
+                    )
+                    .execute(db)
+                    .await
+                    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+                    .rows_affected() as usize;
+                }
+                continue;
+            };
+
+            let current = match self.fetch_nonce(&account).await {
+                Ok(data) => data.blockhash(),
+                Err((_, e)) => {
+                    tracing::warn!(nonce_account = %account, "Failed to read nonce account: {}", e);
+                    continue;
+                }
+            };
+            if current.to_string() != leased_nonce {
+                self.release(db, &account).await?;
+                released += 1;
+            } else if lease.expired
+                && let Err(e) = self.advance(&account).await
+            {
+                tracing::warn!(nonce_account = %account, "Failed to advance expired nonce: {}", e);
+            }
+        }
+
+        Ok(released)
+    }
+
+    /// Runs `reclaim` every `interval` for as long as the pool is alive.
+    pub fn spawn_reclaim(self: &Arc<Self>, db: PgPool, interval: Duration) {
+        let pool = Arc::downgrade(self);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(interval);
+            loop {
+                interval.tick().await;
+                let Some(pool) = pool.upgrade() else {
+                    break;
+                };
+                if let Err((_, e)) = pool.reclaim(&db).await {
+                    tracing::warn!("Failed to reclaim nonce accounts: {}", e);
+                }
+            }
+        });
+    }
+
+    /// Submits an `advance_nonce_account` paid for by the authority. The
+    /// account is released by a later sweep once the new nonce is visible.
+    async fn advance(&self, account: &Pubkey) -> Result<(), String> {
+        let blockhash = self
+            .blockhash_cache
+            .get()
+            .await
+            .map_err(|e| e.to_string())?
+            .blockhash;
+        let authority = self.authority.pubkey();
+        let transaction = Transaction::new_signed_with_payer(
+            &[system_instruction::advance_nonce_account(
+                account, &authority,
+            )],
+            Some(&authority),
+            &[&self.authority],
+            blockhash,
+        );
+
+        self.blockhash_cache
+            .rpc()
+            .call(|client| {
+                let transaction = transaction.clone();
+                async move { client.send_transaction(&transaction).await }
+            })
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    async fn fetch_nonce(&self, account: &Pubkey) -> Result<Data, (StatusCode, String)> {
+        let rpc = self.blockhash_cache.rpc();
+        let commitment = rpc.commitment();
+        let account_data = rpc
+            .call(|client| async move {
+                client
+                    .get_account_with_commitment(account, commitment)
+                    .await
+            })
+            .await
+            .map_err(|e| {
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("RPC Error: {}", e),
+                )
+            })?
+            .value
+            .ok_or((
+                StatusCode::BAD_REQUEST,
+                format!("Nonce account {} does not exist", account),
+            ))?;
+
+        nonce_utils::data_from_account(&account_data).map_err(|e| {
+            (
+                StatusCode::BAD_REQUEST,
+                format!("{} is not a usable nonce account: {}", account, e),
+            )
+        })
+    }
+}
+
+fn parse_account(pubkey: &str) -> Result<Pubkey, (StatusCode, String)> {
+    Pubkey::from_str(pubkey).map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Invalid stored nonce account {}: {}", pubkey, e),
+        )
+    })
+}
@@ -0,0 +1,569 @@
+use crate::domain::{RaffleConfig, Ticket, draw_winners, ticket_list_hash};
+use crate::invoices::Currency;
+use crate::models::{RaffleDraw, RaffleTicket};
+use crate::rpc_pool::RpcPool;
+use axum::http::StatusCode;
+use chrono::{DateTime, Utc};
+use serde_json::{Value, json};
+use solana_client::rpc_request::RpcRequest;
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    native_token::LAMPORTS_PER_SOL,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+use sqlx::PgPool;
+use std::str::FromStr;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// The shortest slot time the draw slot is estimated with. Slots are rarely
+/// faster, so the cluster reaches the slot no earlier than estimated.
+const MIN_SLOT_MS: i64 = 400;
+
+/// Sells the tickets of raffle blinks and draws their winners.
+///
+/// The first sale fixes a raffle's terms and commits its draw slot, which
+/// the cluster reaches only after the raffle has closed and its last
+/// purchases have settled. Every purchase carries a fresh reference key and
+/// becomes tickets once it is found on the cluster; purchases not found
+/// within `pending` are dropped. The winners are drawn from the blockhash of
+/// the first block at or after the draw slot, which no one knows while
+/// tickets are sold.
+pub struct TicketBooth {
+    pending: Duration,
+}
+
+/// A raffle as shown on its blink.
+#[derive(Debug)]
+pub struct RaffleState {
+    pub closes_at: DateTime<Utc>,
+    pub draw_slot: i64,
+    pub tickets_sold: i64,
+    /// Wallets drawn, once the raffle has been drawn.
+    pub winners: Vec<String>,
+}
+
+impl TicketBooth {
+    pub fn new(pending: Duration) -> Self {
+        Self { pending }
+    }
+
+    /// Records a purchase of `tickets` by `buyer`, committing the draw slot
+    /// with the first sale, and returns the reference the transfer must
+    /// carry and its lamports. Closed raffles and wallets over the cap get a
+    /// `403`.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn buy(
+        &self,
+        db: &PgPool,
+        rpc: &RpcPool,
+        blink_id: Uuid,
+        config: &RaffleConfig,
+        seller: &Pubkey,
+        buyer: &Pubkey,
+        tickets: u32,
+    ) -> Result<(Pubkey, u64), (StatusCode, String)> {
+        self.settle(db, rpc, blink_id).await?;
+
+        let mut transaction = db
+            .begin()
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        // Serializes sales of one raffle.
+        sqlx::query!("SELECT id FROM blinks WHERE id = $1 FOR UPDATE", blink_id)
+            .fetch_one(&mut *transaction)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+        let raffle = sqlx::query!(
+            r#"
+            SELECT ticket_price, max_per_wallet, closes_at
+            FROM raffles
+            WHERE blink_id = $1
+            "#,
+            blink_id
+        )
+        .fetch_optional(&mut *transaction)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        let (ticket_price, max_per_wallet, closes_at) = match &raffle {
+            Some(raffle) => (
+                raffle.ticket_price as u64,
+                raffle.max_per_wallet as u32,
+                raffle.closes_at,
+            ),
+            None => (
+                (config.ticket_price * LAMPORTS_PER_SOL as f64).round() as u64,
+                config.max_per_wallet,
+                config.closes_at,
+            ),
+        };
+        if closes_at <= Utc::now() {
+            return Err((StatusCode::FORBIDDEN, "This raffle has closed".to_string()));
+        }
+        if !(1..=max_per_wallet).contains(&tickets) {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!("Buy 1 to {} tickets", max_per_wallet),
+            ));
+        }
+
+        let held = sqlx::query_scalar!(
+            r#"
+            SELECT COALESCE(SUM(tickets), 0) AS "held!"
+            FROM raffle_purchases
+            WHERE blink_id = $1 AND wallet = $2
+            "#,
+            blink_id,
+            buyer.to_string()
+        )
+        .fetch_one(&mut *transaction)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))? as u32;
+        if held + tickets > max_per_wallet {
+            let message = match max_per_wallet.saturating_sub(held) {
+                0 => format!(
+                    "This wallet holds the maximum of {} tickets",
+                    max_per_wallet
+                ),
+                1 => "This wallet can buy 1 more ticket".to_string(),
+                left => format!("This wallet can buy {} more tickets", left),
+            };
+            return Err((StatusCode::FORBIDDEN, message));
+        }
+
+        if raffle.is_none() {
+            let draw_slot = self.draw_slot(rpc, &closes_at).await?;
+            sqlx::query!(
+                r#"
+                INSERT INTO raffles (
+                    blink_id, wallet, ticket_price, max_per_wallet, winners,
+                    closes_at, draw_slot
+                )
+                VALUES ($1, $2, $3, $4, $5, $6, $7)
+                "#,
+                blink_id,
+                seller.to_string(),
+                ticket_price as i64,
+                max_per_wallet as i32,
+                config.winners as i32,
+                closes_at,
+                draw_slot as i64
+            )
+            .execute(&mut *transaction)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        }
+
+        let reference = Keypair::new().pubkey();
+        let lamports = ticket_price * u64::from(tickets);
+        sqlx::query!(
+            r#"
+            INSERT INTO raffle_purchases (reference, blink_id, wallet, tickets, amount)
+            VALUES ($1, $2, $3, $4, $5)
+            "#,
+            reference.to_string(),
+            blink_id,
+            buyer.to_string(),
+            tickets as i32,
+            lamports as i64
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+        transaction
+            .commit()
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        Ok((reference, lamports))
+    }
+
+    /// Looks the open purchases of a raffle up on the cluster. Landed
+    /// purchases become tickets, purchases not found in time are dropped.
+    /// When the cluster cannot be reached the purchases stay open.
+    pub async fn settle(
+        &self,
+        db: &PgPool,
+        rpc: &RpcPool,
+        blink_id: Uuid,
+    ) -> Result<(), (StatusCode, String)> {
+        let open = sqlx::query!(
+            r#"
+            SELECT
+                p.reference, p.amount, r.wallet,
+                p.created_at < now() - make_interval(secs => $2) AS "expired!"
+            FROM raffle_purchases p
+            JOIN raffles r USING (blink_id)
+            WHERE p.blink_id = $1 AND p.confirmed_at IS NULL
+            ORDER BY p.created_at
+            "#,
+            blink_id,
+            self.pending.as_secs_f64()
+        )
+        .fetch_all(db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+        for purchase in open {
+            let seller = parse_stored(&purchase.wallet, "wallet")?;
+            let reference = parse_stored(&purchase.reference, "reference")?;
+            match Currency::Sol
+                .find_payment(rpc, &seller, purchase.amount as u64, &reference)
+                .await
+            {
+                Ok(Some(landed)) => {
+                    sqlx::query!(
+                        r#"
+                        UPDATE raffle_purchases
+                        SET signature = $2, confirmed_at = now()
+                        WHERE reference = $1 AND confirmed_at IS NULL
+                        "#,
+                        purchase.reference,
+                        landed.signature.to_string()
+                    )
+                    .execute(db)
+                    .await
+                    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+                }
+                Ok(None) if purchase.expired => {
+                    sqlx::query!(
+                        r#"
+                        DELETE FROM raffle_purchases
+                        WHERE reference = $1 AND confirmed_at IS NULL
+                        "#,
+                        purchase.reference
+                    )
+                    .execute(db)
+                    .await
+                    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    tracing::warn!(blink_id = %blink_id, "Failed to settle raffle tickets: {}", e);
+                    return Ok(());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The raffle of a blink after settling its purchases, or `None` before
+    /// the first sale.
+    pub async fn state(
+        &self,
+        db: &PgPool,
+        rpc: &RpcPool,
+        blink_id: Uuid,
+    ) -> Result<Option<RaffleState>, (StatusCode, String)> {
+        self.settle(db, rpc, blink_id).await?;
+        let raffle = sqlx::query!(
+            r#"
+            SELECT
+                r.closes_at, r.draw_slot,
+                (
+                    SELECT COALESCE(SUM(tickets), 0) FROM raffle_purchases p
+                    WHERE p.blink_id = r.blink_id AND p.confirmed_at IS NOT NULL
+                ) AS "tickets_sold!"
+            FROM raffles r
+            WHERE r.blink_id = $1
+            "#,
+            blink_id
+        )
+        .fetch_optional(db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        let Some(raffle) = raffle else {
+            return Ok(None);
+        };
+        let winners = sqlx::query_scalar!(
+            "SELECT wallet FROM raffle_winners WHERE blink_id = $1 ORDER BY place",
+            blink_id
+        )
+        .fetch_all(db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+        Ok(Some(RaffleState {
+            closes_at: raffle.closes_at,
+            draw_slot: raffle.draw_slot,
+            tickets_sold: raffle.tickets_sold,
+            winners,
+        }))
+    }
+
+    /// The draw of a raffle, drawing it first once the raffle has closed,
+    /// its purchases have settled and the draw slot has been produced.
+    /// Raffles that cannot be drawn yet get a `409`.
+    pub async fn draw(
+        &self,
+        db: &PgPool,
+        rpc: &RpcPool,
+        blink_id: Uuid,
+    ) -> Result<RaffleDraw, (StatusCode, String)> {
+        if let Some(draw) = stored_draw(db, blink_id).await? {
+            return Ok(draw);
+        }
+        let raffle = sqlx::query!(
+            "SELECT closes_at, draw_slot, winners FROM raffles WHERE blink_id = $1",
+            blink_id
+        )
+        .fetch_optional(db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or((
+            StatusCode::NOT_FOUND,
+            "No tickets have been sold".to_string(),
+        ))?;
+        if Utc::now() < raffle.closes_at {
+            return Err((
+                StatusCode::CONFLICT,
+                format!(
+                    "The raffle closes at {}",
+                    raffle.closes_at.format("%Y-%m-%d %H:%M UTC")
+                ),
+            ));
+        }
+
+        self.settle(db, rpc, blink_id).await?;
+        let settling = sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS (
+                SELECT 1 FROM raffle_purchases WHERE blink_id = $1 AND confirmed_at IS NULL
+            ) AS "settling!"
+            "#,
+            blink_id
+        )
+        .fetch_one(db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        if settling {
+            return Err((
+                StatusCode::CONFLICT,
+                "Ticket purchases are still settling".to_string(),
+            ));
+        }
+
+        let Some((seed_slot, seed)) = seed(rpc, raffle.draw_slot as u64).await? else {
+            return Err((
+                StatusCode::CONFLICT,
+                format!("The draw slot {} has not been reached", raffle.draw_slot),
+            ));
+        };
+        let tickets = tickets(db, blink_id).await?;
+        let winners = draw_winners(&seed, &tickets, raffle.winners as u32);
+
+        let mut transaction = db
+            .begin()
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        // Concurrent draws reach the same result; the first one is stored.
+        let drawn = sqlx::query!(
+            r#"
+            UPDATE raffles
+            SET seed = $2, seed_slot = $3, ticket_hash = $4, drawn_at = now()
+            WHERE blink_id = $1 AND drawn_at IS NULL
+            "#,
+            blink_id,
+            seed,
+            seed_slot as i64,
+            ticket_list_hash(&tickets)
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .rows_affected();
+        if drawn > 0 {
+            for (place, winner) in winners.iter().enumerate() {
+                sqlx::query!(
+                    r#"
+                    INSERT INTO raffle_winners (blink_id, place, ticket, wallet)
+                    VALUES ($1, $2, $3, $4)
+                    "#,
+                    blink_id,
+                    place as i32 + 1,
+                    winner.number as i32,
+                    winner.wallet
+                )
+                .execute(&mut *transaction)
+                .await
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+            }
+        }
+        transaction
+            .commit()
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+        stored_draw(db, blink_id).await?.ok_or((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "The draw was not stored".to_string(),
+        ))
+    }
+
+    /// A slot the cluster reaches after `closes_at` plus the time purchases
+    /// take to settle.
+    async fn draw_slot(
+        &self,
+        rpc: &RpcPool,
+        closes_at: &DateTime<Utc>,
+    ) -> Result<u64, (StatusCode, String)> {
+        let commitment = rpc.commitment();
+        let current = rpc
+            .call(|client| async move { client.get_slot_with_commitment(commitment).await })
+            .await
+            .map_err(|e| {
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("RPC Error: {}", e),
+                )
+            })?;
+        let until = (*closes_at - Utc::now()).num_milliseconds() + self.pending.as_millis() as i64;
+        Ok(current + (until.max(0) / MIN_SLOT_MS + 1) as u64)
+    }
+}
+
+/// The first finalized block at or after `draw_slot` and its blockhash, or
+/// `None` while there is none.
+async fn seed(
+    rpc: &RpcPool,
+    draw_slot: u64,
+) -> Result<Option<(u64, String)>, (StatusCode, String)> {
+    let rpc_error = |e: solana_client::client_error::ClientError| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("RPC Error: {}", e),
+        )
+    };
+    let blocks = rpc
+        .call(|client| async move {
+            client
+                .get_blocks_with_limit_and_commitment(draw_slot, 1, CommitmentConfig::finalized())
+                .await
+        })
+        .await
+        .map_err(rpc_error)?;
+    let Some(&slot) = blocks.first() else {
+        return Ok(None);
+    };
+
+    let params = json!([
+        slot,
+        {
+            "commitment": "finalized",
+            "transactionDetails": "none",
+            "rewards": false,
+            "maxSupportedTransactionVersion": 0
+        }
+    ]);
+    let block: Value = rpc
+        .call(|client| {
+            let params = params.clone();
+            async move { client.send(RpcRequest::GetBlock, params).await }
+        })
+        .await
+        .map_err(rpc_error)?;
+    let blockhash = block["blockhash"].as_str().ok_or((
+        StatusCode::INTERNAL_SERVER_ERROR,
+        format!("Block {} has no blockhash", slot),
+    ))?;
+    Ok(Some((slot, blockhash.to_string())))
+}
+
+/// The tickets of a raffle, numbered in the order their purchases landed.
+async fn tickets(db: &PgPool, blink_id: Uuid) -> Result<Vec<Ticket>, (StatusCode, String)> {
+    let purchases = sqlx::query!(
+        r#"
+        SELECT wallet, tickets, signature as "signature!"
+        FROM raffle_purchases
+        WHERE blink_id = $1 AND confirmed_at IS NOT NULL
+        ORDER BY confirmed_at, reference
+        "#,
+        blink_id
+    )
+    .fetch_all(db)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let mut tickets = Vec::new();
+    for purchase in purchases {
+        for _ in 0..purchase.tickets {
+            tickets.push(Ticket {
+                number: tickets.len() as u32 + 1,
+                wallet: purchase.wallet.clone(),
+                signature: purchase.signature.clone(),
+            });
+        }
+    }
+    Ok(tickets)
+}
+
+async fn stored_draw(
+    db: &PgPool,
+    blink_id: Uuid,
+) -> Result<Option<RaffleDraw>, (StatusCode, String)> {
+    let raffle = sqlx::query!(
+        r#"
+        SELECT
+            closes_at, draw_slot, seed as "seed!", seed_slot as "seed_slot!",
+            ticket_hash as "ticket_hash!", drawn_at as "drawn_at!"
+        FROM raffles
+        WHERE blink_id = $1 AND drawn_at IS NOT NULL
+        "#,
+        blink_id
+    )
+    .fetch_optional(db)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let Some(raffle) = raffle else {
+        return Ok(None);
+    };
+    let winners = sqlx::query!(
+        "SELECT ticket, wallet FROM raffle_winners WHERE blink_id = $1 ORDER BY place",
+        blink_id
+    )
+    .fetch_all(db)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let tickets = tickets(db, blink_id).await?;
+
+    let winners = winners
+        .into_iter()
+        .map(|winner| RaffleTicket {
+            number: winner.ticket as u32,
+            signature: tickets
+                .get(winner.ticket as usize - 1)
+                .map(|ticket| ticket.signature.clone())
+                .unwrap_or_default(),
+            wallet: winner.wallet,
+        })
+        .collect();
+    Ok(Some(RaffleDraw {
+        blink_id,
+        closes_at: raffle.closes_at,
+        draw_slot: raffle.draw_slot as u64,
+        seed_slot: raffle.seed_slot as u64,
+        seed: raffle.seed,
+        ticket_hash: raffle.ticket_hash,
+        tickets: tickets
+            .into_iter()
+            .map(|ticket| RaffleTicket {
+                number: ticket.number,
+                wallet: ticket.wallet,
+                signature: ticket.signature,
+            })
+            .collect(),
+        winners,
+        drawn_at: raffle.drawn_at,
+    }))
+}
+
+fn parse_stored(value: &str, what: &str) -> Result<Pubkey, (StatusCode, String)> {
+    Pubkey::from_str(value).map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Invalid stored {}: {}", what, e),
+        )
+    })
+}
@@ -0,0 +1,459 @@
+use crate::authentication::{AdminToken, bearer_token, hash_api_key, is_active_api_key};
+use crate::configuration::{RateLimitKey, RateLimitSettings, RouteRateLimit};
+use crate::models::ActionError;
+use axum::{
+    Json, Router,
+    body::{Body, to_bytes},
+    extract::{ConnectInfo, Request, State},
+    http::{HeaderMap, StatusCode, header},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+};
+use governor::{
+    DefaultKeyedRateLimiter, Quota, RateLimiter,
+    clock::{Clock, DefaultClock},
+};
+use ipnet::IpNet;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::num::NonZeroU32;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Largest request body buffered to find the payer of an action.
+const MAX_PAYER_BODY_BYTES: usize = 64 * 1024;
+
+/// How often idle rate limiting keys are evicted.
+const RETAIN_RECENT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How long the outcome of looking up a bearer token in `api_keys` is reused.
+/// A revoked key keeps its bucket this long; authentication itself is not
+/// cached.
+const API_KEY_LOOKUP_TTL: Duration = Duration::from_secs(30);
+
+/// Most bearer token lookups remembered at once.
+const MAX_API_KEY_LOOKUPS: usize = 10_000;
+
+/// A keyed limiter enforcing one configured route limit.
+#[derive(Clone)]
+pub struct RouteLimiter {
+    key: RateLimitKey,
+    trusted_proxies: Arc<Vec<IpNet>>,
+    limiter: Arc<DefaultKeyedRateLimiter<String>>,
+}
+
+/// The limiters guarding a route group. Requests carrying an active API key
+/// are counted against `api_keys` when set, everything else against
+/// `anonymous`.
+#[derive(Clone)]
+struct RouteLimiters {
+    anonymous: RouteLimiter,
+    api_keys: Option<ApiKeyLimiter>,
+}
+
+/// The limiter for requests with an API key, and what the key is checked
+/// against before it is trusted: `api_keys`, or the admin token.
+#[derive(Clone)]
+struct ApiKeyLimiter {
+    limiter: RouteLimiter,
+    pool: PgPool,
+    admin_token: AdminToken,
+    /// Recent lookups by token hash, unknown tokens included, so repeated
+    /// requests do not each query `api_keys`.
+    lookups: Arc<Mutex<HashMap<String, (bool, Instant)>>>,
+}
+
+impl ApiKeyLimiter {
+    async fn verify(&self, headers: &HeaderMap) -> Option<VerifiedApiKey> {
+        let token = bearer_token(headers)?;
+        let hash = hash_api_key(token);
+        let verified = self.admin_token.verify(headers).is_ok()
+            || match self.cached(&hash) {
+                Some(active) => active,
+                None => match is_active_api_key(&self.pool, token).await {
+                    Ok(active) => {
+                        self.remember(&hash, active);
+                        active
+                    }
+                    Err(e) => {
+                        tracing::warn!("Failed to check API key for rate limiting: {}", e);
+                        false
+                    }
+                },
+            };
+        verified.then_some(VerifiedApiKey(hash))
+    }
+
+    fn cached(&self, hash: &str) -> Option<bool> {
+        let lookups = self.lookups.lock().unwrap();
+        lookups
+            .get(hash)
+            .filter(|(_, looked_up_at)| looked_up_at.elapsed() < API_KEY_LOOKUP_TTL)
+            .map(|(active, _)| *active)
+    }
+
+    fn remember(&self, hash: &str, active: bool) {
+        let mut lookups = self.lookups.lock().unwrap();
+        if lookups.len() >= MAX_API_KEY_LOOKUPS {
+            lookups.retain(|_, (_, looked_up_at)| looked_up_at.elapsed() < API_KEY_LOOKUP_TTL);
+        }
+        if lookups.len() < MAX_API_KEY_LOOKUPS {
+            lookups.insert(hash.to_string(), (active, Instant::now()));
+        }
+    }
+}
+
+/// The `account` of an action POST body, captured before the body reaches
+/// the handler.
+#[derive(Debug, Clone)]
+struct PayerAccount(String);
+
+/// The hash of a bearer token found in `api_keys` or matching the admin
+/// token. Only such tokens key a bucket, so made-up tokens cannot each get a
+/// fresh one.
+#[derive(Debug, Clone)]
+struct VerifiedApiKey(String);
+
+impl RouteLimiter {
+    pub fn new(limit: &RouteRateLimit, trusted_proxies: Vec<IpNet>) -> Self {
+        let quota = Quota::with_period(Duration::from_millis(limit.period_ms))
+            .expect("Rate limit period must be non-zero")
+            .allow_burst(NonZeroU32::new(limit.burst_size).expect("Burst size must be non-zero"));
+
+        let limiter = Arc::new(RateLimiter::keyed(quota));
+
+        let idle = Arc::downgrade(&limiter);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(RETAIN_RECENT_INTERVAL);
+            loop {
+                interval.tick().await;
+                match idle.upgrade() {
+                    Some(limiter) => limiter.retain_recent(),
+                    None => break,
+                }
+            }
+        });
+
+        Self {
+            key: limit.key,
+            trusted_proxies: Arc::new(trusted_proxies),
+            limiter,
+        }
+    }
+
+    fn is_trusted(&self, ip: &IpAddr) -> bool {
+        self.trusted_proxies.iter().any(|net| net.contains(ip))
+    }
+
+    /// The client address: the peer, or when the peer is a trusted proxy, the
+    /// right-most untrusted hop in `X-Forwarded-For`.
+    fn client_ip<T>(&self, req: &Request<T>) -> Option<IpAddr> {
+        let peer = req
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip())?;
+
+        if self.key == RateLimitKey::PeerIp || !self.is_trusted(&peer) {
+            return Some(peer);
+        }
+
+        let hops: Vec<IpAddr> = req
+            .headers()
+            .get_all("x-forwarded-for")
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .filter_map(|hop| hop.trim().parse().ok())
+            .collect();
+
+        Some(
+            hops.iter()
+                .rev()
+                .find(|hop| !self.is_trusted(hop))
+                .or(hops.first())
+                .copied()
+                .unwrap_or(peer),
+        )
+    }
+
+    /// Extracts the configured key. Keys missing from a request fall back to
+    /// the client IP, so anonymous callers and unverified API keys still share
+    /// a per-address bucket.
+    fn key<T>(&self, req: &Request<T>) -> Option<String> {
+        let key = match self.key {
+            RateLimitKey::ApiKey => req
+                .extensions()
+                .get::<VerifiedApiKey>()
+                .map(|VerifiedApiKey(hash)| format!("key:{}", hash)),
+            RateLimitKey::Payer => req
+                .extensions()
+                .get::<PayerAccount>()
+                .map(|PayerAccount(account)| format!("payer:{}", account)),
+            RateLimitKey::PeerIp | RateLimitKey::ForwardedFor => None,
+        };
+
+        key.or_else(|| self.client_ip(req).map(|ip| format!("ip:{}", ip)))
+    }
+
+    fn check<T>(&self, req: &Request<T>) -> Result<(), Box<Response>> {
+        let key = self.key(req).ok_or_else(|| {
+            Box::new(action_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Unable to identify client".to_string(),
+            ))
+        })?;
+
+        self.limiter.check_key(&key).map_err(|not_until| {
+            let wait_time = not_until
+                .wait_time_from(DefaultClock::default().now())
+                .as_secs()
+                .max(1);
+            tracing::info!(rate_limit_key = %key, "Rate limit exceeded, retry in {}s", wait_time);
+
+            let mut response = action_error(
+                StatusCode::TOO_MANY_REQUESTS,
+                format!("Too many requests, retry in {}s", wait_time),
+            );
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, wait_time.into());
+            Box::new(response)
+        })
+    }
+}
+
+/// Applies `limit` to every route of `router`.
+pub fn rate_limited<S>(
+    router: Router<S>,
+    limit: &RouteRateLimit,
+    settings: &RateLimitSettings,
+) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    with_limiters(
+        router,
+        RouteLimiters {
+            anonymous: RouteLimiter::new(limit, settings.trusted_proxies.clone()),
+            api_keys: None,
+        },
+    )
+}
+
+/// Applies `anonymous` to requests without an API key and `api_keys` to
+/// requests presenting an active one (or the admin token), so scripted
+/// clients get their own bucket.
+pub fn rate_limited_by_api_key<S>(
+    router: Router<S>,
+    anonymous: &RouteRateLimit,
+    api_keys: &RouteRateLimit,
+    settings: &RateLimitSettings,
+    pool: PgPool,
+    admin_token: AdminToken,
+) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    with_limiters(
+        router,
+        RouteLimiters {
+            anonymous: RouteLimiter::new(anonymous, settings.trusted_proxies.clone()),
+            api_keys: Some(ApiKeyLimiter {
+                limiter: RouteLimiter::new(api_keys, settings.trusted_proxies.clone()),
+                pool,
+                admin_token,
+                lookups: Arc::default(),
+            }),
+        },
+    )
+}
+
+fn with_limiters<S>(router: Router<S>, limiters: RouteLimiters) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    let captures_payer = limiters.anonymous.key == RateLimitKey::Payer;
+    let router = router.route_layer(middleware::from_fn_with_state(limiters, enforce_rate_limit));
+
+    if captures_payer {
+        router.route_layer(middleware::from_fn(capture_payer_account))
+    } else {
+        router
+    }
+}
+
+async fn enforce_rate_limit(
+    State(limiters): State<RouteLimiters>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, Response> {
+    let mut limiter = &limiters.anonymous;
+    if let Some(api_keys) = &limiters.api_keys
+        && let Some(verified) = api_keys.verify(request.headers()).await
+    {
+        request.extensions_mut().insert(verified);
+        limiter = &api_keys.limiter;
+    }
+    limiter.check(&request).map_err(|rejection| *rejection)?;
+
+    Ok(next.run(request).await)
+}
+
+/// Buffers the body of action POSTs to read the payer `account` and hands the
+/// body on untouched.
+async fn capture_payer_account(request: Request, next: Next) -> Result<Response, Response> {
+    let (mut parts, body) = request.into_parts();
+    let bytes = to_bytes(body, MAX_PAYER_BODY_BYTES).await.map_err(|_| {
+        action_error(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            "Request body too large".to_string(),
+        )
+    })?;
+
+    if let Some(account) = serde_json::from_slice::<serde_json::Value>(&bytes)
+        .ok()
+        .and_then(|body| body.get("account")?.as_str().map(str::to_string))
+    {
+        parts.extensions.insert(PayerAccount(account));
+    }
+
+    Ok(next
+        .run(Request::from_parts(parts, Body::from(bytes)))
+        .await)
+}
+
+fn action_error(status: StatusCode, message: String) -> Response {
+    (status, Json(ActionError { message })).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(peer: &str, forwarded_for: Option<&str>) -> Request<()> {
+        let mut builder = Request::builder();
+        if let Some(forwarded_for) = forwarded_for {
+            builder = builder.header("x-forwarded-for", forwarded_for);
+        }
+        let mut request = builder.body(()).unwrap();
+        request
+            .extensions_mut()
+            .insert(ConnectInfo(peer.parse::<SocketAddr>().unwrap()));
+        request
+    }
+
+    fn limiter(key: RateLimitKey) -> RouteLimiter {
+        RouteLimiter::new(
+            &RouteRateLimit {
+                period_ms: 1000,
+                burst_size: 1,
+                key,
+            },
+            vec!["10.0.0.0/8".parse().unwrap()],
+        )
+    }
+
+    #[tokio::test]
+    async fn forwarded_for_is_ignored_from_untrusted_peers() {
+        let key = limiter(RateLimitKey::ForwardedFor)
+            .key(&request("203.0.113.7:4000", Some("198.51.100.1")))
+            .unwrap();
+
+        assert_eq!(key, "ip:203.0.113.7");
+    }
+
+    #[tokio::test]
+    async fn forwarded_for_uses_right_most_untrusted_hop_behind_trusted_proxy() {
+        let key = limiter(RateLimitKey::ForwardedFor)
+            .key(&request(
+                "10.0.0.2:4000",
+                Some("192.0.2.99, 198.51.100.1, 10.0.0.5"),
+            ))
+            .unwrap();
+
+        assert_eq!(key, "ip:198.51.100.1");
+    }
+
+    #[tokio::test]
+    async fn peer_ip_key_never_reads_forwarded_for() {
+        let key = limiter(RateLimitKey::PeerIp)
+            .key(&request("10.0.0.2:4000", Some("198.51.100.1")))
+            .unwrap();
+
+        assert_eq!(key, "ip:10.0.0.2");
+    }
+
+    #[tokio::test]
+    async fn only_verified_api_keys_key_a_bucket() {
+        let mut with_token = request("203.0.113.7:4000", None);
+        with_token
+            .headers_mut()
+            .insert(header::AUTHORIZATION, "Bearer bz_secret".parse().unwrap());
+
+        let limiter = limiter(RateLimitKey::ApiKey);
+
+        assert_eq!(limiter.key(&with_token).unwrap(), "ip:203.0.113.7");
+        with_token
+            .extensions_mut()
+            .insert(VerifiedApiKey(hash_api_key("bz_secret")));
+        assert_eq!(
+            limiter.key(&with_token).unwrap(),
+            format!("key:{}", hash_api_key("bz_secret"))
+        );
+        assert_eq!(
+            limiter.key(&request("203.0.113.7:4000", None)).unwrap(),
+            "ip:203.0.113.7"
+        );
+    }
+
+    #[tokio::test]
+    async fn recent_api_key_lookups_skip_the_database() {
+        let api_keys = ApiKeyLimiter {
+            limiter: limiter(RateLimitKey::ApiKey),
+            // Nothing listens here, so a query would fail.
+            pool: PgPool::connect_lazy("postgres://localhost:1/none").unwrap(),
+            admin_token: AdminToken(String::new().into()),
+            lookups: Arc::default(),
+        };
+        api_keys.remember(&hash_api_key("bz_known"), true);
+        api_keys.remember(&hash_api_key("bz_made_up"), false);
+        let headers = |token: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(
+                header::AUTHORIZATION,
+                format!("Bearer {}", token).parse().unwrap(),
+            );
+            headers
+        };
+
+        let known = api_keys.verify(&headers("bz_known")).await.unwrap();
+
+        assert_eq!(known.0, hash_api_key("bz_known"));
+        assert!(api_keys.verify(&headers("bz_made_up")).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn payer_key_uses_captured_account() {
+        let mut request = request("203.0.113.7:4000", None);
+        request
+            .extensions_mut()
+            .insert(PayerAccount("Payer111".to_string()));
+
+        let key = limiter(RateLimitKey::Payer).key(&request).unwrap();
+
+        assert_eq!(key, "payer:Payer111");
+    }
+
+    #[tokio::test]
+    async fn exceeding_the_quota_sets_retry_after() {
+        let limiter = limiter(RateLimitKey::PeerIp);
+        let request = request("203.0.113.7:4000", None);
+
+        assert!(limiter.check(&request).is_ok());
+        let response = limiter.check(&request).unwrap_err();
+
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "1");
+    }
+}
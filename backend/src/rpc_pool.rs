@@ -0,0 +1,420 @@
+use crate::models::RpcEndpointMetrics;
+use rand::Rng;
+use solana_client::{
+    client_error::{ClientError, ClientErrorKind},
+    nonblocking::rpc_client::RpcClient,
+    rpc_custom_error::{
+        JSON_RPC_SERVER_ERROR_BLOCK_NOT_AVAILABLE,
+        JSON_RPC_SERVER_ERROR_MIN_CONTEXT_SLOT_NOT_REACHED, JSON_RPC_SERVER_ERROR_NODE_UNHEALTHY,
+    },
+    rpc_request::RpcError,
+};
+use solana_sdk::commitment_config::CommitmentConfig;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// JSON-RPC "internal error", returned by overloaded nodes.
+const JSON_RPC_INTERNAL_ERROR: i64 = -32603;
+
+/// Weight of the newest sample in the moving latency average.
+const LATENCY_SMOOTHING: f64 = 0.2;
+
+/// One upstream RPC endpoint and its health.
+pub struct RpcEndpoint {
+    label: String,
+    weight: u32,
+    client: Arc<RpcClient>,
+    state: Mutex<EndpointState>,
+}
+
+#[derive(Default)]
+struct EndpointState {
+    requests: u64,
+    errors: u64,
+    average_latency_ms: Option<f64>,
+    last_error: Option<String>,
+    consecutive_failures: u32,
+    open_until: Option<Instant>,
+    /// Result of the last health check; endpoints start out healthy.
+    unhealthy: bool,
+    slot: Option<u64>,
+}
+
+impl RpcEndpoint {
+    pub fn new(url: &str, weight: u32) -> Self {
+        Self::with_client(
+            endpoint_label(url),
+            weight,
+            RpcClient::new_with_commitment(url.to_string(), CommitmentConfig::confirmed()),
+        )
+    }
+
+    pub fn with_client(label: String, weight: u32, client: RpcClient) -> Self {
+        Self {
+            label,
+            weight,
+            client: Arc::new(client),
+            state: Mutex::new(EndpointState::default()),
+        }
+    }
+
+    /// Open circuits reject requests until their cooldown has passed, then
+    /// let requests through again (half-open).
+    fn is_open(&self) -> bool {
+        self.state
+            .lock()
+            .unwrap()
+            .open_until
+            .is_some_and(|open_until| Instant::now() < open_until)
+    }
+
+    fn is_healthy(&self) -> bool {
+        !self.state.lock().unwrap().unhealthy
+    }
+
+    fn record(&self, latency: Duration, error: Option<&ClientError>, breaker: &CircuitBreaker) {
+        let mut state = self.state.lock().unwrap();
+        let latency_ms = latency.as_secs_f64() * 1000.0;
+
+        state.requests += 1;
+        state.average_latency_ms = Some(match state.average_latency_ms {
+            Some(average) => average + LATENCY_SMOOTHING * (latency_ms - average),
+            None => latency_ms,
+        });
+
+        match error {
+            None => {
+                state.consecutive_failures = 0;
+                state.open_until = None;
+            }
+            Some(error) => {
+                state.errors += 1;
+                state.last_error = Some(error.to_string());
+                state.consecutive_failures += 1;
+                if state.consecutive_failures >= breaker.failure_threshold {
+                    tracing::warn!(
+                        rpc_endpoint = %self.label,
+                        "Opening circuit after {} consecutive failures: {}",
+                        state.consecutive_failures,
+                        error
+                    );
+                    state.open_until = Some(Instant::now() + breaker.cooldown);
+                }
+            }
+        }
+    }
+
+    fn metrics(&self) -> RpcEndpointMetrics {
+        let state = self.state.lock().unwrap();
+        RpcEndpointMetrics {
+            endpoint: self.label.clone(),
+            weight: self.weight,
+            healthy: !state.unhealthy,
+            circuit_open: state
+                .open_until
+                .is_some_and(|open_until| Instant::now() < open_until),
+            slot: state.slot,
+            requests: state.requests,
+            errors: state.errors,
+            average_latency_ms: state.average_latency_ms,
+            last_error: state.last_error.clone(),
+        }
+    }
+}
+
+/// When an endpoint's circuit opens and for how long.
+#[derive(Debug, Clone, Copy)]
+pub struct CircuitBreaker {
+    pub failure_threshold: u32,
+    pub cooldown: Duration,
+}
+
+/// Weighted pool of RPC endpoints with failover.
+///
+/// Each call goes to an endpoint picked by weight among those that are healthy
+/// and whose circuit is closed, and is retried on another endpoint when it
+/// fails for reasons that are the endpoint's fault (transport errors, 5xx,
+/// unhealthy nodes). Errors about the request itself are returned as is.
+pub struct RpcPool {
+    endpoints: Vec<RpcEndpoint>,
+    breaker: CircuitBreaker,
+    max_attempts: usize,
+    max_slot_lag: u64,
+}
+
+impl RpcPool {
+    pub fn new(
+        endpoints: Vec<RpcEndpoint>,
+        breaker: CircuitBreaker,
+        max_attempts: usize,
+        max_slot_lag: u64,
+    ) -> Self {
+        assert!(
+            !endpoints.is_empty(),
+            "RPC pool needs at least one endpoint"
+        );
+        Self {
+            endpoints,
+            breaker,
+            max_attempts: max_attempts.max(1),
+            max_slot_lag,
+        }
+    }
+
+    /// The commitment shared by every endpoint of the pool.
+    pub fn commitment(&self) -> CommitmentConfig {
+        self.endpoints[0].client.commitment()
+    }
+
+    /// Runs `request` against the pool, failing over to other endpoints.
+    pub async fn call<T, F, Fut>(&self, request: F) -> Result<T, ClientError>
+    where
+        F: Fn(Arc<RpcClient>) -> Fut,
+        Fut: Future<Output = Result<T, ClientError>>,
+    {
+        let mut last_error = None;
+
+        for endpoint in self.candidates().into_iter().take(self.max_attempts) {
+            let started = Instant::now();
+            match request(endpoint.client.clone()).await {
+                Ok(value) => {
+                    endpoint.record(started.elapsed(), None, &self.breaker);
+                    return Ok(value);
+                }
+                Err(error) if is_endpoint_failure(&error) => {
+                    tracing::info!(rpc_endpoint = %endpoint.label, "RPC request failed: {}", error);
+                    endpoint.record(started.elapsed(), Some(&error), &self.breaker);
+                    last_error = Some(error);
+                }
+                Err(error) => {
+                    endpoint.record(started.elapsed(), None, &self.breaker);
+                    return Err(error);
+                }
+            }
+        }
+
+        Err(last_error.expect("RPC pool tried no endpoint"))
+    }
+
+    /// Endpoints in the order to try them: healthy endpoints with a closed
+    /// circuit by weighted random order, then the rest as a last resort.
+    fn candidates(&self) -> Vec<&RpcEndpoint> {
+        let mut rng = rand::thread_rng();
+        let mut ranked: Vec<(bool, f64, &RpcEndpoint)> = self
+            .endpoints
+            .iter()
+            .map(|endpoint| {
+                let available = endpoint.is_healthy() && !endpoint.is_open();
+                // Weighted random order (Efraimidis-Spirakis): higher weights
+                // draw larger keys more often.
+                let key = rng.r#gen::<f64>().powf(1.0 / endpoint.weight.max(1) as f64);
+                (available, key, endpoint)
+            })
+            .collect();
+
+        ranked.sort_by(|a, b| b.0.cmp(&a.0).then(b.1.total_cmp(&a.1)));
+        ranked
+            .into_iter()
+            .map(|(_, _, endpoint)| endpoint)
+            .collect()
+    }
+
+    /// Checks `getHealth` and the slot of every endpoint. Endpoints that
+    /// report unhealthy or lag the highest slot by more than `max_slot_lag`
+    /// are only used when no other endpoint is available.
+    pub async fn check_health(&self) {
+        let results = futures::future::join_all(self.endpoints.iter().map(|endpoint| async move {
+            let health = endpoint.client.get_health().await;
+            let slot = endpoint.client.get_slot().await;
+            (health, slot)
+        }))
+        .await;
+
+        let highest_slot = results
+            .iter()
+            .filter_map(|(_, slot)| slot.as_ref().ok())
+            .max()
+            .copied()
+            .unwrap_or_default();
+
+        for (endpoint, (health, slot)) in self.endpoints.iter().zip(results) {
+            let slot = slot.ok();
+            let lagging = slot.is_none_or(|slot| slot + self.max_slot_lag < highest_slot);
+            let unhealthy = health.is_err() || lagging;
+
+            let mut state = endpoint.state.lock().unwrap();
+            if unhealthy && !state.unhealthy {
+                tracing::warn!(
+                    rpc_endpoint = %endpoint.label,
+                    slot = ?slot,
+                    highest_slot,
+                    "RPC endpoint failed its health check"
+                );
+            }
+            state.unhealthy = unhealthy;
+            state.slot = slot;
+        }
+    }
+
+    pub fn spawn_health_checks(self: &Arc<Self>, interval: Duration) {
+        let pool = Arc::downgrade(self);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(interval);
+            loop {
+                interval.tick().await;
+                let Some(pool) = pool.upgrade() else {
+                    break;
+                };
+                pool.check_health().await;
+            }
+        });
+    }
+
+    pub fn metrics(&self) -> Vec<RpcEndpointMetrics> {
+        self.endpoints.iter().map(RpcEndpoint::metrics).collect()
+    }
+}
+
+/// Whether an error says the endpoint, rather than the request, is at fault.
+fn is_endpoint_failure(error: &ClientError) -> bool {
+    match error.kind() {
+        ClientErrorKind::Io(_)
+        | ClientErrorKind::Reqwest(_)
+        | ClientErrorKind::Middleware(_)
+        | ClientErrorKind::SerdeJson(_) => true,
+        ClientErrorKind::RpcError(RpcError::RpcRequestError(_) | RpcError::ParseError(_)) => true,
+        ClientErrorKind::RpcError(RpcError::RpcResponseError { code, .. }) => matches!(
+            *code,
+            JSON_RPC_INTERNAL_ERROR
+                | JSON_RPC_SERVER_ERROR_NODE_UNHEALTHY
+                | JSON_RPC_SERVER_ERROR_BLOCK_NOT_AVAILABLE
+                | JSON_RPC_SERVER_ERROR_MIN_CONTEXT_SLOT_NOT_REACHED
+        ),
+        _ => false,
+    }
+}
+
+/// Scheme and host of an endpoint URL. Paths and query strings of paid RPC
+/// URLs often carry API keys, so they are kept out of logs and metrics.
+fn endpoint_label(url: &str) -> String {
+    match reqwest::Url::parse(url) {
+        Ok(url) => match (url.host_str(), url.port()) {
+            (Some(host), Some(port)) => format!("{}://{}:{}", url.scheme(), host, port),
+            (Some(host), None) => format!("{}://{}", url.scheme(), host),
+            _ => url.scheme().to_string(),
+        },
+        Err(_) => "invalid-url".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn breaker() -> CircuitBreaker {
+        CircuitBreaker {
+            failure_threshold: 2,
+            cooldown: Duration::from_secs(60),
+        }
+    }
+
+    fn mock(label: &str, weight: u32, behaviour: &str) -> RpcEndpoint {
+        RpcEndpoint::with_client(
+            label.to_string(),
+            weight,
+            RpcClient::new_mock(behaviour.to_string()),
+        )
+    }
+
+    fn io_error() -> ClientError {
+        std::io::Error::other("connection refused").into()
+    }
+
+    #[test]
+    fn labels_hide_paths_and_query_strings() {
+        assert_eq!(
+            endpoint_label("https://rpc.example.com/v2/secret-key?api-key=x"),
+            "https://rpc.example.com"
+        );
+        assert_eq!(
+            endpoint_label("http://127.0.0.1:8899"),
+            "http://127.0.0.1:8899"
+        );
+    }
+
+    #[test]
+    fn request_errors_are_not_endpoint_failures() {
+        let preflight: ClientError = RpcError::RpcResponseError {
+            code: -32002,
+            message: "Transaction simulation failed".to_string(),
+            data: solana_client::rpc_request::RpcResponseErrorData::Empty,
+        }
+        .into();
+
+        assert!(!is_endpoint_failure(&preflight));
+        assert!(is_endpoint_failure(&io_error()));
+    }
+
+    #[test]
+    fn circuit_opens_after_consecutive_failures_and_closes_on_success() {
+        let endpoint = mock("a", 1, "succeeds");
+
+        endpoint.record(Duration::from_millis(5), Some(&io_error()), &breaker());
+        assert!(!endpoint.is_open());
+        endpoint.record(Duration::from_millis(5), Some(&io_error()), &breaker());
+        assert!(endpoint.is_open());
+
+        endpoint.record(Duration::from_millis(5), None, &breaker());
+        assert!(!endpoint.is_open());
+
+        let metrics = endpoint.metrics();
+        assert_eq!(metrics.requests, 3);
+        assert_eq!(metrics.errors, 2);
+    }
+
+    #[test]
+    fn open_and_unhealthy_endpoints_are_tried_last() {
+        let pool = RpcPool::new(
+            vec![mock("open", 100, "succeeds"), mock("closed", 1, "succeeds")],
+            breaker(),
+            3,
+            50,
+        );
+        for _ in 0..2 {
+            pool.endpoints[0].record(Duration::ZERO, Some(&io_error()), &pool.breaker);
+        }
+
+        for _ in 0..20 {
+            assert_eq!(pool.candidates()[0].label, "closed");
+        }
+    }
+
+    #[tokio::test]
+    async fn call_fails_over_to_the_next_endpoint() {
+        let pool = RpcPool::new(
+            vec![mock("a", 1, "succeeds"), mock("b", 1, "succeeds")],
+            breaker(),
+            3,
+            50,
+        );
+        let attempts = std::sync::atomic::AtomicUsize::new(0);
+
+        let slot = pool
+            .call(|client| {
+                let first = attempts.fetch_add(1, std::sync::atomic::Ordering::SeqCst) == 0;
+                async move {
+                    if first {
+                        Err(io_error())
+                    } else {
+                        client.get_slot().await
+                    }
+                }
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(slot, 0);
+        let errors: u64 = pool.metrics().iter().map(|m| m.errors).sum();
+        assert_eq!(errors, 1);
+    }
+}
@@ -0,0 +1,345 @@
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Nonce};
+use axum::http::StatusCode;
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use rand::RngCore;
+use secrecy::{ExposeSecret, SecretBox, SecretString};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use solana_sdk::{
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    transaction::Transaction,
+};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// PBKDF2 rounds used when sealing new keypair files.
+pub const DEFAULT_SEAL_ROUNDS: u32 = 600_000;
+
+/// Keypairs the backend co-signs action transactions with.
+///
+/// Every key carries a [`SigningPolicy`]: the blinks that may use it and the
+/// programs and instructions it may sign for. A key only signs transactions
+/// that name it as a signer, and only when every instruction passes its
+/// policy. Each signature is recorded in `signer_audit_log` before the
+/// transaction is handed out.
+pub struct SignerVault {
+    keys: HashMap<String, VaultKey>,
+}
+
+pub struct VaultKey {
+    keypair: Keypair,
+    pub policy: SigningPolicy,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SigningPolicy {
+    /// API key owners whose blinks may use the key. `None` allows every
+    /// blink, including anonymous ones.
+    pub owners: Option<Vec<String>>,
+    pub programs: Vec<ProgramRule>,
+}
+
+/// A program the key may sign for. With `data_prefixes`, only instructions
+/// whose data starts with one of them are allowed.
+#[derive(Debug, Clone)]
+pub struct ProgramRule {
+    pub program_id: Pubkey,
+    pub data_prefixes: Option<Vec<Vec<u8>>>,
+}
+
+impl SigningPolicy {
+    pub fn allows_owner(&self, owner: Option<&str>) -> bool {
+        match (&self.owners, owner) {
+            (None, _) => true,
+            (Some(owners), Some(owner)) => owners.iter().any(|o| o == owner),
+            (Some(_), None) => false,
+        }
+    }
+
+    /// Checks every instruction of the transaction, since a signature
+    /// authorizes all of them.
+    pub fn check(&self, transaction: &Transaction) -> Result<(), String> {
+        let message = &transaction.message;
+        for instruction in &message.instructions {
+            let program_id = message
+                .account_keys
+                .get(usize::from(instruction.program_id_index))
+                .ok_or("Instruction references a missing program")?;
+            let allowed = self.programs.iter().any(|rule| {
+                rule.program_id == *program_id
+                    && rule.data_prefixes.as_ref().is_none_or(|prefixes| {
+                        prefixes
+                            .iter()
+                            .any(|prefix| instruction.data.starts_with(prefix))
+                    })
+            });
+            if !allowed {
+                return Err(format!(
+                    "instruction of program {} is not allowed",
+                    program_id
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl SignerVault {
+    pub fn new(keys: HashMap<String, VaultKey>) -> Self {
+        Self { keys }
+    }
+
+    pub fn pubkey(&self, name: &str) -> Option<Pubkey> {
+        self.keys.get(name).map(|key| key.keypair.pubkey())
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = (&str, &VaultKey)> {
+        self.keys.iter().map(|(name, key)| (name.as_str(), key))
+    }
+
+    /// Checks that a blink of `owner` may use the key `name`.
+    pub fn check_owner(&self, name: &str, owner: Option<&str>) -> Result<(), String> {
+        let key = self
+            .keys
+            .get(name)
+            .ok_or_else(|| format!("Unknown signer: {}", name))?;
+        if !key.policy.allows_owner(owner) {
+            return Err(format!("Signer {} is not available to this blink", name));
+        }
+        Ok(())
+    }
+
+    /// Adds the signatures of the named keys that the transaction requires
+    /// and audit-logs each of them. Keys the transaction does not name as
+    /// signers are skipped.
+    pub async fn sign(
+        &self,
+        db: &PgPool,
+        names: &[String],
+        blink_id: Uuid,
+        owner: Option<&str>,
+        transaction: &mut Transaction,
+    ) -> Result<(), (StatusCode, String)> {
+        let signers = &transaction.message.account_keys
+            [..usize::from(transaction.message.header.num_required_signatures)];
+        let mut signing = Vec::new();
+        for name in names {
+            self.check_owner(name, owner)
+                .map_err(|e| (StatusCode::FORBIDDEN, e))?;
+            let key = &self.keys[name.as_str()];
+            if !signers.contains(&key.keypair.pubkey()) {
+                continue;
+            }
+            if let Err(e) = key.policy.check(transaction) {
+                tracing::warn!(signer = %name, %blink_id, "Refused to sign: {}", e);
+                return Err((
+                    StatusCode::FORBIDDEN,
+                    format!("Signer {} refused to sign: {}", name, e),
+                ));
+            }
+            signing.push((name, key));
+        }
+        if signing.is_empty() {
+            return Ok(());
+        }
+
+        let blockhash = transaction.message.recent_blockhash;
+        let keypairs = signing
+            .iter()
+            .map(|(_, key)| &key.keypair)
+            .collect::<Vec<_>>();
+        transaction
+            .try_partial_sign(&keypairs, blockhash)
+            .map_err(|e| {
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("Failed to sign transaction: {}", e),
+                )
+            })?;
+
+        let message = &transaction.message;
+        let payer = message.account_keys[0].to_string();
+        let programs = message
+            .instructions
+            .iter()
+            .map(|instruction| {
+                message.account_keys[usize::from(instruction.program_id_index)].to_string()
+            })
+            .collect::<Vec<_>>();
+        for (name, key) in signing {
+            let pubkey = key.keypair.pubkey();
+            let index = message
+                .account_keys
+                .iter()
+                .position(|account| *account == pubkey)
+                .expect("signer is an account of the message");
+            sqlx::query!(
+                r#"
+                INSERT INTO signer_audit_log (signer, pubkey, blink_id, payer, signature, programs)
+                VALUES ($1, $2, $3, $4, $5, $6)
+                "#,
+                name,
+                pubkey.to_string(),
+                blink_id,
+                payer,
+                transaction.signatures[index].to_string(),
+                &programs
+            )
+            .execute(db)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        }
+        Ok(())
+    }
+}
+
+impl VaultKey {
+    pub fn new(keypair: Keypair, policy: SigningPolicy) -> Self {
+        Self { keypair, policy }
+    }
+
+    pub fn pubkey(&self) -> Pubkey {
+        self.keypair.pubkey()
+    }
+}
+
+/// Names of the vault keys in a blink's `config.signers`.
+pub fn blink_signers(config: &serde_json::Value) -> Result<Vec<String>, String> {
+    match config.get("signers") {
+        None | Some(serde_json::Value::Null) => Ok(Vec::new()),
+        Some(signers) => {
+            Vec::<String>::deserialize(signers).map_err(|e| format!("Invalid signers: {}", e))
+        }
+    }
+}
+
+/// A keypair encrypted with AES-256-GCM under a PBKDF2-SHA256 key derived
+/// from a passphrase, stored as JSON.
+#[derive(Serialize, Deserialize)]
+struct SealedKeypair {
+    rounds: u32,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+/// Encrypts a keypair into the file format read by [`unseal`].
+pub fn seal(keypair: &Keypair, passphrase: &SecretString, rounds: u32) -> String {
+    let mut salt = [0u8; 16];
+    let mut nonce = [0u8; 12];
+    rand::thread_rng().fill_bytes(&mut salt);
+    rand::thread_rng().fill_bytes(&mut nonce);
+    let cipher = cipher(passphrase, &salt, rounds);
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), keypair.to_bytes().as_slice())
+        .expect("AES-GCM encryption does not fail");
+
+    serde_json::to_string_pretty(&SealedKeypair {
+        rounds,
+        salt: BASE64.encode(salt),
+        nonce: BASE64.encode(nonce),
+        ciphertext: BASE64.encode(ciphertext),
+    })
+    .expect("sealed keypairs serialize")
+}
+
+/// Decrypts a keypair file written by [`seal`].
+pub fn unseal(contents: &str, passphrase: &SecretString) -> Result<Keypair, String> {
+    let sealed: SealedKeypair =
+        serde_json::from_str(contents).map_err(|e| format!("Invalid keypair file: {}", e))?;
+    let decode = |field: &str| {
+        BASE64
+            .decode(field)
+            .map_err(|e| format!("Invalid keypair file: {}", e))
+    };
+    let salt = decode(&sealed.salt)?;
+    let nonce = decode(&sealed.nonce)?;
+    if nonce.len() != 12 {
+        return Err("Invalid keypair file: nonce must be 12 bytes".to_string());
+    }
+    let cipher = cipher(passphrase, &salt, sealed.rounds);
+    let bytes = SecretBox::new(Box::new(
+        cipher
+            .decrypt(
+                Nonce::from_slice(&nonce),
+                decode(&sealed.ciphertext)?.as_slice(),
+            )
+            .map_err(|_| "Wrong passphrase or corrupted keypair file".to_string())?,
+    ));
+    Keypair::from_bytes(bytes.expose_secret()).map_err(|e| format!("Invalid keypair: {}", e))
+}
+
+fn cipher(passphrase: &SecretString, salt: &[u8], rounds: u32) -> Aes256Gcm {
+    let mut key = [0u8; 32];
+    pbkdf2::pbkdf2_hmac::<Sha256>(
+        passphrase.expose_secret().as_bytes(),
+        salt,
+        rounds,
+        &mut key,
+    );
+    Aes256Gcm::new(&key.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::{message::Message, system_instruction, system_program};
+
+    fn transfer(from: &Pubkey) -> Transaction {
+        Transaction::new_unsigned(Message::new(
+            &[system_instruction::transfer(from, &Pubkey::new_unique(), 1)],
+            Some(from),
+        ))
+    }
+
+    #[test]
+    fn unseals_what_it_sealed() {
+        let keypair = Keypair::new();
+        let passphrase = SecretString::from("correct horse");
+
+        let sealed = seal(&keypair, &passphrase, 1000);
+
+        assert_eq!(
+            unseal(&sealed, &passphrase).unwrap().pubkey(),
+            keypair.pubkey()
+        );
+        assert!(unseal(&sealed, &SecretString::from("battery staple")).is_err());
+    }
+
+    #[test]
+    fn policy_matches_programs_and_data_prefixes() {
+        let payer = Pubkey::new_unique();
+        let mut policy = SigningPolicy {
+            owners: None,
+            programs: vec![ProgramRule {
+                program_id: system_program::id(),
+                // Transfer.
+                data_prefixes: Some(vec![vec![2, 0, 0, 0]]),
+            }],
+        };
+        assert!(policy.check(&transfer(&payer)).is_ok());
+
+        policy.programs[0].data_prefixes = Some(vec![vec![0, 0, 0, 0]]);
+        assert!(policy.check(&transfer(&payer)).is_err());
+
+        policy.programs[0].program_id = Pubkey::new_unique();
+        policy.programs[0].data_prefixes = None;
+        assert!(policy.check(&transfer(&payer)).is_err());
+    }
+
+    #[test]
+    fn owners_restrict_blinks() {
+        let policy = SigningPolicy {
+            owners: Some(vec!["acme".to_string()]),
+            programs: Vec::new(),
+        };
+
+        assert!(policy.allows_owner(Some("acme")));
+        assert!(!policy.allows_owner(Some("other")));
+        assert!(!policy.allows_owner(None));
+        assert!(SigningPolicy::default().allows_owner(None));
+    }
+}
@@ -0,0 +1,356 @@
+use crate::domain::SponsorConfig;
+use crate::handlers::MEMO_PROGRAM_ID;
+use crate::rpc_pool::RpcPool;
+use axum::http::StatusCode;
+use solana_sdk::{
+    compute_budget, instruction::Instruction, pubkey::Pubkey, signature::Signature, system_program,
+};
+use sqlx::PgPool;
+use std::str::FromStr;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Base fee the cluster charges per signature.
+pub const LAMPORTS_PER_SIGNATURE: u64 = 5_000;
+/// Signatures per `getSignatureStatuses` request.
+const MAX_SIGNATURES_PER_REQUEST: usize = 256;
+/// `SystemInstruction::Transfer`.
+const TRANSFER: [u8; 4] = [2, 0, 0, 0];
+
+/// Pays the fees of sponsored blinks from a vault key.
+///
+/// Every sponsored transaction is recorded as pending when it is built and
+/// counts against the blink's budget and the wallet's daily limits. Once its
+/// blockhash can no longer land, its status is read from the cluster: landed
+/// transactions stay charged, the others give their fee back to the budget.
+pub struct Sponsorship {
+    max_daily_per_wallet: u32,
+    settle_after: Duration,
+}
+
+impl Sponsorship {
+    pub fn new(max_daily_per_wallet: u32, settle_after: Duration) -> Self {
+        Self {
+            max_daily_per_wallet,
+            settle_after,
+        }
+    }
+
+    /// Checks that the sponsor only pays fees: instructions are memos or
+    /// transfers, and the sponsor is not an account of any of them.
+    pub fn check_instructions(
+        instructions: &[Instruction],
+        sponsor: &Pubkey,
+    ) -> Result<(), String> {
+        let memo_program = Pubkey::from_str(MEMO_PROGRAM_ID).expect("valid memo program id");
+        for instruction in instructions {
+            let allowed = instruction.program_id == memo_program
+                || (instruction.program_id == system_program::id()
+                    && instruction.data.starts_with(&TRANSFER));
+            if !allowed {
+                return Err(format!(
+                    "Instructions of program {} cannot be sponsored",
+                    instruction.program_id
+                ));
+            }
+            if instruction
+                .accounts
+                .iter()
+                .any(|account| account.pubkey == *sponsor)
+            {
+                return Err("The sponsor can only pay fees".to_string());
+            }
+        }
+        Ok(())
+    }
+
+    /// Priority fees come out of the sponsor's budget, so sponsored
+    /// transactions go without them.
+    pub fn strip_priority_fees(instructions: &mut Vec<Instruction>) {
+        instructions.retain(|instruction| instruction.program_id != compute_budget::id());
+    }
+
+    /// Records a sponsored transaction paying `fee` for `wallet`, or refuses
+    /// it with a `403` when the budget or a limit is exhausted.
+    pub async fn reserve(
+        &self,
+        db: &PgPool,
+        rpc: &RpcPool,
+        blink_id: Uuid,
+        config: &SponsorConfig,
+        wallet: &Pubkey,
+        fee: u64,
+    ) -> Result<i64, (StatusCode, String)> {
+        // Statuses are read before the blink is locked, so a slow cluster
+        // does not hold up other requests. Unsettled transactions keep
+        // counting against the budget until a later request settles them.
+        if let Err((_, e)) = self.settle_pending(db, rpc, blink_id).await {
+            tracing::warn!(%blink_id, "Failed to settle sponsored transactions: {}", e);
+        }
+
+        let mut transaction = db
+            .begin()
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        // Serializes budget checks of one blink.
+        sqlx::query!("SELECT id FROM blinks WHERE id = $1 FOR UPDATE", blink_id)
+            .fetch_one(&mut *transaction)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+        if let Some(message) = self
+            .limit_reached(&mut transaction, blink_id, config, wallet, fee)
+            .await?
+        {
+            return Err((StatusCode::FORBIDDEN, message));
+        }
+
+        let id = sqlx::query_scalar!(
+            r#"
+            INSERT INTO sponsored_transactions (blink_id, wallet, fee_lamports)
+            VALUES ($1, $2, $3)
+            RETURNING id
+            "#,
+            blink_id,
+            wallet.to_string(),
+            fee as i64
+        )
+        .fetch_one(&mut *transaction)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+        transaction
+            .commit()
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        Ok(id)
+    }
+
+    /// Why another transaction of `wallet` costing `fee` cannot be
+    /// sponsored, if it cannot.
+    async fn limit_reached(
+        &self,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+        blink_id: Uuid,
+        config: &SponsorConfig,
+        wallet: &Pubkey,
+        fee: u64,
+    ) -> Result<Option<String>, (StatusCode, String)> {
+        let usage = sqlx::query!(
+            r#"
+            SELECT
+                COALESCE(SUM(fee_lamports) FILTER (WHERE status <> 'expired'), 0)::BIGINT AS "charged!",
+                COUNT(*) FILTER (
+                    WHERE wallet = $2 AND created_at > now() - INTERVAL '1 day'
+                ) AS "wallet_today!"
+            FROM sponsored_transactions
+            WHERE blink_id = $1
+            "#,
+            blink_id,
+            wallet.to_string()
+        )
+        .fetch_one(&mut **transaction)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        if usage.charged as u64 + fee > config.budget_lamports {
+            return Ok(Some(
+                "The sponsorship budget of this blink is used up".to_string(),
+            ));
+        }
+        if usage.wallet_today >= i64::from(config.daily_wallet_limit) {
+            return Ok(Some(format!(
+                "This wallet has used its {} sponsored transactions for today",
+                config.daily_wallet_limit
+            )));
+        }
+
+        // Across all blinks, so new blinks do not reset a wallet's allowance.
+        let wallet_today = sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*) AS "count!"
+            FROM sponsored_transactions
+            WHERE wallet = $1 AND created_at > now() - INTERVAL '1 day'
+            "#,
+            wallet.to_string()
+        )
+        .fetch_one(&mut **transaction)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        if wallet_today >= i64::from(self.max_daily_per_wallet) {
+            return Ok(Some(
+                "This wallet has used all sponsored transactions for today".to_string(),
+            ));
+        }
+        Ok(None)
+    }
+
+    /// Stores the sponsor's signature, by which the transaction is looked up
+    /// on the cluster.
+    pub async fn record_signature(
+        &self,
+        db: &PgPool,
+        id: i64,
+        signature: &Signature,
+    ) -> Result<(), (StatusCode, String)> {
+        sqlx::query!(
+            "UPDATE sponsored_transactions SET signature = $2 WHERE id = $1",
+            id,
+            signature.to_string()
+        )
+        .execute(db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        Ok(())
+    }
+
+    /// Gives back a reservation whose transaction was not handed out, so it
+    /// counts against neither the budget nor the wallet's limits.
+    pub async fn cancel(&self, db: &PgPool, id: i64) -> Result<(), (StatusCode, String)> {
+        sqlx::query!("DELETE FROM sponsored_transactions WHERE id = $1", id)
+            .execute(db)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        Ok(())
+    }
+
+    /// Settles pending transactions of a blink that are old enough to have
+    /// landed or expired. Transactions that were never signed expire.
+    async fn settle_pending(
+        &self,
+        db: &PgPool,
+        rpc: &RpcPool,
+        blink_id: Uuid,
+    ) -> Result<(), (StatusCode, String)> {
+        let pending = sqlx::query!(
+            r#"
+            SELECT id, signature
+            FROM sponsored_transactions
+            WHERE blink_id = $1
+              AND status = 'pending'
+              AND created_at < now() - make_interval(secs => $2)
+            "#,
+            blink_id,
+            self.settle_after.as_secs_f64()
+        )
+        .fetch_all(db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        if pending.is_empty() {
+            return Ok(());
+        }
+
+        let signed = pending
+            .iter()
+            .filter_map(|row| Some((row.id, row.signature.as_deref()?)))
+            .map(|(id, signature)| Signature::from_str(signature).map(|signature| (id, signature)))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| {
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("Invalid stored signature: {}", e),
+                )
+            })?;
+        let mut landed = Vec::new();
+        for chunk in signed.chunks(MAX_SIGNATURES_PER_REQUEST) {
+            let signatures = chunk
+                .iter()
+                .map(|(_, signature)| *signature)
+                .collect::<Vec<_>>();
+            let statuses = rpc
+                .call(|client| {
+                    let signatures = signatures.clone();
+                    async move {
+                        client
+                            .get_signature_statuses_with_history(&signatures)
+                            .await
+                    }
+                })
+                .await
+                .map_err(|e| {
+                    (
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        format!("RPC Error: {}", e),
+                    )
+                })?
+                .value;
+            // Failed transactions are charged their fee as well.
+            landed.extend(
+                chunk
+                    .iter()
+                    .zip(statuses)
+                    .filter(|(_, status)| status.is_some())
+                    .map(|((id, _), _)| *id),
+            );
+        }
+
+        // Another request may have settled some of them meanwhile.
+        let ids = pending.iter().map(|row| row.id).collect::<Vec<_>>();
+        sqlx::query!(
+            r#"
+            UPDATE sponsored_transactions
+            SET status = CASE WHEN id = ANY($2) THEN 'landed' ELSE 'expired' END::sponsored_status
+            WHERE id = ANY($1) AND status = 'pending'
+            "#,
+            &ids,
+            &landed
+        )
+        .execute(db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::{instruction::AccountMeta, system_instruction};
+
+    fn memo(signer: &Pubkey) -> Instruction {
+        Instruction {
+            program_id: Pubkey::from_str(MEMO_PROGRAM_ID).unwrap(),
+            accounts: vec![AccountMeta::new_readonly(*signer, true)],
+            data: b"hello".to_vec(),
+        }
+    }
+
+    #[test]
+    fn sponsors_memos_and_transfers_of_the_user() {
+        let sponsor = Pubkey::new_unique();
+        let user = Pubkey::new_unique();
+        let instructions = vec![
+            memo(&user),
+            system_instruction::transfer(&user, &Pubkey::new_unique(), 1),
+        ];
+
+        assert!(Sponsorship::check_instructions(&instructions, &sponsor).is_ok());
+    }
+
+    #[test]
+    fn refuses_instructions_that_touch_the_sponsor() {
+        let sponsor = Pubkey::new_unique();
+        let user = Pubkey::new_unique();
+
+        for instruction in [
+            system_instruction::transfer(&sponsor, &user, 1),
+            memo(&sponsor),
+            system_instruction::assign(&user, &Pubkey::new_unique()),
+        ] {
+            assert!(Sponsorship::check_instructions(&[instruction], &sponsor).is_err());
+        }
+    }
+
+    #[test]
+    fn strips_priority_fees() {
+        let user = Pubkey::new_unique();
+        let mut instructions = vec![
+            solana_sdk::compute_budget::ComputeBudgetInstruction::set_compute_unit_price(50_000),
+            memo(&user),
+        ];
+
+        Sponsorship::strip_priority_fees(&mut instructions);
+
+        assert_eq!(instructions, vec![memo(&user)]);
+    }
+}
@@ -1,26 +1,159 @@
+use crate::authentication::AdminToken;
+use crate::blockhash_cache::BlockhashCache;
+use crate::claims::ClaimDistributor;
 use crate::configuration::{DatabaseSettings, Settings};
+use crate::domain::ActionRuleSet;
+use crate::escrows::EscrowAgent;
 use crate::handlers::{
-    create_blink, get_action_json, get_action_metadata, health, post_action_transaction,
+    cancel_invoice, cancel_subscription, confirm_action, create_api_key, create_blink,
+    create_invoice, diff_blink_revisions, get_action_json, get_action_metadata,
+    get_claim_allocation, get_escrow, get_invoice, get_invoice_receipt, get_raffle_draw,
+    get_share_page, get_sponsorship_usage, get_vote_results, health, list_api_keys,
+    list_blink_revisions, list_invoices, list_messages, list_nonce_accounts, list_revision_builds,
+    list_rpc_endpoints, list_signer_audit_log, list_signers, list_subscribers,
+    post_action_transaction, register_nonce_account, revoke_api_key, rollback_blink_revision,
+    rotate_api_key, update_blink, update_blink_slug, upload_allowlist,
 };
+use crate::metadata_cache::MetadataCache;
+use crate::nft_minter::NftMinter;
+use crate::nonce_pool::NoncePool;
+use crate::raffles::TicketBooth;
+use crate::rate_limit::{rate_limited, rate_limited_by_api_key};
+use crate::rpc_pool::RpcPool;
+use crate::signer_vault::SignerVault;
+use crate::sponsorship::Sponsorship;
+use crate::subscriptions::SubscriptionBiller;
+use crate::swaps::SwapRouter;
 use axum::{
     Router,
+    extract::FromRef,
     http::{Method, header},
-    routing::{get, post},
+    routing::{delete, get, post, put},
 };
 use sqlx::PgPool;
 use sqlx::postgres::PgPoolOptions;
-use std::net::TcpListener;
+use std::net::{SocketAddr, TcpListener};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::task::JoinHandle;
-use tower_governor::{
-    GovernorLayer, governor::GovernorConfigBuilder, key_extractor::PeerIpKeyExtractor,
-};
 use tower_http::cors::{Any, CorsLayer};
 
 pub struct Application {
     server_task: JoinHandle<Result<(), std::io::Error>>,
 }
 
+#[derive(Clone)]
+pub struct AppState {
+    pub db_pool: PgPool,
+    pub action_rules: Arc<ActionRuleSet>,
+    pub admin_token: AdminToken,
+    pub metadata_cache: Arc<MetadataCache>,
+    pub blockhash_cache: Arc<BlockhashCache>,
+    pub rpc_pool: Arc<RpcPool>,
+    /// `None` when durable nonces are disabled.
+    pub nonce_pool: Option<Arc<NoncePool>>,
+    /// `None` when mint blinks are disabled.
+    pub nft_minter: Option<Arc<NftMinter>>,
+    pub signer_vault: Arc<SignerVault>,
+    pub claim_distributor: Arc<ClaimDistributor>,
+    pub sponsorship: Option<Arc<Sponsorship>>,
+    pub subscription_biller: Arc<SubscriptionBiller>,
+    pub escrow_agent: Arc<EscrowAgent>,
+    pub ticket_booth: Arc<TicketBooth>,
+    /// `None` when swap blinks are disabled.
+    pub swap_router: Option<Arc<SwapRouter>>,
+}
+
+impl FromRef<AppState> for PgPool {
+    fn from_ref(state: &AppState) -> Self {
+        state.db_pool.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<ActionRuleSet> {
+    fn from_ref(state: &AppState) -> Self {
+        state.action_rules.clone()
+    }
+}
+
+impl FromRef<AppState> for AdminToken {
+    fn from_ref(state: &AppState) -> Self {
+        state.admin_token.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<MetadataCache> {
+    fn from_ref(state: &AppState) -> Self {
+        state.metadata_cache.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<BlockhashCache> {
+    fn from_ref(state: &AppState) -> Self {
+        state.blockhash_cache.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<RpcPool> {
+    fn from_ref(state: &AppState) -> Self {
+        state.rpc_pool.clone()
+    }
+}
+
+impl FromRef<AppState> for Option<Arc<NoncePool>> {
+    fn from_ref(state: &AppState) -> Self {
+        state.nonce_pool.clone()
+    }
+}
+
+impl FromRef<AppState> for Option<Arc<NftMinter>> {
+    fn from_ref(state: &AppState) -> Self {
+        state.nft_minter.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<SignerVault> {
+    fn from_ref(state: &AppState) -> Self {
+        state.signer_vault.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<ClaimDistributor> {
+    fn from_ref(state: &AppState) -> Self {
+        state.claim_distributor.clone()
+    }
+}
+
+impl FromRef<AppState> for Option<Arc<Sponsorship>> {
+    fn from_ref(state: &AppState) -> Self {
+        state.sponsorship.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<SubscriptionBiller> {
+    fn from_ref(state: &AppState) -> Self {
+        state.subscription_biller.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<EscrowAgent> {
+    fn from_ref(state: &AppState) -> Self {
+        state.escrow_agent.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<TicketBooth> {
+    fn from_ref(state: &AppState) -> Self {
+        state.ticket_booth.clone()
+    }
+}
+
+impl FromRef<AppState> for Option<Arc<SwapRouter>> {
+    fn from_ref(state: &AppState) -> Self {
+        state.swap_router.clone()
+    }
+}
+
 impl Application {
     pub async fn build(configuration: Settings) -> Result<Self, anyhow::Error> {
         let connection_pool = get_connection_pool(&configuration.database);
@@ -31,7 +164,7 @@ impl Application {
         );
         let listener = TcpListener::bind(&address)?;
 
-        let server_task = run(listener, connection_pool, false).await?;
+        let server_task = run(listener, connection_pool, &configuration).await?;
 
         tracing::info!("Server running at : {}", address);
 
@@ -50,59 +183,200 @@ pub fn get_connection_pool(configuration: &DatabaseSettings) -> PgPool {
 pub async fn run(
     listener: TcpListener,
     db_pool: PgPool,
-    enable_rate_limiting: bool,
-) -> Result<JoinHandle<Result<(), std::io::Error>>, std::io::Error> {
+    configuration: &Settings,
+) -> Result<JoinHandle<Result<(), std::io::Error>>, anyhow::Error> {
     listener.set_nonblocking(true)?;
     let tokio_listener = tokio::net::TcpListener::from_std(listener)?;
 
+    let action_rules = configuration
+        .actions
+        .rule_set()
+        .map_err(|e| anyhow::anyhow!("Invalid actions.json rule: {}", e))?;
+
+    let rpc_pool = Arc::new(
+        configuration
+            .solana
+            .rpc_pool()
+            .map_err(|e| anyhow::anyhow!("Invalid RPC configuration: {}", e))?,
+    );
+    if configuration.solana.rpc_pool.health_check_interval_ms > 0 {
+        rpc_pool.spawn_health_checks(Duration::from_millis(
+            configuration.solana.rpc_pool.health_check_interval_ms,
+        ));
+    }
+
+    let blockhash_cache = Arc::new(configuration.solana.blockhash_cache(rpc_pool.clone()));
+    if configuration.solana.blockhash_refresh_ms > 0 {
+        blockhash_cache.spawn_refresh(Duration::from_millis(
+            configuration.solana.blockhash_refresh_ms,
+        ));
+    }
+
+    let nonce_pool = configuration
+        .solana
+        .nonce_pool(blockhash_cache.clone())
+        .map_err(|e| anyhow::anyhow!("Invalid durable nonce configuration: {}", e))?
+        .map(Arc::new);
+    let reclaim_interval_ms = configuration.solana.durable_nonce.reclaim_interval_ms;
+    if let Some(nonce_pool) = &nonce_pool
+        && reclaim_interval_ms > 0
+    {
+        nonce_pool.spawn_reclaim(db_pool.clone(), Duration::from_millis(reclaim_interval_ms));
+    }
+
+    let signer_vault = Arc::new(
+        configuration
+            .solana
+            .signer_vault()
+            .map_err(|e| anyhow::anyhow!("Invalid signer configuration: {}", e))?,
+    );
+    let nft_minter = configuration
+        .solana
+        .nft_minter(&signer_vault)
+        .map_err(|e| anyhow::anyhow!("Invalid NFT mint configuration: {}", e))?
+        .map(Arc::new);
+
+    let subscription_biller = Arc::new(
+        configuration
+            .solana
+            .subscription_biller(blockhash_cache.clone(), signer_vault.clone()),
+    );
+    let billing_interval_ms = configuration.solana.subscriptions.billing_interval_ms;
+    if billing_interval_ms > 0 {
+        subscription_biller
+            .spawn_billing(db_pool.clone(), Duration::from_millis(billing_interval_ms));
+    }
+
+    let swap_router = configuration
+        .solana
+        .swap_router()
+        .map_err(|e| anyhow::anyhow!("Invalid swap configuration: {}", e))?
+        .map(Arc::new);
+
+    let state = AppState {
+        db_pool,
+        action_rules: Arc::new(action_rules),
+        admin_token: AdminToken(configuration.authentication.admin_token.clone()),
+        metadata_cache: Arc::new(configuration.metadata_cache.cache()),
+        blockhash_cache,
+        rpc_pool,
+        nonce_pool,
+        nft_minter,
+        signer_vault,
+        claim_distributor: Arc::new(configuration.solana.claim_distributor()),
+        sponsorship: configuration.solana.sponsorship().map(Arc::new),
+        subscription_biller,
+        escrow_agent: Arc::new(configuration.solana.escrow_agent()),
+        ticket_booth: Arc::new(configuration.solana.ticket_booth()),
+        swap_router,
+    };
+
     let cors = CorsLayer::new()
         .allow_origin(Any)
-        .allow_methods([Method::GET, Method::POST, Method::OPTIONS])
+        .allow_methods([
+            Method::GET,
+            Method::POST,
+            Method::PUT,
+            Method::DELETE,
+            Method::OPTIONS,
+        ])
         .allow_headers(Any)
         .expose_headers([
             header::HeaderName::from_static("x-action-version"),
             header::HeaderName::from_static("x-blockchain-ids"),
+            header::RETRY_AFTER,
+            header::ETAG,
         ]);
 
-    let app = if enable_rate_limiting {
-        let governor_conf = Arc::new(
-            GovernorConfigBuilder::default()
-                .per_second(60)
-                .burst_size(5)
-                .key_extractor(PeerIpKeyExtractor)
-                .finish()
-                .unwrap(),
-        );
+    let blinks = Router::new()
+        .route("/api/blinks", post(create_blink))
+        .route("/api/blinks/{id}", put(update_blink))
+        .route("/api/blinks/{id}/slug", put(update_blink_slug))
+        .route("/api/blinks/{id}/results", get(get_vote_results))
+        .route("/api/blinks/{id}/sponsorship", get(get_sponsorship_usage))
+        .route("/api/blinks/{id}/allowlist", put(upload_allowlist))
+        .route(
+            "/api/blinks/{id}/allowlist/{wallet}",
+            get(get_claim_allocation),
+        )
+        .route("/api/blinks/{id}/subscribers", get(list_subscribers))
+        .route(
+            "/api/blinks/{id}/subscribers/{wallet}/cancel",
+            post(cancel_subscription),
+        )
+        .route("/api/blinks/{id}/escrow", get(get_escrow))
+        .route("/api/blinks/{id}/draw", get(get_raffle_draw))
+        .route("/api/blinks/{id}/messages", get(list_messages))
+        .route("/api/blinks/{id}/revisions", get(list_blink_revisions))
+        .route("/api/blinks/{id}/revisions/diff", get(diff_blink_revisions))
+        .route(
+            "/api/blinks/{id}/revisions/{revision}/rollback",
+            post(rollback_blink_revision),
+        )
+        .route(
+            "/api/blinks/{id}/revisions/{revision}/builds",
+            get(list_revision_builds),
+        )
+        .route("/api/invoices", post(create_invoice).get(list_invoices))
+        .route("/api/invoices/{id}", get(get_invoice))
+        .route("/api/invoices/{id}/cancel", post(cancel_invoice))
+        .route("/api/invoices/{id}/receipt", get(get_invoice_receipt))
+        .route("/api/keys", post(create_api_key).get(list_api_keys))
+        .route("/api/keys/{id}", delete(revoke_api_key))
+        .route("/api/keys/{id}/rotate", post(rotate_api_key))
+        .route("/api/rpc/endpoints", get(list_rpc_endpoints))
+        .route(
+            "/api/nonces",
+            post(register_nonce_account).get(list_nonce_accounts),
+        )
+        .route("/api/signers", get(list_signers))
+        .route("/api/signers/audit", get(list_signer_audit_log));
+
+    let actions = Router::new()
+        .route(
+            "/api/actions/{id}",
+            get(get_action_metadata).post(post_action_transaction),
+        )
+        .route("/api/actions/{id}/confirm", post(confirm_action));
 
-        Router::new()
-            .route("/health", get(health))
-            .route("/.well-known/actions.json", get(get_action_json))
-            .route("/actions.json", get(get_action_json))
-            .route(
-                "/api/blinks",
-                post(create_blink).layer(GovernorLayer::new(governor_conf)),
-            )
-            .route(
-                "/api/actions/{id}",
-                get(get_action_metadata).post(post_action_transaction),
-            )
-            .layer(cors)
-            .with_state(db_pool)
+    let pages = Router::new()
+        .route("/.well-known/actions.json", get(get_action_json))
+        .route("/actions.json", get(get_action_json))
+        .route("/b/{id}", get(get_share_page));
+
+    let rate_limit = &configuration.rate_limit;
+    let (blinks, actions, pages) = if rate_limit.enabled {
+        (
+            rate_limited_by_api_key(
+                blinks,
+                &rate_limit.blinks,
+                &rate_limit.api_keys,
+                rate_limit,
+                state.db_pool.clone(),
+                state.admin_token.clone(),
+            ),
+            rate_limited(actions, &rate_limit.actions, rate_limit),
+            rate_limited(pages, &rate_limit.pages, rate_limit),
+        )
     } else {
-        Router::new()
-            .route("/health", get(health))
-            .route("/.well-known/actions.json", get(get_action_json))
-            .route("/actions.json", get(get_action_json))
-            .route("/api/blinks", post(create_blink))
-            .route(
-                "/api/actions/{id}",
-                get(get_action_metadata).post(post_action_transaction),
-            )
-            .layer(cors)
-            .with_state(db_pool)
+        (blinks, actions, pages)
     };
 
-    let handle = tokio::spawn(async move { axum::serve(tokio_listener, app).await });
+    let app = Router::new()
+        .route("/health", get(health))
+        .merge(blinks)
+        .merge(actions)
+        .merge(pages)
+        .layer(cors)
+        .with_state(state);
+
+    let handle = tokio::spawn(async move {
+        axum::serve(
+            tokio_listener,
+            app.into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .await
+    });
 
     Ok(handle)
 }
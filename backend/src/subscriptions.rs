@@ -0,0 +1,473 @@
+use crate::blockhash_cache::BlockhashCache;
+use crate::domain::SubscriptionConfig;
+use crate::invoices::Currency;
+use crate::signer_vault::SignerVault;
+use axum::http::StatusCode;
+use solana_sdk::{
+    message::Message,
+    pubkey::Pubkey,
+    signature::{Keypair, Signature, Signer},
+    transaction::Transaction,
+};
+use sqlx::PgPool;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Bills subscription blinks.
+///
+/// Solana has no pull payments, so every period is paid by a transaction
+/// of its own: built by the subscriber from the blink or a renewal link, or
+/// charged by the blink's delegate from a token allowance the subscriber
+/// approved. Each payment carries a fresh reference key and extends the
+/// subscription once it is found on the cluster. Payments that are not
+/// found within `pending` are dropped.
+pub struct SubscriptionBiller {
+    blockhash_cache: Arc<BlockhashCache>,
+    signer_vault: Arc<SignerVault>,
+    pending: Duration,
+}
+
+/// An allowance approved along with a payment.
+#[derive(Debug, Clone, Copy)]
+pub struct Approval {
+    pub delegate: Pubkey,
+    pub amount: u64,
+}
+
+impl SubscriptionBiller {
+    pub fn new(
+        blockhash_cache: Arc<BlockhashCache>,
+        signer_vault: Arc<SignerVault>,
+        pending: Duration,
+    ) -> Self {
+        Self {
+            blockhash_cache,
+            signer_vault,
+            pending,
+        }
+    }
+
+    /// Records a payment `subscriber` is about to make, subscribing it first
+    /// if needed, and returns the reference the transfer must carry.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn open_payment(
+        &self,
+        db: &PgPool,
+        blink_id: Uuid,
+        subscriber: &Pubkey,
+        currency: &Currency,
+        amount: u64,
+        period_days: u32,
+        approval: Option<Approval>,
+    ) -> Result<Pubkey, (StatusCode, String)> {
+        let subscription_id = sqlx::query_scalar!(
+            r#"
+            INSERT INTO subscriptions (blink_id, subscriber)
+            VALUES ($1, $2)
+            ON CONFLICT (blink_id, subscriber) DO UPDATE SET subscriber = EXCLUDED.subscriber
+            RETURNING id
+            "#,
+            blink_id,
+            subscriber.to_string()
+        )
+        .fetch_one(db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+        let reference = Keypair::new().pubkey();
+        insert_payment(
+            db,
+            &reference,
+            subscription_id,
+            currency,
+            amount,
+            period_days,
+            approval,
+            false,
+        )
+        .await?;
+        Ok(reference)
+    }
+
+    /// Looks the open payments of a blink, or of every blink, up on the
+    /// cluster. Landed payments renew their subscription, payments not found
+    /// in time are dropped. When the cluster cannot be reached the payments
+    /// stay open for a later attempt.
+    pub async fn settle(
+        &self,
+        db: &PgPool,
+        blink_id: Option<Uuid>,
+    ) -> Result<(), (StatusCode, String)> {
+        let open = sqlx::query!(
+            r#"
+            SELECT
+                p.reference, p.currency, p.decimals, p.amount, b.wallet_address,
+                p.created_at < now() - make_interval(secs => $2) AS "expired!"
+            FROM subscription_payments p
+            JOIN subscriptions s ON s.id = p.subscription_id
+            JOIN blinks b ON b.id = s.blink_id
+            WHERE p.confirmed_at IS NULL AND ($1::UUID IS NULL OR s.blink_id = $1)
+            ORDER BY p.created_at
+            "#,
+            blink_id,
+            self.pending.as_secs_f64()
+        )
+        .fetch_all(db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+        let rpc = self.blockhash_cache.rpc();
+        for payment in open {
+            let currency = Currency::from_stored(&payment.currency, payment.decimals)
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+            let merchant = parse_stored(&payment.wallet_address, "wallet")?;
+            let reference = parse_stored(&payment.reference, "reference")?;
+
+            match currency
+                .find_payment(rpc, &merchant, payment.amount as u64, &reference)
+                .await
+            {
+                Ok(Some(landed)) => confirm(db, &payment.reference, &landed.signature).await?,
+                Ok(None) if payment.expired => {
+                    sqlx::query!(
+                        r#"
+                        DELETE FROM subscription_payments
+                        WHERE reference = $1 AND confirmed_at IS NULL
+                        "#,
+                        payment.reference
+                    )
+                    .execute(db)
+                    .await
+                    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    tracing::warn!("Failed to settle subscription payments: {}", e);
+                    return Ok(());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Charges the renewals falling due within a day from the allowances
+    /// subscribers approved, and returns how many charges were sent.
+    pub async fn charge_due(&self, db: &PgPool) -> Result<usize, (StatusCode, String)> {
+        // A subscription with a payment in flight is not charged on top.
+        let due = sqlx::query!(
+            r#"
+            SELECT
+                s.id, s.blink_id, s.subscriber, s.delegate as "delegate!",
+                s.delegate_mint as "delegate_mint!", s.allowance,
+                b.wallet_address, b.config, b.owner
+            FROM subscriptions s
+            JOIN blinks b ON b.id = s.blink_id
+            WHERE s.status = 'active'
+              AND b.type = 'subscription'
+              AND s.delegate IS NOT NULL
+              AND s.delegate_mint IS NOT NULL
+              AND s.allowance > 0
+              AND s.paid_through < now() + interval '1 day'
+              AND NOT EXISTS (
+                  SELECT 1 FROM subscription_payments p
+                  WHERE p.subscription_id = s.id AND p.confirmed_at IS NULL
+              )
+            "#
+        )
+        .fetch_all(db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+        let mut charged = 0;
+        for subscription in due {
+            let config = match SubscriptionConfig::from_config(&subscription.config) {
+                Ok(config) => config,
+                Err(e) => {
+                    tracing::warn!(blink_id = %subscription.blink_id, "Skipping renewal: {}", e);
+                    continue;
+                }
+            };
+            // Allowances only cover the delegate and mint they were given for.
+            let Some(name) = &config.delegate else {
+                continue;
+            };
+            let Some(delegate) = self
+                .signer_vault
+                .pubkey(name)
+                .filter(|key| key.to_string() == subscription.delegate)
+            else {
+                continue;
+            };
+            if config.currency != subscription.delegate_mint {
+                continue;
+            }
+            let charge = Charge {
+                subscription_id: subscription.id,
+                blink_id: subscription.blink_id,
+                owner: subscription.owner.as_deref(),
+                delegate_name: name,
+                delegate: &delegate,
+                subscriber: parse_stored(&subscription.subscriber, "subscriber")?,
+                merchant: parse_stored(&subscription.wallet_address, "wallet")?,
+                allowance: subscription.allowance as u64,
+            };
+            match self.charge(db, &config, &charge).await {
+                Ok(true) => charged += 1,
+                Ok(false) => {}
+                Err((_, e)) => {
+                    tracing::warn!(
+                        subscription_id = %subscription.id,
+                        "Failed to charge renewal: {}",
+                        e
+                    );
+                }
+            }
+        }
+        Ok(charged)
+    }
+
+    /// Settles open payments and charges due renewals every `interval` for
+    /// as long as the biller is alive.
+    pub fn spawn_billing(self: &Arc<Self>, db: PgPool, interval: Duration) {
+        let biller = Arc::downgrade(self);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(interval);
+            loop {
+                interval.tick().await;
+                let Some(biller) = biller.upgrade() else {
+                    break;
+                };
+                if let Err((_, e)) = biller.settle(&db, None).await {
+                    tracing::warn!("Failed to settle subscription payments: {}", e);
+                }
+                if let Err((_, e)) = biller.charge_due(&db).await {
+                    tracing::warn!("Failed to charge subscription renewals: {}", e);
+                }
+            }
+        });
+    }
+
+    /// Sends one delegated charge. `false` when the allowance left does not
+    /// cover the price.
+    async fn charge(
+        &self,
+        db: &PgPool,
+        config: &SubscriptionConfig,
+        charge: &Charge<'_>,
+    ) -> Result<bool, (StatusCode, String)> {
+        let rpc = self.blockhash_cache.rpc();
+        let currency = Currency::resolve(rpc, &config.currency)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+        let amount = currency.base_units(config.price);
+        if amount > charge.allowance {
+            return Ok(false);
+        }
+
+        let reference = Keypair::new().pubkey();
+        let instructions = currency
+            .delegated_payment_instructions(
+                charge.delegate,
+                &charge.subscriber,
+                &charge.merchant,
+                amount,
+                &reference,
+            )
+            .ok_or((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "SOL cannot be charged by a delegate".to_string(),
+            ))?;
+        insert_payment(
+            db,
+            &reference,
+            charge.subscription_id,
+            &currency,
+            amount,
+            config.period_days,
+            None,
+            true,
+        )
+        .await?;
+
+        let sent = async {
+            let blockhash = self
+                .blockhash_cache
+                .get()
+                .await
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+                .blockhash;
+            let message =
+                Message::new_with_blockhash(&instructions, Some(charge.delegate), &blockhash);
+            let mut transaction = Transaction::new_unsigned(message);
+            self.signer_vault
+                .sign(
+                    db,
+                    &[charge.delegate_name.to_string()],
+                    charge.blink_id,
+                    charge.owner,
+                    &mut transaction,
+                )
+                .await?;
+            rpc.call(|client| {
+                let transaction = transaction.clone();
+                async move { client.send_transaction(&transaction).await }
+            })
+            .await
+            .map_err(|e| {
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("RPC Error: {}", e),
+                )
+            })
+        }
+        .await;
+
+        match sent {
+            Ok(signature) => {
+                sqlx::query!(
+                    "UPDATE subscription_payments SET signature = $2 WHERE reference = $1",
+                    reference.to_string(),
+                    signature.to_string()
+                )
+                .execute(db)
+                .await
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+                Ok(true)
+            }
+            Err(e) => {
+                // Retried on the next run.
+                sqlx::query!(
+                    "DELETE FROM subscription_payments WHERE reference = $1",
+                    reference.to_string()
+                )
+                .execute(db)
+                .await
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+                Err(e)
+            }
+        }
+    }
+}
+
+/// A renewal charged from a subscriber's allowance.
+struct Charge<'a> {
+    subscription_id: Uuid,
+    blink_id: Uuid,
+    owner: Option<&'a str>,
+    delegate_name: &'a str,
+    delegate: &'a Pubkey,
+    subscriber: Pubkey,
+    merchant: Pubkey,
+    allowance: u64,
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn insert_payment(
+    db: &PgPool,
+    reference: &Pubkey,
+    subscription_id: Uuid,
+    currency: &Currency,
+    amount: u64,
+    period_days: u32,
+    approval: Option<Approval>,
+    charged: bool,
+) -> Result<(), (StatusCode, String)> {
+    sqlx::query!(
+        r#"
+        INSERT INTO subscription_payments (
+            reference, subscription_id, currency, decimals, amount, period_days,
+            delegate, approves, charged
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+        "#,
+        reference.to_string(),
+        subscription_id,
+        currency.code(),
+        i16::from(currency.decimals()),
+        amount as i64,
+        period_days as i32,
+        approval.map(|approval| approval.delegate.to_string()),
+        approval.map(|approval| approval.amount.min(i64::MAX as u64) as i64),
+        charged
+    )
+    .execute(db)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    Ok(())
+}
+
+/// Marks a payment landed and renews its subscription from the end of the
+/// paid period, or from when the payment was built once it has lapsed.
+/// Payments of the subscriber reactivate cancelled subscriptions; charges
+/// draw down the allowance.
+async fn confirm(
+    db: &PgPool,
+    reference: &str,
+    signature: &Signature,
+) -> Result<(), (StatusCode, String)> {
+    let mut transaction = db
+        .begin()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let payment = sqlx::query!(
+        r#"
+        UPDATE subscription_payments
+        SET signature = $2, confirmed_at = now()
+        WHERE reference = $1 AND confirmed_at IS NULL
+        RETURNING
+            subscription_id, currency, amount, period_days, delegate, approves, charged,
+            created_at
+        "#,
+        reference,
+        signature.to_string()
+    )
+    .fetch_optional(&mut *transaction)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let Some(payment) = payment else {
+        return Ok(());
+    };
+
+    sqlx::query!(
+        r#"
+        UPDATE subscriptions
+        SET paid_through = GREATEST(paid_through, $2) + make_interval(days => $3),
+            status = CASE WHEN $4 THEN status ELSE 'active' END,
+            delegate = CASE WHEN $5::TEXT IS NULL THEN delegate ELSE $5 END,
+            delegate_mint = CASE WHEN $5::TEXT IS NULL THEN delegate_mint ELSE $6 END,
+            allowance = CASE
+                WHEN $7::BIGINT IS NOT NULL THEN $7
+                WHEN $4 THEN GREATEST(allowance - $8, 0)
+                ELSE allowance
+            END
+        WHERE id = $1
+        "#,
+        payment.subscription_id,
+        payment.created_at,
+        payment.period_days,
+        payment.charged,
+        payment.delegate,
+        payment.currency,
+        payment.approves,
+        payment.amount
+    )
+    .execute(&mut *transaction)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    transaction
+        .commit()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
+fn parse_stored(pubkey: &str, name: &str) -> Result<Pubkey, (StatusCode, String)> {
+    Pubkey::from_str(pubkey).map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Invalid stored {}: {}", name, e),
+        )
+    })
+}
@@ -0,0 +1,272 @@
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use futures::future::BoxFuture;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use solana_sdk::{pubkey::Pubkey, transaction::VersionedTransaction};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// A swap of `amount` base units of `input_mint` for `output_mint`, signed
+/// and paid for by `user`.
+#[derive(Debug, Clone)]
+pub struct SwapRequest {
+    pub input_mint: Pubkey,
+    pub output_mint: Pubkey,
+    pub amount: u64,
+    pub slippage_bps: u16,
+    /// Referral fee taken by the platform, sent to `fee_account`.
+    pub fee_bps: u16,
+    pub fee_account: Option<Pubkey>,
+    pub user: Pubkey,
+}
+
+/// A quoted route and the transaction executing it.
+#[derive(Debug, Clone)]
+pub struct PreparedSwap {
+    /// Base units of the output mint the route is quoted at.
+    pub out_amount: u64,
+    /// Least the user receives within the slippage.
+    pub min_out_amount: u64,
+    pub transaction: VersionedTransaction,
+}
+
+/// Quotes swap routes and prepares their transactions.
+pub trait QuoteProvider: Send + Sync {
+    /// Name of the provider in logs and errors.
+    fn name(&self) -> &str;
+
+    fn prepare<'a>(
+        &'a self,
+        request: &'a SwapRequest,
+    ) -> BoxFuture<'a, Result<PreparedSwap, String>>;
+}
+
+/// Client of a Jupiter-compatible swap API: `GET /quote` for a route and
+/// `POST /swap` for its transaction.
+pub struct JupiterClient {
+    base_url: String,
+    http: reqwest::Client,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JupiterQuote {
+    #[serde(deserialize_with = "serde_aux::field_attributes::deserialize_number_from_string")]
+    out_amount: u64,
+    #[serde(deserialize_with = "serde_aux::field_attributes::deserialize_number_from_string")]
+    other_amount_threshold: u64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JupiterSwap {
+    swap_transaction: String,
+}
+
+impl JupiterClient {
+    pub fn new(base_url: &str, timeout: Duration) -> Result<Self, String> {
+        let http = reqwest::Client::builder()
+            .timeout(timeout)
+            .build()
+            .map_err(|e| format!("Failed to build HTTP client: {}", e))?;
+        Ok(Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            http,
+        })
+    }
+
+    async fn quote(&self, request: &SwapRequest) -> Result<Value, String> {
+        let mut query = vec![
+            ("inputMint", request.input_mint.to_string()),
+            ("outputMint", request.output_mint.to_string()),
+            ("amount", request.amount.to_string()),
+            ("slippageBps", request.slippage_bps.to_string()),
+        ];
+        if request.fee_bps > 0 {
+            query.push(("platformFeeBps", request.fee_bps.to_string()));
+        }
+        let response = self
+            .http
+            .get(format!("{}/quote", self.base_url))
+            .query(&query)
+            .send()
+            .await
+            .map_err(|e| format!("Quote request failed: {}", e))?;
+        read_json(response).await
+    }
+
+    async fn swap(&self, request: &SwapRequest) -> Result<PreparedSwap, String> {
+        let quote_response = self.quote(request).await?;
+        let quote = JupiterQuote::deserialize(&quote_response)
+            .map_err(|e| format!("Invalid quote: {}", e))?;
+
+        let mut body = json!({
+            "quoteResponse": quote_response,
+            "userPublicKey": request.user.to_string(),
+            "wrapAndUnwrapSol": true,
+            "dynamicComputeUnitLimit": true,
+        });
+        if let Some(fee_account) = request.fee_account.filter(|_| request.fee_bps > 0) {
+            body["feeAccount"] = json!(fee_account.to_string());
+        }
+        let response = self
+            .http
+            .post(format!("{}/swap", self.base_url))
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| format!("Swap request failed: {}", e))?;
+        let swap = JupiterSwap::deserialize(&read_json(response).await?)
+            .map_err(|e| format!("Invalid swap: {}", e))?;
+        let bytes = BASE64
+            .decode(&swap.swap_transaction)
+            .map_err(|e| format!("Invalid swap transaction: {}", e))?;
+        let transaction =
+            bincode::deserialize(&bytes).map_err(|e| format!("Invalid swap transaction: {}", e))?;
+
+        Ok(PreparedSwap {
+            out_amount: quote.out_amount,
+            min_out_amount: quote.other_amount_threshold,
+            transaction,
+        })
+    }
+}
+
+impl QuoteProvider for JupiterClient {
+    fn name(&self) -> &str {
+        &self.base_url
+    }
+
+    fn prepare<'a>(
+        &'a self,
+        request: &'a SwapRequest,
+    ) -> BoxFuture<'a, Result<PreparedSwap, String>> {
+        Box::pin(self.swap(request))
+    }
+}
+
+/// Body of a response, or the error the API reported.
+async fn read_json(response: reqwest::Response) -> Result<Value, String> {
+    let status = response.status();
+    let body: Value = response
+        .json()
+        .await
+        .map_err(|e| format!("Invalid response ({}): {}", status, e))?;
+    if !status.is_success() {
+        let error = body["error"].as_str().unwrap_or("no details");
+        return Err(format!("{}: {}", status, error));
+    }
+    Ok(body)
+}
+
+/// Prepares swaps with the first provider that returns a route, and only
+/// hands out transactions that call allowlisted programs.
+pub struct SwapRouter {
+    providers: Vec<Arc<dyn QuoteProvider>>,
+    allowed_programs: Vec<Pubkey>,
+}
+
+impl SwapRouter {
+    pub fn new(providers: Vec<Arc<dyn QuoteProvider>>, allowed_programs: Vec<Pubkey>) -> Self {
+        Self {
+            providers,
+            allowed_programs,
+        }
+    }
+
+    /// Tries every provider in order. A transaction that fails the checks
+    /// counts as a failed provider.
+    pub async fn prepare(&self, request: &SwapRequest) -> Result<PreparedSwap, String> {
+        let mut last_error = "No quote provider is configured".to_string();
+        for provider in &self.providers {
+            let swap = provider.prepare(request).await.and_then(|swap| {
+                self.check_transaction(&swap.transaction, &request.user)?;
+                Ok(swap)
+            });
+            match swap {
+                Ok(swap) => return Ok(swap),
+                Err(error) => {
+                    tracing::warn!(provider = %provider.name(), "Swap not prepared: {}", error);
+                    last_error = error;
+                }
+            }
+        }
+        Err(last_error)
+    }
+
+    /// Checks that `user` is the only signer and pays the fee, and that every
+    /// instruction calls an allowlisted program.
+    pub fn check_transaction(
+        &self,
+        transaction: &VersionedTransaction,
+        user: &Pubkey,
+    ) -> Result<(), String> {
+        let message = &transaction.message;
+        let keys = message.static_account_keys();
+        if keys.first() != Some(user) || message.header().num_required_signatures != 1 {
+            return Err("Swap transaction must be signed by the user alone".to_string());
+        }
+        // Programs cannot be loaded from lookup tables, so they are always
+        // static keys.
+        for instruction in message.instructions() {
+            let program = keys
+                .get(usize::from(instruction.program_id_index))
+                .ok_or("Swap transaction calls a program outside its keys")?;
+            if !self.allowed_programs.contains(program) {
+                return Err(format!(
+                    "Swap transaction calls program {}, which is not allowed",
+                    program
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::{
+        hash::Hash,
+        instruction::{AccountMeta, Instruction},
+        message::{VersionedMessage, v0},
+        system_instruction,
+    };
+
+    fn transaction(payer: &Pubkey, instructions: &[Instruction]) -> VersionedTransaction {
+        let message = v0::Message::try_compile(payer, instructions, &[], Hash::default()).unwrap();
+        VersionedTransaction {
+            signatures: vec![Default::default(); 1],
+            message: VersionedMessage::V0(message),
+        }
+    }
+
+    #[test]
+    fn only_allowlisted_programs_pass() {
+        let router = SwapRouter::new(Vec::new(), vec![solana_sdk::system_program::id()]);
+        let user = Pubkey::new_unique();
+        let transfer = system_instruction::transfer(&user, &Pubkey::new_unique(), 1);
+        let drain = Instruction::new_with_bytes(
+            Pubkey::new_unique(),
+            &[],
+            vec![AccountMeta::new(user, true)],
+        );
+
+        assert!(
+            router
+                .check_transaction(&transaction(&user, std::slice::from_ref(&transfer)), &user)
+                .is_ok()
+        );
+        assert!(
+            router
+                .check_transaction(&transaction(&user, &[transfer.clone(), drain]), &user)
+                .is_err()
+        );
+        let other = Pubkey::new_unique();
+        assert!(
+            router
+                .check_transaction(&transaction(&other, &[transfer]), &user)
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,118 @@
+mod helpers;
+
+use helpers::spawn_app;
+
+async fn get_rules(address: &str) -> Vec<serde_json::Value> {
+    let response = reqwest::get(format!("{}/.well-known/actions.json", address))
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(200, response.status().as_u16());
+
+    let body: serde_json::Value = response.json().await.unwrap();
+    body["rules"].as_array().unwrap().clone()
+}
+
+#[tokio::test]
+async fn actions_json_serves_configured_rules_in_spec_format() {
+    let app = spawn_app().await;
+
+    let rules = get_rules(&app.address).await;
+
+    assert_eq!(rules.len(), 2);
+    assert_eq!(rules[0]["pathPattern"], "/b/*");
+    assert_eq!(rules[1]["pathPattern"], "/api/actions/*");
+    assert!(
+        rules[1]["apiPath"]
+            .as_str()
+            .unwrap()
+            .ends_with("/api/actions/*")
+    );
+}
+
+#[tokio::test]
+async fn actions_json_appends_rules_stored_in_the_database() {
+    let app = spawn_app().await;
+    sqlx::query(
+        "INSERT INTO action_rules (position, path_pattern, api_path) VALUES
+            (2, '/campaigns/**', 'https://api.example.com/api/actions/**'),
+            (1, '/donate', '/api/actions/coffee')",
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    let rules = get_rules(&app.address).await;
+
+    assert_eq!(rules.len(), 4);
+    assert_eq!(rules[2]["pathPattern"], "/donate");
+    assert!(
+        rules[2]["apiPath"]
+            .as_str()
+            .unwrap()
+            .ends_with("/api/actions/coffee")
+    );
+    assert_eq!(rules[3]["pathPattern"], "/campaigns/**");
+    assert_eq!(
+        rules[3]["apiPath"],
+        "https://api.example.com/api/actions/**"
+    );
+}
+
+#[tokio::test]
+async fn actions_json_applies_stored_exclusions() {
+    let app = spawn_app().await;
+    sqlx::query(
+        "INSERT INTO action_rules (position, path_pattern, api_path, exclude) VALUES
+            (1, '/blog/**', NULL, true),
+            (2, '/blog/donate', '/api/actions/coffee', false)",
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    let rules = get_rules(&app.address).await;
+
+    assert_eq!(rules.len(), 2);
+    assert!(rules.iter().all(|rule| rule["pathPattern"] != "/blog/**"));
+    assert!(
+        rules
+            .iter()
+            .all(|rule| rule["pathPattern"] != "/blog/donate")
+    );
+}
+
+#[tokio::test]
+async fn actions_json_skips_invalid_stored_rules() {
+    let app = spawn_app().await;
+    sqlx::query(
+        "INSERT INTO action_rules (path_pattern, api_path) VALUES
+            ('/bad/**/glob', '/api/actions/**'),
+            ('no-leading-slash', '/api/actions/x')",
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    let rules = get_rules(&app.address).await;
+
+    assert_eq!(rules.len(), 2);
+}
+
+#[tokio::test]
+async fn actions_json_skips_stored_rules_an_exclusion_covers_in_part() {
+    let app = spawn_app().await;
+    sqlx::query(
+        "INSERT INTO action_rules (position, path_pattern, api_path, exclude) VALUES
+            (1, '/blog/about', NULL, true),
+            (2, '/blog/*', '/api/actions/*', false),
+            (3, '/donate', '/api/actions/coffee', false)",
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    let rules = get_rules(&app.address).await;
+
+    assert_eq!(rules.len(), 3);
+    assert_eq!(rules[2]["pathPattern"], "/donate");
+}
@@ -0,0 +1,208 @@
+mod helpers;
+
+use helpers::{ADMIN_TOKEN, TestApp, donation_blink, spawn_app, spawn_app_with};
+use reqwest::Client;
+use secrecy::SecretString;
+use serde_json::json;
+
+async fn post_key(app: &TestApp, token: &str, body: &serde_json::Value) -> reqwest::Response {
+    Client::new()
+        .post(format!("{}/api/keys", &app.address))
+        .bearer_auth(token)
+        .json(body)
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+async fn list_keys(app: &TestApp) -> Vec<serde_json::Value> {
+    Client::new()
+        .get(format!("{}/api/keys?owner=acme", &app.address))
+        .bearer_auth(ADMIN_TOKEN)
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json()
+        .await
+        .expect("Failed to parse response")
+}
+
+async fn post_blink_with(app: &TestApp, key: &str) -> reqwest::Response {
+    Client::new()
+        .post(format!("{}/api/blinks", &app.address))
+        .bearer_auth(key)
+        .json(&donation_blink())
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+#[tokio::test]
+async fn key_management_requires_the_admin_token() {
+    let app = spawn_app().await;
+    let body = json!({ "name": "ci", "owner": "acme", "scopes": ["create"] });
+
+    let response = post_key(&app, "wrong-token", &body).await;
+
+    assert_eq!(401, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn key_management_is_disabled_without_an_admin_token() {
+    let app = spawn_app_with(|c| c.authentication.admin_token = SecretString::from("")).await;
+    let body = json!({ "name": "ci", "owner": "acme", "scopes": ["create"] });
+
+    let response = post_key(&app, ADMIN_TOKEN, &body).await;
+
+    assert_eq!(403, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn created_key_is_shown_once_and_stored_hashed() {
+    let app = spawn_app().await;
+    let body = json!({ "name": "ci", "owner": "acme", "scopes": ["create", "read"] });
+
+    let response = post_key(&app, ADMIN_TOKEN, &body).await;
+    assert_eq!(200, response.status().as_u16());
+    let created: serde_json::Value = response.json().await.unwrap();
+    let key = created["key"].as_str().unwrap();
+    assert!(key.starts_with(created["prefix"].as_str().unwrap()));
+    assert_eq!(created["scopes"], json!(["create", "read"]));
+
+    let stored = sqlx::query!("SELECT key_hash FROM api_keys")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_ne!(stored.key_hash, key);
+
+    let listed = list_keys(&app).await;
+    assert_eq!(listed.len(), 1);
+    assert!(listed[0].get("key").is_none());
+}
+
+#[tokio::test]
+async fn create_key_rejects_unknown_scopes() {
+    let app = spawn_app().await;
+    let body = json!({ "name": "ci", "owner": "acme", "scopes": ["admin"] });
+
+    let response = post_key(&app, ADMIN_TOKEN, &body).await;
+
+    assert_eq!(422, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn blinks_created_with_a_key_belong_to_its_owner_and_record_use() {
+    let app = spawn_app().await;
+    let key = app.create_api_key("acme", &["create"]).await;
+
+    let blink = app.create_blink_with_key(&key, &donation_blink()).await;
+
+    let owner = sqlx::query_scalar!(
+        "SELECT owner FROM blinks WHERE id = $1",
+        uuid::Uuid::parse_str(blink["id"].as_str().unwrap()).unwrap()
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(owner.as_deref(), Some("acme"));
+    assert!(!list_keys(&app).await[0]["last_used_at"].is_null());
+}
+
+#[tokio::test]
+async fn keys_without_the_scope_are_forbidden() {
+    let app = spawn_app().await;
+    let key = app.create_api_key("acme", &["read"]).await;
+
+    let response = post_blink_with(&app, &key).await;
+
+    assert_eq!(403, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn expired_and_revoked_keys_are_rejected() {
+    let app = spawn_app().await;
+    let expired = post_key(
+        &app,
+        ADMIN_TOKEN,
+        &json!({
+            "name": "expired",
+            "owner": "acme",
+            "scopes": ["create"],
+            "expires_at": "2020-01-01T00:00:00Z"
+        }),
+    )
+    .await
+    .json::<serde_json::Value>()
+    .await
+    .unwrap();
+    assert_eq!(
+        401,
+        post_blink_with(&app, expired["key"].as_str().unwrap())
+            .await
+            .status()
+            .as_u16()
+    );
+
+    let revoked = app.create_api_key("acme", &["create"]).await;
+    let id = list_keys(&app)
+        .await
+        .into_iter()
+        .find(|k| k["name"] == "test")
+        .unwrap()["id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    let response = Client::new()
+        .delete(format!("{}/api/keys/{}", &app.address, id))
+        .bearer_auth(ADMIN_TOKEN)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(204, response.status().as_u16());
+
+    assert_eq!(401, post_blink_with(&app, &revoked).await.status().as_u16());
+}
+
+#[tokio::test]
+async fn rotated_keys_keep_working_for_the_grace_period() {
+    let app = spawn_app().await;
+    let old_key = app.create_api_key("acme", &["create"]).await;
+    let id = list_keys(&app).await[0]["id"].as_str().unwrap().to_string();
+    let rotate = |grace_period_secs: u32| {
+        Client::new()
+            .post(format!("{}/api/keys/{}/rotate", &app.address, id))
+            .bearer_auth(ADMIN_TOKEN)
+            .json(&json!({ "grace_period_secs": grace_period_secs }))
+            .send()
+    };
+
+    let response = rotate(3600).await.unwrap();
+    assert_eq!(200, response.status().as_u16());
+    let rotated: serde_json::Value = response.json().await.unwrap();
+    let new_key = rotated["key"].as_str().unwrap();
+
+    assert_ne!(new_key, old_key);
+    assert_eq!(rotated["owner"], "acme");
+    assert_eq!(200, post_blink_with(&app, &old_key).await.status().as_u16());
+    assert_eq!(200, post_blink_with(&app, new_key).await.status().as_u16());
+
+    // A key can only be rotated once.
+    assert_eq!(404, rotate(0).await.unwrap().status().as_u16());
+}
+
+#[tokio::test]
+async fn rotating_without_grace_period_expires_the_old_key() {
+    let app = spawn_app().await;
+    let old_key = app.create_api_key("acme", &["create"]).await;
+    let id = list_keys(&app).await[0]["id"].as_str().unwrap().to_string();
+
+    let response = Client::new()
+        .post(format!("{}/api/keys/{}/rotate", &app.address, id))
+        .bearer_auth(ADMIN_TOKEN)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(200, response.status().as_u16());
+
+    assert_eq!(401, post_blink_with(&app, &old_key).await.status().as_u16());
+}
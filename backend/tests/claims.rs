@@ -0,0 +1,305 @@
+mod helpers;
+
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use blinkzero::domain::verify_allocation;
+use helpers::{
+    MockRpc, TestApp, donation_blink, post_action, spawn_app, spawn_app_with, vault_signer,
+};
+use serde_json::{Value, json};
+use solana_sdk::{
+    hash::Hash,
+    pubkey::Pubkey,
+    signature::{Keypair, Signature, Signer},
+    system_instruction::SystemInstruction,
+    system_program,
+    transaction::Transaction,
+};
+use std::str::FromStr;
+
+/// Programs of a claim transaction.
+const CLAIM_PROGRAMS: [Pubkey; 3] = [
+    solana_sdk::pubkey!("ComputeBudget111111111111111111111111111111"),
+    solana_sdk::pubkey!("11111111111111111111111111111111"),
+    solana_sdk::pubkey!("MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr"),
+];
+
+async fn spawn_claiming_app(
+    rpc: &MockRpc,
+    distributor: &Keypair,
+    reservation_secs: u64,
+) -> TestApp {
+    spawn_app_with(|c| {
+        c.solana.rpc_endpoints = vec![rpc.endpoint(1)];
+        c.solana.signers = vec![vault_signer("airdrop", distributor, &CLAIM_PROGRAMS)];
+        c.solana.claims.reservation_secs = reservation_secs;
+    })
+    .await
+}
+
+fn claim_blink() -> Value {
+    let mut body = donation_blink();
+    body["type"] = json!("claim");
+    body["description"] = json!("Community giveaway");
+    body["label"] = json!("Claim");
+    body["config"] = json!({ "distributor": "airdrop" });
+    body
+}
+
+/// Creates a claim blink allocating 1.5 SOL to `claimer` and 2 SOL to
+/// another wallet, and returns its id and the API key owning it.
+async fn create_airdrop(app: &TestApp, claimer: &Pubkey) -> (String, String) {
+    let key = app.create_api_key("acme", &["create", "update"]).await;
+    let blink = app.create_blink_with_key(&key, &claim_blink()).await;
+    let id = blink["id"].as_str().unwrap().to_string();
+    let entries = json!([
+        { "wallet": claimer.to_string(), "amount": 1.5 },
+        { "wallet": Pubkey::new_unique().to_string(), "amount": 2 }
+    ]);
+
+    let response = app.put_allowlist(&key, &id, &entries).await;
+
+    assert_eq!(200, response.status().as_u16());
+    (id, key)
+}
+
+async fn message(response: reqwest::Response) -> String {
+    let body: Value = response.json().await.unwrap();
+    body["message"].as_str().unwrap().to_string()
+}
+
+async fn description(app: &TestApp, id: &str) -> String {
+    let body: Value = reqwest::get(format!("{}/api/actions/{}", &app.address, id))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    body["description"].as_str().unwrap().to_string()
+}
+
+#[tokio::test]
+async fn claim_pays_the_allocation_from_the_distributor() {
+    let rpc = MockRpc::spawn().await;
+    let distributor = Keypair::new();
+    let app = spawn_claiming_app(&rpc, &distributor, 300).await;
+    let claimer = Pubkey::new_unique();
+    let (id, _) = create_airdrop(&app, &claimer).await;
+    assert_eq!(
+        description(&app, &id).await,
+        "Community giveaway\n\n3.5 of 3.5 SOL left to claim"
+    );
+
+    let response = post_action(&app, &id, &claimer).await;
+
+    assert_eq!(200, response.status().as_u16());
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["message"], "Claim 1.5 SOL");
+    let bytes = BASE64
+        .decode(body["transaction"].as_str().unwrap())
+        .unwrap();
+    let transaction: Transaction = bincode::deserialize(&bytes).unwrap();
+    let message = &transaction.message;
+    // The distributor pays the fee; the claimer signs the memo.
+    assert_eq!(message.account_keys[0], distributor.pubkey());
+    assert_eq!(message.account_keys[1], claimer);
+    assert_eq!(message.header.num_required_signatures, 2);
+    assert_ne!(transaction.signatures[0], Signature::default());
+    assert_eq!(transaction.signatures[1], Signature::default());
+    let transfer = message
+        .instructions
+        .iter()
+        .find(|ix| message.account_keys[usize::from(ix.program_id_index)] == system_program::id())
+        .unwrap();
+    assert_eq!(
+        bincode::deserialize::<SystemInstruction>(&transfer.data).unwrap(),
+        SystemInstruction::Transfer {
+            lamports: 1_500_000_000
+        }
+    );
+
+    assert_eq!(
+        description(&app, &id).await,
+        "Community giveaway\n\n2 of 3.5 SOL left to claim"
+    );
+}
+
+#[tokio::test]
+async fn only_allowlisted_wallets_can_claim_once() {
+    let rpc = MockRpc::spawn().await;
+    let app = spawn_claiming_app(&rpc, &Keypair::new(), 300).await;
+    let claimer = Pubkey::new_unique();
+    let (id, _) = create_airdrop(&app, &claimer).await;
+
+    let stranger = post_action(&app, &id, &Pubkey::new_unique()).await;
+    let first = post_action(&app, &id, &claimer).await;
+    let second = post_action(&app, &id, &claimer).await;
+
+    assert_eq!(403, stranger.status().as_u16());
+    assert_eq!(
+        message(stranger).await,
+        "This wallet is not on the allowlist"
+    );
+    assert_eq!(200, first.status().as_u16());
+    assert_eq!(403, second.status().as_u16());
+}
+
+#[tokio::test]
+async fn landed_claims_are_final() {
+    let rpc = MockRpc::spawn().await;
+    let app = spawn_claiming_app(&rpc, &Keypair::new(), 0).await;
+    let claimer = Pubkey::new_unique();
+    let (id, _) = create_airdrop(&app, &claimer).await;
+    rpc.set_result(
+        "getSignatureStatuses",
+        json!({
+            "context": { "slot": 1 },
+            "value": [{
+                "slot": 1,
+                "confirmations": null,
+                "err": null,
+                "status": { "Ok": null },
+                "confirmationStatus": "finalized"
+            }]
+        }),
+    );
+
+    let first = post_action(&app, &id, &claimer).await;
+    let second = post_action(&app, &id, &claimer).await;
+
+    assert_eq!(200, first.status().as_u16());
+    assert_eq!(403, second.status().as_u16());
+    assert_eq!(
+        message(second).await,
+        "This wallet has already claimed its allocation"
+    );
+    let allocation: Value = reqwest::get(format!(
+        "{}/api/blinks/{}/allowlist/{}",
+        &app.address, id, claimer
+    ))
+    .await
+    .unwrap()
+    .json()
+    .await
+    .unwrap();
+    assert_eq!(allocation["claimed"], true);
+}
+
+#[tokio::test]
+async fn expired_claims_free_the_allocation() {
+    let rpc = MockRpc::spawn().await;
+    let app = spawn_claiming_app(&rpc, &Keypair::new(), 0).await;
+    let claimer = Pubkey::new_unique();
+    let (id, _) = create_airdrop(&app, &claimer).await;
+    rpc.set_result(
+        "getSignatureStatuses",
+        json!({ "context": { "slot": 1 }, "value": [null] }),
+    );
+
+    let first = post_action(&app, &id, &claimer).await;
+    let second = post_action(&app, &id, &claimer).await;
+
+    assert_eq!(200, first.status().as_u16());
+    assert_eq!(200, second.status().as_u16());
+}
+
+#[tokio::test]
+async fn allocations_prove_against_the_merkle_root() {
+    let rpc = MockRpc::spawn().await;
+    let app = spawn_claiming_app(&rpc, &Keypair::new(), 300).await;
+    let claimer = Pubkey::new_unique();
+    let (id, _) = create_airdrop(&app, &claimer).await;
+
+    let response = reqwest::get(format!(
+        "{}/api/blinks/{}/allowlist/{}",
+        &app.address, id, claimer
+    ))
+    .await
+    .unwrap();
+
+    assert_eq!(200, response.status().as_u16());
+    let allocation: Value = response.json().await.unwrap();
+    assert_eq!(allocation["lamports"], 1_500_000_000u64);
+    assert_eq!(allocation["claimed"], false);
+    let root = Hash::from_str(allocation["merkle_root"].as_str().unwrap()).unwrap();
+    let proof = allocation["proof"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|hash| Hash::from_str(hash.as_str().unwrap()).unwrap())
+        .collect::<Vec<_>>();
+    assert!(verify_allocation(&root, &claimer, 1_500_000_000, &proof));
+}
+
+#[tokio::test]
+async fn allowlists_freeze_once_claims_start() {
+    let rpc = MockRpc::spawn().await;
+    let app = spawn_claiming_app(&rpc, &Keypair::new(), 300).await;
+    let claimer = Pubkey::new_unique();
+    let (id, key) = create_airdrop(&app, &claimer).await;
+    let entries = json!([{ "wallet": claimer.to_string(), "amount": 5 }]);
+
+    assert_eq!(
+        200,
+        app.put_allowlist(&key, &id, &entries)
+            .await
+            .status()
+            .as_u16()
+    );
+    assert_eq!(
+        200,
+        post_action(&app, &id, &claimer).await.status().as_u16()
+    );
+    let response = app.put_allowlist(&key, &id, &entries).await;
+
+    assert_eq!(409, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn allowlist_uploads_are_validated() {
+    let rpc = MockRpc::spawn().await;
+    let app = spawn_claiming_app(&rpc, &Keypair::new(), 300).await;
+    let key = app.create_api_key("acme", &["create", "update"]).await;
+    let claim = app.create_blink_with_key(&key, &claim_blink()).await;
+    let claim_id = claim["id"].as_str().unwrap();
+    let donation = app.create_blink_with_key(&key, &donation_blink()).await;
+    let wallet = Pubkey::new_unique().to_string();
+
+    for entries in [
+        json!([]),
+        json!([{ "wallet": "nope", "amount": 1 }]),
+        json!([{ "wallet": wallet, "amount": 0 }]),
+        json!([{ "wallet": wallet, "amount": 1 }, { "wallet": wallet, "amount": 2 }]),
+    ] {
+        let response = app.put_allowlist(&key, claim_id, &entries).await;
+        assert_eq!(400, response.status().as_u16(), "{}", entries);
+    }
+    let entries = json!([{ "wallet": wallet, "amount": 1 }]);
+    let response = app
+        .put_allowlist(&key, donation["id"].as_str().unwrap(), &entries)
+        .await;
+    assert_eq!(400, response.status().as_u16());
+    let other = app.create_api_key("other", &["update"]).await;
+    let response = app.put_allowlist(&other, claim_id, &entries).await;
+    assert_eq!(404, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn create_blink_rejects_invalid_claim_configs() {
+    let app = spawn_app().await;
+
+    // The vault has no key named `airdrop`.
+    let response = app.post_blink(&claim_blink()).await;
+    assert_eq!(400, response.status().as_u16());
+
+    for config in [
+        json!({}),
+        json!({ "distributor": "airdrop", "durable_nonce": true }),
+    ] {
+        let mut body = claim_blink();
+        body["config"] = config;
+
+        let response = app.post_blink(&body).await;
+
+        assert_eq!(400, response.status().as_u16());
+    }
+}
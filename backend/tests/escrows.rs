@@ -0,0 +1,290 @@
+mod helpers;
+
+use helpers::{
+    MockRpc, TestApp, decode_transaction, donation_blink, land, land_action, post_action_with,
+    spawn_app_with, system_transfer, vault_signer,
+};
+use reqwest::Client;
+use serde_json::{Value, json};
+use solana_sdk::{
+    pubkey::Pubkey, signature::Keypair, signer::Signer, system_program, transaction::Transaction,
+};
+
+/// Programs of a payout from the escrow key.
+const ESCROW_PROGRAMS: [Pubkey; 2] = [
+    solana_sdk::pubkey!("ComputeBudget111111111111111111111111111111"),
+    system_program::ID,
+];
+
+struct Deal {
+    app: TestApp,
+    rpc: MockRpc,
+    escrow: Keypair,
+    seller: Pubkey,
+    id: String,
+    key: String,
+}
+
+/// Creates an escrow blink selling for 2 SOL.
+async fn open_deal(config: Value) -> Deal {
+    let rpc = MockRpc::spawn().await;
+    rpc.set_result("getSignaturesForAddress", json!([]));
+    let escrow = Keypair::new();
+    let app = spawn_app_with(|c| {
+        c.solana.rpc_endpoints = vec![rpc.endpoint(1)];
+        c.solana.signers = vec![vault_signer("escrow", &escrow, &ESCROW_PROGRAMS)];
+    })
+    .await;
+    let key = app.create_api_key("acme", &["create", "read"]).await;
+    let seller = Pubkey::new_unique();
+    let mut body = donation_blink();
+    body["type"] = json!("escrow");
+    body["title"] = json!("Vintage camera");
+    body["description"] = json!("Ships within a week");
+    body["label"] = json!("Buy");
+    body["wallet_address"] = json!(seller.to_string());
+    body["config"] = config;
+    let blink = app.create_blink_with_key(&key, &body).await;
+
+    Deal {
+        app,
+        rpc,
+        escrow,
+        seller,
+        id: blink["id"].as_str().unwrap().to_string(),
+        key,
+    }
+}
+
+impl Deal {
+    async fn post(&self, step: &str, wallet: &Pubkey) -> reqwest::Response {
+        post_action_with(&self.app, &self.id, &format!("step={}", step), wallet).await
+    }
+
+    async fn transaction(&self, step: &str, wallet: &Pubkey) -> Transaction {
+        decode_transaction(self.post(step, wallet).await).await
+    }
+
+    async fn metadata(&self, viewer: Option<&Pubkey>) -> Value {
+        let url = match viewer {
+            Some(viewer) => format!(
+                "{}/api/actions/{}?account={}",
+                &self.app.address, self.id, viewer
+            ),
+            None => format!("{}/api/actions/{}", &self.app.address, self.id),
+        };
+        reqwest::get(url).await.unwrap().json().await.unwrap()
+    }
+
+    async fn escrow_state(&self) -> Value {
+        let response = Client::new()
+            .get(format!(
+                "{}/api/blinks/{}/escrow",
+                &self.app.address, self.id
+            ))
+            .bearer_auth(&self.key)
+            .send()
+            .await
+            .expect("Failed to execute request.");
+        assert_eq!(200, response.status().as_u16());
+        response.json().await.unwrap()
+    }
+
+    fn land(&self, transaction: &Transaction) {
+        land(&self.rpc, transaction);
+    }
+
+    /// Deposits from `buyer` and lands the deposit.
+    async fn fund(&self, buyer: &Keypair) {
+        land_action(
+            &self.rpc,
+            self.post("deposit", &buyer.pubkey()).await,
+            buyer,
+        )
+        .await;
+        assert_eq!(self.escrow_state().await["status"], "funded");
+    }
+}
+
+fn labels(metadata: &Value) -> Vec<&str> {
+    metadata["links"]["actions"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|action| action["label"].as_str().unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn released_deposits_pay_the_seller() {
+    let deal = open_deal(json!({ "amount": 2, "escrow": "escrow" })).await;
+    let metadata = deal.metadata(None).await;
+    assert_eq!(labels(&metadata), ["Deposit 2 SOL"]);
+    assert!(
+        metadata["links"]["actions"][0]["href"]
+            .as_str()
+            .unwrap()
+            .ends_with("?step=deposit")
+    );
+    let buyer = Keypair::new();
+
+    let deposit = deal.transaction("deposit", &buyer.pubkey()).await;
+    assert_eq!(
+        system_transfer(&deposit),
+        (buyer.pubkey(), deal.escrow.pubkey(), 2_000_000_000)
+    );
+    let mut deposit = deposit;
+    deposit.sign(&[&buyer], deposit.message.recent_blockhash);
+    deal.land(&deposit);
+
+    let state = deal.escrow_state().await;
+    assert_eq!(state["status"], "funded");
+    assert_eq!(state["buyer"], buyer.pubkey().to_string());
+    assert_eq!(state["amount"], "2");
+    assert_eq!(
+        labels(&deal.metadata(Some(&buyer.pubkey())).await),
+        ["Release 2 SOL to seller"]
+    );
+    assert_eq!(
+        labels(&deal.metadata(Some(&deal.seller)).await),
+        ["Refund 2 SOL to buyer"]
+    );
+    let stranger = deal.metadata(Some(&Pubkey::new_unique())).await;
+    assert_eq!(stranger["disabled"], true);
+    assert_eq!(
+        stranger["error"]["message"],
+        "This deal is between other wallets"
+    );
+
+    let release = deal.transaction("release", &buyer.pubkey()).await;
+    assert_eq!(release.message.account_keys[0], buyer.pubkey());
+    assert_eq!(
+        system_transfer(&release),
+        (deal.escrow.pubkey(), deal.seller, 2_000_000_000)
+    );
+    let escrow_index = release
+        .message
+        .account_keys
+        .iter()
+        .position(|key| *key == deal.escrow.pubkey())
+        .unwrap();
+    assert!(
+        release.signatures[escrow_index]
+            .verify(deal.escrow.pubkey().as_ref(), &release.message_data())
+    );
+
+    let mut release = release;
+    release.partial_sign(&[&buyer], release.message.recent_blockhash);
+    deal.land(&release);
+    let state = deal.escrow_state().await;
+    assert_eq!(state["status"], "released");
+    assert_eq!(
+        state["settlement_signature"],
+        release.signatures[0].to_string()
+    );
+    let metadata = deal.metadata(None).await;
+    assert_eq!(metadata["disabled"], true);
+    assert_eq!(
+        metadata["error"]["message"],
+        "This deal was released to the seller"
+    );
+}
+
+#[tokio::test]
+async fn steps_are_limited_to_their_party() {
+    let deal = open_deal(json!({ "amount": 2, "escrow": "escrow" })).await;
+    let buyer = Keypair::new();
+
+    let response = deal.post("release", &buyer.pubkey()).await;
+    assert_eq!(403, response.status().as_u16());
+    let response = deal.post("deposit", &deal.seller).await;
+    assert_eq!(403, response.status().as_u16());
+
+    deal.fund(&buyer).await;
+    let response = deal.post("deposit", &Pubkey::new_unique()).await;
+    assert_eq!(403, response.status().as_u16());
+    let response = deal.post("refund", &buyer.pubkey()).await;
+    assert_eq!(403, response.status().as_u16());
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["message"], "Only the seller can refund this deal");
+    let response = deal.post("release", &deal.seller).await;
+    assert_eq!(403, response.status().as_u16());
+    let body: Value = response.json().await.unwrap();
+    assert!(
+        body["message"]
+            .as_str()
+            .unwrap()
+            .starts_with("The seller can release this deal from ")
+    );
+
+    let refund = deal.transaction("refund", &deal.seller).await;
+    assert_eq!(
+        system_transfer(&refund),
+        (deal.escrow.pubkey(), buyer.pubkey(), 2_000_000_000)
+    );
+    // Nothing else is paid out while the refund is in flight.
+    let response = deal.post("release", &buyer.pubkey()).await;
+    assert_eq!(403, response.status().as_u16());
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(
+        body["message"],
+        "A refund of this deal is in flight, try again in a few minutes"
+    );
+
+    deal.land(&refund);
+    assert_eq!(deal.escrow_state().await["status"], "refunded");
+    let response = deal.post("release", &buyer.pubkey()).await;
+    assert_eq!(403, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn the_timeout_step_opens_to_both_parties() {
+    let deal = open_deal(json!({ "amount": 2, "escrow": "escrow", "timeout_days": 3 })).await;
+    let buyer = Keypair::new();
+    deal.fund(&buyer).await;
+
+    sqlx::query("UPDATE escrows SET funded_at = now() - interval '4 days'")
+        .execute(&deal.app.db_pool)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        labels(&deal.metadata(Some(&deal.seller)).await),
+        ["Release 2 SOL to seller", "Refund 2 SOL to buyer"]
+    );
+    let release = deal.transaction("release", &deal.seller).await;
+    assert_eq!(
+        system_transfer(&release),
+        (deal.escrow.pubkey(), deal.seller, 2_000_000_000)
+    );
+    // Refunds remain with the seller.
+    let response = deal.post("refund", &buyer.pubkey()).await;
+    assert_eq!(403, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn escrow_configs_are_validated() {
+    let app = spawn_app_with(|c| {
+        c.solana.signers = vec![vault_signer("escrow", &Keypair::new(), &ESCROW_PROGRAMS)];
+    })
+    .await;
+    let key = app.create_api_key("acme", &["create"]).await;
+
+    for config in [
+        json!({ "amount": 2, "escrow": "missing" }),
+        json!({ "amount": 2, "escrow": "escrow", "on_timeout": "deposit" }),
+        json!({ "amount": -1, "escrow": "escrow" }),
+    ] {
+        let mut body = donation_blink();
+        body["type"] = json!("escrow");
+        body["config"] = config;
+        let response = Client::new()
+            .post(format!("{}/api/blinks", &app.address))
+            .bearer_auth(&key)
+            .json(&body)
+            .send()
+            .await
+            .expect("Failed to execute request.");
+        assert_eq!(400, response.status().as_u16());
+    }
+}
@@ -0,0 +1,246 @@
+mod helpers;
+
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use helpers::{MockRpc, TestApp, donation_blink, post_action_with, spawn_app_with_rpc};
+use serde_json::{Value, json};
+use solana_sdk::{pubkey::Pubkey, transaction::Transaction};
+
+const GOVERNANCE_PROGRAM_ID: Pubkey =
+    solana_sdk::pubkey!("GovER5Lthms3bLBqWub97yVrMPEFH8H6yHbzpDHp6ibx");
+const SPL_TOKEN_PROGRAM_ID: Pubkey =
+    solana_sdk::pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+
+struct Realm {
+    app: TestApp,
+    rpc: MockRpc,
+    realm: Pubkey,
+    governance: Pubkey,
+    proposal: Pubkey,
+    mint: Pubkey,
+    author_record: Pubkey,
+    id: String,
+}
+
+/// `ProposalV2` account data of a Yes/No proposal with 700, 200 and 100
+/// tokens voted yes, no and abstain.
+fn proposal_data(realm: &Realm, state: u8) -> Vec<u8> {
+    let mut data = vec![14];
+    data.extend_from_slice(realm.governance.as_ref());
+    data.extend_from_slice(realm.mint.as_ref());
+    data.push(state);
+    data.extend_from_slice(realm.author_record.as_ref());
+    data.extend_from_slice(&[1, 1, 0]);
+    data.extend_from_slice(&1u32.to_le_bytes());
+    data.extend_from_slice(&7u32.to_le_bytes());
+    data.extend_from_slice(b"Approve");
+    data.extend_from_slice(&700_000_000u64.to_le_bytes());
+    data.extend_from_slice(&[0; 7]);
+    data.push(1);
+    data.extend_from_slice(&200_000_000u64.to_le_bytes());
+    data.push(0);
+    data.push(1);
+    data.extend_from_slice(&100_000_000u64.to_le_bytes());
+    data.push(0);
+    data.extend_from_slice(&1_700_000_000i64.to_le_bytes());
+    data.extend_from_slice(&[0, 1]);
+    data.extend_from_slice(&1_700_000_100i64.to_le_bytes());
+    data.extend_from_slice(&[0; 4]);
+    data.extend_from_slice(&[0, 0, 0]);
+    data.extend_from_slice(&[1, 0, 60]);
+    data.extend_from_slice(&[0; 64]);
+    data.extend_from_slice(&14u32.to_le_bytes());
+    data.extend_from_slice(b"Fund the grant");
+    data.extend_from_slice(&0u32.to_le_bytes());
+    data.extend_from_slice(&0u64.to_le_bytes());
+    data
+}
+
+fn token_owner_record(realm: &Realm, owner: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            b"governance",
+            realm.realm.as_ref(),
+            realm.mint.as_ref(),
+            owner.as_ref(),
+        ],
+        &GOVERNANCE_PROGRAM_ID,
+    )
+    .0
+}
+
+async fn open_proposal(state: u8) -> Realm {
+    let rpc = MockRpc::spawn().await;
+    let app = spawn_app_with_rpc(&rpc).await;
+    let mut realm = Realm {
+        app,
+        rpc,
+        realm: Pubkey::new_unique(),
+        governance: Pubkey::new_unique(),
+        proposal: Pubkey::new_unique(),
+        mint: Pubkey::new_unique(),
+        author_record: Pubkey::new_unique(),
+        id: String::new(),
+    };
+    let mut mint = vec![0u8; 82];
+    mint[44] = 6;
+    realm
+        .rpc
+        .set_account(&realm.mint, &SPL_TOKEN_PROGRAM_ID, &mint);
+    realm.rpc.set_account(
+        &realm.proposal,
+        &GOVERNANCE_PROGRAM_ID,
+        &proposal_data(&realm, state),
+    );
+
+    let mut body = donation_blink();
+    body["type"] = json!("governance");
+    body["description"] = json!("");
+    body["config"] = json!({
+        "realm": realm.realm.to_string(),
+        "governance": realm.governance.to_string(),
+        "proposal": realm.proposal.to_string()
+    });
+    let blink = realm.app.create_blink(&body).await;
+    realm.id = blink["id"].as_str().unwrap().to_string();
+    realm
+}
+
+impl Realm {
+    /// Registers a token owner record of `owner` holding `deposit` tokens.
+    fn deposit(&self, owner: &Pubkey, deposit: u64) {
+        let mut data = vec![17];
+        data.extend_from_slice(self.realm.as_ref());
+        data.extend_from_slice(self.mint.as_ref());
+        data.extend_from_slice(owner.as_ref());
+        data.extend_from_slice(&deposit.to_le_bytes());
+        self.rpc.set_account(
+            &token_owner_record(self, owner),
+            &GOVERNANCE_PROGRAM_ID,
+            &data,
+        );
+    }
+
+    async fn metadata(&self) -> Value {
+        reqwest::get(format!("{}/api/actions/{}", &self.app.address, self.id))
+            .await
+            .expect("Failed to execute request.")
+            .json()
+            .await
+            .unwrap()
+    }
+
+    async fn vote(&self, vote: &str, voter: &Pubkey) -> reqwest::Response {
+        post_action_with(&self.app, &self.id, &format!("vote={}", vote), voter).await
+    }
+}
+
+#[tokio::test]
+async fn metadata_shows_the_proposal_and_its_tallies() {
+    let realm = open_proposal(2).await;
+
+    let metadata = realm.metadata().await;
+
+    assert_eq!(
+        metadata["description"],
+        "Fund the grant · Voting\nYes 700 · No 200 · Abstain 100"
+    );
+    let actions = metadata["links"]["actions"].as_array().unwrap();
+    let labels: Vec<&str> = actions
+        .iter()
+        .map(|action| action["label"].as_str().unwrap())
+        .collect();
+    assert_eq!(labels, ["Yes", "No", "Abstain"]);
+    assert!(
+        actions[2]["href"]
+            .as_str()
+            .unwrap()
+            .ends_with("?vote=abstain")
+    );
+    assert!(metadata["disabled"].is_null());
+}
+
+#[tokio::test]
+async fn votes_cast_from_the_voters_token_owner_record() {
+    let realm = open_proposal(2).await;
+    let voter = Pubkey::new_unique();
+    realm.deposit(&voter, 5_000_000);
+
+    let response = realm.vote("yes", &voter).await;
+
+    assert_eq!(200, response.status().as_u16());
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["message"], "Vote Yes on Fund the grant");
+    let bytes = BASE64
+        .decode(body["transaction"].as_str().unwrap())
+        .unwrap();
+    let transaction: Transaction = bincode::deserialize(&bytes).unwrap();
+    let message = &transaction.message;
+    let cast_vote = message
+        .instructions
+        .iter()
+        .find(|instruction| {
+            message.account_keys[usize::from(instruction.program_id_index)] == GOVERNANCE_PROGRAM_ID
+        })
+        .unwrap();
+    assert_eq!(cast_vote.data, [13, 0, 1, 0, 0, 0, 0, 100]);
+    let accounts: Vec<Pubkey> = cast_vote
+        .accounts
+        .iter()
+        .map(|index| message.account_keys[usize::from(*index)])
+        .collect();
+    assert_eq!(
+        accounts[..5],
+        [
+            realm.realm,
+            realm.governance,
+            realm.proposal,
+            realm.author_record,
+            token_owner_record(&realm, &voter)
+        ]
+    );
+    assert_eq!(accounts[5], voter);
+    assert_eq!(accounts[7], realm.mint);
+
+    let response = realm.vote("no", &voter).await;
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["message"], "Vote No on Fund the grant");
+}
+
+#[tokio::test]
+async fn wallets_without_deposits_cannot_vote() {
+    let realm = open_proposal(2).await;
+    let voter = Pubkey::new_unique();
+
+    let response = realm.vote("yes", &voter).await;
+    assert_eq!(403, response.status().as_u16());
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(
+        body["message"],
+        "This wallet has no governing tokens deposited in the realm"
+    );
+
+    realm.deposit(&voter, 0);
+    let response = realm.vote("abstain", &voter).await;
+    assert_eq!(403, response.status().as_u16());
+    let response = realm.vote("maybe", &voter).await;
+    assert_eq!(400, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn closed_proposals_are_disabled() {
+    let realm = open_proposal(3).await;
+    let voter = Pubkey::new_unique();
+    realm.deposit(&voter, 5_000_000);
+
+    let metadata = realm.metadata().await;
+    assert_eq!(
+        metadata["description"],
+        "Fund the grant · Succeeded\nYes 700 · No 200 · Abstain 100"
+    );
+    assert_eq!(metadata["disabled"], true);
+
+    let response = realm.vote("yes", &voter).await;
+    assert_eq!(403, response.status().as_u16());
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["message"], "This proposal is not open for voting");
+}
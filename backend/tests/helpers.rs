@@ -1,9 +1,28 @@
-use blinkzero::configuration::{DatabaseSettings, get_configuration};
+use axum::{Json, Router, extract::State, http::StatusCode, response::IntoResponse, routing::post};
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use blinkzero::configuration::{
+    DatabaseSettings, ProgramRuleSettings, RpcEndpointSettings, Settings, SignerSettings,
+    get_configuration,
+};
 use blinkzero::startup::run;
 use blinkzero::telemetry::{get_subscriber, init_subscriber};
 use once_cell::sync::Lazy;
+use secrecy::SecretString;
+use serde_json::{Value, json};
+use solana_sdk::{
+    hash::Hash,
+    nonce::state::{DurableNonce, State as NonceState, Versions},
+    pubkey::Pubkey,
+    signature::Keypair,
+    system_instruction::SystemInstruction,
+    system_program,
+    transaction::Transaction,
+};
 use sqlx::{Connection, Executor, PgConnection, PgPool};
+use std::collections::HashMap;
 use std::net::TcpListener;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 use uuid::Uuid;
 
 static TRACING: Lazy<()> = Lazy::new(|| {
@@ -18,13 +37,599 @@ static TRACING: Lazy<()> = Lazy::new(|| {
     }
 });
 
+const SPL_TOKEN_PROGRAM_ID: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+const METADATA_PROGRAM_ID: &str = "metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s";
+
+/// Admin token of `configuration/local.yaml`.
+pub const ADMIN_TOKEN: &str = "local-admin-token";
+
 pub struct TestApp {
     pub address: String,
     #[allow(dead_code)]
     pub db_pool: PgPool,
 }
 
+#[allow(dead_code)]
+impl TestApp {
+    pub async fn post_blink(&self, body: &serde_json::Value) -> reqwest::Response {
+        reqwest::Client::new()
+            .post(format!("{}/api/blinks", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn create_blink(&self, body: &serde_json::Value) -> serde_json::Value {
+        let response = self.post_blink(body).await;
+        assert_eq!(200, response.status().as_u16());
+        response.json().await.expect("Failed to parse response")
+    }
+
+    /// Creates an API key for `owner` and returns the plain key.
+    pub async fn create_api_key(&self, owner: &str, scopes: &[&str]) -> String {
+        let response = reqwest::Client::new()
+            .post(format!("{}/api/keys", &self.address))
+            .bearer_auth(ADMIN_TOKEN)
+            .json(&serde_json::json!({ "name": "test", "owner": owner, "scopes": scopes }))
+            .send()
+            .await
+            .expect("Failed to execute request.");
+        assert_eq!(200, response.status().as_u16());
+        let body: serde_json::Value = response.json().await.expect("Failed to parse response");
+        body["key"].as_str().unwrap().to_string()
+    }
+
+    pub async fn create_blink_with_key(
+        &self,
+        key: &str,
+        body: &serde_json::Value,
+    ) -> serde_json::Value {
+        let response = reqwest::Client::new()
+            .post(format!("{}/api/blinks", &self.address))
+            .bearer_auth(key)
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.");
+        assert_eq!(200, response.status().as_u16());
+        response.json().await.expect("Failed to parse response")
+    }
+
+    pub async fn put_slug(&self, key: &str, blink_id: &str, slug: &str) -> reqwest::Response {
+        reqwest::Client::new()
+            .put(format!("{}/api/blinks/{}/slug", &self.address, blink_id))
+            .bearer_auth(key)
+            .json(&serde_json::json!({ "slug": slug }))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn put_allowlist(
+        &self,
+        key: &str,
+        blink_id: &str,
+        entries: &serde_json::Value,
+    ) -> reqwest::Response {
+        reqwest::Client::new()
+            .put(format!(
+                "{}/api/blinks/{}/allowlist",
+                &self.address, blink_id
+            ))
+            .bearer_auth(key)
+            .json(&serde_json::json!({ "entries": entries }))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+}
+
+/// A local JSON-RPC server standing in for a Solana RPC endpoint.
+#[allow(dead_code)]
+pub struct MockRpc {
+    pub url: String,
+    state: Arc<MockRpcState>,
+}
+
+#[derive(Default)]
+struct MockRpcState {
+    failing: AtomicBool,
+    unhealthy: AtomicBool,
+    slot: AtomicU64,
+    calls: AtomicUsize,
+    methods: Mutex<Vec<String>>,
+    results: Mutex<HashMap<String, Value>>,
+    accounts: Mutex<HashMap<String, Value>>,
+}
+
+#[allow(dead_code)]
+impl MockRpc {
+    pub async fn spawn() -> MockRpc {
+        let state = Arc::new(MockRpcState::default());
+        state.slot.store(1000, Ordering::SeqCst);
+
+        let app = Router::new()
+            .route("/", post(mock_rpc_handler))
+            .with_state(state.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("Failed to bind random port");
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        MockRpc { url, state }
+    }
+
+    /// Answers every request with a 503.
+    pub fn set_failing(&self, failing: bool) {
+        self.state.failing.store(failing, Ordering::SeqCst);
+    }
+
+    /// Makes `getHealth` report the node as behind.
+    pub fn set_unhealthy(&self, unhealthy: bool) {
+        self.state.unhealthy.store(unhealthy, Ordering::SeqCst);
+    }
+
+    pub fn set_slot(&self, slot: u64) {
+        self.state.slot.store(slot, Ordering::SeqCst);
+    }
+
+    /// Answers `method` with `result`.
+    pub fn set_result(&self, method: &str, result: Value) {
+        self.state
+            .results
+            .lock()
+            .unwrap()
+            .insert(method.to_string(), result);
+    }
+
+    /// Serves `data` owned by `owner` at `address`. Once an account is set,
+    /// `getAccountInfo` and `getMultipleAccounts` only return accounts set
+    /// here.
+    pub fn set_account(&self, address: &Pubkey, owner: &Pubkey, data: &[u8]) {
+        self.state.accounts.lock().unwrap().insert(
+            address.to_string(),
+            json!({
+                "data": [BASE64.encode(data), "base64"],
+                "executable": false,
+                "lamports": 1_000_000,
+                "owner": owner.to_string(),
+                "rentEpoch": 0,
+                "space": data.len()
+            }),
+        );
+    }
+
+    pub fn calls(&self) -> usize {
+        self.state.calls.load(Ordering::SeqCst)
+    }
+
+    /// Number of requests made for `method`.
+    pub fn calls_to(&self, method: &str) -> usize {
+        self.state
+            .methods
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|called| called.as_str() == method)
+            .count()
+    }
+
+    pub fn endpoint(&self, weight: u32) -> RpcEndpointSettings {
+        RpcEndpointSettings {
+            url: self.url.clone(),
+            weight,
+        }
+    }
+}
+
+async fn mock_rpc_handler(
+    State(state): State<Arc<MockRpcState>>,
+    Json(request): Json<Value>,
+) -> axum::response::Response {
+    state.calls.fetch_add(1, Ordering::SeqCst);
+    if state.failing.load(Ordering::SeqCst) {
+        return StatusCode::SERVICE_UNAVAILABLE.into_response();
+    }
+
+    let id = request["id"].clone();
+    let method = request["method"].as_str().unwrap_or_default();
+    state.methods.lock().unwrap().push(method.to_string());
+    let slot = state.slot.load(Ordering::SeqCst);
+
+    let accounts = state.accounts.lock().unwrap();
+    if !accounts.is_empty() {
+        let account = |address: &Value| {
+            address
+                .as_str()
+                .and_then(|address| accounts.get(address))
+                .cloned()
+                .unwrap_or(Value::Null)
+        };
+        let value = match method {
+            "getAccountInfo" => Some(account(&request["params"][0])),
+            "getMultipleAccounts" => Some(Value::Array(
+                request["params"][0]
+                    .as_array()
+                    .map(|addresses| addresses.iter().map(account).collect())
+                    .unwrap_or_default(),
+            )),
+            _ => None,
+        };
+        if let Some(value) = value {
+            return Json(json!({
+                "jsonrpc": "2.0",
+                "result": { "context": { "slot": slot }, "value": value },
+                "id": id
+            }))
+            .into_response();
+        }
+    }
+    drop(accounts);
+
+    if let Some(result) = state.results.lock().unwrap().get(method) {
+        return Json(json!({ "jsonrpc": "2.0", "result": result, "id": id })).into_response();
+    }
+
+    let result = match method {
+        "getHealth" if state.unhealthy.load(Ordering::SeqCst) => {
+            return Json(json!({
+                "jsonrpc": "2.0",
+                "error": {
+                    "code": -32005,
+                    "message": "Node is behind",
+                    "data": { "numSlotsBehind": 100 }
+                },
+                "id": id
+            }))
+            .into_response();
+        }
+        "getHealth" => json!("ok"),
+        "getSlot" => json!(slot),
+        "getLatestBlockhash" => json!({
+            "context": { "slot": slot },
+            "value": {
+                "blockhash": "4uQeVj5tqViQh7yWWGStvkEG1Zmhx6uasJtWCJziofM",
+                "lastValidBlockHeight": slot + 150
+            }
+        }),
+        _ => {
+            return Json(json!({
+                "jsonrpc": "2.0",
+                "error": { "code": -32601, "message": "Method not found" },
+                "id": id
+            }))
+            .into_response();
+        }
+    };
+
+    Json(json!({ "jsonrpc": "2.0", "result": result, "id": id })).into_response()
+}
+
+/// `getAccountInfo` result for an initialized nonce account.
+#[allow(dead_code)]
+pub fn nonce_account_info(authority: &Pubkey, blockhash: &Hash) -> Value {
+    let state =
+        NonceState::new_initialized(authority, DurableNonce::from_blockhash(blockhash), 5000);
+    let data = bincode::serialize(&Versions::new(state)).unwrap();
+    json!({
+        "context": { "slot": 1000 },
+        "value": {
+            "data": [BASE64.encode(&data), "base64"],
+            "executable": false,
+            "lamports": 1_447_680,
+            "owner": system_program::id().to_string(),
+            "rentEpoch": 0,
+            "space": data.len()
+        }
+    })
+}
+
+/// `getAccountInfo` result for an SPL token mint with `decimals`.
+#[allow(dead_code)]
+pub fn mint_account_info(decimals: u8) -> Value {
+    let mut data = vec![0u8; 82];
+    data[44] = decimals;
+    json!({
+        "context": { "slot": 1000 },
+        "value": {
+            "data": [BASE64.encode(&data), "base64"],
+            "executable": false,
+            "lamports": 1_461_600,
+            "owner": SPL_TOKEN_PROGRAM_ID,
+            "rentEpoch": 0,
+            "space": 82
+        }
+    })
+}
+
+/// `getTokenAccountsByOwner` result listing `(mint, raw amount, decimals)`
+/// balances of `owner`.
+#[allow(dead_code)]
+pub fn token_accounts_info(owner: &Pubkey, balances: &[(Pubkey, u64, u8)]) -> Value {
+    let accounts: Vec<Value> = balances
+        .iter()
+        .map(|(mint, amount, decimals)| {
+            let ui_amount = *amount as f64 / 10f64.powi(*decimals as i32);
+            json!({
+                "pubkey": Pubkey::new_unique().to_string(),
+                "account": {
+                    "data": {
+                        "program": "spl-token",
+                        "parsed": {
+                            "type": "account",
+                            "info": {
+                                "mint": mint.to_string(),
+                                "owner": owner.to_string(),
+                                "state": "initialized",
+                                "isNative": false,
+                                "tokenAmount": {
+                                    "amount": amount.to_string(),
+                                    "decimals": decimals,
+                                    "uiAmount": ui_amount,
+                                    "uiAmountString": ui_amount.to_string()
+                                }
+                            }
+                        },
+                        "space": 165
+                    },
+                    "executable": false,
+                    "lamports": 2_039_280,
+                    "owner": SPL_TOKEN_PROGRAM_ID,
+                    "rentEpoch": 0,
+                    "space": 165
+                }
+            })
+        })
+        .collect();
+    json!({ "context": { "slot": 1000 }, "value": accounts })
+}
+
+/// `getMultipleAccounts` result with one Metaplex metadata account that is a
+/// verified member of `collection`.
+#[allow(dead_code)]
+pub fn collection_metadata_info(collection: &Pubkey) -> Value {
+    let mut data = vec![4];
+    data.extend_from_slice(Pubkey::new_unique().as_ref());
+    data.extend_from_slice(Pubkey::new_unique().as_ref());
+    for field in ["Member #1", "MBR", "https://example.com/1.json"] {
+        data.extend_from_slice(&(field.len() as u32).to_le_bytes());
+        data.extend_from_slice(field.as_bytes());
+    }
+    // Seller fee, no creators, primary sale, mutable, no edition nonce,
+    // no token standard, verified collection.
+    data.extend_from_slice(&[0, 0, 0, 1, 1, 0, 0, 1, 1]);
+    data.extend_from_slice(collection.as_ref());
+
+    json!({
+        "context": { "slot": 1000 },
+        "value": [{
+            "data": [BASE64.encode(&data), "base64"],
+            "executable": false,
+            "lamports": 5_616_720,
+            "owner": METADATA_PROGRAM_ID,
+            "rentEpoch": 0,
+            "space": data.len()
+        }]
+    })
+}
+
+/// `getProgramAccounts` result listing SPL token accounts sliced to
+/// `(owner, raw amount)`, as read by a vote snapshot at `slot`.
+#[allow(dead_code)]
+pub fn program_accounts_info(slot: u64, balances: &[(Pubkey, u64)]) -> Value {
+    let accounts: Vec<Value> = balances
+        .iter()
+        .map(|(owner, amount)| {
+            let mut data = owner.to_bytes().to_vec();
+            data.extend_from_slice(&amount.to_le_bytes());
+            json!({
+                "pubkey": Pubkey::new_unique().to_string(),
+                "account": {
+                    "data": [BASE64.encode(&data), "base64"],
+                    "executable": false,
+                    "lamports": 2_039_280,
+                    "owner": SPL_TOKEN_PROGRAM_ID,
+                    "rentEpoch": 0,
+                    "space": 165
+                }
+            })
+        })
+        .collect();
+    json!({ "context": { "slot": slot }, "value": accounts })
+}
+
+/// `getTransaction` result for a successful `transaction`.
+#[allow(dead_code)]
+pub fn confirmed_transaction_info(transaction: &Transaction) -> Value {
+    let data = bincode::serialize(transaction).unwrap();
+    json!({
+        "slot": 1000,
+        "blockTime": null,
+        "transaction": [BASE64.encode(&data), "base64"],
+        "meta": { "err": null, "fee": 5000 }
+    })
+}
+
+/// Makes the cluster report `transaction` as the latest confirmed signature
+/// of its accounts.
+#[allow(dead_code)]
+pub fn land(rpc: &MockRpc, transaction: &Transaction) {
+    rpc.set_result(
+        "getSignaturesForAddress",
+        json!([{
+            "signature": transaction.signatures[0].to_string(),
+            "slot": 1000,
+            "err": null,
+            "memo": null,
+            "blockTime": null,
+            "confirmationStatus": "confirmed"
+        }]),
+    );
+    rpc.set_result("getTransaction", confirmed_transaction_info(transaction));
+}
+
+/// The system transfer of a transaction: source, destination and lamports.
+#[allow(dead_code)]
+pub fn system_transfer(transaction: &Transaction) -> (Pubkey, Pubkey, u64) {
+    let message = &transaction.message;
+    let transfer = message
+        .instructions
+        .iter()
+        .find(|ix| message.account_keys[usize::from(ix.program_id_index)] == system_program::id())
+        .unwrap();
+    let SystemInstruction::Transfer { lamports } = bincode::deserialize(&transfer.data).unwrap()
+    else {
+        panic!("not a transfer");
+    };
+    let account = |index: usize| message.account_keys[usize::from(transfer.accounts[index])];
+    (account(0), account(1), lamports)
+}
+
+/// A vault key allowed to sign instructions of `programs`.
+#[allow(dead_code)]
+pub fn vault_signer(name: &str, keypair: &Keypair, programs: &[Pubkey]) -> SignerSettings {
+    SignerSettings {
+        name: name.to_string(),
+        keypair: Some(SecretString::from(keypair.to_base58_string())),
+        keypair_env: None,
+        keypair_file: None,
+        passphrase_env: None,
+        owners: None,
+        programs: programs
+            .iter()
+            .map(|program_id| ProgramRuleSettings {
+                program_id: program_id.to_string(),
+                data_prefixes: None,
+            })
+            .collect(),
+    }
+}
+
+/// Programs of a mint transaction.
+#[allow(dead_code)]
+pub const MINT_PROGRAMS: [Pubkey; 5] = [
+    solana_sdk::pubkey!("ComputeBudget111111111111111111111111111111"),
+    solana_sdk::pubkey!("11111111111111111111111111111111"),
+    solana_sdk::pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA"),
+    solana_sdk::pubkey!("ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL"),
+    solana_sdk::pubkey!("metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s"),
+];
+
+/// Enables minting with `authority` as the `collection` vault key, allowed to
+/// sign instructions of `programs`.
+#[allow(dead_code)]
+pub fn enable_minting(c: &mut Settings, rpc: &MockRpc, authority: &Keypair, programs: &[Pubkey]) {
+    c.solana.rpc_endpoints = vec![rpc.endpoint(1)];
+    c.solana.signers = vec![vault_signer("collection", authority, programs)];
+    c.solana.nft_mint.enabled = true;
+    c.solana.nft_mint.signer = "collection".to_string();
+}
+
+#[allow(dead_code)]
+pub async fn spawn_minting_app(rpc: &MockRpc, authority: &Keypair, programs: &[Pubkey]) -> TestApp {
+    spawn_app_with(|c| enable_minting(c, rpc, authority, programs)).await
+}
+
+#[allow(dead_code)]
+pub fn mint_blink(supply: u32, per_wallet_limit: Option<u32>) -> Value {
+    let mut body = donation_blink();
+    body["type"] = json!("mint");
+    body["title"] = json!("Season Pass");
+    body["label"] = json!("Mint");
+    body["config"] = json!({
+        "collection": Pubkey::new_unique().to_string(),
+        "uri": "https://example.com/passes/{number}.json",
+        "symbol": "PASS",
+        "price": 0.5,
+        "supply": supply
+    });
+    if let Some(limit) = per_wallet_limit {
+        body["config"]["per_wallet_limit"] = json!(limit);
+    }
+    body
+}
+
+/// POSTs the action of blink `id` for `account`.
+#[allow(dead_code)]
+pub async fn post_action(app: &TestApp, id: &str, account: &Pubkey) -> reqwest::Response {
+    reqwest::Client::new()
+        .post(format!("{}/api/actions/{}", &app.address, id))
+        .json(&json!({ "account": account.to_string() }))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+/// POSTs the action of blink `id` with the parameters in `query` for
+/// `account`.
+#[allow(dead_code)]
+pub async fn post_action_with(
+    app: &TestApp,
+    id: &str,
+    query: &str,
+    account: &Pubkey,
+) -> reqwest::Response {
+    reqwest::Client::new()
+        .post(format!("{}/api/actions/{}?{}", &app.address, id, query))
+        .json(&json!({ "account": account.to_string() }))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+/// The transaction served by a successful action POST.
+#[allow(dead_code)]
+pub async fn decode_transaction(response: reqwest::Response) -> Transaction {
+    assert_eq!(200, response.status().as_u16());
+    let body: Value = response.json().await.unwrap();
+    let bytes = BASE64
+        .decode(body["transaction"].as_str().unwrap())
+        .unwrap();
+    bincode::deserialize(&bytes).unwrap()
+}
+
+/// Signs the transaction served by `response` as `signer` and lands it.
+#[allow(dead_code)]
+pub async fn land_action(
+    rpc: &MockRpc,
+    response: reqwest::Response,
+    signer: &Keypair,
+) -> Transaction {
+    let mut transaction = decode_transaction(response).await;
+    transaction.sign(&[signer], transaction.message.recent_blockhash);
+    land(rpc, &transaction);
+    transaction
+}
+
+#[allow(dead_code)]
+pub fn donation_blink() -> serde_json::Value {
+    serde_json::json!({
+        "title": "Test Blink",
+        "icon_url": "https://example.com/icon.png",
+        "description": "A test blink",
+        "label": "Donate",
+        "wallet_address": "11111111111111111111111111111111",
+        "type": "donation",
+        "config": { "amount": 0.1 }
+    })
+}
+
+#[allow(dead_code)]
 pub async fn spawn_app() -> TestApp {
+    spawn_app_with(|_| {}).await
+}
+
+/// Spawns the app with `rpc` as its only RPC endpoint.
+#[allow(dead_code)]
+pub async fn spawn_app_with_rpc(rpc: &MockRpc) -> TestApp {
+    spawn_app_with(|c| c.solana.rpc_endpoints = vec![rpc.endpoint(1)]).await
+}
+
+/// Spawns the app after letting the test adjust the configuration.
+#[allow(dead_code)]
+pub async fn spawn_app_with(configure: impl FnOnce(&mut Settings)) -> TestApp {
     Lazy::force(&TRACING);
 
     let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind random port");
@@ -33,11 +638,17 @@ pub async fn spawn_app() -> TestApp {
 
     let mut configuration = get_configuration().expect("Failed to read configuration");
     configuration.database.database_name = Uuid::new_v4().to_string();
+    // Disable rate limiting for tests
+    configuration.rate_limit.enabled = false;
+    // No background RPC traffic from tests
+    configuration.solana.blockhash_refresh_ms = 0;
+    configuration.solana.rpc_pool.health_check_interval_ms = 0;
+    configuration.solana.subscriptions.billing_interval_ms = 0;
+    configure(&mut configuration);
 
     let connection_pool = configure_database(&configuration.database).await;
 
-    // Disable rate limiting for tests
-    let server = run(listener, connection_pool.clone(), false)
+    let server = run(listener, connection_pool.clone(), &configuration)
         .await
         .expect("Failed to bind address");
 
@@ -0,0 +1,326 @@
+mod helpers;
+
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use helpers::{MockRpc, TestApp, land, mint_account_info, spawn_app_with_rpc};
+use reqwest::Client;
+use serde_json::{Value, json};
+use solana_sdk::{
+    pubkey::Pubkey, system_instruction::SystemInstruction, system_program, transaction::Transaction,
+};
+
+const SPL_TOKEN_PROGRAM_ID: Pubkey =
+    solana_sdk::pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+
+async fn spawn_invoicing_app(rpc: &MockRpc) -> TestApp {
+    rpc.set_result("getSignaturesForAddress", json!([]));
+    spawn_app_with_rpc(rpc).await
+}
+
+fn invoice_request(number: &str) -> Value {
+    json!({
+        "number": number,
+        "amount": 1.5,
+        "currency": "SOL",
+        "due_date": "2099-01-31",
+        "note": "Design work, January",
+        "wallet_address": Pubkey::new_unique().to_string(),
+        "icon_url": "https://example.com/logo.png"
+    })
+}
+
+async fn post_invoice(app: &TestApp, key: &str, body: &Value) -> reqwest::Response {
+    Client::new()
+        .post(format!("{}/api/invoices", &app.address))
+        .bearer_auth(key)
+        .json(body)
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+async fn create_invoice(app: &TestApp, key: &str, body: &Value) -> Value {
+    let response = post_invoice(app, key, body).await;
+    assert_eq!(200, response.status().as_u16());
+    response.json().await.unwrap()
+}
+
+async fn get(app: &TestApp, key: &str, path: &str) -> reqwest::Response {
+    Client::new()
+        .get(format!("{}{}", &app.address, path))
+        .bearer_auth(key)
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+async fn metadata(app: &TestApp, blink_id: &str) -> Value {
+    reqwest::get(format!("{}/api/actions/{}", &app.address, blink_id))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap()
+}
+
+async fn pay(app: &TestApp, blink_id: &str, payer: &Pubkey) -> reqwest::Response {
+    Client::new()
+        // The amount of an invoice cannot be overridden.
+        .post(format!(
+            "{}/api/actions/{}?amount=0.01",
+            &app.address, blink_id
+        ))
+        .json(&json!({ "account": payer.to_string() }))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+fn transaction(body: &Value) -> Transaction {
+    let bytes = BASE64
+        .decode(body["transaction"].as_str().unwrap())
+        .unwrap();
+    bincode::deserialize(&bytes).unwrap()
+}
+
+#[tokio::test]
+async fn invoices_are_fixed_amount_payment_blinks() {
+    let rpc = MockRpc::spawn().await;
+    let app = spawn_invoicing_app(&rpc).await;
+    let key = app.create_api_key("acme", &["create"]).await;
+    let request = invoice_request("INV-1");
+
+    let invoice = create_invoice(&app, &key, &request).await;
+
+    assert_eq!(invoice["status"], "open");
+    assert_eq!(invoice["amount"], "1.5");
+    assert_eq!(invoice["currency"], "SOL");
+    let blink_id = invoice["blink_id"].as_str().unwrap();
+    let metadata = metadata(&app, blink_id).await;
+    assert_eq!(
+        metadata["description"],
+        "Design work, January\n\nInvoice INV-1: 1.5 SOL due 2099-01-31"
+    );
+    let actions = metadata["links"]["actions"].as_array().unwrap();
+    assert_eq!(actions.len(), 1);
+    assert_eq!(actions[0]["label"], "Pay 1.5 SOL");
+    assert!(actions[0]["parameters"].is_null());
+
+    let payer = Pubkey::new_unique();
+    let response = pay(&app, blink_id, &payer).await;
+    assert_eq!(200, response.status().as_u16());
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["message"], "Pay invoice INV-1: 1.5 SOL");
+    let transaction = transaction(&body);
+    let message = &transaction.message;
+    let transfer = message
+        .instructions
+        .iter()
+        .find(|ix| message.account_keys[usize::from(ix.program_id_index)] == system_program::id())
+        .unwrap();
+    assert_eq!(
+        bincode::deserialize::<SystemInstruction>(&transfer.data).unwrap(),
+        SystemInstruction::Transfer {
+            lamports: 1_500_000_000
+        }
+    );
+    let reference: Pubkey = invoice["reference"].as_str().unwrap().parse().unwrap();
+    assert_eq!(
+        message.account_keys[usize::from(transfer.accounts[2])],
+        reference
+    );
+}
+
+#[tokio::test]
+async fn paid_invoices_disable_their_blink_and_have_a_receipt() {
+    let rpc = MockRpc::spawn().await;
+    let app = spawn_invoicing_app(&rpc).await;
+    let key = app
+        .create_api_key("acme", &["create", "read", "update"])
+        .await;
+    let invoice = create_invoice(&app, &key, &invoice_request("INV-2")).await;
+    let id = invoice["id"].as_str().unwrap();
+    let blink_id = invoice["blink_id"].as_str().unwrap();
+    let payer = Pubkey::new_unique();
+    let body: Value = pay(&app, blink_id, &payer).await.json().await.unwrap();
+    let transaction = transaction(&body);
+
+    land(&rpc, &transaction);
+    let invoice: Value = get(&app, &key, &format!("/api/invoices/{}", id))
+        .await
+        .json()
+        .await
+        .unwrap();
+
+    assert_eq!(invoice["status"], "paid");
+    assert_eq!(invoice["payer"], payer.to_string());
+    assert_eq!(invoice["signature"], transaction.signatures[0].to_string());
+    let metadata = metadata(&app, blink_id).await;
+    assert_eq!(metadata["disabled"], true);
+    assert_eq!(metadata["error"]["message"], "This invoice has been paid");
+    assert_eq!(403, pay(&app, blink_id, &payer).await.status().as_u16());
+
+    let response = get(&app, &key, &format!("/api/invoices/{}/receipt", id)).await;
+    assert_eq!(200, response.status().as_u16());
+    assert_eq!(
+        response.headers()["content-disposition"],
+        "attachment; filename=\"receipt-INV-2.txt\""
+    );
+    let receipt = response.text().await.unwrap();
+    assert!(receipt.contains("Amount:      1.5 SOL"));
+    assert!(receipt.contains(&payer.to_string()));
+
+    let response = Client::new()
+        .post(format!("{}/api/invoices/{}/cancel", &app.address, id))
+        .bearer_auth(&key)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(409, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn transfers_of_other_amounts_do_not_pay_the_invoice() {
+    let rpc = MockRpc::spawn().await;
+    let app = spawn_invoicing_app(&rpc).await;
+    let key = app.create_api_key("acme", &["create", "read"]).await;
+    let invoice = create_invoice(&app, &key, &invoice_request("INV-3")).await;
+    let body: Value = pay(
+        &app,
+        invoice["blink_id"].as_str().unwrap(),
+        &Pubkey::new_unique(),
+    )
+    .await
+    .json()
+    .await
+    .unwrap();
+    let mut transaction = transaction(&body);
+    let transfer = transaction
+        .message
+        .instructions
+        .iter_mut()
+        .find(|ix| ix.data.len() == 12)
+        .unwrap();
+    transfer.data = bincode::serialize(&SystemInstruction::Transfer { lamports: 1 }).unwrap();
+
+    land(&rpc, &transaction);
+    let invoice: Value = get(
+        &app,
+        &key,
+        &format!("/api/invoices/{}", invoice["id"].as_str().unwrap()),
+    )
+    .await
+    .json()
+    .await
+    .unwrap();
+
+    assert_eq!(invoice["status"], "open");
+}
+
+#[tokio::test]
+async fn cancelled_invoices_cannot_be_paid() {
+    let rpc = MockRpc::spawn().await;
+    let app = spawn_invoicing_app(&rpc).await;
+    let key = app
+        .create_api_key("acme", &["create", "read", "update"])
+        .await;
+    let invoice = create_invoice(&app, &key, &invoice_request("INV-4")).await;
+    let id = invoice["id"].as_str().unwrap();
+    let blink_id = invoice["blink_id"].as_str().unwrap();
+
+    let response = Client::new()
+        .post(format!("{}/api/invoices/{}/cancel", &app.address, id))
+        .bearer_auth(&key)
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(200, response.status().as_u16());
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["status"], "cancelled");
+    let response = pay(&app, blink_id, &Pubkey::new_unique()).await;
+    assert_eq!(403, response.status().as_u16());
+    let metadata = metadata(&app, blink_id).await;
+    assert_eq!(metadata["error"]["message"], "This invoice was cancelled");
+    let response = get(&app, &key, &format!("/api/invoices/{}/receipt", id)).await;
+    assert_eq!(409, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn open_invoices_past_their_due_date_are_overdue() {
+    let rpc = MockRpc::spawn().await;
+    let app = spawn_invoicing_app(&rpc).await;
+    let key = app.create_api_key("acme", &["create", "read"]).await;
+    let mut request = invoice_request("INV-5");
+    request["due_date"] = json!("2020-01-01");
+    create_invoice(&app, &key, &request).await;
+
+    let invoices: Vec<Value> = get(&app, &key, "/api/invoices").await.json().await.unwrap();
+
+    assert_eq!(invoices.len(), 1);
+    assert_eq!(invoices[0]["status"], "overdue");
+}
+
+#[tokio::test]
+async fn token_invoices_pay_with_transfer_checked() {
+    let rpc = MockRpc::spawn().await;
+    let app = spawn_invoicing_app(&rpc).await;
+    let key = app.create_api_key("acme", &["create"]).await;
+    let mint = Pubkey::new_unique();
+    rpc.set_result("getAccountInfo", mint_account_info(6));
+    let mut request = invoice_request("INV-6");
+    request["currency"] = json!(mint.to_string());
+    request["amount"] = json!(12.5);
+
+    let invoice = create_invoice(&app, &key, &request).await;
+
+    assert_eq!(invoice["amount"], "12.5");
+    let body: Value = pay(
+        &app,
+        invoice["blink_id"].as_str().unwrap(),
+        &Pubkey::new_unique(),
+    )
+    .await
+    .json()
+    .await
+    .unwrap();
+    let transaction = transaction(&body);
+    let message = &transaction.message;
+    let transfer = message
+        .instructions
+        .iter()
+        .find(|ix| message.account_keys[usize::from(ix.program_id_index)] == SPL_TOKEN_PROGRAM_ID)
+        .unwrap();
+    let mut expected = vec![12];
+    expected.extend_from_slice(&12_500_000u64.to_le_bytes());
+    expected.push(6);
+    assert_eq!(transfer.data, expected);
+}
+
+#[tokio::test]
+async fn invoice_requests_are_validated() {
+    let rpc = MockRpc::spawn().await;
+    let app = spawn_invoicing_app(&rpc).await;
+    let key = app.create_api_key("acme", &["create"]).await;
+    create_invoice(&app, &key, &invoice_request("INV-7")).await;
+
+    let duplicate = post_invoice(&app, &key, &invoice_request("INV-7")).await;
+    assert_eq!(409, duplicate.status().as_u16());
+    for (field, value) in [
+        ("amount", json!(0)),
+        ("number", json!("")),
+        ("currency", json!("DOGE")),
+        ("wallet_address", json!("nope")),
+    ] {
+        let mut request = invoice_request("INV-8");
+        request[field] = value;
+
+        let response = post_invoice(&app, &key, &request).await;
+
+        assert_eq!(400, response.status().as_u16(), "{}", field);
+    }
+    // Reading invoices needs the `read` scope.
+    assert_eq!(
+        403,
+        get(&app, &key, "/api/invoices").await.status().as_u16()
+    );
+}
@@ -0,0 +1,192 @@
+mod helpers;
+
+use helpers::{
+    MockRpc, TestApp, confirmed_transaction_info, donation_blink, land_action, post_action_with,
+    spawn_app_with_rpc,
+};
+use reqwest::Client;
+use serde_json::{Value, json};
+use solana_sdk::{
+    hash::Hash,
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    transaction::Transaction,
+};
+
+const MEMO_PROGRAM_ID: Pubkey = solana_sdk::pubkey!("MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr");
+
+struct Guestbook {
+    app: TestApp,
+    rpc: MockRpc,
+    wallet: Pubkey,
+    id: String,
+}
+
+async fn open_guestbook(config: Value) -> Guestbook {
+    let rpc = MockRpc::spawn().await;
+    let app = spawn_app_with_rpc(&rpc).await;
+    let wallet = Pubkey::new_unique();
+    let mut body = donation_blink();
+    body["type"] = json!("message");
+    body["description"] = json!("");
+    body["label"] = json!("Sign the guestbook");
+    body["wallet_address"] = json!(wallet.to_string());
+    body["config"] = config;
+    let blink = app.create_blink(&body).await;
+
+    Guestbook {
+        app,
+        rpc,
+        wallet,
+        id: blink["id"].as_str().unwrap().to_string(),
+    }
+}
+
+impl Guestbook {
+    async fn post(&self, query: &str, author: &Pubkey) -> reqwest::Response {
+        post_action_with(&self.app, &self.id, query, author).await
+    }
+
+    /// Signs the served transaction and makes the cluster report it.
+    async fn land(&self, query: &str, author: &Keypair) -> Transaction {
+        land_action(&self.rpc, self.post(query, &author.pubkey()).await, author).await
+    }
+
+    async fn confirm(&self, author: &Keypair, transaction: &Transaction) -> reqwest::Response {
+        Client::new()
+            .post(format!(
+                "{}/api/actions/{}/confirm",
+                &self.app.address, self.id
+            ))
+            .json(&json!({
+                "account": author.pubkey().to_string(),
+                "signature": transaction.signatures[0].to_string()
+            }))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    async fn feed(&self) -> Value {
+        reqwest::get(format!(
+            "{}/api/blinks/{}/messages",
+            &self.app.address, self.id
+        ))
+        .await
+        .expect("Failed to execute request.")
+        .json()
+        .await
+        .unwrap()
+    }
+}
+
+#[tokio::test]
+async fn confirmed_messages_are_added_to_the_feed() {
+    let guestbook = open_guestbook(json!({ "tips": true })).await;
+    let metadata: Value = reqwest::get(format!(
+        "{}/api/actions/{}",
+        &guestbook.app.address, guestbook.id
+    ))
+    .await
+    .unwrap()
+    .json()
+    .await
+    .unwrap();
+    assert!(
+        metadata["links"]["actions"][0]["href"]
+            .as_str()
+            .unwrap()
+            .ends_with("?message={message}&amount={amount}")
+    );
+    assert_eq!(metadata["description"], "0 messages posted");
+    let author = Keypair::new();
+
+    let response = guestbook
+        .post("message=gm%20frens&amount=0.5", &author.pubkey())
+        .await;
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(
+        body["message"],
+        "Post a message to Test Blink with a 0.5 SOL tip"
+    );
+    assert_eq!(
+        body["links"]["next"]["href"],
+        format!("/api/actions/{}/confirm", guestbook.id)
+    );
+    let transaction = guestbook
+        .land("message=gm%20frens&amount=0.5", &author)
+        .await;
+    let memo = String::from_utf8(transaction.message.instructions[1].data.clone()).unwrap();
+    assert_eq!(memo, format!("msg:{}:gm frens", guestbook.id));
+    assert!(transaction.message.account_keys.contains(&guestbook.wallet));
+
+    let response = guestbook.confirm(&author, &transaction).await;
+    assert_eq!(200, response.status().as_u16());
+    // Confirming twice posts the message once.
+    guestbook.confirm(&author, &transaction).await;
+
+    let feed = guestbook.feed().await;
+    assert_eq!(feed.as_array().unwrap().len(), 1);
+    assert_eq!(feed[0]["body"], "gm frens");
+    assert_eq!(feed[0]["author"], author.pubkey().to_string());
+    assert_eq!(feed[0]["tip"], "0.5");
+    assert_eq!(feed[0]["signature"], transaction.signatures[0].to_string());
+    let metadata: Value = reqwest::get(format!(
+        "{}/api/actions/{}",
+        &guestbook.app.address, guestbook.id
+    ))
+    .await
+    .unwrap()
+    .json()
+    .await
+    .unwrap();
+    assert_eq!(metadata["description"], "1 message posted");
+}
+
+#[tokio::test]
+async fn messages_are_checked_before_they_are_served() {
+    let guestbook = open_guestbook(json!({ "max_length": 20 })).await;
+    let author = Pubkey::new_unique();
+
+    for query in [
+        "message=%20%20",
+        "message=this%20message%20is%20far%20too%20long",
+        "message=what%20the%20fuck",
+        "message=a%07b",
+    ] {
+        let response = guestbook.post(query, &author).await;
+        assert_eq!(400, response.status().as_u16(), "{}", query);
+    }
+    // Tips are only taken when the blink asks for them.
+    let response = guestbook.post("message=hi&amount=1", &author).await;
+    assert_eq!(400, response.status().as_u16());
+    let response = guestbook.post("message=hi&amount=", &author).await;
+    assert_eq!(200, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn memos_written_without_the_blink_are_checked_again() {
+    let guestbook = open_guestbook(json!({})).await;
+    let author = Keypair::new();
+    let memo = Instruction {
+        program_id: MEMO_PROGRAM_ID,
+        accounts: vec![AccountMeta::new_readonly(author.pubkey(), true)],
+        data: format!("msg:{}:sh1t post", guestbook.id).into_bytes(),
+    };
+    let transaction = Transaction::new_signed_with_payer(
+        &[memo],
+        Some(&author.pubkey()),
+        &[&author],
+        Hash::default(),
+    );
+    guestbook
+        .rpc
+        .set_result("getTransaction", confirmed_transaction_info(&transaction));
+
+    let response = guestbook.confirm(&author, &transaction).await;
+    assert_eq!(400, response.status().as_u16());
+    let response = guestbook.confirm(&Keypair::new(), &transaction).await;
+    assert_eq!(400, response.status().as_u16());
+    assert_eq!(guestbook.feed().await, json!([]));
+}
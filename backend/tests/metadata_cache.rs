@@ -0,0 +1,118 @@
+mod helpers;
+
+use helpers::{donation_blink, spawn_app, spawn_app_with};
+use reqwest::Client;
+use serde_json::{Value, json};
+
+#[tokio::test]
+async fn metadata_carries_etag_and_cache_control() {
+    let app = spawn_app().await;
+    let blink = app.create_blink(&donation_blink()).await;
+    let url = format!(
+        "{}/api/actions/{}",
+        &app.address,
+        blink["id"].as_str().unwrap()
+    );
+
+    let response = reqwest::get(&url).await.unwrap();
+
+    assert_eq!(200, response.status().as_u16());
+    assert!(
+        response.headers()["etag"]
+            .to_str()
+            .unwrap()
+            .starts_with("\"1-")
+    );
+    assert_eq!(response.headers()["cache-control"], "public, max-age=30");
+    assert_eq!(response.headers()["x-action-version"], "2.1.3");
+}
+
+#[tokio::test]
+async fn matching_if_none_match_returns_304() {
+    let app = spawn_app().await;
+    let blink = app.create_blink(&donation_blink()).await;
+    let url = format!(
+        "{}/api/actions/{}",
+        &app.address,
+        blink["id"].as_str().unwrap()
+    );
+    let etag = reqwest::get(&url).await.unwrap().headers()["etag"].clone();
+
+    let response = Client::new()
+        .get(&url)
+        .header("if-none-match", etag.clone())
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(304, response.status().as_u16());
+    assert_eq!(response.headers()["etag"], etag);
+    assert!(response.bytes().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn cached_metadata_is_served_without_the_database() {
+    let app = spawn_app().await;
+    let blink = app.create_blink(&donation_blink()).await;
+    let id = blink["id"].as_str().unwrap();
+    let url = format!("{}/api/actions/{}", &app.address, id);
+    reqwest::get(&url).await.unwrap();
+
+    app.db_pool.close().await;
+
+    let response = reqwest::get(&url).await.unwrap();
+    assert_eq!(200, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn updates_invalidate_the_cache() {
+    let app = spawn_app().await;
+    let key = app.create_api_key("acme", &["create", "update"]).await;
+    let blink = app.create_blink_with_key(&key, &donation_blink()).await;
+    let id = blink["id"].as_str().unwrap();
+    let url = format!("{}/api/actions/{}", &app.address, id);
+    let etag = reqwest::get(&url).await.unwrap().headers()["etag"].clone();
+
+    Client::new()
+        .put(format!("{}/api/blinks/{}", &app.address, id))
+        .bearer_auth(&key)
+        .json(&json!({
+            "title": "Updated",
+            "icon_url": "https://example.com/icon.png",
+            "description": "A test blink",
+            "label": "Donate",
+            "type": "donation",
+            "config": { "amount": 0.1 }
+        }))
+        .send()
+        .await
+        .unwrap();
+
+    let response = Client::new()
+        .get(&url)
+        .header("if-none-match", etag.clone())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(200, response.status().as_u16());
+    assert_ne!(response.headers()["etag"], etag);
+    let metadata: Value = response.json().await.unwrap();
+    assert_eq!(metadata["title"], "Updated");
+}
+
+#[tokio::test]
+async fn zero_ttl_disables_caching() {
+    let app = spawn_app_with(|c| c.metadata_cache.ttl_secs = 0).await;
+    let blink = app.create_blink(&donation_blink()).await;
+    let url = format!(
+        "{}/api/actions/{}",
+        &app.address,
+        blink["id"].as_str().unwrap()
+    );
+    reqwest::get(&url).await.unwrap();
+
+    app.db_pool.close().await;
+
+    let response = reqwest::get(&url).await.unwrap();
+    assert_eq!(500, response.status().as_u16());
+}
@@ -0,0 +1,134 @@
+mod helpers;
+
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use helpers::{
+    MINT_PROGRAMS, MockRpc, enable_minting, mint_blink, post_action, spawn_app, spawn_app_with,
+    spawn_minting_app,
+};
+use serde_json::{Value, json};
+use solana_sdk::{
+    pubkey::Pubkey,
+    signature::{Keypair, Signature, Signer},
+    transaction::Transaction,
+};
+
+fn transaction(response: &Value) -> Transaction {
+    let bytes = BASE64
+        .decode(response["transaction"].as_str().unwrap())
+        .unwrap();
+    bincode::deserialize(&bytes).unwrap()
+}
+
+#[tokio::test]
+async fn mint_returns_a_transaction_signed_by_the_server() {
+    let rpc = MockRpc::spawn().await;
+    let authority = Keypair::new();
+    let app = spawn_minting_app(&rpc, &authority, &MINT_PROGRAMS).await;
+    let blink = app.create_blink(&mint_blink(10, None)).await;
+    let id = blink["id"].as_str().unwrap();
+    let buyer = Pubkey::new_unique();
+
+    let response = post_action(&app, id, &buyer).await;
+
+    assert_eq!(200, response.status().as_u16());
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["message"], "Mint Season Pass #1 for 0.5 SOL");
+    let transaction = transaction(&body);
+    let keys = &transaction.message.account_keys;
+    assert_eq!(keys[0], buyer);
+    assert_eq!(transaction.message.header.num_required_signatures, 3);
+    // The buyer signs last, in their wallet.
+    assert_eq!(transaction.signatures[0], Signature::default());
+    assert!(
+        transaction.signatures[1..]
+            .iter()
+            .all(|signature| *signature != Signature::default())
+    );
+    assert!(keys.contains(&authority.pubkey()));
+}
+
+#[tokio::test]
+async fn serial_numbers_stop_at_the_supply() {
+    let rpc = MockRpc::spawn().await;
+    let app = spawn_minting_app(&rpc, &Keypair::new(), &MINT_PROGRAMS).await;
+    let blink = app.create_blink(&mint_blink(1, None)).await;
+    let id = blink["id"].as_str().unwrap();
+
+    let first = post_action(&app, id, &Pubkey::new_unique()).await;
+    let second = post_action(&app, id, &Pubkey::new_unique()).await;
+
+    assert_eq!(200, first.status().as_u16());
+    assert_eq!(403, second.status().as_u16());
+    let body: Value = second.json().await.unwrap();
+    assert_eq!(body["message"], "Sold out");
+}
+
+#[tokio::test]
+async fn wallets_cannot_mint_past_their_limit() {
+    let rpc = MockRpc::spawn().await;
+    let app = spawn_minting_app(&rpc, &Keypair::new(), &MINT_PROGRAMS).await;
+    let blink = app.create_blink(&mint_blink(10, Some(1))).await;
+    let id = blink["id"].as_str().unwrap();
+    let buyer = Pubkey::new_unique();
+
+    let first = post_action(&app, id, &buyer).await;
+    let second = post_action(&app, id, &buyer).await;
+    let other = post_action(&app, id, &Pubkey::new_unique()).await;
+
+    assert_eq!(200, first.status().as_u16());
+    assert_eq!(403, second.status().as_u16());
+    let body: Value = second.json().await.unwrap();
+    assert_eq!(
+        body["message"],
+        "This wallet has reached the limit of 1 mints"
+    );
+    assert_eq!(200, other.status().as_u16());
+}
+
+#[tokio::test]
+async fn expired_reservations_that_never_landed_are_released() {
+    let rpc = MockRpc::spawn().await;
+    let authority = Keypair::new();
+    let app = spawn_app_with(|c| {
+        enable_minting(c, &rpc, &authority, &MINT_PROGRAMS);
+        c.solana.nft_mint.reservation_secs = 0;
+    })
+    .await;
+    let blink = app.create_blink(&mint_blink(1, None)).await;
+    let id = blink["id"].as_str().unwrap();
+    rpc.set_result(
+        "getMultipleAccounts",
+        json!({ "context": { "slot": 1 }, "value": [null] }),
+    );
+
+    let first = post_action(&app, id, &Pubkey::new_unique()).await;
+    let second = post_action(&app, id, &Pubkey::new_unique()).await;
+
+    assert_eq!(200, first.status().as_u16());
+    assert_eq!(200, second.status().as_u16());
+    let body: Value = second.json().await.unwrap();
+    assert_eq!(body["message"], "Mint Season Pass #1 for 0.5 SOL");
+    assert_eq!(rpc.calls_to("getMultipleAccounts"), 1);
+}
+
+#[tokio::test]
+async fn mint_is_unavailable_when_minting_is_disabled() {
+    let app = spawn_app().await;
+    let blink = app.create_blink(&mint_blink(10, None)).await;
+    let id = blink["id"].as_str().unwrap();
+
+    let response = post_action(&app, id, &Pubkey::new_unique()).await;
+
+    assert_eq!(503, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn create_blink_rejects_invalid_mint_configs() {
+    let app = spawn_app().await;
+    let mut body = mint_blink(10, None);
+    body["config"]["supply"] = json!(0);
+
+    let response = app.post_blink(&body).await;
+
+    assert_eq!(400, response.status().as_u16());
+}
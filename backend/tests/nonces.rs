@@ -0,0 +1,287 @@
+mod helpers;
+
+use blinkzero::configuration::Settings;
+use helpers::{
+    ADMIN_TOKEN, MockRpc, TestApp, decode_transaction, donation_blink, nonce_account_info,
+    post_action, spawn_app_with,
+};
+use reqwest::Client;
+use secrecy::SecretString;
+use serde_json::{Value, json};
+use solana_sdk::{
+    hash::Hash,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    system_program,
+    transaction::Transaction,
+};
+use std::time::Duration;
+
+fn enable_durable_nonces(c: &mut Settings, rpc: &MockRpc, authority: &Keypair) {
+    c.solana.rpc_endpoints = vec![rpc.endpoint(1)];
+    c.solana.durable_nonce.enabled = true;
+    c.solana.durable_nonce.authority_keypair = SecretString::from(authority.to_base58_string());
+    c.solana.durable_nonce.reclaim_interval_ms = 0;
+}
+
+fn durable_blink() -> Value {
+    let mut body = donation_blink();
+    body["config"]["durable_nonce"] = json!(true);
+    body
+}
+
+async fn register_nonce_account(app: &TestApp, pubkey: &Pubkey) -> reqwest::Response {
+    Client::new()
+        .post(format!("{}/api/nonces", &app.address))
+        .bearer_auth(ADMIN_TOKEN)
+        .json(&json!({ "pubkey": pubkey.to_string() }))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+/// Waits up to five seconds for a background sweep to make `condition` true.
+async fn eventually(condition: impl Fn() -> bool) {
+    for _ in 0..100 {
+        if condition() {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    panic!("condition not met in time");
+}
+
+/// The nonce account advanced by the transaction's first instruction.
+fn nonce_account_of(transaction: &Transaction) -> Pubkey {
+    let message = &transaction.message;
+    let advance = &message.instructions[0];
+    assert_eq!(
+        message.account_keys[advance.program_id_index as usize],
+        system_program::id()
+    );
+    message.account_keys[advance.accounts[0] as usize]
+}
+
+#[tokio::test]
+async fn durable_nonce_blinks_build_transactions_on_a_leased_nonce() {
+    let rpc = MockRpc::spawn().await;
+    let authority = Keypair::new();
+    let blockhash = Hash::new_unique();
+    rpc.set_result(
+        "getAccountInfo",
+        nonce_account_info(&authority.pubkey(), &blockhash),
+    );
+    let app = spawn_app_with(|c| enable_durable_nonces(c, &rpc, &authority)).await;
+    let nonce_account = Pubkey::new_unique();
+    assert_eq!(
+        201,
+        register_nonce_account(&app, &nonce_account)
+            .await
+            .status()
+            .as_u16()
+    );
+    let blink = app.create_blink(&durable_blink()).await;
+    let payer = Pubkey::new_unique();
+
+    let transaction =
+        decode_transaction(post_action(&app, blink["id"].as_str().unwrap(), &payer).await).await;
+
+    assert_eq!(nonce_account_of(&transaction), nonce_account);
+    assert_eq!(transaction.message.account_keys[0], payer);
+    let authority_index = transaction
+        .message
+        .account_keys
+        .iter()
+        .position(|key| *key == authority.pubkey())
+        .unwrap();
+    assert!(
+        transaction.signatures[authority_index]
+            .verify(authority.pubkey().as_ref(), &transaction.message_data())
+    );
+
+    let accounts: Vec<Value> = Client::new()
+        .get(format!("{}/api/nonces", &app.address))
+        .bearer_auth(ADMIN_TOKEN)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(accounts[0]["pubkey"], nonce_account.to_string());
+    assert_eq!(
+        accounts[0]["leased_nonce"],
+        transaction.message.recent_blockhash.to_string()
+    );
+}
+
+#[tokio::test]
+async fn concurrent_requests_never_share_a_nonce_account() {
+    let rpc = MockRpc::spawn().await;
+    let authority = Keypair::new();
+    rpc.set_result(
+        "getAccountInfo",
+        nonce_account_info(&authority.pubkey(), &Hash::new_unique()),
+    );
+    let app = spawn_app_with(|c| enable_durable_nonces(c, &rpc, &authority)).await;
+    for _ in 0..3 {
+        register_nonce_account(&app, &Pubkey::new_unique()).await;
+    }
+    let blink = app.create_blink(&durable_blink()).await;
+    let id = blink["id"].as_str().unwrap();
+    let payers: Vec<Pubkey> = (0..5).map(|_| Pubkey::new_unique()).collect();
+
+    let responses =
+        futures::future::join_all(payers.iter().map(|payer| post_action(&app, id, payer))).await;
+
+    let mut leased = Vec::new();
+    let mut exhausted = 0;
+    for response in responses {
+        match response.status().as_u16() {
+            200 => leased.push(nonce_account_of(&decode_transaction(response).await)),
+            503 => exhausted += 1,
+            status => panic!("unexpected status {}", status),
+        }
+    }
+    leased.sort();
+    leased.dedup();
+    assert_eq!(leased.len(), 3);
+    assert_eq!(exhausted, 2);
+}
+
+#[tokio::test]
+async fn used_nonce_accounts_return_to_the_pool() {
+    let rpc = MockRpc::spawn().await;
+    let authority = Keypair::new();
+    rpc.set_result(
+        "getAccountInfo",
+        nonce_account_info(&authority.pubkey(), &Hash::new_unique()),
+    );
+    let app = spawn_app_with(|c| {
+        enable_durable_nonces(c, &rpc, &authority);
+        c.solana.durable_nonce.reclaim_interval_ms = 50;
+    })
+    .await;
+    register_nonce_account(&app, &Pubkey::new_unique()).await;
+    let blink = app.create_blink(&durable_blink()).await;
+    let id = blink["id"].as_str().unwrap();
+    let payer = Pubkey::new_unique();
+
+    let first = decode_transaction(post_action(&app, id, &payer).await).await;
+    assert_eq!(503, post_action(&app, id, &payer).await.status().as_u16());
+
+    // The transaction landed and advanced the nonce.
+    rpc.set_result(
+        "getAccountInfo",
+        nonce_account_info(&authority.pubkey(), &Hash::new_unique()),
+    );
+    let mut response = post_action(&app, id, &payer).await;
+    for _ in 0..100 {
+        if response.status().as_u16() != 503 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        response = post_action(&app, id, &payer).await;
+    }
+    let second = decode_transaction(response).await;
+    assert_eq!(nonce_account_of(&second), nonce_account_of(&first));
+    assert_ne!(
+        second.message.recent_blockhash,
+        first.message.recent_blockhash
+    );
+}
+
+#[tokio::test]
+async fn failed_actions_give_the_nonce_account_back() {
+    let rpc = MockRpc::spawn().await;
+    let authority = Keypair::new();
+    rpc.set_result(
+        "getAccountInfo",
+        nonce_account_info(&authority.pubkey(), &Hash::new_unique()),
+    );
+    let app = spawn_app_with(|c| enable_durable_nonces(c, &rpc, &authority)).await;
+    register_nonce_account(&app, &Pubkey::new_unique()).await;
+    let blink = app.create_blink(&durable_blink()).await;
+    let id = blink["id"].as_str().unwrap();
+    let payer = Pubkey::new_unique();
+    // The build of a leased transaction cannot be recorded.
+    sqlx::query("ALTER TABLE action_builds ADD CONSTRAINT no_nonces CHECK (nonce_account IS NULL)")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    assert_eq!(500, post_action(&app, id, &payer).await.status().as_u16());
+
+    sqlx::query("ALTER TABLE action_builds DROP CONSTRAINT no_nonces")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    decode_transaction(post_action(&app, id, &payer).await).await;
+}
+
+#[tokio::test]
+async fn expired_unused_leases_are_advanced_by_the_backend() {
+    let rpc = MockRpc::spawn().await;
+    let authority = Keypair::new();
+    rpc.set_result(
+        "getAccountInfo",
+        nonce_account_info(&authority.pubkey(), &Hash::new_unique()),
+    );
+    let app = spawn_app_with(|c| {
+        enable_durable_nonces(c, &rpc, &authority);
+        c.solana.durable_nonce.lease_secs = 1;
+        c.solana.durable_nonce.reclaim_interval_ms = 50;
+    })
+    .await;
+    register_nonce_account(&app, &Pubkey::new_unique()).await;
+    let blink = app.create_blink(&durable_blink()).await;
+    let id = blink["id"].as_str().unwrap();
+    let payer = Pubkey::new_unique();
+    decode_transaction(post_action(&app, id, &payer).await).await;
+
+    eventually(|| rpc.calls_to("sendTransaction") > 0).await;
+    // Not handed out again while the stale transaction could still land.
+    assert_eq!(503, post_action(&app, id, &payer).await.status().as_u16());
+}
+
+#[tokio::test]
+async fn register_rejects_accounts_with_another_authority() {
+    let rpc = MockRpc::spawn().await;
+    let authority = Keypair::new();
+    rpc.set_result(
+        "getAccountInfo",
+        nonce_account_info(&Pubkey::new_unique(), &Hash::new_unique()),
+    );
+    let app = spawn_app_with(|c| enable_durable_nonces(c, &rpc, &authority)).await;
+
+    let response = register_nonce_account(&app, &Pubkey::new_unique()).await;
+
+    assert_eq!(400, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn register_requires_the_admin_token() {
+    let rpc = MockRpc::spawn().await;
+    let authority = Keypair::new();
+    let app = spawn_app_with(|c| enable_durable_nonces(c, &rpc, &authority)).await;
+
+    let response = Client::new()
+        .post(format!("{}/api/nonces", &app.address))
+        .json(&json!({ "pubkey": Pubkey::new_unique().to_string() }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(401, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn durable_nonce_blinks_return_503_when_nonces_are_disabled() {
+    let rpc = MockRpc::spawn().await;
+    let app = spawn_app_with(|c| c.solana.rpc_endpoints = vec![rpc.endpoint(1)]).await;
+    let blink = app.create_blink(&durable_blink()).await;
+
+    let response = post_action(&app, blink["id"].as_str().unwrap(), &Pubkey::new_unique()).await;
+
+    assert_eq!(503, response.status().as_u16());
+}
@@ -0,0 +1,209 @@
+mod helpers;
+
+use chrono::{Duration, Utc};
+use helpers::{
+    MockRpc, TestApp, donation_blink, land_action, post_action_with, spawn_app_with,
+    system_transfer,
+};
+use reqwest::Client;
+use serde_json::{Value, json};
+use solana_sdk::{pubkey::Pubkey, signature::Keypair, signer::Signer, transaction::Transaction};
+
+struct Raffle {
+    app: TestApp,
+    rpc: MockRpc,
+    seller: Pubkey,
+    id: String,
+}
+
+/// Creates a raffle blink selling tickets for 0.1 SOL, at most 3 a wallet.
+async fn open_raffle(closes_in: Duration) -> Raffle {
+    let rpc = MockRpc::spawn().await;
+    rpc.set_result("getSignaturesForAddress", json!([]));
+    let app = spawn_app_with(|c| {
+        c.solana.rpc_endpoints = vec![rpc.endpoint(1)];
+    })
+    .await;
+    let key = app.create_api_key("acme", &["create"]).await;
+    let seller = Pubkey::new_unique();
+    let mut body = donation_blink();
+    body["type"] = json!("raffle");
+    body["title"] = json!("Community raffle");
+    body["description"] = json!("");
+    body["label"] = json!("Buy tickets");
+    body["wallet_address"] = json!(seller.to_string());
+    body["config"] = json!({
+        "ticket_price": 0.1,
+        "max_per_wallet": 3,
+        "closes_at": Utc::now() + closes_in,
+    });
+    let blink = app.create_blink_with_key(&key, &body).await;
+
+    Raffle {
+        app,
+        rpc,
+        seller,
+        id: blink["id"].as_str().unwrap().to_string(),
+    }
+}
+
+impl Raffle {
+    async fn post(&self, tickets: u32, wallet: &Pubkey) -> reqwest::Response {
+        post_action_with(&self.app, &self.id, &format!("tickets={}", tickets), wallet).await
+    }
+
+    /// Buys `tickets` for `buyer` and lands the purchase.
+    async fn buy(&self, tickets: u32, buyer: &Keypair) -> Transaction {
+        land_action(&self.rpc, self.post(tickets, &buyer.pubkey()).await, buyer).await
+    }
+
+    async fn metadata(&self) -> Value {
+        reqwest::get(format!("{}/api/actions/{}", &self.app.address, self.id))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap()
+    }
+
+    async fn draw(&self) -> reqwest::Response {
+        reqwest::get(format!("{}/api/blinks/{}/draw", &self.app.address, self.id))
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    /// Closes the raffle as if its close time had passed.
+    async fn close(&self) {
+        sqlx::query("UPDATE raffles SET closes_at = now() - interval '1 minute'")
+            .execute(&self.app.db_pool)
+            .await
+            .unwrap();
+    }
+}
+
+#[tokio::test]
+async fn landed_purchases_become_tickets() {
+    let raffle = open_raffle(Duration::days(2) + Duration::minutes(30)).await;
+    let metadata = raffle.metadata().await;
+    assert_eq!(
+        metadata["links"]["actions"][0]["label"],
+        "Buy 1 ticket (0.1 SOL)"
+    );
+    assert_eq!(metadata["description"], "0 tickets sold · Closes in 2d 0h");
+    let buyer = Keypair::new();
+
+    let purchase = raffle.buy(2, &buyer).await;
+    assert_eq!(
+        system_transfer(&purchase),
+        (buyer.pubkey(), raffle.seller, 200_000_000)
+    );
+    assert_eq!(
+        raffle.metadata().await["description"],
+        "2 tickets sold · Closes in 2d 0h"
+    );
+
+    let response = raffle.post(2, &buyer.pubkey()).await;
+    assert_eq!(403, response.status().as_u16());
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["message"], "This wallet can buy 1 more ticket");
+    let response = raffle.post(4, &Pubkey::new_unique()).await;
+    assert_eq!(400, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn closed_raffles_sell_no_tickets() {
+    let raffle = open_raffle(Duration::days(1)).await;
+    raffle.buy(1, &Keypair::new()).await;
+    raffle.close().await;
+
+    let response = raffle.post(1, &Pubkey::new_unique()).await;
+    assert_eq!(403, response.status().as_u16());
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["message"], "This raffle has closed");
+    let metadata = raffle.metadata().await;
+    assert_eq!(metadata["disabled"], true);
+    assert_eq!(
+        metadata["description"],
+        "1 ticket sold · Closed, the draw is pending"
+    );
+}
+
+#[tokio::test]
+async fn draws_wait_for_the_committed_slot() {
+    let raffle = open_raffle(Duration::days(1)).await;
+    let first = Keypair::new();
+    let second = Keypair::new();
+    raffle.buy(3, &first).await;
+    raffle.buy(1, &second).await;
+
+    let response = raffle.draw().await;
+    assert_eq!(409, response.status().as_u16());
+
+    raffle.close().await;
+    raffle.rpc.set_result("getBlocksWithLimit", json!([]));
+    let response = raffle.draw().await;
+    assert_eq!(409, response.status().as_u16());
+
+    let draw_slot: i64 = sqlx::query_scalar("SELECT draw_slot FROM raffles")
+        .fetch_one(&raffle.app.db_pool)
+        .await
+        .unwrap();
+    assert!(draw_slot > 1000 + 24 * 60 * 60 * 1000 / 400);
+    raffle
+        .rpc
+        .set_result("getBlocksWithLimit", json!([draw_slot + 2]));
+    raffle.rpc.set_result(
+        "getBlock",
+        json!({
+            "blockhash": "9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFin",
+            "previousBlockhash": "4uQeVj5tqViQh7yWWGStvkEG1Zmhx6uasJtWCJziofM",
+            "parentSlot": draw_slot + 1,
+            "blockHeight": null,
+            "blockTime": null
+        }),
+    );
+    let response = raffle.draw().await;
+    assert_eq!(200, response.status().as_u16());
+    let draw: Value = response.json().await.unwrap();
+    assert_eq!(draw["seed_slot"], draw_slot + 2);
+    assert_eq!(draw["seed"], "9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFin");
+    assert_eq!(draw["tickets"].as_array().unwrap().len(), 4);
+    assert_eq!(draw["ticket_hash"].as_str().unwrap().len(), 64);
+    assert_eq!(draw["winners"].as_array().unwrap().len(), 1);
+
+    // The stored draw is published from then on.
+    raffle.rpc.set_result("getBlock", json!(null));
+    let again: Value = raffle.draw().await.json().await.unwrap();
+    assert_eq!(again, draw);
+    let winner = draw["winners"][0]["wallet"].as_str().unwrap();
+    assert!(
+        raffle.metadata().await["description"]
+            .as_str()
+            .unwrap()
+            .ends_with(&format!("Drawn: {}", winner))
+    );
+}
+
+#[tokio::test]
+async fn raffle_configs_are_validated() {
+    let app = spawn_app_with(|_| {}).await;
+    let key = app.create_api_key("acme", &["create"]).await;
+
+    for config in [
+        json!({ "ticket_price": 0.1 }),
+        json!({ "ticket_price": -1, "closes_at": "2026-03-01T18:00:00Z" }),
+        json!({ "ticket_price": 0.1, "closes_at": "2026-03-01T18:00:00Z", "durable_nonce": true }),
+    ] {
+        let mut body = donation_blink();
+        body["type"] = json!("raffle");
+        body["config"] = config;
+        let response = Client::new()
+            .post(format!("{}/api/blinks", &app.address))
+            .bearer_auth(&key)
+            .json(&body)
+            .send()
+            .await
+            .expect("Failed to execute request.");
+        assert_eq!(400, response.status().as_u16());
+    }
+}
@@ -0,0 +1,211 @@
+mod helpers;
+
+use blinkzero::configuration::{RateLimitKey, RouteRateLimit};
+use helpers::{donation_blink, spawn_app_with};
+use reqwest::Client;
+use serde_json::json;
+
+fn strict(key: RateLimitKey) -> RouteRateLimit {
+    RouteRateLimit {
+        period_ms: 60_000,
+        burst_size: 2,
+        key,
+    }
+}
+
+#[tokio::test]
+async fn action_endpoints_return_429_action_error_with_retry_after() {
+    let app = spawn_app_with(|c| {
+        c.rate_limit.enabled = true;
+        c.rate_limit.actions = strict(RateLimitKey::PeerIp);
+    })
+    .await;
+    let blink = app.create_blink(&donation_blink()).await;
+    let url = format!(
+        "{}/api/actions/{}",
+        &app.address,
+        blink["id"].as_str().unwrap()
+    );
+
+    for _ in 0..2 {
+        let response = reqwest::get(&url).await.unwrap();
+        assert_eq!(200, response.status().as_u16());
+    }
+    let response = reqwest::get(&url).await.unwrap();
+
+    assert_eq!(429, response.status().as_u16());
+    let retry_after: u64 = response.headers()["retry-after"]
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!(retry_after > 0);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert!(
+        body["message"]
+            .as_str()
+            .unwrap()
+            .starts_with("Too many requests")
+    );
+}
+
+#[tokio::test]
+async fn route_groups_have_independent_buckets() {
+    let app = spawn_app_with(|c| {
+        c.rate_limit.enabled = true;
+        c.rate_limit.pages = strict(RateLimitKey::PeerIp);
+    })
+    .await;
+
+    for _ in 0..2 {
+        let response = reqwest::get(format!("{}/actions.json", &app.address))
+            .await
+            .unwrap();
+        assert_eq!(200, response.status().as_u16());
+    }
+    let response = reqwest::get(format!("{}/actions.json", &app.address))
+        .await
+        .unwrap();
+    assert_eq!(429, response.status().as_u16());
+
+    let response = app.post_blink(&donation_blink()).await;
+    assert_eq!(200, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn forwarded_for_keys_clients_separately_behind_trusted_proxy() {
+    let app = spawn_app_with(|c| {
+        c.rate_limit.enabled = true;
+        c.rate_limit.trusted_proxies = vec!["127.0.0.1/32".parse().unwrap()];
+        c.rate_limit.blinks = strict(RateLimitKey::ForwardedFor);
+    })
+    .await;
+    let client = Client::new();
+    let post_as = |ip: &'static str| {
+        client
+            .post(format!("{}/api/blinks", &app.address))
+            .header("x-forwarded-for", ip)
+            .json(&donation_blink())
+            .send()
+    };
+
+    for _ in 0..2 {
+        assert_eq!(
+            200,
+            post_as("198.51.100.1").await.unwrap().status().as_u16()
+        );
+    }
+    assert_eq!(
+        429,
+        post_as("198.51.100.1").await.unwrap().status().as_u16()
+    );
+    assert_eq!(
+        200,
+        post_as("198.51.100.2").await.unwrap().status().as_u16()
+    );
+}
+
+#[tokio::test]
+async fn payer_key_limits_each_account_separately() {
+    let app = spawn_app_with(|c| {
+        c.rate_limit.enabled = true;
+        c.rate_limit.actions = strict(RateLimitKey::Payer);
+    })
+    .await;
+    let blink = app.create_blink(&donation_blink()).await;
+    let url = format!(
+        "{}/api/actions/{}",
+        &app.address,
+        blink["id"].as_str().unwrap()
+    );
+    let client = Client::new();
+
+    for _ in 0..2 {
+        let response = client
+            .post(&url)
+            .json(&json!({ "account": "payer-one" }))
+            .send()
+            .await
+            .unwrap();
+        assert_ne!(429, response.status().as_u16());
+    }
+    let response = client
+        .post(&url)
+        .json(&json!({ "account": "payer-one" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(429, response.status().as_u16());
+
+    let response = client
+        .post(&url)
+        .json(&json!({ "account": "payer-two" }))
+        .send()
+        .await
+        .unwrap();
+    assert_ne!(429, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn api_keys_have_their_own_bucket() {
+    let app = spawn_app_with(|c| {
+        c.rate_limit.enabled = true;
+        c.rate_limit.blinks = strict(RateLimitKey::PeerIp);
+        c.rate_limit.api_keys = strict(RateLimitKey::ApiKey);
+    })
+    .await;
+    let first_key = app.create_api_key("acme", &["create"]).await;
+    let second_key = app.create_api_key("acme", &["create"]).await;
+    let post_with = |key: &str| {
+        Client::new()
+            .post(format!("{}/api/blinks", &app.address))
+            .bearer_auth(key)
+            .json(&donation_blink())
+            .send()
+    };
+
+    for _ in 0..2 {
+        assert_eq!(
+            200,
+            app.post_blink(&donation_blink()).await.status().as_u16()
+        );
+    }
+    assert_eq!(
+        429,
+        app.post_blink(&donation_blink()).await.status().as_u16()
+    );
+
+    for _ in 0..2 {
+        assert_eq!(200, post_with(&first_key).await.unwrap().status().as_u16());
+    }
+    assert_eq!(429, post_with(&first_key).await.unwrap().status().as_u16());
+    assert_eq!(200, post_with(&second_key).await.unwrap().status().as_u16());
+}
+
+#[tokio::test]
+async fn unknown_bearer_tokens_are_limited_by_ip() {
+    let app = spawn_app_with(|c| {
+        c.rate_limit.enabled = true;
+        c.rate_limit.blinks = strict(RateLimitKey::PeerIp);
+        c.rate_limit.api_keys = strict(RateLimitKey::ApiKey);
+    })
+    .await;
+    let post_with = |key: String| {
+        Client::new()
+            .post(format!("{}/api/blinks", &app.address))
+            .bearer_auth(key)
+            .json(&donation_blink())
+            .send()
+    };
+
+    for _ in 0..2 {
+        assert_eq!(
+            200,
+            app.post_blink(&donation_blink()).await.status().as_u16()
+        );
+    }
+    for _ in 0..3 {
+        let token = format!("bz_{}", uuid::Uuid::new_v4().simple());
+        assert_eq!(429, post_with(token).await.unwrap().status().as_u16());
+    }
+}
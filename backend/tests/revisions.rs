@@ -0,0 +1,248 @@
+mod helpers;
+
+use helpers::{TestApp, donation_blink, spawn_app};
+use reqwest::Client;
+use serde_json::{Value, json};
+
+fn edited_blink(title: &str, amount: f64) -> Value {
+    json!({
+        "title": title,
+        "icon_url": "https://example.com/icon.png",
+        "description": "A test blink",
+        "label": "Donate",
+        "type": "donation",
+        "config": { "amount": amount }
+    })
+}
+
+async fn put_blink(app: &TestApp, key: &str, id: &str, body: &Value) -> reqwest::Response {
+    Client::new()
+        .put(format!("{}/api/blinks/{}", &app.address, id))
+        .bearer_auth(key)
+        .json(body)
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+async fn get_json(app: &TestApp, key: &str, path: &str) -> reqwest::Response {
+    Client::new()
+        .get(format!("{}{}", &app.address, path))
+        .bearer_auth(key)
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+/// Creates a blink owned by `acme` and returns its id and a key with every
+/// scope.
+async fn owned_blink(app: &TestApp) -> (String, String) {
+    let key = app
+        .create_api_key("acme", &["create", "read", "update"])
+        .await;
+    let blink = app.create_blink_with_key(&key, &donation_blink()).await;
+    (blink["id"].as_str().unwrap().to_string(), key)
+}
+
+#[tokio::test]
+async fn updating_a_blink_records_a_revision_and_serves_it() {
+    let app = spawn_app().await;
+    let (id, key) = owned_blink(&app).await;
+
+    let response = put_blink(&app, &key, &id, &edited_blink("Coffee fund", 0.5)).await;
+    assert_eq!(200, response.status().as_u16());
+    let revision: Value = response.json().await.unwrap();
+    assert_eq!(revision["revision"], 2);
+    assert_eq!(revision["author"], "acme");
+
+    let metadata: Value = reqwest::get(format!("{}/api/actions/{}", &app.address, id))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(metadata["title"], "Coffee fund");
+
+    let revisions: Vec<Value> = get_json(&app, &key, &format!("/api/blinks/{}/revisions", id))
+        .await
+        .json()
+        .await
+        .unwrap();
+    let numbers: Vec<i64> = revisions
+        .iter()
+        .map(|r| r["revision"].as_i64().unwrap())
+        .collect();
+    assert_eq!(numbers, vec![2, 1]);
+    assert_eq!(revisions[1]["title"], "Test Blink");
+}
+
+#[tokio::test]
+async fn diff_lists_changed_fields() {
+    let app = spawn_app().await;
+    let (id, key) = owned_blink(&app).await;
+    put_blink(&app, &key, &id, &edited_blink("Coffee fund", 0.5)).await;
+
+    let response = get_json(
+        &app,
+        &key,
+        &format!("/api/blinks/{}/revisions/diff?from=1&to=2", id),
+    )
+    .await;
+    assert_eq!(200, response.status().as_u16());
+    let diff: Value = response.json().await.unwrap();
+
+    assert_eq!(
+        diff["changes"],
+        json!([
+            { "field": "title", "from": "Test Blink", "to": "Coffee fund" },
+            { "field": "config.amount", "from": 0.1, "to": 0.5 }
+        ])
+    );
+}
+
+#[tokio::test]
+async fn rollback_restores_an_old_revision_as_a_new_one() {
+    let app = spawn_app().await;
+    let (id, key) = owned_blink(&app).await;
+    put_blink(&app, &key, &id, &edited_blink("Coffee fund", 0.5)).await;
+
+    let response = Client::new()
+        .post(format!(
+            "{}/api/blinks/{}/revisions/1/rollback",
+            &app.address, id
+        ))
+        .bearer_auth(&key)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(200, response.status().as_u16());
+    let revision: Value = response.json().await.unwrap();
+    assert_eq!(revision["revision"], 3);
+    assert_eq!(revision["rolled_back_from"], 1);
+    assert_eq!(revision["title"], "Test Blink");
+
+    let metadata: Value = reqwest::get(format!("{}/api/actions/{}", &app.address, id))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(metadata["title"], "Test Blink");
+}
+
+#[tokio::test]
+async fn rollback_to_unknown_revision_returns_404() {
+    let app = spawn_app().await;
+    let (id, key) = owned_blink(&app).await;
+
+    let response = Client::new()
+        .post(format!(
+            "{}/api/blinks/{}/revisions/7/rollback",
+            &app.address, id
+        ))
+        .bearer_auth(&key)
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(404, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn rollback_validates_the_revision_like_an_update() {
+    let app = spawn_app().await;
+    let (id, key) = owned_blink(&app).await;
+    put_blink(&app, &key, &id, &edited_blink("Coffee fund", 0.5)).await;
+    // A revision saved before the current checks existed.
+    sqlx::query(
+        "INSERT INTO blink_revisions
+            (blink_id, revision, title, icon_url, description, label, type, config)
+        SELECT blink_id, 0, title, icon_url, description, label, 'swap', $2
+        FROM blink_revisions WHERE blink_id = $1 AND revision = 1",
+    )
+    .bind(uuid::Uuid::parse_str(&id).unwrap())
+    .bind(json!({ "output_mint": "not-a-mint" }))
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    let response = Client::new()
+        .post(format!(
+            "{}/api/blinks/{}/revisions/0/rollback",
+            &app.address, id
+        ))
+        .bearer_auth(&key)
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(400, response.status().as_u16());
+    let metadata: Value = reqwest::get(format!("{}/api/actions/{}", &app.address, id))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(metadata["title"], "Coffee fund");
+}
+
+#[tokio::test]
+async fn revisions_cannot_be_modified() {
+    let app = spawn_app().await;
+    let (id, _) = owned_blink(&app).await;
+
+    let result = sqlx::query("UPDATE blink_revisions SET title = 'rewritten' WHERE blink_id = $1")
+        .bind(uuid::Uuid::parse_str(&id).unwrap())
+        .execute(&app.db_pool)
+        .await;
+
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn other_owners_cannot_edit_or_read_history() {
+    let app = spawn_app().await;
+    let (id, _) = owned_blink(&app).await;
+    let other_key = app.create_api_key("globex", &["read", "update"]).await;
+
+    let response = put_blink(&app, &other_key, &id, &edited_blink("Hijacked", 9.0)).await;
+    assert_eq!(404, response.status().as_u16());
+
+    let response = get_json(&app, &other_key, &format!("/api/blinks/{}/revisions", id)).await;
+    assert_eq!(404, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn builds_are_listed_per_revision() {
+    let app = spawn_app().await;
+    let (id, key) = owned_blink(&app).await;
+    put_blink(&app, &key, &id, &edited_blink("Coffee fund", 0.5)).await;
+    let blink_id = uuid::Uuid::parse_str(&id).unwrap();
+
+    for (revision, account) in [(1, "payer-one"), (2, "payer-two")] {
+        sqlx::query(
+            "INSERT INTO action_builds (blink_id, revision, account, transaction, message)
+             VALUES ($1, $2, $3, 'dHg=', 'Send SOL')",
+        )
+        .bind(blink_id)
+        .bind(revision)
+        .bind(account)
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    }
+
+    let builds: Vec<Value> = get_json(
+        &app,
+        &key,
+        &format!("/api/blinks/{}/revisions/1/builds", id),
+    )
+    .await
+    .json()
+    .await
+    .unwrap();
+
+    assert_eq!(builds.len(), 1);
+    assert_eq!(builds[0]["account"], "payer-one");
+    assert_eq!(builds[0]["revision"], 1);
+}
@@ -0,0 +1,139 @@
+mod helpers;
+
+use helpers::{ADMIN_TOKEN, MockRpc, TestApp, donation_blink, spawn_app_with};
+use reqwest::Client;
+use serde_json::{Value, json};
+use solana_sdk::pubkey::Pubkey;
+use std::time::Duration;
+
+async fn post_action(app: &TestApp, id: &str) -> reqwest::Response {
+    Client::new()
+        .post(format!("{}/api/actions/{}?amount=0.1", &app.address, id))
+        .json(&json!({ "account": Pubkey::new_unique().to_string() }))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+async fn rpc_metrics(app: &TestApp) -> Vec<Value> {
+    let response = Client::new()
+        .get(format!("{}/api/rpc/endpoints", &app.address))
+        .bearer_auth(ADMIN_TOKEN)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(200, response.status().as_u16());
+    response.json().await.unwrap()
+}
+
+fn metrics_for<'a>(metrics: &'a [Value], rpc: &MockRpc) -> &'a Value {
+    metrics
+        .iter()
+        .find(|m| m["endpoint"] == rpc.url.as_str())
+        .unwrap()
+}
+
+#[tokio::test]
+async fn action_transactions_fail_over_to_a_working_endpoint() {
+    let down = MockRpc::spawn().await;
+    down.set_failing(true);
+    let up = MockRpc::spawn().await;
+    let app = spawn_app_with(|c| {
+        c.solana.rpc_endpoints = vec![down.endpoint(1), up.endpoint(1)];
+        c.solana.blockhash_max_age_ms = 0;
+    })
+    .await;
+    let blink = app.create_blink(&donation_blink()).await;
+    let id = blink["id"].as_str().unwrap();
+
+    for _ in 0..5 {
+        let response = post_action(&app, id).await;
+        assert_eq!(200, response.status().as_u16());
+        let body: Value = response.json().await.unwrap();
+        assert!(body["transaction"].is_string());
+    }
+
+    let metrics = rpc_metrics(&app).await;
+    assert_eq!(metrics_for(&metrics, &up)["errors"], 0);
+    assert!(metrics_for(&metrics, &up)["requests"].as_u64().unwrap() >= 5);
+}
+
+#[tokio::test]
+async fn open_circuits_are_skipped() {
+    let down = MockRpc::spawn().await;
+    down.set_failing(true);
+    let up = MockRpc::spawn().await;
+    let app = spawn_app_with(|c| {
+        c.solana.rpc_endpoints = vec![down.endpoint(1000), up.endpoint(1)];
+        c.solana.rpc_pool.failure_threshold = 1;
+        c.solana.blockhash_max_age_ms = 0;
+    })
+    .await;
+    let blink = app.create_blink(&donation_blink()).await;
+    let id = blink["id"].as_str().unwrap();
+
+    while down.calls() == 0 {
+        assert_eq!(200, post_action(&app, id).await.status().as_u16());
+    }
+    let calls = down.calls();
+    for _ in 0..5 {
+        assert_eq!(200, post_action(&app, id).await.status().as_u16());
+    }
+
+    assert_eq!(down.calls(), calls);
+    let metrics = rpc_metrics(&app).await;
+    assert_eq!(metrics_for(&metrics, &down)["circuit_open"], true);
+    assert!(metrics_for(&metrics, &down)["last_error"].is_string());
+}
+
+#[tokio::test]
+async fn all_endpoints_down_returns_rpc_error() {
+    let down = MockRpc::spawn().await;
+    down.set_failing(true);
+    let app = spawn_app_with(|c| c.solana.rpc_endpoints = vec![down.endpoint(1)]).await;
+    let blink = app.create_blink(&donation_blink()).await;
+
+    let response = post_action(&app, blink["id"].as_str().unwrap()).await;
+
+    assert_eq!(500, response.status().as_u16());
+    assert!(response.text().await.unwrap().starts_with("RPC Error"));
+}
+
+#[tokio::test]
+async fn health_checks_flag_unhealthy_and_lagging_endpoints() {
+    let healthy = MockRpc::spawn().await;
+    let unhealthy = MockRpc::spawn().await;
+    unhealthy.set_unhealthy(true);
+    let lagging = MockRpc::spawn().await;
+    lagging.set_slot(10);
+    let app = spawn_app_with(|c| {
+        c.solana.rpc_endpoints = vec![
+            healthy.endpoint(1),
+            unhealthy.endpoint(1),
+            lagging.endpoint(1),
+        ];
+        c.solana.rpc_pool.health_check_interval_ms = 50;
+        c.solana.rpc_pool.max_slot_lag = 50;
+    })
+    .await;
+
+    tokio::time::sleep(Duration::from_millis(300)).await;
+
+    let metrics = rpc_metrics(&app).await;
+    assert_eq!(metrics_for(&metrics, &healthy)["healthy"], true);
+    assert_eq!(metrics_for(&metrics, &healthy)["slot"], 1000);
+    assert_eq!(metrics_for(&metrics, &unhealthy)["healthy"], false);
+    assert_eq!(metrics_for(&metrics, &lagging)["healthy"], false);
+}
+
+#[tokio::test]
+async fn rpc_metrics_require_the_admin_token() {
+    let rpc = MockRpc::spawn().await;
+    let app = spawn_app_with(|c| c.solana.rpc_endpoints = vec![rpc.endpoint(1)]).await;
+
+    let response = reqwest::get(format!("{}/api/rpc/endpoints", &app.address))
+        .await
+        .unwrap();
+
+    assert_eq!(401, response.status().as_u16());
+}
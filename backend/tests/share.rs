@@ -0,0 +1,109 @@
+mod helpers;
+
+use blinkzero::configuration::ActionRuleSettings;
+use helpers::{donation_blink, spawn_app, spawn_app_with};
+use serde_json::json;
+
+#[tokio::test]
+async fn share_page_renders_open_graph_tags() {
+    let app = spawn_app().await;
+    let blink = app.create_blink(&donation_blink()).await;
+    let id = blink["id"].as_str().unwrap();
+
+    let response = reqwest::get(format!("{}/b/{}", &app.address, id))
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(200, response.status().as_u16());
+    let content_type = response.headers()["content-type"].to_str().unwrap();
+    assert!(content_type.starts_with("text/html"));
+
+    let body = response.text().await.unwrap();
+    assert!(body.contains(r#"<meta property="og:title" content="Test Blink">"#));
+    assert!(body.contains(r#"<meta property="og:description" content="A test blink">"#));
+    assert!(body.contains(r#"<meta property="og:image" content="https://example.com/icon.png">"#));
+    assert!(body.contains(r#"<meta name="twitter:card" content="summary_large_image">"#));
+    assert!(body.contains("solana-action:"));
+    assert!(body.contains(&format!("/api/actions/{}", id)));
+}
+
+#[tokio::test]
+async fn share_page_escapes_blink_metadata() {
+    let app = spawn_app().await;
+    let mut body = donation_blink();
+    body["title"] = json!(r#"<script>alert("hi")</script>"#);
+    let blink = app.create_blink(&body).await;
+
+    let response = reqwest::get(format!(
+        "{}/b/{}",
+        &app.address,
+        blink["id"].as_str().unwrap()
+    ))
+    .await
+    .expect("Failed to execute request.");
+
+    let body = response.text().await.unwrap();
+    assert!(!body.contains("<script>"));
+    assert!(body.contains("&lt;script&gt;"));
+}
+
+#[tokio::test]
+async fn share_page_returns_404_for_unknown_blink() {
+    let app = spawn_app().await;
+
+    let response = reqwest::get(format!("{}/b/{}", &app.address, uuid::Uuid::new_v4()))
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(404, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn actions_json_maps_share_pages_to_the_action_api() {
+    let app = spawn_app().await;
+
+    let response = reqwest::get(format!("{}/actions.json", &app.address))
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(200, response.status().as_u16());
+    let body: serde_json::Value = response.json().await.unwrap();
+    let rules = body["rules"].as_array().unwrap();
+    assert!(rules.iter().any(|rule| {
+        rule["pathPattern"] == "/b/*"
+            && rule["apiPath"]
+                .as_str()
+                .unwrap()
+                .ends_with("/api/actions/*")
+    }));
+}
+
+#[tokio::test]
+async fn share_page_returns_404_when_its_path_is_excluded() {
+    let app = spawn_app_with(|c| {
+        c.actions.rules = vec![
+            ActionRuleSettings {
+                path_pattern: "/b/**".to_string(),
+                api_path: None,
+                exclude: true,
+            },
+            ActionRuleSettings {
+                path_pattern: "/api/actions/*".to_string(),
+                api_path: None,
+                exclude: false,
+            },
+        ]
+    })
+    .await;
+    let blink = app.create_blink(&donation_blink()).await;
+
+    let response = reqwest::get(format!(
+        "{}/b/{}",
+        &app.address,
+        blink["id"].as_str().unwrap()
+    ))
+    .await
+    .expect("Failed to execute request.");
+
+    assert_eq!(404, response.status().as_u16());
+}
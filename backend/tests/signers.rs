@@ -0,0 +1,161 @@
+mod helpers;
+
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use blinkzero::signer_vault::seal;
+use helpers::{
+    ADMIN_TOKEN, MINT_PROGRAMS, MockRpc, TestApp, donation_blink, mint_blink, post_action,
+    spawn_app_with, spawn_minting_app, vault_signer,
+};
+use reqwest::Client;
+use secrecy::SecretString;
+use serde_json::{Value, json};
+use solana_sdk::{
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    transaction::Transaction,
+};
+
+async fn admin_get(app: &TestApp, path: &str) -> reqwest::Response {
+    Client::new()
+        .get(format!("{}{}", &app.address, path))
+        .bearer_auth(ADMIN_TOKEN)
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+#[tokio::test]
+async fn signatures_are_audit_logged() {
+    let rpc = MockRpc::spawn().await;
+    let authority = Keypair::new();
+    let app = spawn_minting_app(&rpc, &authority, &MINT_PROGRAMS).await;
+    let blink = app.create_blink(&mint_blink(10, None)).await;
+    let id = blink["id"].as_str().unwrap();
+    let buyer = Pubkey::new_unique();
+
+    let response = post_action(&app, id, &buyer).await;
+
+    assert_eq!(200, response.status().as_u16());
+    let body: Value = response.json().await.unwrap();
+    let bytes = BASE64
+        .decode(body["transaction"].as_str().unwrap())
+        .unwrap();
+    let transaction: Transaction = bincode::deserialize(&bytes).unwrap();
+    let index = transaction
+        .message
+        .account_keys
+        .iter()
+        .position(|key| *key == authority.pubkey())
+        .unwrap();
+
+    let entries: Vec<Value> = admin_get(&app, "/api/signers/audit?signer=collection")
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0]["blink_id"], id);
+    assert_eq!(entries[0]["payer"], buyer.to_string());
+    assert_eq!(entries[0]["pubkey"], authority.pubkey().to_string());
+    assert_eq!(
+        entries[0]["signature"],
+        transaction.signatures[index].to_string()
+    );
+    assert_eq!(
+        entries[0]["programs"].as_array().unwrap().len(),
+        transaction.message.instructions.len()
+    );
+}
+
+#[tokio::test]
+async fn keys_refuse_instructions_outside_their_policy() {
+    let rpc = MockRpc::spawn().await;
+    // Everything but the metadata program.
+    let app = spawn_minting_app(&rpc, &Keypair::new(), &MINT_PROGRAMS[..4]).await;
+    let blink = app.create_blink(&mint_blink(10, None)).await;
+    let id = blink["id"].as_str().unwrap();
+
+    let response = post_action(&app, id, &Pubkey::new_unique()).await;
+
+    assert_eq!(403, response.status().as_u16());
+    let body: Value = response.json().await.unwrap();
+    assert!(
+        body["message"]
+            .as_str()
+            .unwrap()
+            .starts_with("Signer collection refused to sign")
+    );
+    let entries: Vec<Value> = admin_get(&app, "/api/signers/audit")
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert!(entries.is_empty());
+}
+
+#[tokio::test]
+async fn blinks_can_only_name_keys_available_to_them() {
+    let app = spawn_app_with(|c| {
+        let mut restricted = vault_signer("treasury", &Keypair::new(), &[]);
+        restricted.owners = Some(vec!["acme".to_string()]);
+        c.solana.signers = vec![restricted];
+    })
+    .await;
+
+    for signers in [json!(["missing"]), json!(["treasury"]), json!("treasury")] {
+        let mut body = donation_blink();
+        body["config"]["signers"] = signers;
+
+        let response = app.post_blink(&body).await;
+
+        assert_eq!(400, response.status().as_u16());
+    }
+    let key = app.create_api_key("acme", &["create"]).await;
+    let mut body = donation_blink();
+    body["config"]["signers"] = json!(["treasury"]);
+    app.create_blink_with_key(&key, &body).await;
+}
+
+#[tokio::test]
+async fn signers_load_from_sealed_keypair_files() {
+    let keypair = Keypair::new();
+    let path = std::env::temp_dir().join(format!("{}.json", uuid::Uuid::new_v4()));
+    std::fs::write(
+        &path,
+        seal(&keypair, &SecretString::from("correct horse"), 1000),
+    )
+    .unwrap();
+    let passphrase_env = format!("SIGNER_PASSPHRASE_{}", uuid::Uuid::new_v4().simple());
+    // SAFETY: the variable name is unique to this test.
+    unsafe { std::env::set_var(&passphrase_env, "correct horse") };
+
+    let app = spawn_app_with(|c| {
+        let mut signer = vault_signer("sealed", &Keypair::new(), &[]);
+        signer.keypair = None;
+        signer.keypair_file = Some(path.to_string_lossy().into_owned());
+        signer.passphrase_env = Some(passphrase_env);
+        c.solana.signers = vec![signer];
+    })
+    .await;
+
+    let response = admin_get(&app, "/api/signers").await;
+    assert_eq!(200, response.status().as_u16());
+    let signers: Vec<Value> = response.json().await.unwrap();
+    assert_eq!(signers.len(), 1);
+    assert_eq!(signers[0]["name"], "sealed");
+    assert_eq!(signers[0]["pubkey"], keypair.pubkey().to_string());
+    std::fs::remove_file(path).unwrap();
+}
+
+#[tokio::test]
+async fn signer_routes_require_the_admin_token() {
+    let app = spawn_app_with(|_| {}).await;
+
+    for path in ["/api/signers", "/api/signers/audit"] {
+        let response = reqwest::get(format!("{}{}", &app.address, path))
+            .await
+            .unwrap();
+
+        assert_eq!(401, response.status().as_u16());
+    }
+}
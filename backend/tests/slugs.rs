@@ -0,0 +1,187 @@
+mod helpers;
+
+use helpers::{donation_blink, spawn_app};
+use reqwest::{Client, redirect::Policy};
+use serde_json::json;
+
+fn blink_with_slug(slug: &str) -> serde_json::Value {
+    let mut body = donation_blink();
+    body["slug"] = json!(slug);
+    body
+}
+
+fn no_redirect_client() -> Client {
+    Client::builder().redirect(Policy::none()).build().unwrap()
+}
+
+#[tokio::test]
+async fn blink_created_with_slug_resolves_by_slug_and_uuid() {
+    let app = spawn_app().await;
+    let blink = app.create_blink(&blink_with_slug("Coffee-For-Devs")).await;
+
+    assert_eq!(blink["slug"], "coffee-for-devs");
+    assert!(
+        blink["action_url"]
+            .as_str()
+            .unwrap()
+            .ends_with("/api/actions/coffee-for-devs")
+    );
+
+    for key in ["coffee-for-devs", blink["id"].as_str().unwrap()] {
+        let response = reqwest::get(format!("{}/api/actions/{}", &app.address, key))
+            .await
+            .expect("Failed to execute request.");
+        assert_eq!(200, response.status().as_u16(), "key: {}", key);
+
+        let body: serde_json::Value = response.json().await.unwrap();
+        let href = body["links"]["actions"][0]["href"].as_str().unwrap();
+        assert!(href.contains("/api/actions/coffee-for-devs?"), "{}", href);
+    }
+}
+
+#[tokio::test]
+async fn create_blink_returns_400_for_invalid_slugs() {
+    let app = spawn_app().await;
+
+    for slug in ["ab", "api", "has space", "-dash", "fuck-off"] {
+        let response = app.post_blink(&blink_with_slug(slug)).await;
+        assert_eq!(400, response.status().as_u16(), "slug: {}", slug);
+    }
+}
+
+#[tokio::test]
+async fn create_blink_returns_409_for_taken_slug() {
+    let app = spawn_app().await;
+    app.create_blink(&blink_with_slug("taken")).await;
+
+    let response = app.post_blink(&blink_with_slug("taken")).await;
+
+    assert_eq!(409, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn renamed_blink_redirects_from_old_slug() {
+    let app = spawn_app().await;
+    let key = app.create_api_key("acme", &["create", "update"]).await;
+    let blink = app
+        .create_blink_with_key(&key, &blink_with_slug("old-name"))
+        .await;
+    let client = no_redirect_client();
+
+    let response = app
+        .put_slug(&key, blink["id"].as_str().unwrap(), "new-name")
+        .await;
+    assert_eq!(200, response.status().as_u16());
+
+    let response = client
+        .get(format!("{}/api/actions/old-name?amount=1", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(308, response.status().as_u16());
+    assert_eq!(
+        response.headers()["location"],
+        "/api/actions/new-name?amount=1"
+    );
+
+    let response = client
+        .get(format!("{}/b/old-name", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(308, response.status().as_u16());
+    assert_eq!(response.headers()["location"], "/b/new-name");
+
+    let response = client
+        .get(format!("{}/api/actions/new-name", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(200, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn old_slugs_cannot_be_claimed_by_other_blinks() {
+    let app = spawn_app().await;
+    let key = app.create_api_key("acme", &["create", "update"]).await;
+    let first = app
+        .create_blink_with_key(&key, &blink_with_slug("first-name"))
+        .await;
+
+    let response = app
+        .put_slug(&key, first["id"].as_str().unwrap(), "second-name")
+        .await;
+    assert_eq!(200, response.status().as_u16());
+
+    let response = app.post_blink(&blink_with_slug("first-name")).await;
+
+    assert_eq!(409, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn blink_can_move_back_to_its_own_old_slug() {
+    let app = spawn_app().await;
+    let key = app.create_api_key("acme", &["create", "update"]).await;
+    let blink = app
+        .create_blink_with_key(&key, &blink_with_slug("original"))
+        .await;
+
+    for slug in ["renamed", "original"] {
+        let response = app
+            .put_slug(&key, blink["id"].as_str().unwrap(), slug)
+            .await;
+        assert_eq!(200, response.status().as_u16());
+    }
+
+    let response = reqwest::get(format!("{}/api/actions/original", &app.address))
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(200, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn update_slug_returns_404_for_unknown_blink() {
+    let app = spawn_app().await;
+    let key = app.create_api_key("acme", &["update"]).await;
+
+    let response = app
+        .put_slug(&key, &uuid::Uuid::new_v4().to_string(), "anything")
+        .await;
+
+    assert_eq!(404, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn update_slug_requires_an_api_key() {
+    let app = spawn_app().await;
+    let blink = app.create_blink(&blink_with_slug("keyless")).await;
+
+    let response = Client::new()
+        .put(format!(
+            "{}/api/blinks/{}/slug",
+            &app.address,
+            blink["id"].as_str().unwrap()
+        ))
+        .json(&json!({ "slug": "renamed" }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(401, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn update_slug_rejects_blinks_of_other_owners() {
+    let app = spawn_app().await;
+    let owner_key = app.create_api_key("acme", &["create"]).await;
+    let other_key = app.create_api_key("globex", &["update"]).await;
+    let blink = app
+        .create_blink_with_key(&owner_key, &blink_with_slug("owned"))
+        .await;
+
+    let response = app
+        .put_slug(&other_key, blink["id"].as_str().unwrap(), "stolen")
+        .await;
+
+    assert_eq!(404, response.status().as_u16());
+}
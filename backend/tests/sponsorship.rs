@@ -0,0 +1,298 @@
+mod helpers;
+
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use blinkzero::configuration::Settings;
+use helpers::{MockRpc, TestApp, donation_blink, spawn_app, spawn_app_with, vault_signer};
+use reqwest::Client;
+use serde_json::{Value, json};
+use solana_sdk::{
+    pubkey::Pubkey,
+    signature::{Keypair, Signature, Signer},
+    system_program,
+    transaction::Transaction,
+};
+
+const MEMO_PROGRAM_ID: Pubkey = solana_sdk::pubkey!("MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr");
+
+fn enable_sponsorship(c: &mut Settings, rpc: &MockRpc, sponsor: &Keypair, settle_after_secs: u64) {
+    c.solana.rpc_endpoints = vec![rpc.endpoint(1)];
+    c.solana.signers = vec![vault_signer(
+        "gas",
+        sponsor,
+        &[MEMO_PROGRAM_ID, system_program::id()],
+    )];
+    c.solana.sponsorship.enabled = true;
+    c.solana.sponsorship.settle_after_secs = settle_after_secs;
+}
+
+async fn spawn_sponsoring_app(rpc: &MockRpc, sponsor: &Keypair, settle_after_secs: u64) -> TestApp {
+    spawn_app_with(|c| enable_sponsorship(c, rpc, sponsor, settle_after_secs)).await
+}
+
+/// A sponsored poll whose budget covers `transactions` votes.
+fn sponsored_vote(transactions: u64, daily_wallet_limit: u32) -> Value {
+    let mut body = donation_blink();
+    body["type"] = json!("vote");
+    body["config"] = json!({
+        "options": ["Yes", "No"],
+        "sponsor": {
+            "signer": "gas",
+            // Sponsor and voter sign each vote.
+            "budget_sol": (transactions * 10_000) as f64 / 1e9,
+            "daily_wallet_limit": daily_wallet_limit
+        }
+    });
+    body
+}
+
+async fn post_vote(app: &TestApp, id: &str, voter: &Pubkey) -> reqwest::Response {
+    Client::new()
+        .post(format!("{}/api/actions/{}?selection=Yes", &app.address, id))
+        .json(&json!({ "account": voter.to_string() }))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+async fn usage(app: &TestApp, id: &str) -> Value {
+    reqwest::get(format!("{}/api/blinks/{}/sponsorship", &app.address, id))
+        .await
+        .expect("Failed to execute request.")
+        .json()
+        .await
+        .unwrap()
+}
+
+async fn message(response: reqwest::Response) -> String {
+    let body: Value = response.json().await.unwrap();
+    body["message"].as_str().unwrap().to_string()
+}
+
+#[tokio::test]
+async fn sponsor_pays_the_fee_of_a_vote() {
+    let rpc = MockRpc::spawn().await;
+    let sponsor = Keypair::new();
+    let app = spawn_sponsoring_app(&rpc, &sponsor, 180).await;
+    let blink = app.create_blink(&sponsored_vote(5, 1)).await;
+    let id = blink["id"].as_str().unwrap();
+    let voter = Pubkey::new_unique();
+
+    let response = post_vote(&app, id, &voter).await;
+
+    assert_eq!(200, response.status().as_u16());
+    let body: Value = response.json().await.unwrap();
+    let bytes = BASE64
+        .decode(body["transaction"].as_str().unwrap())
+        .unwrap();
+    let transaction: Transaction = bincode::deserialize(&bytes).unwrap();
+    let message = &transaction.message;
+    assert_eq!(message.account_keys[0], sponsor.pubkey());
+    assert_eq!(message.account_keys[1], voter);
+    assert_eq!(message.header.num_required_signatures, 2);
+    assert_ne!(transaction.signatures[0], Signature::default());
+    assert_eq!(transaction.signatures[1], Signature::default());
+    // No priority fee and nothing but the memo.
+    assert_eq!(message.instructions.len(), 1);
+    assert_eq!(
+        message.account_keys[usize::from(message.instructions[0].program_id_index)],
+        MEMO_PROGRAM_ID
+    );
+
+    let usage = usage(&app, id).await;
+    assert_eq!(usage["budget_lamports"], 50_000);
+    assert_eq!(usage["charged_lamports"], 10_000);
+    assert_eq!(usage["pending_transactions"], 1);
+}
+
+#[tokio::test]
+async fn wallets_get_a_daily_number_of_sponsored_transactions() {
+    let rpc = MockRpc::spawn().await;
+    let app = spawn_sponsoring_app(&rpc, &Keypair::new(), 180).await;
+    let blink = app.create_blink(&sponsored_vote(5, 2)).await;
+    let id = blink["id"].as_str().unwrap();
+    let voter = Pubkey::new_unique();
+
+    for _ in 0..2 {
+        assert_eq!(200, post_vote(&app, id, &voter).await.status().as_u16());
+    }
+    let response = post_vote(&app, id, &voter).await;
+
+    assert_eq!(403, response.status().as_u16());
+    assert_eq!(
+        message(response).await,
+        "This wallet has used its 2 sponsored transactions for today"
+    );
+}
+
+#[tokio::test]
+async fn refused_signatures_give_the_reservation_back() {
+    let rpc = MockRpc::spawn().await;
+    let sponsor = Keypair::new();
+    let app = spawn_app_with(|c| {
+        enable_sponsorship(c, &rpc, &sponsor, 180);
+        // The key may not sign the memo of a vote.
+        c.solana.signers = vec![vault_signer("gas", &sponsor, &[system_program::id()])];
+    })
+    .await;
+    let blink = app.create_blink(&sponsored_vote(5, 1)).await;
+    let id = blink["id"].as_str().unwrap();
+    let voter = Pubkey::new_unique();
+
+    for _ in 0..2 {
+        let response = post_vote(&app, id, &voter).await;
+        assert_eq!(403, response.status().as_u16());
+        assert!(
+            message(response)
+                .await
+                .starts_with("Signer gas refused to sign")
+        );
+    }
+
+    let usage = usage(&app, id).await;
+    assert_eq!(usage["charged_lamports"], 0);
+    assert_eq!(usage["pending_transactions"], 0);
+}
+
+#[tokio::test]
+async fn wallets_share_a_daily_limit_across_blinks() {
+    let rpc = MockRpc::spawn().await;
+    let app = spawn_app_with(|c| {
+        enable_sponsorship(c, &rpc, &Keypair::new(), 180);
+        c.solana.sponsorship.max_daily_per_wallet = 1;
+    })
+    .await;
+    let first = app.create_blink(&sponsored_vote(5, 5)).await;
+    let second = app.create_blink(&sponsored_vote(5, 5)).await;
+    let voter = Pubkey::new_unique();
+
+    let allowed = post_vote(&app, first["id"].as_str().unwrap(), &voter).await;
+    let refused = post_vote(&app, second["id"].as_str().unwrap(), &voter).await;
+
+    assert_eq!(200, allowed.status().as_u16());
+    assert_eq!(403, refused.status().as_u16());
+}
+
+#[tokio::test]
+async fn sponsorship_stops_when_the_budget_is_spent() {
+    let rpc = MockRpc::spawn().await;
+    let app = spawn_sponsoring_app(&rpc, &Keypair::new(), 180).await;
+    let blink = app.create_blink(&sponsored_vote(1, 1)).await;
+    let id = blink["id"].as_str().unwrap();
+
+    let first = post_vote(&app, id, &Pubkey::new_unique()).await;
+    let second = post_vote(&app, id, &Pubkey::new_unique()).await;
+
+    assert_eq!(200, first.status().as_u16());
+    assert_eq!(403, second.status().as_u16());
+    assert_eq!(
+        message(second).await,
+        "The sponsorship budget of this blink is used up"
+    );
+}
+
+#[tokio::test]
+async fn expired_transactions_give_their_fee_back() {
+    let rpc = MockRpc::spawn().await;
+    let app = spawn_sponsoring_app(&rpc, &Keypair::new(), 0).await;
+    let blink = app.create_blink(&sponsored_vote(1, 1)).await;
+    let id = blink["id"].as_str().unwrap();
+    rpc.set_result(
+        "getSignatureStatuses",
+        json!({ "context": { "slot": 1 }, "value": [null] }),
+    );
+
+    let first = post_vote(&app, id, &Pubkey::new_unique()).await;
+    let second = post_vote(&app, id, &Pubkey::new_unique()).await;
+
+    assert_eq!(200, first.status().as_u16());
+    assert_eq!(200, second.status().as_u16());
+    let usage = usage(&app, id).await;
+    assert_eq!(usage["charged_lamports"], 10_000);
+    assert_eq!(usage["landed_transactions"], 0);
+}
+
+#[tokio::test]
+async fn landed_transactions_stay_charged() {
+    let rpc = MockRpc::spawn().await;
+    let app = spawn_sponsoring_app(&rpc, &Keypair::new(), 0).await;
+    let blink = app.create_blink(&sponsored_vote(1, 1)).await;
+    let id = blink["id"].as_str().unwrap();
+    rpc.set_result(
+        "getSignatureStatuses",
+        json!({
+            "context": { "slot": 1 },
+            "value": [{
+                "slot": 1,
+                "confirmations": null,
+                "err": null,
+                "status": { "Ok": null },
+                "confirmationStatus": "finalized"
+            }]
+        }),
+    );
+
+    let first = post_vote(&app, id, &Pubkey::new_unique()).await;
+    let second = post_vote(&app, id, &Pubkey::new_unique()).await;
+
+    assert_eq!(200, first.status().as_u16());
+    assert_eq!(403, second.status().as_u16());
+    assert_eq!(usage(&app, id).await["landed_transactions"], 1);
+}
+
+#[tokio::test]
+async fn sponsored_donations_move_only_the_donors_funds() {
+    let rpc = MockRpc::spawn().await;
+    let sponsor = Keypair::new();
+    let app = spawn_sponsoring_app(&rpc, &sponsor, 180).await;
+    let mut body = donation_blink();
+    body["config"]["sponsor"] = json!({ "signer": "gas", "budget_sol": 0.01 });
+    let blink = app.create_blink(&body).await;
+    let donor = Pubkey::new_unique();
+
+    let response = Client::new()
+        .post(format!(
+            "{}/api/actions/{}",
+            &app.address,
+            blink["id"].as_str().unwrap()
+        ))
+        .json(&json!({ "account": donor.to_string() }))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(200, response.status().as_u16());
+    let body: Value = response.json().await.unwrap();
+    let bytes = BASE64
+        .decode(body["transaction"].as_str().unwrap())
+        .unwrap();
+    let transaction: Transaction = bincode::deserialize(&bytes).unwrap();
+    assert_eq!(transaction.message.account_keys[0], sponsor.pubkey());
+    assert_eq!(transaction.message.account_keys[1], donor);
+}
+
+#[tokio::test]
+async fn sponsored_blinks_need_sponsorship_enabled() {
+    let app = spawn_app_with(|c| {
+        c.solana.signers = vec![vault_signer("gas", &Keypair::new(), &[])];
+    })
+    .await;
+    let blink = app.create_blink(&sponsored_vote(5, 1)).await;
+
+    let response = post_vote(&app, blink["id"].as_str().unwrap(), &Pubkey::new_unique()).await;
+
+    assert_eq!(503, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn create_blink_rejects_invalid_sponsors() {
+    let app = spawn_app().await;
+
+    // The vault has no key named `gas`.
+    let response = app.post_blink(&sponsored_vote(5, 1)).await;
+    assert_eq!(400, response.status().as_u16());
+
+    let mut body = sponsored_vote(5, 1);
+    body["config"]["sponsor"]["budget_sol"] = json!(-1);
+    let response = app.post_blink(&body).await;
+    assert_eq!(400, response.status().as_u16());
+}
@@ -0,0 +1,157 @@
+mod helpers;
+
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use helpers::{MockRpc, TestApp, donation_blink, spawn_app, spawn_app_with_rpc};
+use reqwest::Client;
+use serde_json::{Value, json};
+use solana_sdk::{
+    native_token::LAMPORTS_PER_SOL,
+    pubkey::Pubkey,
+    stake::{self, instruction::StakeInstruction},
+    system_instruction::SystemInstruction,
+    system_program,
+    transaction::Transaction,
+};
+
+fn stake_blink(validator: &Pubkey) -> Value {
+    let mut body = donation_blink();
+    body["type"] = json!("stake");
+    body["label"] = json!("Stake");
+    body["config"] = json!({
+        "validator": validator.to_string(),
+        "min_amount": 1,
+        "amounts": [1, 5]
+    });
+    body
+}
+
+async fn post_stake(app: &TestApp, id: &str, staker: &Pubkey, amount: &str) -> reqwest::Response {
+    Client::new()
+        .post(format!(
+            "{}/api/actions/{}?amount={}",
+            &app.address, id, amount
+        ))
+        .json(&json!({ "account": staker.to_string() }))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+#[tokio::test]
+async fn stake_creates_a_seeded_stake_account_and_delegates_it() {
+    let rpc = MockRpc::spawn().await;
+    let app = spawn_app_with_rpc(&rpc).await;
+    let validator = Pubkey::new_unique();
+    let blink = app.create_blink(&stake_blink(&validator)).await;
+    let staker = Pubkey::new_unique();
+
+    let response = post_stake(&app, blink["id"].as_str().unwrap(), &staker, "2").await;
+
+    assert_eq!(200, response.status().as_u16());
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["message"], format!("Stake 2 SOL with {}", validator));
+    let bytes = BASE64
+        .decode(body["transaction"].as_str().unwrap())
+        .unwrap();
+    let transaction: Transaction = bincode::deserialize(&bytes).unwrap();
+    let message = &transaction.message;
+    // Only the staker signs: the stake account is derived from a seed.
+    assert_eq!(message.header.num_required_signatures, 1);
+    assert_eq!(message.account_keys[0], staker);
+
+    let program = |index: usize| {
+        message.account_keys[usize::from(message.instructions[index].program_id_index)]
+    };
+    assert_eq!(program(1), system_program::id());
+    let SystemInstruction::CreateAccountWithSeed {
+        base,
+        seed,
+        lamports,
+        owner,
+        ..
+    } = bincode::deserialize(&message.instructions[1].data).unwrap()
+    else {
+        panic!("expected CreateAccountWithSeed");
+    };
+    assert_eq!(base, staker);
+    assert_eq!(owner, stake::program::id());
+    assert!(lamports > 2 * LAMPORTS_PER_SOL);
+    let stake_account = Pubkey::create_with_seed(&staker, &seed, &owner).unwrap();
+    assert!(message.account_keys.contains(&stake_account));
+
+    assert_eq!(program(2), stake::program::id());
+    let StakeInstruction::Initialize(authorized, _) =
+        bincode::deserialize(&message.instructions[2].data).unwrap()
+    else {
+        panic!("expected Initialize");
+    };
+    assert_eq!(authorized.staker, staker);
+    assert_eq!(authorized.withdrawer, staker);
+
+    assert_eq!(program(3), stake::program::id());
+    assert!(matches!(
+        bincode::deserialize(&message.instructions[3].data).unwrap(),
+        StakeInstruction::DelegateStake
+    ));
+    assert!(message.account_keys.contains(&validator));
+}
+
+#[tokio::test]
+async fn stake_rejects_amounts_below_the_minimum() {
+    let rpc = MockRpc::spawn().await;
+    let app = spawn_app_with_rpc(&rpc).await;
+    let blink = app.create_blink(&stake_blink(&Pubkey::new_unique())).await;
+    let id = blink["id"].as_str().unwrap();
+
+    for amount in ["0.5", "abc"] {
+        let response = post_stake(&app, id, &Pubkey::new_unique(), amount).await;
+
+        assert_eq!(400, response.status().as_u16());
+    }
+}
+
+#[tokio::test]
+async fn stake_metadata_offers_presets_and_a_custom_amount() {
+    let app = spawn_app().await;
+    let blink = app.create_blink(&stake_blink(&Pubkey::new_unique())).await;
+
+    let body: Value = reqwest::get(format!(
+        "{}/api/actions/{}",
+        &app.address,
+        blink["id"].as_str().unwrap()
+    ))
+    .await
+    .unwrap()
+    .json()
+    .await
+    .unwrap();
+
+    let actions = body["links"]["actions"].as_array().unwrap();
+    assert_eq!(actions.len(), 3);
+    assert_eq!(actions[0]["label"], "Stake 1 SOL");
+    assert!(actions[1]["href"].as_str().unwrap().ends_with("?amount=5"));
+    assert!(
+        actions[2]["href"]
+            .as_str()
+            .unwrap()
+            .ends_with("?amount={amount}")
+    );
+    assert_eq!(actions[2]["parameters"][0]["name"], "amount");
+}
+
+#[tokio::test]
+async fn create_blink_rejects_invalid_stake_configs() {
+    let app = spawn_app().await;
+
+    for config in [
+        json!({ "validator": "not-a-pubkey" }),
+        json!({ "validator": Pubkey::new_unique().to_string(), "min_amount": 2, "amounts": [1] }),
+    ] {
+        let mut body = stake_blink(&Pubkey::new_unique());
+        body["config"] = config;
+
+        let response = app.post_blink(&body).await;
+
+        assert_eq!(400, response.status().as_u16());
+    }
+}
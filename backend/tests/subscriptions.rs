@@ -0,0 +1,348 @@
+mod helpers;
+
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use chrono::{DateTime, Duration, Utc};
+use helpers::{
+    MockRpc, TestApp, donation_blink, land, mint_account_info, spawn_app_with, vault_signer,
+};
+use reqwest::Client;
+use serde_json::{Value, json};
+use solana_sdk::{
+    pubkey::Pubkey, signature::Keypair, signer::Signer, system_instruction::SystemInstruction,
+    system_program, transaction::Transaction,
+};
+use uuid::Uuid;
+
+const SPL_TOKEN_PROGRAM_ID: Pubkey =
+    solana_sdk::pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+
+/// Programs of a delegated renewal charge.
+const BILLING_PROGRAMS: [Pubkey; 3] = [
+    solana_sdk::pubkey!("ComputeBudget111111111111111111111111111111"),
+    solana_sdk::pubkey!("ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL"),
+    SPL_TOKEN_PROGRAM_ID,
+];
+
+async fn spawn_billing_app(rpc: &MockRpc, delegate: &Keypair, billing_interval_ms: u64) -> TestApp {
+    rpc.set_result("getSignaturesForAddress", json!([]));
+    spawn_app_with(|c| {
+        c.solana.rpc_endpoints = vec![rpc.endpoint(1)];
+        c.solana.signers = vec![vault_signer("billing", delegate, &BILLING_PROGRAMS)];
+        c.solana.subscriptions.billing_interval_ms = billing_interval_ms;
+    })
+    .await
+}
+
+fn subscription_blink(config: Value) -> Value {
+    let mut body = donation_blink();
+    body["type"] = json!("subscription");
+    body["title"] = json!("Supporters");
+    body["description"] = json!("Monthly supporters");
+    body["label"] = json!("Subscribe");
+    body["wallet_address"] = json!(Pubkey::new_unique().to_string());
+    body["config"] = config;
+    body
+}
+
+/// Creates a subscription blink and returns its id and a key of its owner.
+async fn create_subscription(app: &TestApp, config: Value) -> (String, String) {
+    let key = app
+        .create_api_key("acme", &["create", "read", "update"])
+        .await;
+    let blink = app
+        .create_blink_with_key(&key, &subscription_blink(config))
+        .await;
+    (blink["id"].as_str().unwrap().to_string(), key)
+}
+
+/// Posts to the action at `path`, or at the path of an action URL.
+async fn subscribe(app: &TestApp, path: &str, subscriber: &Pubkey) -> reqwest::Response {
+    let path = &path[path.find("/api/").unwrap()..];
+    Client::new()
+        .post(format!("{}{}", &app.address, path))
+        .json(&json!({ "account": subscriber.to_string() }))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+async fn subscribe_transaction(app: &TestApp, id: &str, subscriber: &Pubkey) -> Transaction {
+    let response = subscribe(app, &format!("/api/actions/{}", id), subscriber).await;
+    assert_eq!(200, response.status().as_u16());
+    let body: Value = response.json().await.unwrap();
+    let bytes = BASE64
+        .decode(body["transaction"].as_str().unwrap())
+        .unwrap();
+    bincode::deserialize(&bytes).unwrap()
+}
+
+async fn subscribers(app: &TestApp, key: &str, id: &str) -> Vec<Value> {
+    let response = Client::new()
+        .get(format!("{}/api/blinks/{}/subscribers", &app.address, id))
+        .bearer_auth(key)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(200, response.status().as_u16());
+    response.json().await.unwrap()
+}
+
+fn days_from_now(timestamp: &Value) -> i64 {
+    let timestamp: DateTime<Utc> = timestamp.as_str().unwrap().parse().unwrap();
+    (timestamp - Utc::now() + Duration::hours(1)).num_days()
+}
+
+#[tokio::test]
+async fn payments_that_land_extend_the_subscription() {
+    let rpc = MockRpc::spawn().await;
+    let app = spawn_billing_app(&rpc, &Keypair::new(), 0).await;
+    let (id, key) = create_subscription(&app, json!({ "price": 0.5 })).await;
+    let metadata: Value = reqwest::get(format!("{}/api/actions/{}", &app.address, id))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(
+        metadata["links"]["actions"][0]["label"],
+        "Subscribe (0.5 SOL every 30 days)"
+    );
+    let subscriber = Pubkey::new_unique();
+
+    let transaction = subscribe_transaction(&app, &id, &subscriber).await;
+
+    let message = &transaction.message;
+    let transfer = message
+        .instructions
+        .iter()
+        .find(|ix| message.account_keys[usize::from(ix.program_id_index)] == system_program::id())
+        .unwrap();
+    assert_eq!(
+        bincode::deserialize::<SystemInstruction>(&transfer.data).unwrap(),
+        SystemInstruction::Transfer {
+            lamports: 500_000_000
+        }
+    );
+    assert_eq!(transfer.accounts.len(), 3);
+    // Not a subscriber until the payment lands.
+    assert!(subscribers(&app, &key, &id).await.is_empty());
+
+    land(&rpc, &transaction);
+    let listed = subscribers(&app, &key, &id).await;
+    assert_eq!(listed.len(), 1);
+    assert_eq!(listed[0]["wallet"], subscriber.to_string());
+    assert_eq!(listed[0]["status"], "active");
+    assert_eq!(listed[0]["auto_renew"], false);
+    assert_eq!(days_from_now(&listed[0]["renews_at"]), 30);
+    assert!(
+        listed[0]["renewal_url"]
+            .as_str()
+            .unwrap()
+            .ends_with(&format!("/api/actions/{}?subscriber={}", id, subscriber))
+    );
+
+    let renewal = subscribe_transaction(&app, &id, &subscriber).await;
+    land(&rpc, &renewal);
+    let listed = subscribers(&app, &key, &id).await;
+    assert_eq!(days_from_now(&listed[0]["renews_at"]), 60);
+}
+
+#[tokio::test]
+async fn renewal_links_belong_to_their_subscriber() {
+    let rpc = MockRpc::spawn().await;
+    let app = spawn_billing_app(&rpc, &Keypair::new(), 0).await;
+    let (id, _) = create_subscription(&app, json!({ "price": 0.5, "period_days": 7 })).await;
+    let subscriber = Pubkey::new_unique();
+    let transaction = subscribe_transaction(&app, &id, &subscriber).await;
+    land(&rpc, &transaction);
+
+    let metadata: Value = reqwest::get(format!(
+        "{}/api/actions/{}?subscriber={}",
+        &app.address, id, subscriber
+    ))
+    .await
+    .unwrap()
+    .json()
+    .await
+    .unwrap();
+
+    assert!(
+        metadata["description"]
+            .as_str()
+            .unwrap()
+            .starts_with("Monthly supporters\n\nSubscribed until ")
+    );
+    let renew = &metadata["links"]["actions"][0];
+    assert_eq!(renew["label"], "Renew (0.5 SOL every 7 days)");
+    let href = renew["href"].as_str().unwrap();
+    assert_eq!(
+        200,
+        subscribe(&app, href, &subscriber).await.status().as_u16()
+    );
+    let response = subscribe(&app, href, &Pubkey::new_unique()).await;
+    assert_eq!(403, response.status().as_u16());
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(
+        body["message"],
+        "This renewal link belongs to another wallet"
+    );
+
+    let stranger: Value = reqwest::get(format!(
+        "{}/api/actions/{}?subscriber={}",
+        &app.address,
+        id,
+        Pubkey::new_unique()
+    ))
+    .await
+    .unwrap()
+    .json()
+    .await
+    .unwrap();
+    assert_eq!(stranger["disabled"], true);
+}
+
+#[tokio::test]
+async fn subscriptions_lapse_and_can_be_cancelled() {
+    let rpc = MockRpc::spawn().await;
+    let app = spawn_billing_app(&rpc, &Keypair::new(), 0).await;
+    let (id, key) = create_subscription(&app, json!({ "price": 0.5 })).await;
+    let subscriber = Pubkey::new_unique();
+    let transaction = subscribe_transaction(&app, &id, &subscriber).await;
+    land(&rpc, &transaction);
+    subscribers(&app, &key, &id).await;
+
+    sqlx::query("UPDATE subscriptions SET paid_through = now() - interval '1 day'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    let listed = subscribers(&app, &key, &id).await;
+    assert_eq!(listed[0]["status"], "lapsed");
+
+    let cancel = |wallet: String| {
+        Client::new()
+            .post(format!(
+                "{}/api/blinks/{}/subscribers/{}/cancel",
+                &app.address, id, wallet
+            ))
+            .bearer_auth(&key)
+            .send()
+    };
+    let response = cancel(subscriber.to_string()).await.unwrap();
+    assert_eq!(200, response.status().as_u16());
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["status"], "cancelled");
+    let response = cancel(Pubkey::new_unique().to_string()).await.unwrap();
+    assert_eq!(404, response.status().as_u16());
+
+    // Paying again reactivates the subscription.
+    let renewal = subscribe_transaction(&app, &id, &subscriber).await;
+    land(&rpc, &renewal);
+    let listed = subscribers(&app, &key, &id).await;
+    assert_eq!(listed[0]["status"], "active");
+    assert_eq!(days_from_now(&listed[0]["renews_at"]), 30);
+}
+
+#[tokio::test]
+async fn token_subscribers_approve_the_delegate_to_charge_renewals() {
+    let rpc = MockRpc::spawn().await;
+    let delegate = Keypair::new();
+    let app = spawn_billing_app(&rpc, &delegate, 50).await;
+    let mint = Pubkey::new_unique();
+    rpc.set_result("getAccountInfo", mint_account_info(6));
+    let (id, key) = create_subscription(
+        &app,
+        json!({
+            "price": 5,
+            "currency": mint.to_string(),
+            "delegate": "billing",
+            "approve_periods": 3
+        }),
+    )
+    .await;
+    let subscriber = Pubkey::new_unique();
+
+    let transaction = subscribe_transaction(&app, &id, &subscriber).await;
+
+    let message = &transaction.message;
+    let token_instructions = message
+        .instructions
+        .iter()
+        .filter(|ix| message.account_keys[usize::from(ix.program_id_index)] == SPL_TOKEN_PROGRAM_ID)
+        .collect::<Vec<_>>();
+    assert_eq!(token_instructions.len(), 2);
+    let approve = token_instructions[1];
+    let mut expected = vec![13];
+    expected.extend_from_slice(&15_000_000u64.to_le_bytes());
+    expected.push(6);
+    assert_eq!(approve.data, expected);
+    assert_eq!(
+        message.account_keys[usize::from(approve.accounts[2])],
+        delegate.pubkey()
+    );
+
+    land(&rpc, &transaction);
+    let listed = subscribers(&app, &key, &id).await;
+    assert_eq!(listed[0]["auto_renew"], true);
+
+    sqlx::query("UPDATE subscriptions SET paid_through = now() + interval '1 hour'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    // The vault logs the signature before the charge is sent, so wait for
+    // the send itself.
+    for _ in 0..100 {
+        if rpc.calls_to("sendTransaction") > 0 {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+    assert!(
+        rpc.calls_to("sendTransaction") > 0,
+        "renewal was not charged"
+    );
+    let charged = sqlx::query_scalar::<_, i64>(
+        "SELECT COUNT(*) FROM signer_audit_log WHERE signer = 'billing' AND blink_id = $1",
+    )
+    .bind(Uuid::parse_str(&id).unwrap())
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert!(charged > 0);
+}
+
+#[tokio::test]
+async fn subscription_blinks_are_validated() {
+    let rpc = MockRpc::spawn().await;
+    let app = spawn_billing_app(&rpc, &Keypair::new(), 0).await;
+    let key = app.create_api_key("acme", &["create", "read"]).await;
+    let mint = Pubkey::new_unique().to_string();
+
+    for config in [
+        json!({ "price": 1, "delegate": "billing" }),
+        json!({ "price": 1, "currency": mint, "delegate": "unknown" }),
+        json!({ "price": 1, "period_days": 400 }),
+        json!({ "price": 1, "durable_nonce": true }),
+    ] {
+        let response = Client::new()
+            .post(format!("{}/api/blinks", &app.address))
+            .bearer_auth(&key)
+            .json(&subscription_blink(config.clone()))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(400, response.status().as_u16(), "{}", config);
+    }
+
+    let donation = app.create_blink_with_key(&key, &donation_blink()).await;
+    let response = Client::new()
+        .get(format!(
+            "{}/api/blinks/{}/subscribers",
+            &app.address,
+            donation["id"].as_str().unwrap()
+        ))
+        .bearer_auth(&key)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(400, response.status().as_u16());
+}
@@ -0,0 +1,255 @@
+mod helpers;
+
+use axum::{
+    Json, Router,
+    extract::{Query, State},
+    http::StatusCode,
+    routing::{get, post},
+};
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use helpers::{MockRpc, TestApp, donation_blink, mint_account_info, spawn_app_with};
+use reqwest::Client;
+use serde_json::{Value, json};
+use solana_sdk::{
+    compute_budget::ComputeBudgetInstruction,
+    hash::Hash,
+    instruction::{AccountMeta, Instruction},
+    message::{VersionedMessage, v0},
+    pubkey::Pubkey,
+    transaction::VersionedTransaction,
+};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+const JUPITER_PROGRAM_ID: Pubkey =
+    solana_sdk::pubkey!("JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4");
+
+/// A Jupiter-compatible swap API. Routes call `program`.
+struct MockJupiterState {
+    program: Pubkey,
+    quotes: Mutex<Vec<HashMap<String, String>>>,
+    swaps: Mutex<Vec<Value>>,
+}
+
+struct MockJupiter {
+    url: String,
+    state: Arc<MockJupiterState>,
+}
+
+impl MockJupiter {
+    async fn spawn(program: Pubkey) -> MockJupiter {
+        let state = Arc::new(MockJupiterState {
+            program,
+            quotes: Mutex::default(),
+            swaps: Mutex::default(),
+        });
+        let app = Router::new()
+            .route("/quote", get(quote))
+            .route("/swap", post(swap))
+            .with_state(state.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("Failed to bind random port");
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        MockJupiter { url, state }
+    }
+}
+
+async fn quote(
+    State(state): State<Arc<MockJupiterState>>,
+    Query(query): Query<HashMap<String, String>>,
+) -> Json<Value> {
+    let amount: u64 = query["amount"].parse().unwrap();
+    state.quotes.lock().unwrap().push(query.clone());
+    // 1 SOL buys 2000 tokens of 6 decimals.
+    let out_amount = amount * 2;
+    Json(json!({
+        "inputMint": query["inputMint"],
+        "outputMint": query["outputMint"],
+        "inAmount": amount.to_string(),
+        "outAmount": out_amount.to_string(),
+        "otherAmountThreshold": (out_amount / 100 * 99).to_string(),
+        "slippageBps": query["slippageBps"].parse::<u16>().unwrap(),
+        "routePlan": []
+    }))
+}
+
+async fn swap(State(state): State<Arc<MockJupiterState>>, Json(body): Json<Value>) -> Json<Value> {
+    state.swaps.lock().unwrap().push(body.clone());
+    let user: Pubkey = body["userPublicKey"].as_str().unwrap().parse().unwrap();
+    let instructions = [
+        ComputeBudgetInstruction::set_compute_unit_limit(300_000),
+        Instruction::new_with_bytes(
+            state.program,
+            &[1, 2, 3],
+            vec![AccountMeta::new(user, true)],
+        ),
+    ];
+    let message = v0::Message::try_compile(&user, &instructions, &[], Hash::default()).unwrap();
+    let transaction = VersionedTransaction {
+        signatures: vec![Default::default()],
+        message: VersionedMessage::V0(message),
+    };
+    Json(json!({
+        "swapTransaction": BASE64.encode(bincode::serialize(&transaction).unwrap()),
+        "lastValidBlockHeight": 1150
+    }))
+}
+
+/// A swap API that finds no route.
+async fn spawn_routeless_api() -> String {
+    let app = Router::new().route(
+        "/quote",
+        get(|| async {
+            (
+                StatusCode::BAD_REQUEST,
+                Json(json!({ "error": "Could not find any route" })),
+            )
+        }),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("Failed to bind random port");
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    url
+}
+
+struct SwapDesk {
+    app: TestApp,
+    jupiter: MockJupiter,
+    _rpc: MockRpc,
+    output_mint: Pubkey,
+    fee_account: Pubkey,
+    id: String,
+}
+
+async fn open_swap(program: Pubkey) -> SwapDesk {
+    let rpc = MockRpc::spawn().await;
+    rpc.set_result("getAccountInfo", mint_account_info(6));
+    let routeless = spawn_routeless_api().await;
+    let jupiter = MockJupiter::spawn(program).await;
+    let quote_urls = vec![routeless, jupiter.url.clone()];
+    let app = spawn_app_with(|c| {
+        c.solana.rpc_endpoints = vec![rpc.endpoint(1)];
+        c.solana.swaps.enabled = true;
+        c.solana.swaps.quote_urls = quote_urls;
+    })
+    .await;
+    let output_mint = Pubkey::new_unique();
+    let fee_account = Pubkey::new_unique();
+    let mut body = donation_blink();
+    body["type"] = json!("swap");
+    body["label"] = json!("Buy");
+    body["config"] = json!({
+        "output_mint": output_mint.to_string(),
+        "max_slippage_bps": 200,
+        "fee_bps": 20,
+        "fee_account": fee_account.to_string()
+    });
+    let blink = app.create_blink(&body).await;
+
+    SwapDesk {
+        app,
+        jupiter,
+        _rpc: rpc,
+        output_mint,
+        fee_account,
+        id: blink["id"].as_str().unwrap().to_string(),
+    }
+}
+
+impl SwapDesk {
+    async fn post(&self, query: &str, user: &Pubkey) -> reqwest::Response {
+        Client::new()
+            .post(format!(
+                "{}/api/actions/{}?{}",
+                &self.app.address, self.id, query
+            ))
+            .json(&json!({ "account": user.to_string() }))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+}
+
+#[tokio::test]
+async fn swaps_are_prepared_by_the_first_provider_with_a_route() {
+    let desk = open_swap(JUPITER_PROGRAM_ID).await;
+    let user = Pubkey::new_unique();
+
+    let response = desk.post("amount=0.5&slippage=1", &user).await;
+
+    assert_eq!(200, response.status().as_u16());
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(
+        body["message"],
+        "Swap 0.5 SOL for about 1000 tokens, at least 990 (1% max slippage)"
+    );
+    let bytes = BASE64
+        .decode(body["transaction"].as_str().unwrap())
+        .unwrap();
+    let transaction: VersionedTransaction = bincode::deserialize(&bytes).unwrap();
+    assert_eq!(transaction.message.static_account_keys()[0], user);
+
+    let quotes = desk.jupiter.state.quotes.lock().unwrap().clone();
+    assert_eq!(quotes.len(), 1);
+    assert_eq!(quotes[0]["outputMint"], desk.output_mint.to_string());
+    assert_eq!(quotes[0]["amount"], "500000000");
+    assert_eq!(quotes[0]["slippageBps"], "100");
+    assert_eq!(quotes[0]["platformFeeBps"], "20");
+    let swaps = desk.jupiter.state.swaps.lock().unwrap().clone();
+    assert_eq!(swaps[0]["feeAccount"], desk.fee_account.to_string());
+    assert_eq!(swaps[0]["userPublicKey"], user.to_string());
+}
+
+#[tokio::test]
+async fn transactions_calling_other_programs_are_refused() {
+    let desk = open_swap(Pubkey::new_unique()).await;
+
+    let response = desk.post("amount=0.5", &Pubkey::new_unique()).await;
+
+    assert_eq!(502, response.status().as_u16());
+    let body: Value = response.json().await.unwrap();
+    assert!(
+        body["message"]
+            .as_str()
+            .unwrap()
+            .contains("which is not allowed")
+    );
+}
+
+#[tokio::test]
+async fn slippage_is_capped_by_the_blink() {
+    let desk = open_swap(JUPITER_PROGRAM_ID).await;
+    let user = Pubkey::new_unique();
+    let metadata: Value = reqwest::get(format!("{}/api/actions/{}", &desk.app.address, desk.id))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(
+        metadata["links"]["actions"][0]["href"]
+            .as_str()
+            .unwrap()
+            .ends_with("?amount={amount}&slippage={slippage}")
+    );
+    assert_eq!(
+        metadata["links"]["actions"][0]["parameters"][1]["label"],
+        "Slippage % (default 0.5, max 2)"
+    );
+
+    let response = desk.post("amount=0.5&slippage=3", &user).await;
+    assert_eq!(400, response.status().as_u16());
+    let response = desk.post("amount=-1", &user).await;
+    assert_eq!(400, response.status().as_u16());
+    // An empty slippage falls back to the blink's default.
+    let response = desk.post("amount=0.5&slippage=", &user).await;
+    assert_eq!(200, response.status().as_u16());
+    let quotes = desk.jupiter.state.quotes.lock().unwrap().clone();
+    assert_eq!(quotes.len(), 1);
+    assert_eq!(quotes[0]["slippageBps"], "50");
+}
@@ -0,0 +1,169 @@
+mod helpers;
+
+use helpers::{
+    MockRpc, collection_metadata_info, donation_blink, post_action, spawn_app_with_rpc,
+    token_accounts_info,
+};
+use serde_json::{Value, json};
+use solana_sdk::pubkey::Pubkey;
+
+fn gated_blink(gate: Value) -> Value {
+    let mut body = donation_blink();
+    body["config"] = json!({ "amount": 0.5, "gate": gate });
+    body
+}
+
+#[tokio::test]
+async fn token_gate_admits_wallets_holding_enough_tokens() {
+    let rpc = MockRpc::spawn().await;
+    let app = spawn_app_with_rpc(&rpc).await;
+    let mint = Pubkey::new_unique();
+    let payer = Pubkey::new_unique();
+    let blink = app
+        .create_blink(&gated_blink(json!({
+            "kind": "token",
+            "mint": mint.to_string(),
+            "min_amount": 100
+        })))
+        .await;
+    rpc.set_result(
+        "getTokenAccountsByOwner",
+        token_accounts_info(&payer, &[(mint, 60_000_000, 6), (mint, 40_000_000, 6)]),
+    );
+
+    let response = post_action(&app, blink["id"].as_str().unwrap(), &payer).await;
+
+    assert_eq!(200, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn token_gate_rejects_unqualified_wallets_with_action_error() {
+    let rpc = MockRpc::spawn().await;
+    let app = spawn_app_with_rpc(&rpc).await;
+    let mint = Pubkey::new_unique();
+    let payer = Pubkey::new_unique();
+    let blink = app
+        .create_blink(&gated_blink(json!({
+            "kind": "token",
+            "mint": mint.to_string(),
+            "min_amount": 100
+        })))
+        .await;
+    rpc.set_result(
+        "getTokenAccountsByOwner",
+        token_accounts_info(&payer, &[(mint, 99_000_000, 6)]),
+    );
+
+    let response = post_action(&app, blink["id"].as_str().unwrap(), &payer).await;
+
+    assert_eq!(403, response.status().as_u16());
+    let body: Value = response.json().await.unwrap();
+    assert!(
+        body["message"]
+            .as_str()
+            .unwrap()
+            .contains(&mint.to_string())
+    );
+    assert_eq!(rpc.calls_to("getLatestBlockhash"), 0);
+}
+
+#[tokio::test]
+async fn collection_holders_get_the_discount() {
+    let rpc = MockRpc::spawn().await;
+    let app = spawn_app_with_rpc(&rpc).await;
+    let collection = Pubkey::new_unique();
+    let payer = Pubkey::new_unique();
+    let blink = app
+        .create_blink(&gated_blink(json!({
+            "kind": "collection",
+            "collection": collection.to_string(),
+            "discount_percent": 20
+        })))
+        .await;
+    rpc.set_result(
+        "getTokenAccountsByOwner",
+        token_accounts_info(&payer, &[(Pubkey::new_unique(), 1, 0)]),
+    );
+    rpc.set_result("getMultipleAccounts", collection_metadata_info(&collection));
+
+    let response = post_action(&app, blink["id"].as_str().unwrap(), &payer).await;
+
+    assert_eq!(200, response.status().as_u16());
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(
+        body["message"],
+        "Send 0.4 SOL to Test Blink (20% holder discount)"
+    );
+}
+
+#[tokio::test]
+async fn wallets_outside_a_discount_gate_pay_full_price() {
+    let rpc = MockRpc::spawn().await;
+    let app = spawn_app_with_rpc(&rpc).await;
+    let collection = Pubkey::new_unique();
+    let payer = Pubkey::new_unique();
+    let blink = app
+        .create_blink(&gated_blink(json!({
+            "kind": "collection",
+            "collection": collection.to_string(),
+            "discount_percent": 20
+        })))
+        .await;
+    rpc.set_result(
+        "getTokenAccountsByOwner",
+        token_accounts_info(&payer, &[(Pubkey::new_unique(), 1, 0)]),
+    );
+    rpc.set_result(
+        "getMultipleAccounts",
+        collection_metadata_info(&Pubkey::new_unique()),
+    );
+
+    let response = post_action(&app, blink["id"].as_str().unwrap(), &payer).await;
+
+    assert_eq!(200, response.status().as_u16());
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["message"], "Send 0.5 SOL to Test Blink");
+}
+
+#[tokio::test]
+async fn metadata_for_an_unqualified_account_hint_is_disabled() {
+    let rpc = MockRpc::spawn().await;
+    let app = spawn_app_with_rpc(&rpc).await;
+    let mint = Pubkey::new_unique();
+    let payer = Pubkey::new_unique();
+    let blink = app
+        .create_blink(&gated_blink(
+            json!({ "kind": "token", "mint": mint.to_string() }),
+        ))
+        .await;
+    rpc.set_result("getTokenAccountsByOwner", token_accounts_info(&payer, &[]));
+    let url = format!(
+        "{}/api/actions/{}",
+        &app.address,
+        blink["id"].as_str().unwrap()
+    );
+
+    let response = reqwest::get(format!("{}?account={}", url, payer))
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(200, response.status().as_u16());
+    assert_eq!(response.headers()["cache-control"], "private, no-store");
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["disabled"], true);
+    assert!(body["error"]["message"].is_string());
+
+    let body: Value = reqwest::get(&url).await.unwrap().json().await.unwrap();
+    assert!(body.get("disabled").is_none());
+}
+
+#[tokio::test]
+async fn create_blink_returns_400_for_invalid_gate() {
+    let rpc = MockRpc::spawn().await;
+    let app = spawn_app_with_rpc(&rpc).await;
+
+    let response = app
+        .post_blink(&gated_blink(json!({ "kind": "token", "mint": "nope" })))
+        .await;
+
+    assert_eq!(400, response.status().as_u16());
+}
@@ -0,0 +1,491 @@
+mod helpers;
+
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use helpers::{
+    MockRpc, TestApp, confirmed_transaction_info, donation_blink, program_accounts_info,
+    spawn_app_with_rpc,
+};
+use reqwest::Client;
+use serde_json::{Value, json};
+use solana_sdk::{
+    hash::Hash,
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    transaction::Transaction,
+};
+
+const MEMO_PROGRAM_ID: Pubkey = solana_sdk::pubkey!("MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr");
+
+fn poll_blink(config: Value) -> Value {
+    let mut body = donation_blink();
+    body["type"] = json!("vote");
+    body["config"] = config;
+    body
+}
+
+fn candidates() -> Value {
+    json!([
+        { "id": "a", "label": "Alice" },
+        { "id": "b", "label": "Bob" },
+        { "id": "c", "label": "Carol" }
+    ])
+}
+
+fn vote_blink(weighting: Option<Value>) -> Value {
+    let mut body = donation_blink();
+    body["type"] = json!("vote");
+    body["config"] = json!({ "options": ["Yes", "No"] });
+    if let Some(weighting) = weighting {
+        body["config"]["weighting"] = weighting;
+    }
+    body
+}
+
+async fn post_vote(app: &TestApp, id: &str, voter: &Pubkey, selection: &str) -> reqwest::Response {
+    Client::new()
+        .post(format!(
+            "{}/api/actions/{}?selection={}",
+            &app.address, id, selection
+        ))
+        .json(&json!({ "account": voter.to_string() }))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+/// Signs the transaction served for a vote, as the wallet would.
+fn sign_vote(response: &Value, voter: &Keypair) -> Transaction {
+    let bytes = BASE64
+        .decode(response["transaction"].as_str().unwrap())
+        .unwrap();
+    let mut transaction: Transaction = bincode::deserialize(&bytes).unwrap();
+    let blockhash = transaction.message.recent_blockhash;
+    transaction.sign(&[voter], blockhash);
+    transaction
+}
+
+async fn confirm(
+    app: &TestApp,
+    id: &str,
+    voter: &Keypair,
+    transaction: &Transaction,
+) -> reqwest::Response {
+    Client::new()
+        .post(format!("{}/api/actions/{}/confirm", &app.address, id))
+        .json(&json!({
+            "account": voter.pubkey().to_string(),
+            "signature": transaction.signatures[0].to_string()
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+async fn results(app: &TestApp, id: &str) -> Value {
+    reqwest::get(format!("{}/api/blinks/{}/results", &app.address, id))
+        .await
+        .expect("Failed to execute request.")
+        .json()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn weighted_vote_carries_the_snapshot_balance() {
+    let rpc = MockRpc::spawn().await;
+    let app = spawn_app_with_rpc(&rpc).await;
+    let mint = Pubkey::new_unique();
+    let voter = Pubkey::new_unique();
+    rpc.set_result(
+        "getProgramAccounts",
+        program_accounts_info(900, &[(voter, 500)]),
+    );
+    let blink = app
+        .create_blink(&vote_blink(Some(
+            json!({ "mint": mint.to_string(), "snapshot": "creation" }),
+        )))
+        .await;
+    let id = blink["id"].as_str().unwrap();
+    // Balances read after creation do not count.
+    rpc.set_result(
+        "getProgramAccounts",
+        program_accounts_info(950, &[(voter, 9)]),
+    );
+
+    let response = post_vote(&app, id, &voter, "Yes").await;
+
+    assert_eq!(200, response.status().as_u16());
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["message"], "Vote for: Yes (weight 500)");
+    assert_eq!(body["links"]["next"]["type"], "post");
+    assert_eq!(
+        body["links"]["next"]["href"],
+        format!("/api/actions/{}/confirm", id)
+    );
+    let bytes = BASE64
+        .decode(body["transaction"].as_str().unwrap())
+        .unwrap();
+    let transaction: Transaction = bincode::deserialize(&bytes).unwrap();
+    let memo = String::from_utf8(transaction.message.instructions[1].data.clone()).unwrap();
+    assert_eq!(memo, format!("wvote:{}:{}:900:500:Yes", id, mint));
+    assert_eq!(rpc.calls_to("getProgramAccounts"), 1);
+}
+
+#[tokio::test]
+async fn wallets_without_snapshot_balance_cannot_vote() {
+    let rpc = MockRpc::spawn().await;
+    let app = spawn_app_with_rpc(&rpc).await;
+    let mint = Pubkey::new_unique();
+    rpc.set_result(
+        "getProgramAccounts",
+        program_accounts_info(900, &[(Pubkey::new_unique(), 500)]),
+    );
+    let blink = app
+        .create_blink(&vote_blink(Some(
+            json!({ "mint": mint.to_string(), "snapshot": "creation" }),
+        )))
+        .await;
+
+    let response = post_vote(
+        &app,
+        blink["id"].as_str().unwrap(),
+        &Pubkey::new_unique(),
+        "Yes",
+    )
+    .await;
+
+    assert_eq!(403, response.status().as_u16());
+    let body: Value = response.json().await.unwrap();
+    assert!(
+        body["message"]
+            .as_str()
+            .unwrap()
+            .contains(&mint.to_string())
+    );
+}
+
+#[tokio::test]
+async fn slot_snapshots_open_voting_once_the_slot_is_reached() {
+    let rpc = MockRpc::spawn().await;
+    let app = spawn_app_with_rpc(&rpc).await;
+    let voter = Pubkey::new_unique();
+    rpc.set_result(
+        "getProgramAccounts",
+        program_accounts_info(2000, &[(voter, 7)]),
+    );
+    let blink = app
+        .create_blink(&vote_blink(Some(json!({
+            "mint": Pubkey::new_unique().to_string(),
+            "snapshot": { "slot": 2000 }
+        }))))
+        .await;
+    let id = blink["id"].as_str().unwrap();
+
+    let response = post_vote(&app, id, &voter, "No").await;
+    assert_eq!(403, response.status().as_u16());
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["message"], "Voting opens at slot 2000");
+    assert_eq!(rpc.calls_to("getProgramAccounts"), 0);
+
+    rpc.set_slot(2000);
+    let response = post_vote(&app, id, &voter, "No").await;
+    assert_eq!(200, response.status().as_u16());
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["message"], "Vote for: No (weight 7)");
+}
+
+#[tokio::test]
+async fn confirmed_weighted_votes_are_tallied() {
+    let rpc = MockRpc::spawn().await;
+    let app = spawn_app_with_rpc(&rpc).await;
+    let mint = Pubkey::new_unique();
+    let voter = Keypair::new();
+    rpc.set_result(
+        "getProgramAccounts",
+        program_accounts_info(900, &[(voter.pubkey(), 500)]),
+    );
+    let blink = app
+        .create_blink(&vote_blink(Some(
+            json!({ "mint": mint.to_string(), "snapshot": "creation" }),
+        )))
+        .await;
+    let id = blink["id"].as_str().unwrap();
+    let served: Value = post_vote(&app, id, &voter.pubkey(), "Yes")
+        .await
+        .json()
+        .await
+        .unwrap();
+    let transaction = sign_vote(&served, &voter);
+    rpc.set_result("getTransaction", confirmed_transaction_info(&transaction));
+
+    let response = confirm(&app, id, &voter, &transaction).await;
+    assert_eq!(200, response.status().as_u16());
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["type"], "completed");
+
+    // Confirming the same transaction again is harmless.
+    let response = confirm(&app, id, &voter, &transaction).await;
+    assert_eq!(200, response.status().as_u16());
+
+    let results = results(&app, id).await;
+    assert_eq!(results["weighting"]["mint"], mint.to_string());
+    assert_eq!(results["weighting"]["snapshot_slot"], 900);
+    assert_eq!(
+        results["options"],
+        json!([
+            { "option": "Yes", "label": "Yes", "votes": 1, "weight": "500" },
+            { "option": "No", "label": "No", "votes": 0, "weight": "0" }
+        ])
+    );
+    assert_eq!(results["total_votes"], 1);
+    assert_eq!(results["total_weight"], "500");
+}
+
+#[tokio::test]
+async fn a_wallet_votes_only_once() {
+    let rpc = MockRpc::spawn().await;
+    let app = spawn_app_with_rpc(&rpc).await;
+    let voter = Keypair::new();
+    let blink = app.create_blink(&vote_blink(None)).await;
+    let id = blink["id"].as_str().unwrap();
+
+    for (selection, status) in [("Yes", 200), ("No", 409)] {
+        let served: Value = post_vote(&app, id, &voter.pubkey(), selection)
+            .await
+            .json()
+            .await
+            .unwrap();
+        let transaction = sign_vote(&served, &voter);
+        rpc.set_result("getTransaction", confirmed_transaction_info(&transaction));
+
+        let response = confirm(&app, id, &voter, &transaction).await;
+
+        assert_eq!(status, response.status().as_u16());
+    }
+
+    let results = results(&app, id).await;
+    assert_eq!(results["ballot"], "single");
+    assert!(results["weighting"].is_null());
+    assert!(results["total_weight"].is_null());
+    assert_eq!(
+        results["options"],
+        json!([
+            { "option": "Yes", "label": "Yes", "votes": 1, "weight": null },
+            { "option": "No", "label": "No", "votes": 0, "weight": null }
+        ])
+    );
+}
+
+#[tokio::test]
+async fn inflated_weight_claims_are_rejected() {
+    let rpc = MockRpc::spawn().await;
+    let app = spawn_app_with_rpc(&rpc).await;
+    let mint = Pubkey::new_unique();
+    let voter = Keypair::new();
+    rpc.set_result(
+        "getProgramAccounts",
+        program_accounts_info(900, &[(voter.pubkey(), 500)]),
+    );
+    let blink = app
+        .create_blink(&vote_blink(Some(
+            json!({ "mint": mint.to_string(), "snapshot": "creation" }),
+        )))
+        .await;
+    let id = blink["id"].as_str().unwrap();
+    let memo = Instruction {
+        program_id: MEMO_PROGRAM_ID,
+        accounts: vec![AccountMeta::new_readonly(voter.pubkey(), true)],
+        data: format!("wvote:{}:{}:900:5000:Yes", id, mint).into_bytes(),
+    };
+    let transaction = Transaction::new_signed_with_payer(
+        &[memo],
+        Some(&voter.pubkey()),
+        &[&voter],
+        Hash::default(),
+    );
+    rpc.set_result("getTransaction", confirmed_transaction_info(&transaction));
+
+    let response = confirm(&app, id, &voter, &transaction).await;
+
+    assert_eq!(400, response.status().as_u16());
+    assert_eq!(results(&app, id).await["total_votes"], 0);
+}
+
+#[tokio::test]
+async fn unconfirmed_votes_are_not_counted() {
+    let rpc = MockRpc::spawn().await;
+    let app = spawn_app_with_rpc(&rpc).await;
+    let voter = Keypair::new();
+    let blink = app.create_blink(&vote_blink(None)).await;
+    let id = blink["id"].as_str().unwrap();
+    let served: Value = post_vote(&app, id, &voter.pubkey(), "Yes")
+        .await
+        .json()
+        .await
+        .unwrap();
+    rpc.set_result("getTransaction", Value::Null);
+
+    let response = confirm(&app, id, &voter, &sign_vote(&served, &voter)).await;
+
+    assert_eq!(404, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn only_vote_blinks_can_be_weighted() {
+    let rpc = MockRpc::spawn().await;
+    let app = spawn_app_with_rpc(&rpc).await;
+    let mut body = donation_blink();
+    body["config"]["weighting"] = json!({
+        "mint": Pubkey::new_unique().to_string(),
+        "snapshot": "creation"
+    });
+
+    let response = app.post_blink(&body).await;
+
+    assert_eq!(400, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn option_ids_are_encoded_in_hrefs() {
+    let rpc = MockRpc::spawn().await;
+    let app = spawn_app_with_rpc(&rpc).await;
+    let blink = app
+        .create_blink(&poll_blink(
+            json!({ "options": ["Yes & No", "Maybe later"] }),
+        ))
+        .await;
+    let id = blink["id"].as_str().unwrap();
+
+    let body: Value = reqwest::get(format!("{}/api/actions/{}", &app.address, id))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let href = body["links"]["actions"][0]["href"].as_str().unwrap();
+    assert!(href.ends_with("?selection=Yes%20%26%20No"), "{}", href);
+
+    let voter = Pubkey::new_unique();
+    let response = Client::new()
+        .post(format!(
+            "{}/api/actions/{}?selection=Maybe%20later",
+            &app.address, id
+        ))
+        .json(&json!({ "account": voter.to_string() }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(200, response.status().as_u16());
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["message"], "Vote for: Maybe later");
+}
+
+#[tokio::test]
+async fn multi_select_polls_take_a_checkbox_group() {
+    let rpc = MockRpc::spawn().await;
+    let app = spawn_app_with_rpc(&rpc).await;
+    let blink = app
+        .create_blink(&poll_blink(json!({
+            "options": candidates(),
+            "ballot": { "kind": "multi", "min": 1, "max": 2 }
+        })))
+        .await;
+    let id = blink["id"].as_str().unwrap();
+    let voter = Pubkey::new_unique();
+
+    let body: Value = reqwest::get(format!("{}/api/actions/{}", &app.address, id))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let action = &body["links"]["actions"][0];
+    assert!(
+        action["href"]
+            .as_str()
+            .unwrap()
+            .ends_with("?selection={selection}")
+    );
+    assert_eq!(action["parameters"][0]["type"], "checkbox");
+    assert_eq!(action["parameters"][0]["max"], 2);
+    assert_eq!(action["parameters"][0]["options"][1]["value"], "b");
+
+    let response = post_vote(&app, id, &voter, "c,a").await;
+    assert_eq!(200, response.status().as_u16());
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["message"], "Vote for: Alice, Carol");
+    let bytes = BASE64
+        .decode(body["transaction"].as_str().unwrap())
+        .unwrap();
+    let transaction: Transaction = bincode::deserialize(&bytes).unwrap();
+    let memo = String::from_utf8(transaction.message.instructions[1].data.clone()).unwrap();
+    assert_eq!(memo, format!("vote:{}:a,c", id));
+
+    let response = post_vote(&app, id, &voter, "a,b,c").await;
+    assert_eq!(400, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn ranked_polls_are_decided_by_instant_runoff() {
+    let rpc = MockRpc::spawn().await;
+    let app = spawn_app_with_rpc(&rpc).await;
+    let blink = app
+        .create_blink(&poll_blink(json!({
+            "options": candidates(),
+            "ballot": { "kind": "ranked", "max": 2 }
+        })))
+        .await;
+    let id = blink["id"].as_str().unwrap();
+
+    let body: Value = reqwest::get(format!("{}/api/actions/{}", &app.address, id))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let action = &body["links"]["actions"][0];
+    assert!(
+        action["href"]
+            .as_str()
+            .unwrap()
+            .ends_with("?selection={rank1},{rank2}")
+    );
+    assert_eq!(action["parameters"][1]["name"], "rank2");
+
+    for ranking in ["a,b", "a,b", "b,a", "b,a", "c,b"] {
+        let voter = Keypair::new();
+        let served: Value = post_vote(&app, id, &voter.pubkey(), ranking)
+            .await
+            .json()
+            .await
+            .unwrap();
+        let transaction = sign_vote(&served, &voter);
+        rpc.set_result("getTransaction", confirmed_transaction_info(&transaction));
+        let response = confirm(&app, id, &voter, &transaction).await;
+        assert_eq!(200, response.status().as_u16());
+    }
+
+    let results = results(&app, id).await;
+    assert_eq!(results["ballot"], "ranked");
+    assert_eq!(results["total_votes"], 5);
+    assert_eq!(results["options"][0]["votes"], 2);
+    assert_eq!(results["rounds"][0]["eliminated"], "c");
+    assert_eq!(results["rounds"][1]["options"][1]["votes"], 3);
+    assert_eq!(results["winner"], "b");
+}
+
+#[tokio::test]
+async fn create_blink_returns_400_for_invalid_poll() {
+    let rpc = MockRpc::spawn().await;
+    let app = spawn_app_with_rpc(&rpc).await;
+
+    let response = app
+        .post_blink(&poll_blink(json!({
+            "options": ["Yes", "No"],
+            "ballot": { "kind": "ranked" }
+        })))
+        .await;
+
+    assert_eq!(400, response.status().as_u16());
+}